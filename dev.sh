@@ -20,7 +20,7 @@ if [ ! -d "node_modules" ]; then
 fi
 
 # Check if Rust dependencies are cached
-if [ ! -d "src-tauri/target" ]; then
+if [ ! -d "target" ]; then
     echo "🦀 First run - Rust dependencies will be downloaded and compiled..."
     echo "   This may take a few minutes..."
 fi
@@ -0,0 +1,155 @@
+use crate::models::{ConnectedPeer, DiscoveredDevice, DiscoveryStatus, PeerMetrics, SyncMetrics};
+use crate::AppState;
+use tauri::State;
+
+/// Whether LAN discovery is running, retrying after a failure, or not
+/// possible because the network does not carry multicast.
+#[tauri::command]
+pub async fn get_discovery_status(state: State<'_, AppState>) -> Result<DiscoveryStatus, String> {
+    state.service_manager.query(|manager| Box::pin(async move { manager.get_discovery_status() })).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_discovered_devices(state: State<'_, AppState>) -> Result<Vec<DiscoveredDevice>, String> {
+    state.service_manager.query(|manager| Box::pin(manager.get_discovered_devices())).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_sync_status(state: State<'_, AppState>) -> Result<bool, String> {
+    state.service_manager.query(|manager| Box::pin(manager.is_running())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Counters for the current sync session.
+///
+/// # Returns
+/// Message, byte, and peer counts plus uptime, or `None` when sync is not running
+#[tauri::command]
+pub async fn get_sync_metrics(state: State<'_, AppState>) -> Result<Option<SyncMetrics>, String> {
+    state.service_manager.query(|manager| Box::pin(manager.get_sync_metrics())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Zero the sync counters and every connected peer's counters, so they
+/// count from now. Uptime and connections are left alone.
+#[tauri::command]
+pub async fn reset_sync_metrics(state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.query(|manager| Box::pin(manager.reset_sync_metrics())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Peers with an open session, so the UI can tell them apart from devices
+/// that are only discovered.
+///
+/// # Returns
+/// One entry per live connection with its direction and activity times,
+/// empty when sync is not running
+#[tauri::command]
+pub async fn get_connected_peers(state: State<'_, AppState>) -> Result<Vec<ConnectedPeer>, String> {
+    state.service_manager.query(|manager| Box::pin(manager.get_connected_peers())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Counters for each connected peer, for a per-device breakdown.
+///
+/// # Returns
+/// One entry per live connection, empty when sync is not running
+#[tauri::command]
+pub async fn get_peer_metrics(state: State<'_, AppState>) -> Result<Vec<PeerMetrics>, String> {
+    state.service_manager.query(|manager| Box::pin(manager.get_peer_metrics())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Mark a device as trusted and persist the decision.
+///
+/// # Arguments
+/// * `address` - IP address of the device's sync server
+/// * `port` - Port of the device's sync server
+#[tauri::command]
+pub async fn trust_device(address: String, port: u16, state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.set_device_trusted(address, port, true))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Trust a device for a limited time, e.g. a colleague's machine during a
+/// working session. When the time is up it is unpaired and anything held
+/// from it is dropped.
+///
+/// # Arguments
+/// * `address` - IP address of the device's sync server
+/// * `port` - Port of the device's sync server
+/// * `hours` - How long the pairing lasts; 8 hours when `None`
+///
+/// # Errors
+/// When `hours` is out of range or the device's certificate cannot be pinned
+#[tauri::command]
+pub async fn pair_guest_device(address: String, port: u16, hours: Option<u32>, state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.pair_guest(address, port, hours))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Mark a device as explicitly untrusted and persist the decision.
+#[tauri::command]
+pub async fn untrust_device(address: String, port: u16, state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.set_device_trusted(address, port, false))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Ask a connected device to stop sending clipboard content here for a
+/// while. Both sides show the mute and its expiry in their device list.
+///
+/// # Arguments
+/// * `address` - IP address of the device
+/// * `minutes` - How long to mute it; the device caps this at a day
+///
+/// # Errors
+/// When sync is not running or the device is not connected
+#[tauri::command]
+pub async fn mute_device(address: String, minutes: u32, state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.request_device_mute(address, Some(minutes)))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Tell a muted device it may send content here again.
+#[tauri::command]
+pub async fn unmute_device(address: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.request_device_mute(address, None))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Forget a device entirely, dropping its trust decision and discovery entry.
+#[tauri::command]
+pub async fn forget_device(address: String, port: u16, state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.forget_device(address, port))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Connect to a device by address, for networks where mDNS discovery does
+/// not work. The peer is saved and reconnected whenever sync runs.
+///
+/// # Arguments
+/// * `address` - IP address of the device's sync server
+/// * `port` - Port of the device's sync server
+/// * `fingerprint` - TLS certificate fingerprint to pin, if the device serves TLS
+///
+/// # Errors
+/// When the address is not an IP address or the port is 0
+#[tauri::command]
+pub async fn add_manual_peer(address: String, port: u16, fingerprint: Option<String>, state: State<'_, AppState>) -> Result<(), String> {
+    let address = address.trim().parse().map_err(|_| format!("Not an IP address: {}", address))?;
+    state.service_manager.execute(move |manager| Box::pin(manager.add_manual_peer(address, port, fingerprint))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Stop connecting to a manually added device.
+///
+/// # Returns
+/// Whether the peer was in the list
+#[tauri::command]
+pub async fn remove_manual_peer(address: String, port: u16, state: State<'_, AppState>) -> Result<bool, String> {
+    let address = address.trim().parse().map_err(|_| format!("Not an IP address: {}", address))?;
+    state.service_manager.execute(move |manager| Box::pin(manager.remove_manual_peer(address, port))).await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,152 @@
+use crate::models::{CrashReport, EffectiveConfig, SafeModeStatus};
+use crate::services::audit::{self, AuditEntry};
+use crate::services::diagnostics;
+use crate::services::protocol_trace::{self, TraceEvent, TraceFilter};
+use crate::utils::{crash_report, log_buffer, log_file, log_level, paths, safe_mode};
+use crate::AppState;
+use std::time::Duration;
+use tauri::{AppHandle, State};
+use super::StartSyncError;
+
+/// The configuration actually in effect, including what the running
+/// services chose (bound port, advertised address, backends).
+///
+/// # Returns
+/// Effective settings with the security key removed
+#[tauri::command]
+pub async fn get_effective_config(state: State<'_, AppState>) -> Result<EffectiveConfig, String> {
+    state.service_manager.query(|manager| Box::pin(manager.get_effective_config())).await
+        .map_err(|e| e.to_string())
+}
+
+/// The report written by the last crash, for attaching to bug reports.
+/// Reports stay on this device.
+///
+/// # Returns
+/// The report, or `None` if the app has not crashed
+///
+/// # Errors
+/// When the report exists but cannot be read
+#[tauri::command]
+pub async fn get_last_crash_report(app: AppHandle) -> Result<Option<CrashReport>, String> {
+    let dir = paths::data_dir(&app).map_err(|e| e.to_string())?;
+    crash_report::load(&dir).map_err(|e| e.to_string())
+}
+
+/// Whether the app started in safe mode after repeated crashes. In safe
+/// mode services stay stopped and only settings and diagnostics work.
+#[tauri::command]
+pub async fn get_safe_mode_status() -> Result<SafeModeStatus, String> {
+    Ok(safe_mode::status())
+}
+
+/// Leave safe mode, e.g. after changing the setting that crashed the app.
+///
+/// # Arguments
+/// * `start_sync` - Start services right away
+///
+/// # Errors
+/// When services fail to start
+#[tauri::command]
+pub async fn exit_safe_mode(start_sync: Option<bool>, state: State<'_, AppState>) -> Result<(), StartSyncError> {
+    safe_mode::exit();
+    if start_sync.unwrap_or(false) {
+        state.service_manager.start().await?;
+    }
+    Ok(())
+}
+
+/// Security-relevant actions taken on received data, such as formats
+/// sanitized before a clip was applied.
+///
+/// # Arguments
+/// * `limit` - Most recent entries to return
+///
+/// # Returns
+/// Entries, oldest first
+///
+/// # Errors
+/// When the audit trail cannot be read
+#[tauri::command]
+pub async fn get_audit_log(limit: usize) -> Result<Vec<AuditEntry>, String> {
+    audit::recent(limit).map_err(|e| e.to_string())
+}
+
+/// Recent protocol events (handshakes, messages with their ids, sizes,
+/// and verdicts, and closes), without any clip content.
+///
+/// # Arguments
+/// * `limit` - Most recent matching events to return
+/// * `filter` - Narrow to a peer, event kind, message type, or problems
+///
+/// # Returns
+/// Events, oldest first
+#[tauri::command]
+pub async fn get_protocol_trace(limit: usize, filter: Option<TraceFilter>) -> Result<Vec<TraceEvent>, String> {
+    Ok(protocol_trace::recent(limit, &filter.unwrap_or_default()))
+}
+
+/// The log level in effect, e.g. `info`.
+#[tauri::command]
+pub async fn get_log_level() -> Result<String, String> {
+    Ok(log_level::current().to_string().to_lowercase())
+}
+
+/// Change how much is logged, to every output, until changed again or
+/// the app restarts. Lets a user switch to debug while reproducing an
+/// issue and back afterwards.
+///
+/// # Arguments
+/// * `level` - One of `trace`, `debug`, `info`, `warn`, `error`, or `off`
+///
+/// # Errors
+/// When the level is unknown or logging is not initialised
+#[tauri::command]
+pub async fn set_log_level(level: String) -> Result<(), String> {
+    let level = log_level::parse(&level).map_err(|e| e.to_string())?;
+    log_level::set(level).map_err(|e| e.to_string())?;
+    tracing::info!("Log level set to {}", level);
+    Ok(())
+}
+
+/// Recent lines from the log files, for diagnosing discovery and
+/// connection failures where stdout is not visible.
+///
+/// # Arguments
+/// * `lines` - Most recent lines to return, capped at `MAX_RECENT_LINES`
+///
+/// # Returns
+/// Lines, oldest first. Before file logging starts, this run's lines kept
+/// in memory
+///
+/// # Errors
+/// When the log files cannot be read
+#[tauri::command]
+pub async fn get_recent_logs(lines: usize) -> Result<Vec<String>, String> {
+    match log_file::recent_lines(lines).map_err(|e| e.to_string())? {
+        Some(recent) => Ok(recent),
+        None => Ok(log_buffer::recent_lines(lines)),
+    }
+}
+
+/// Log at debug level for a while, then write a bundle for bug reports:
+/// sanitized logs, config without secrets, a health snapshot, recent
+/// errors, and the events, metrics, and peer states seen meanwhile.
+///
+/// # Arguments
+/// * `duration_secs` - How long to record, capped at five minutes
+///
+/// # Returns
+/// Path of the zip file written under the data directory
+///
+/// # Errors
+/// When a capture is already running or the bundle cannot be written
+#[tauri::command]
+pub async fn capture_diagnostics(duration_secs: u64, state: State<'_, AppState>) -> Result<String, String> {
+    let probe = state.service_manager.execute(|manager| Box::pin(manager.diagnostics_probe())).await
+        .map_err(|e| e.to_string())?;
+    let duration = Duration::from_secs(duration_secs.clamp(1, diagnostics::MAX_CAPTURE_SECS));
+    diagnostics::capture(probe, duration).await
+        .map(|path| path.display().to_string())
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,132 @@
+use crate::models::{HistoryEntry, HistoryPage, MaintenanceReport};
+use crate::AppState;
+use tauri::State;
+
+/// List clipboard history, newest first.
+///
+/// # Arguments
+/// * `limit` - Page size (capped at 200)
+/// * `offset` - Number of entries to skip
+#[tauri::command]
+pub async fn get_history(limit: u32, offset: u32, state: State<'_, AppState>) -> Result<HistoryPage, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.get_history(limit, offset))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Search clipboard history, newest first.
+///
+/// # Arguments
+/// * `query` - Text the entry contains, ignoring ASCII case
+/// * `device` - Only entries received from this device
+/// * `limit` - Page size (capped at 200)
+/// * `offset` - Number of matches to skip
+#[tauri::command]
+pub async fn search_history(
+    query: String,
+    device: Option<String>,
+    limit: u32,
+    offset: u32,
+    state: State<'_, AppState>,
+) -> Result<HistoryPage, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.search_history(query, device, limit, offset))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Fetch one history entry.
+///
+/// # Returns
+/// The entry, or `None` when no entry has that id
+#[tauri::command]
+pub async fn get_history_entry(id: i64, state: State<'_, AppState>) -> Result<Option<HistoryEntry>, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.get_history_entry(id))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Delete a single history entry.
+///
+/// # Returns
+/// Whether an entry with that id existed
+#[tauri::command]
+pub async fn delete_history_entry(id: i64, state: State<'_, AppState>) -> Result<bool, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.delete_history_entry(id))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Replace the tags on a history entry. Tags listed in
+/// `history_tag_retention` change how long the entry is kept.
+///
+/// # Arguments
+/// * `id` - History entry id
+/// * `tags` - New tags; an empty list removes them all
+///
+/// # Returns
+/// Whether an entry with that id existed
+#[tauri::command]
+pub async fn set_history_tags(id: i64, tags: Vec<String>, state: State<'_, AppState>) -> Result<bool, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.set_history_tags(id, tags))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Copy a history entry to the clipboard again.
+///
+/// # Arguments
+/// * `id` - History entry id
+/// * `broadcast` - Also send it to peers, as if it had just been copied
+///
+/// # Returns
+/// Whether an entry with that id existed
+///
+/// # Errors
+/// When sync is not running, or `broadcast` is set while sync is paused or
+/// this device is receive-only
+#[tauri::command]
+pub async fn restore_history_entry(id: i64, broadcast: Option<bool>, state: State<'_, AppState>) -> Result<bool, String> {
+    let broadcast = broadcast.unwrap_or(false);
+    state.service_manager.execute(move |manager| Box::pin(manager.restore_history_entry(id, broadcast))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Pin a history entry so retention cleanup never removes it.
+///
+/// # Returns
+/// Whether an entry with that id existed
+#[tauri::command]
+pub async fn pin_history_entry(id: i64, state: State<'_, AppState>) -> Result<bool, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.set_history_pinned(id, true))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Unpin a history entry, returning it to the normal retention limits.
+///
+/// # Returns
+/// Whether an entry with that id existed
+#[tauri::command]
+pub async fn unpin_history_entry(id: i64, state: State<'_, AppState>) -> Result<bool, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.set_history_pinned(id, false))).await
+        .map_err(|e| e.to_string())
+}
+
+/// List every pinned history entry, newest first.
+#[tauri::command]
+pub async fn get_pinned_entries(state: State<'_, AppState>) -> Result<Vec<HistoryEntry>, String> {
+    state.service_manager.execute(|manager| Box::pin(manager.get_pinned_entries())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Delete all history entries, returning how many were removed.
+#[tauri::command]
+pub async fn clear_history(state: State<'_, AppState>) -> Result<usize, String> {
+    state.service_manager.execute(|manager| Box::pin(manager.clear_history())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Prune and compact the clipboard history immediately instead of waiting
+/// for the background task. Also emits `maintenance-completed`.
+///
+/// # Returns
+/// How many entries were removed and how much space was reclaimed
+#[tauri::command]
+pub async fn run_maintenance_now(state: State<'_, AppState>) -> Result<MaintenanceReport, String> {
+    state.service_manager.execute(|manager| Box::pin(manager.run_maintenance_now())).await
+        .map_err(|e| e.to_string())
+}
@@ -1,44 +1,191 @@
-use crate::models::{Config, DiscoveredDevice};
-use crate::AppState;
-use tauri::State;
+use crate::models::{ApiScope, ApiTokenInfo, Config, CreatedApiToken, StatusSummary};
+use crate::services::previews::MessagePreview;
+use crate::services::workspaces;
+use crate::utils::port_probe::BindError;
+use crate::{hotkey, login_item, AppState};
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+mod devices;
+mod diagnostics;
+mod history;
+mod presentation;
+
+pub use devices::*;
+pub use diagnostics::*;
+pub use history::*;
+pub use presentation::*;
+
+/// Error returned by `start_sync`. Port problems carry diagnostics so the
+/// UI can tell the user what holds the port and which one to use instead.
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StartSyncError {
+    PortUnavailable(BindError),
+    Other { message: String },
+}
+
+impl From<anyhow::Error> for StartSyncError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<BindError>() {
+            Ok(bind_error) => StartSyncError::PortUnavailable(bind_error),
+            Err(error) => StartSyncError::Other { message: error.to_string() },
+        }
+    }
+}
 
 #[tauri::command]
 pub async fn get_config(state: State<'_, AppState>) -> Result<Config, String> {
-    let manager = state.service_manager.lock().await;
-    Ok(manager.get_config().await)
+    state.service_manager.query(|manager| Box::pin(manager.get_config())).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn set_config(config: Config, app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let shortcut = config.push_shortcut.clone();
+    let launch_at_login = config.launch_at_login;
+    state.service_manager.update_config(config).await
+        .map_err(|e| e.to_string())?;
+    login_item::apply(&app, launch_at_login)
+        .map_err(|e| format!("Settings saved, but launch at login could not be changed: {}", e))?;
+    hotkey::register(&app, shortcut.as_deref())
+        .map_err(|e| format!("Settings saved, but the push shortcut is unavailable: {}", e))
+}
+
+/// Have the OS start the app when the user logs in, or stop it doing so.
+///
+/// # Arguments
+/// * `enabled` - Whether to add the login item or remove it
+///
+/// # Errors
+/// When the OS login item cannot be changed or the config cannot be saved
+#[tauri::command]
+pub async fn set_launch_at_login(enabled: bool, app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    login_item::apply(&app, enabled).map_err(|e| e.to_string())?;
+    let mut config = state.service_manager.query(|manager| Box::pin(manager.get_config())).await
+        .map_err(|e| e.to_string())?;
+    config.launch_at_login = enabled;
+    state.service_manager.update_config(config).await
+        .map_err(|e| e.to_string())
+}
+
+/// Compact sync state for a menubar extra or widget: cheap enough to poll,
+/// and also pushed as `status-summary-changed` whenever it changes.
+#[tauri::command]
+pub async fn get_status_summary(state: State<'_, AppState>) -> Result<StatusSummary, String> {
+    state.service_manager.query(|manager| Box::pin(manager.get_status_summary())).await
+        .map_err(|e| e.to_string())
 }
 
+/// Export the config as JSON to set up another machine the same way.
+///
+/// # Arguments
+/// * `redact_secrets` - Leave out the security key
 #[tauri::command]
-pub async fn set_config(config: Config, state: State<'_, AppState>) -> Result<(), String> {
-    let mut manager = state.service_manager.lock().await;
-    manager.update_config(config).await
+pub async fn export_config(redact_secrets: bool, state: State<'_, AppState>) -> Result<String, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.export_config(redact_secrets))).await
         .map_err(|e| e.to_string())
 }
 
+/// Replace the config with one from `export_config`. This device keeps its
+/// name, and its security key when the export has none.
+///
+/// # Returns
+/// The config now in effect
+///
+/// # Errors
+/// When the JSON is not a valid export for this version
 #[tauri::command]
-pub async fn start_sync(state: State<'_, AppState>) -> Result<(), String> {
-    let mut manager = state.service_manager.lock().await;
-    manager.start().await
+pub async fn import_config(json: String, app: AppHandle, state: State<'_, AppState>) -> Result<Config, String> {
+    let config = state.service_manager.execute(move |manager| Box::pin(manager.import_config(json))).await
+        .map_err(|e| e.to_string())?;
+    hotkey::register(&app, config.push_shortcut.as_deref())
+        .map_err(|e| format!("Config imported, but the push shortcut is unavailable: {}", e))?;
+    Ok(config)
+}
+
+/// Names of the saved config profiles, sorted.
+#[tauri::command]
+pub async fn list_profiles(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state.service_manager.execute(|manager| Box::pin(async move { manager.list_profiles() })).await
         .map_err(|e| e.to_string())
 }
 
+/// Save the current config under a name, replacing a profile with that name.
+///
+/// # Arguments
+/// * `name` - Profile name, e.g. "home" or "office"
+#[tauri::command]
+pub async fn save_profile(name: String, state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.save_profile(name))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Switch to a saved profile. Services restart when its ports, names, or
+/// TLS setting differ from the current config.
+///
+/// # Returns
+/// The config now in effect
+///
+/// # Errors
+/// When no profile has that name or the services fail to restart
+#[tauri::command]
+pub async fn activate_profile(name: String, app: AppHandle, state: State<'_, AppState>) -> Result<Config, String> {
+    let config = state.service_manager.execute(move |manager| Box::pin(manager.activate_profile(name))).await
+        .map_err(|e| e.to_string())?;
+    hotkey::register(&app, config.push_shortcut.as_deref())
+        .map_err(|e| format!("Profile activated, but the push shortcut is unavailable: {}", e))?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub async fn start_sync(state: State<'_, AppState>) -> Result<(), StartSyncError> {
+    state.service_manager.start().await
+        .map_err(StartSyncError::from)
+}
+
 #[tauri::command]
 pub async fn stop_sync(state: State<'_, AppState>) -> Result<(), String> {
-    let mut manager = state.service_manager.lock().await;
-    manager.stop().await
+    state.service_manager.stop().await
         .map_err(|e| e.to_string())
 }
 
+/// Pause sync while keeping connections and discovery alive: the clipboard
+/// is neither read nor written until `resume_sync`. Peers are told we paused.
+///
+/// # Errors
+/// When sync is not running
 #[tauri::command]
-pub async fn get_discovered_devices(state: State<'_, AppState>) -> Result<Vec<DiscoveredDevice>, String> {
-    let manager = state.service_manager.lock().await;
-    Ok(manager.get_discovered_devices().await)
+pub async fn pause_sync(state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.set_paused(true).await
+        .map_err(|e| e.to_string())
 }
 
+/// Resume a paused sync and tell peers we are back. Anything copied during
+/// the pause stays on this device.
+///
+/// # Errors
+/// When sync is not running
 #[tauri::command]
-pub async fn get_sync_status(state: State<'_, AppState>) -> Result<bool, String> {
-    let manager = state.service_manager.lock().await;
-    Ok(manager.is_running().await)
+pub async fn resume_sync(state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.set_paused(false).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn is_sync_paused(state: State<'_, AppState>) -> Result<bool, String> {
+    state.service_manager.query(|manager| Box::pin(manager.is_paused())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Identifier of the active virtual desktop, as to list it among the
+/// work desktops.
+///
+/// # Returns
+/// `None` where the platform does not expose desktop identifiers
+#[tauri::command]
+pub async fn get_current_desktop() -> Result<Option<String>, String> {
+    Ok(workspaces::current_desktop())
 }
 
 #[tauri::command]
@@ -49,4 +196,118 @@ pub async fn test_connection() -> Result<String, String> {
 #[tauri::command]
 pub async fn is_dev_mode() -> Result<bool, String> {
     Ok(cfg!(any(debug_assertions, feature = "dev-features")))
-}
\ No newline at end of file
+}
+
+/// Fetch recent, sanitized log lines from a connected peer.
+///
+/// # Arguments
+/// * `peer_id` - Connection id of the peer
+/// * `lines` - Number of lines to request
+///
+/// # Errors
+/// When the peer is unreachable or has not opted in to sharing logs
+#[tauri::command]
+pub async fn request_peer_logs(peer_id: String, lines: usize, state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let peer_id = uuid::Uuid::parse_str(&peer_id).map_err(|e| e.to_string())?;
+    let request = state.service_manager
+        .execute(move |manager| Box::pin(async move { manager.request_peer_logs(peer_id, lines) }))
+        .await
+        .map_err(|e| e.to_string())?;
+    // Waiting for the peer happens here, not in the manager's mailbox
+    request.await.map_err(|e| e.to_string())
+}
+
+/// Mint a token for scripts using the local API. Clients present it as
+/// `Authorization: Bearer <token>` (or `?token=`) when connecting from
+/// loopback or an allowed source.
+///
+/// # Arguments
+/// * `scope` - `read` for clipboard events only, `read_write` to also push
+///
+/// # Returns
+/// The token metadata and secret; the secret cannot be retrieved again
+#[tauri::command]
+pub async fn create_api_token(scope: ApiScope, state: State<'_, AppState>) -> Result<CreatedApiToken, String> {
+    state.service_manager.execute(move |manager| Box::pin(manager.create_api_token(scope))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Revoke a local API token and disconnect clients using it.
+///
+/// # Arguments
+/// * `id` - Token id returned by `create_api_token`
+///
+/// # Returns
+/// Whether the token existed
+#[tauri::command]
+pub async fn revoke_api_token(id: String, state: State<'_, AppState>) -> Result<bool, String> {
+    let id = uuid::Uuid::parse_str(&id).map_err(|e| e.to_string())?;
+    state.service_manager.execute(move |manager| Box::pin(manager.revoke_api_token(id))).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn list_api_tokens(state: State<'_, AppState>) -> Result<Vec<ApiTokenInfo>, String> {
+    state.service_manager.query(|manager| Box::pin(manager.list_api_tokens())).await
+        .map_err(|e| e.to_string())
+}
+/// Settle a clip held back by the `prompt` oversize policy.
+///
+/// # Arguments
+/// * `id` - Id from the `oversize-content` event
+/// * `accept` - Sync (or apply) the clip anyway when true, discard it otherwise
+///
+/// # Returns
+/// Whether a held clip with this id was found
+#[tauri::command]
+pub async fn resolve_oversize_content(id: String, accept: bool, state: State<'_, AppState>) -> Result<bool, String> {
+    let id = uuid::Uuid::parse_str(&id).map_err(|e| e.to_string())?;
+    state.service_manager.execute(move |manager| Box::pin(manager.resolve_oversize_content(id, accept))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Settle a clip held back by the `never_overwrite` conflict policy.
+///
+/// # Arguments
+/// * `id` - Id from the `clipboard-conflict` event
+/// * `accept` - Replace the local clip with it when true, discard it otherwise
+///
+/// # Returns
+/// Whether a held clip with this id was found
+///
+/// # Errors
+/// When `id` is not a valid UUID, or accepting while sync is not running
+#[tauri::command]
+pub async fn resolve_clipboard_conflict(id: String, accept: bool, state: State<'_, AppState>) -> Result<bool, String> {
+    let id = uuid::Uuid::parse_str(&id).map_err(|e| e.to_string())?;
+    state.service_manager.execute(move |manager| Box::pin(manager.resolve_clipboard_conflict(id, accept))).await
+        .map_err(|e| e.to_string())
+}
+
+/// Look up the preview of a recently sent or received clip.
+///
+/// # Arguments
+/// * `id` - `message_id` from a `clipboard-synced` event
+///
+/// # Returns
+/// The preview, or `None` once it has aged out of the cache
+///
+/// # Errors
+/// When `id` is not a valid UUID
+#[tauri::command]
+pub async fn get_message_preview(id: String, state: State<'_, AppState>) -> Result<Option<MessagePreview>, String> {
+    let id = uuid::Uuid::parse_str(&id).map_err(|e| e.to_string())?;
+    state.service_manager.query(move |manager| Box::pin(async move { manager.get_message_preview(&id) })).await
+        .map_err(|e| e.to_string())
+}
+
+/// Send the current clipboard to peers right away, the same action as the
+/// push shortcut.
+///
+/// # Errors
+/// When sync is not running or paused, or the clipboard holds no text
+#[tauri::command]
+pub async fn push_clipboard_now(state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(|manager| Box::pin(manager.push_clipboard_now())).await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,63 @@
+use crate::models::PresentationStatus;
+use crate::AppState;
+use tauri::State;
+
+/// Broadcast this clipboard to read-only viewers that join with the
+/// returned session code.
+///
+/// # Returns
+/// The presentation, including its code
+///
+/// # Errors
+/// When sync is not running or this device is viewing a presentation
+#[tauri::command]
+pub async fn start_presentation(state: State<'_, AppState>) -> Result<PresentationStatus, String> {
+    state.service_manager.execute(|manager| Box::pin(manager.start_presentation())).await
+        .map_err(|e| e.to_string())
+}
+
+/// End the presentation and disconnect its viewers.
+///
+/// # Errors
+/// When sync is not running or this device is not presenting
+#[tauri::command]
+pub async fn stop_presentation(state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(|manager| Box::pin(manager.stop_presentation())).await
+        .map_err(|e| e.to_string())
+}
+
+/// Follow a presenter's clipboard as a read-only viewer.
+///
+/// # Arguments
+/// * `address` - IP address of the presenting device
+/// * `port` - Its sync port
+/// * `code` - Session code shown on the presenter
+/// * `fingerprint` - Certificate fingerprint, when the presenter uses TLS
+///
+/// # Errors
+/// When sync is not running, the address is invalid, or this device is
+/// already in a presentation
+#[tauri::command]
+pub async fn join_presentation(address: String, port: u16, code: String, fingerprint: Option<String>, state: State<'_, AppState>) -> Result<PresentationStatus, String> {
+    let address = address.trim().parse().map_err(|_| format!("Not an IP address: {}", address))?;
+    state.service_manager.execute(move |manager| Box::pin(async move {
+        manager.join_presentation(address, port, &code, fingerprint).await
+    })).await
+        .map_err(|e| e.to_string())
+}
+
+/// Stop following the presenter.
+///
+/// # Errors
+/// When sync is not running or this device is not viewing a presentation
+#[tauri::command]
+pub async fn leave_presentation(state: State<'_, AppState>) -> Result<(), String> {
+    state.service_manager.execute(|manager| Box::pin(manager.leave_presentation())).await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn get_presentation_status(state: State<'_, AppState>) -> Result<PresentationStatus, String> {
+    state.service_manager.query(|manager| Box::pin(manager.get_presentation_status())).await
+        .map_err(|e| e.to_string())
+}
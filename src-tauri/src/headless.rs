@@ -0,0 +1,126 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{bail, Context, Result};
+use uuid::Uuid;
+use crate::models::Config;
+use crate::services;
+use crate::services::device_names;
+use crate::services::key_derivation::{self, KeySalt};
+use crate::services::manager::{ManagerHandle, ServiceManager};
+use crate::utils::{crash_report, log_file};
+
+/// Run the sync engine without a window, e.g. on a home server.
+pub const HEADLESS_ARG: &str = "--headless";
+/// Followed by the TOML or JSON config file to run headless with.
+const CONFIG_ARG: &str = "--config";
+/// Followed by the directory for history, certificates, and logs of
+/// applied clips. Defaults to the config file's directory.
+const DATA_DIR_ARG: &str = "--data-dir";
+/// File under the data dir holding this device's stable id.
+const DEVICE_ID_FILE: &str = "device_id";
+/// File under the data dir holding the salt for deriving wire keys.
+const KEY_SALT_FILE: &str = "key_salt";
+
+/// Whether this run was asked to go without the GUI.
+pub fn requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == HEADLESS_ARG)
+}
+
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    args.find(|arg| arg == name)?;
+    args.next()
+}
+
+/// Read a config file, as TOML when its extension says so and as JSON
+/// otherwise. Fields left out keep their defaults.
+fn load_config(path: &Path) -> Result<Config> {
+    let text = fs::read_to_string(path).with_context(|| format!("Cannot read config file {}", path.display()))?;
+    let config = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("toml") => toml::from_str(&text)?,
+        _ => serde_json::from_str(&text)?,
+    };
+    Ok(config)
+}
+
+/// This device's id, created on first run so peers know it across restarts.
+fn device_id(dir: &Path) -> Result<Uuid> {
+    let path = dir.join(DEVICE_ID_FILE);
+    if let Some(id) = fs::read_to_string(&path).ok().and_then(|text| text.trim().parse().ok()) {
+        return Ok(id);
+    }
+    let id = Uuid::new_v4();
+    fs::write(&path, id.to_string())?;
+    tracing::info!("Created device id {}", id);
+    Ok(id)
+}
+
+/// This installation's salt for deriving wire keys, created on first run.
+fn key_salt(dir: &Path) -> Result<KeySalt> {
+    let path = dir.join(KEY_SALT_FILE);
+    if let Some(salt) = fs::read_to_string(&path).ok().and_then(|text| serde_json::from_str(&text).ok()) {
+        return Ok(salt);
+    }
+    let salt = key_derivation::new_salt();
+    fs::write(&path, serde_json::to_string(&salt)?)?;
+    Ok(salt)
+}
+
+/// Wait for Ctrl-C or, where there is one, a service manager's SIGTERM.
+async fn exit_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Start sync with the config file named on the command line and run
+/// until told to exit. Settings changed while running are not written
+/// back to the file.
+///
+/// # Errors
+/// When no config file is given, it cannot be read or is invalid, or the
+/// services fail to start
+pub fn run() -> Result<()> {
+    let Some(config_path) = arg_value(CONFIG_ARG).map(PathBuf::from) else {
+        bail!("{} needs {} <file>", HEADLESS_ARG, CONFIG_ARG);
+    };
+    let config = load_config(&config_path)?;
+    let data_dir = arg_value(DATA_DIR_ARG).map(PathBuf::from).unwrap_or_else(|| {
+        config_path.parent().map(Path::to_path_buf).unwrap_or_default()
+    });
+    fs::create_dir_all(&data_dir).with_context(|| format!("Data directory {} is not writable", data_dir.display()))?;
+    if let Err(e) = log_file::set_dir(&data_dir) {
+        tracing::error!("Failed to open log files: {}", e);
+    }
+    crash_report::set_report_dir(data_dir.clone());
+    services::audit::set_dir(data_dir.clone());
+    device_names::set_local_device_id(device_id(&data_dir)?);
+    let key_salt = key_salt(&data_dir)?;
+    tracing::info!("Running headless with {} and data in {}", config_path.display(), data_dir.display());
+
+    tauri::async_runtime::block_on(async move {
+        let mut manager = ServiceManager::new();
+        manager.set_data_dir(data_dir);
+        manager.set_key_salt(key_salt);
+        manager.update_config(config).await?;
+        // After the config, whose security key may unlock the history
+        if let Err(e) = manager.init_history() {
+            tracing::error!("Failed to open clipboard history: {}", e);
+        }
+        let handle = ManagerHandle::spawn(manager);
+        handle.start().await?;
+
+        exit_signal().await;
+        tracing::info!("Shutting down");
+        handle.shutdown().await
+    })
+}
@@ -0,0 +1,37 @@
+use anyhow::Result;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Manager, Wry};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
+use crate::AppState;
+
+/// The global shortcut plugin, wired to push the clipboard when pressed.
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_global_shortcut::Builder::new()
+        .with_handler(|app, _shortcut, event| {
+            if event.state() != ShortcutState::Pressed {
+                return;
+            }
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let manager = app.state::<AppState>().service_manager.clone();
+                if let Err(e) = manager.execute(|manager| Box::pin(manager.push_clipboard_now())).await {
+                    tracing::warn!("Push shortcut ignored: {}", e);
+                }
+            });
+        })
+        .build()
+}
+
+/// Register the configured push shortcut, replacing any earlier one.
+///
+/// # Errors
+/// When the shortcut cannot be parsed or another app already holds it
+pub fn register(app: &AppHandle, shortcut: Option<&str>) -> Result<()> {
+    let shortcuts = app.global_shortcut();
+    shortcuts.unregister_all()?;
+    if let Some(shortcut) = shortcut.filter(|shortcut| !shortcut.trim().is_empty()) {
+        shortcuts.register(shortcut.trim())?;
+        tracing::info!("Registered push shortcut {}", shortcut.trim());
+    }
+    Ok(())
+}
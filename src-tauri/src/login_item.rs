@@ -0,0 +1,38 @@
+use anyhow::Result;
+use tauri::plugin::TauriPlugin;
+use tauri::{AppHandle, Wry};
+use tauri_plugin_autostart::{MacosLauncher, ManagerExt};
+
+/// Passed to the app when the OS starts it at login, so it can start in
+/// the tray instead of opening its window.
+pub const LOGIN_ARG: &str = "--launched-at-login";
+
+/// The autostart plugin: a registry Run key on Windows, a LaunchAgent on
+/// macOS, and an XDG autostart `.desktop` entry on Linux.
+pub fn plugin() -> TauriPlugin<Wry> {
+    tauri_plugin_autostart::init(MacosLauncher::LaunchAgent, Some(vec![LOGIN_ARG]))
+}
+
+/// Add or remove the login item so it matches `enabled`. Leaves the OS
+/// alone when it already agrees.
+///
+/// # Errors
+/// When the login item cannot be read, written, or removed
+pub fn apply(app: &AppHandle, enabled: bool) -> Result<()> {
+    let autolaunch = app.autolaunch();
+    if autolaunch.is_enabled()? == enabled {
+        return Ok(());
+    }
+    if enabled {
+        autolaunch.enable()?;
+    } else {
+        autolaunch.disable()?;
+    }
+    tracing::info!("{} launch at login", if enabled { "Enabled" } else { "Disabled" });
+    Ok(())
+}
+
+/// Whether the OS started this run at login.
+pub fn launched_at_login() -> bool {
+    std::env::args().any(|arg| arg == LOGIN_ARG)
+}
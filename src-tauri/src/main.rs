@@ -7,45 +7,133 @@ mod commands;
 mod services;
 mod models;
 mod utils;
+mod tray;
+mod hotkey;
+mod headless;
+mod login_item;
 
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use services::manager::ServiceManager;
+use services::manager::{ManagerHandle, ServiceManager};
 use tauri::Manager;
+use tracing_subscriber::{fmt, prelude::*};
+use utils::{crash_report, log_file, log_level, paths, safe_mode};
+use utils::log_buffer::LogBufferWriter;
+use utils::log_file::LogFileWriter;
+
+/// Longest the app waits for services to stop when exiting.
+const SHUTDOWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
 
 struct AppState {
-    service_manager: Arc<Mutex<ServiceManager>>,
+    service_manager: ManagerHandle,
 }
 
 fn main() {
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::INFO)
+    // Log to stdout and keep recent lines in memory for remote
+    // troubleshooting, and in files once the data dir is known
+    tracing_subscriber::registry()
+        .with(log_level::layer())
+        .with(fmt::layer())
+        .with(fmt::layer().with_ansi(false).with_writer(LogBufferWriter))
+        .with(fmt::layer().with_ansi(false).with_writer(LogFileWriter))
         .init();
+    crash_report::install();
+    paths::init();
+
+    // Sync engine only, for machines without a desktop
+    if headless::requested() {
+        if let Err(e) = headless::run() {
+            tracing::error!("Headless mode failed: {:#}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    let service_manager = Arc::new(Mutex::new(ServiceManager::new()));
+    // Spawned from within Tauri's runtime, which the manager's task runs on
+    let service_manager = tauri::async_runtime::block_on(async { ManagerHandle::spawn(ServiceManager::new()) });
+    let exit_manager = service_manager.clone();
 
     tauri::Builder::default()
+        // First, so a second launch hands over to this one before it
+        // starts a backend of its own
+        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+            tracing::info!("App launched again; showing the running instance");
+            tray::show_main_window(app);
+        }))
         .plugin(tauri_plugin_store::Builder::new().build())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(hotkey::plugin())
+        .plugin(login_item::plugin())
         .manage(AppState {
             service_manager: service_manager.clone(),
         })
         .setup(move |app| {
+            match paths::data_dir(app.handle()) {
+                Ok(dir) => {
+                    if let Err(e) = log_file::set_dir(&dir) {
+                        tracing::error!("Failed to open log files: {}", e);
+                    }
+                    crash_report::set_report_dir(dir.clone());
+                    services::audit::set_dir(dir.clone());
+                    safe_mode::begin(dir);
+                }
+                Err(e) => tracing::error!("No app data directory for crash reports and the audit trail: {}", e),
+            }
+            if let Err(e) = tray::init(app.handle()) {
+                tracing::error!("Failed to create tray icon: {}", e);
+            }
+            // Started at login: stay in the tray until the user opens it
+            if login_item::launched_at_login() {
+                if let Some(window) = app.get_webview_window("main") {
+                    let _ = window.hide();
+                }
+            }
+            
             // Set app handle and load config
             let service_manager = service_manager.clone();
             let app_handle = app.app_handle().clone();
             
             tauri::async_runtime::spawn(async move {
-                let mut manager = service_manager.lock().await;
-                manager.set_app_handle(app_handle);
+                let handle = app_handle.clone();
+                let power = service_manager.clone();
+                let config = service_manager.query(move |manager| Box::pin(async move {
+                    services::power::spawn(handle.clone(), power, &manager.background_tasks());
+                    manager.set_app_handle(handle).await;
+                    
+                    // Load saved config
+                    if let Err(e) = manager.load_config().await {
+                        tracing::error!("Failed to load config: {}", e);
+                    }
+                    
+                    // After the config, whose security key may unlock the history
+                    if let Err(e) = manager.init_history() {
+                        tracing::error!("Failed to open clipboard history: {}", e);
+                    }
+                    manager.get_config().await
+                })).await;
+                let Ok(config) = config else {
+                    tracing::error!("Service manager stopped during startup");
+                    return;
+                };
                 
-                // Load saved config
-                if let Err(e) = manager.load_config().await {
-                    tracing::error!("Failed to load config: {}", e);
+                // After repeated crashes, leave everything off and let the
+                // user pick a way out
+                if safe_mode::is_active() {
+                    services::events::emit(Some(&app_handle), services::events::SAFE_MODE_EVENT, safe_mode::status());
+                    return;
                 }
+                tauri::async_runtime::spawn(async {
+                    tokio::time::sleep(safe_mode::STABLE_AFTER).await;
+                    safe_mode::mark_stable();
+                });
                 
-                let config = manager.get_config().await;
+                if let Err(e) = hotkey::register(&app_handle, config.push_shortcut.as_deref()) {
+                    tracing::error!("Failed to register push shortcut: {}", e);
+                }
+                // The login item may have been removed outside the app
+                if let Err(e) = login_item::apply(&app_handle, config.launch_at_login) {
+                    tracing::error!("Failed to update launch at login: {}", e);
+                }
                 if config.auto_start && config.sync_enabled {
-                    if let Err(e) = manager.start().await {
+                    if let Err(e) = service_manager.start().await {
                         tracing::error!("Failed to auto-start services: {}", e);
                     }
                 }
@@ -53,16 +141,93 @@ fn main() {
             
             Ok(())
         })
+        // Closing the window leaves the app running in the tray
+        .on_window_event(|window, event| {
+            if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                api.prevent_close();
+                let _ = window.hide();
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             commands::get_config,
+            commands::get_effective_config,
+            commands::get_last_crash_report,
+            commands::get_safe_mode_status,
+            commands::exit_safe_mode,
+            commands::capture_diagnostics,
+            commands::get_audit_log,
+            commands::get_recent_logs,
+            commands::get_log_level,
+            commands::set_log_level,
+            commands::get_protocol_trace,
             commands::set_config,
+            commands::set_launch_at_login,
+            commands::export_config,
+            commands::import_config,
+            commands::list_profiles,
+            commands::save_profile,
+            commands::activate_profile,
             commands::start_sync,
             commands::stop_sync,
+            commands::pause_sync,
+            commands::resume_sync,
+            commands::is_sync_paused,
             commands::get_discovered_devices,
+            commands::get_discovery_status,
+            commands::trust_device,
+            commands::pair_guest_device,
+            commands::untrust_device,
+            commands::forget_device,
+            commands::mute_device,
+            commands::unmute_device,
+            commands::add_manual_peer,
+            commands::remove_manual_peer,
+            commands::get_history,
+            commands::search_history,
+            commands::get_history_entry,
+            commands::delete_history_entry,
+            commands::restore_history_entry,
+            commands::set_history_tags,
+            commands::pin_history_entry,
+            commands::unpin_history_entry,
+            commands::get_pinned_entries,
+            commands::clear_history,
+            commands::run_maintenance_now,
+            commands::create_api_token,
+            commands::revoke_api_token,
+            commands::list_api_tokens,
+            commands::resolve_oversize_content,
+            commands::resolve_clipboard_conflict,
+            commands::push_clipboard_now,
+            commands::start_presentation,
+            commands::stop_presentation,
+            commands::join_presentation,
+            commands::leave_presentation,
+            commands::get_presentation_status,
+            commands::get_message_preview,
             commands::get_sync_status,
+            commands::get_status_summary,
+            commands::get_sync_metrics,
+            commands::reset_sync_metrics,
+            commands::get_connected_peers,
+            commands::get_peer_metrics,
+            commands::get_current_desktop,
             commands::test_connection,
             commands::is_dev_mode,
+            commands::request_peer_logs,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |_app, event| {
+            // Close peer connections and withdraw the mDNS record on the
+            // way out, however the exit was triggered
+            if let tauri::RunEvent::Exit = event {
+                let shutdown = tokio::time::timeout(SHUTDOWN_TIMEOUT, exit_manager.shutdown());
+                match tauri::async_runtime::block_on(shutdown) {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => tracing::error!("Failed to shut down services: {}", e),
+                    Err(_) => tracing::warn!("Services did not shut down within {:?}", SHUTDOWN_TIMEOUT),
+                }
+            }
+        });
 }
\ No newline at end of file
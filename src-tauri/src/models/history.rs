@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+/// Whether a history entry was copied on this machine or received from a peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryOrigin {
+    Local,
+    Remote,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub content: String,
+    pub origin: HistoryOrigin,
+    /// Sending device for remote entries
+    pub device: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Peers a local entry was sent to, when it was sent
+    pub sent_to: Option<u32>,
+    /// Devices that reported applying a local entry
+    pub applied_on: u32,
+    /// Pinned entries are never removed by retention
+    pub pinned: bool,
+    pub tags: Vec<String>,
+}
+
+/// One page of history, newest first, plus the total entry count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    pub total: u64,
+}
+
+/// Outcome of a history maintenance run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    pub entries_removed: usize,
+    pub blobs_evicted: usize,
+    /// Freed by vacuuming the database and evicting blobs together
+    pub bytes_reclaimed: u64,
+    pub finished_at: DateTime<Utc>,
+}
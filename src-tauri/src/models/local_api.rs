@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use super::{ClipboardContent, HistoryOrigin, StatusSummary};
+
+/// What a local API token lets a script do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiScope {
+    /// Receive clipboard events only
+    Read,
+    /// Receive clipboard events and push clipboard content
+    ReadWrite,
+}
+
+impl ApiScope {
+    /// Whether a client may push clipboard content.
+    pub fn can_push(self) -> bool {
+        self == ApiScope::ReadWrite
+    }
+}
+
+/// A local API token as shown in the UI; the secret itself is never kept.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiTokenInfo {
+    pub id: Uuid,
+    pub scope: ApiScope,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A freshly minted token. `token` is only ever returned here.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedApiToken {
+    #[serde(flatten)]
+    pub info: ApiTokenInfo,
+    pub token: String,
+}
+
+/// Request a local API client may send. `status_summary` answers with the
+/// current summary and subscribes the client to later changes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApiRequest {
+    StatusSummary,
+}
+
+/// Notice pushed to local API clients that asked for one, tagged by `type`
+/// so it can't be mistaken for an `ApiClipboardEvent`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ApiNotice {
+    StatusSummary(StatusSummary),
+}
+
+/// Event pushed to local API clients when the clipboard changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiClipboardEvent {
+    pub content: ClipboardContent,
+    pub origin: HistoryOrigin,
+    pub device: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
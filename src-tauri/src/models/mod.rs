@@ -2,116 +2,458 @@ use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Wire types shared with other clients
+pub use unimesh_proto::message::*;
+
+mod history;
+mod local_api;
+mod peers;
+mod status;
+
+pub use history::*;
+pub use local_api::*;
+pub use peers::*;
+pub use status::*;
+
+/// Broad category of clipboard content, used by per-type policies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
-pub enum MessageType {
-    ClipboardUpdate,
-    Heartbeat,
-    DeviceInfo,
+pub enum ContentKind {
+    #[default]
+    Text,
+    Image,
+    File,
 }
 
+impl From<DescriptorKind> for ContentKind {
+    fn from(kind: DescriptorKind) -> Self {
+        match kind {
+            DescriptorKind::Text | DescriptorKind::Rich => Self::Text,
+            DescriptorKind::Image => Self::Image,
+            DescriptorKind::File => Self::File,
+        }
+    }
+}
+
+/// Where an applied remote clip came from.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ClipboardMessage {
-    pub id: Uuid,
-    #[serde(rename = "type")]
-    pub msg_type: MessageType,
-    pub content: Option<String>,
-    pub timestamp: DateTime<Utc>,
-    pub signature: Option<String>,
+pub struct ClipOrigin {
+    pub peer_id: Uuid,
+    pub address: std::net::SocketAddr,
     pub device: Option<DeviceInfo>,
+    /// Id of the message that carried the clip
+    #[serde(default)]
+    pub message_id: Option<Uuid>,
+    /// When the sender says it sent the clip, by its own clock
+    #[serde(default)]
+    pub sent_at: Option<DateTime<Utc>>,
+    /// When this device received the clip, by our clock
+    #[serde(default)]
+    pub received_at: Option<DateTime<Utc>>,
+    /// What the clip is, from the sender's content descriptor
+    #[serde(default)]
+    pub kind: ContentKind,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DeviceInfo {
-    pub name: String,
-    pub platform: String,
-    pub version: String,
+impl ClipOrigin {
+    /// Human-readable name of the sender, falling back to its IP address.
+    pub fn display_name(&self) -> String {
+        match self.device {
+            Some(ref device) => device.name.clone(),
+            None => self.address.ip().to_string(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    pub websocket_port: u16,
-    pub mdns_service_name: String,
-    pub security_key: Option<String>,
-    pub auto_start: bool,
-    pub sync_enabled: bool,
+/// How the user is told that a remote clip was applied locally.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplyCue {
+    #[default]
+    None,
+    Sound,
+    Notification,
 }
 
-impl Default for Config {
+/// How much sync activity is announced for screen readers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnnouncementVerbosity {
+    #[default]
+    Off,
+    /// Clips received, peers going away, and failures
+    Essential,
+    /// Also clips sent and peers connecting
+    Verbose,
+}
+
+/// What to do with a clip larger than `Config::max_content_bytes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizePolicy {
+    /// Drop the clip
+    #[default]
+    Skip,
+    /// Drop rich formats, then cut the text to the limit
+    Truncate,
+    /// Hold the clip and ask the user via an `oversize-content` event
+    Prompt,
+}
+
+/// How an incoming clip is handled when the local clipboard changed after
+/// the peer sent it, so both sides wrote at about the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Keep whichever clip was written last
+    LastWriterWins,
+    /// Keep the local clip and ask via a `clipboard-conflict` event
+    NeverOverwrite,
+    /// Keep the local clip and only record the incoming one in history
+    HistoryOnly,
+}
+
+/// Conflict policy for each kind of content.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConflictPolicies {
+    pub text: ConflictPolicy,
+    pub image: ConflictPolicy,
+    pub file: ConflictPolicy,
+}
+
+impl Default for ConflictPolicies {
     fn default() -> Self {
         Self {
-            websocket_port: 8765,
-            mdns_service_name: "unimesh-clip".to_string(),
-            security_key: None,
-            auto_start: true,
-            sync_enabled: false,
+            text: ConflictPolicy::LastWriterWins,
+            image: ConflictPolicy::NeverOverwrite,
+            file: ConflictPolicy::HistoryOnly,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DiscoveredDevice {
-    pub name: String,
-    pub address: String,
-    pub port: u16,
-    pub last_seen: DateTime<Utc>,
-    pub trusted: bool,
+impl ConflictPolicies {
+    pub fn for_kind(&self, kind: ContentKind) -> ConflictPolicy {
+        match kind {
+            ContentKind::Text => self.text,
+            ContentKind::Image => self.image,
+            ContentKind::File => self.file,
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct MessageCache {
-    pub processed_messages: std::collections::HashMap<Uuid, DateTime<Utc>>,
-    pub last_cleanup: DateTime<Utc>,
+/// What is stripped from received rich formats before they are applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatSecurityPolicy {
+    /// Remove scripts, event handlers, `javascript:` URLs, and references
+    /// that load external resources from HTML
+    pub sanitize_html: bool,
+    /// Drop RTF that embeds OLE objects, keeping the other formats
+    pub drop_rtf_objects: bool,
+    /// File extensions refused when received, without the dot
+    pub blocked_file_extensions: Vec<String>,
 }
 
-impl MessageCache {
-    pub fn new() -> Self {
+impl Default for FormatSecurityPolicy {
+    fn default() -> Self {
         Self {
-            processed_messages: std::collections::HashMap::new(),
-            last_cleanup: Utc::now(),
+            sanitize_html: true,
+            drop_rtf_objects: true,
+            blocked_file_extensions: [
+                "exe", "com", "scr", "pif", "bat", "cmd", "msi", "msp", "dll", "cpl", "hta", "jar",
+                "js", "jse", "vbs", "vbe", "wsf", "ps1", "reg", "lnk", "sh", "command", "app", "scpt",
+            ]
+            .into_iter()
+            .map(str::to_string)
+            .collect(),
         }
     }
+}
 
-    pub fn is_duplicate(&self, message_id: &Uuid) -> bool {
-        self.processed_messages.contains_key(message_id)
-    }
+/// What a matching `FilterRule` does to an outgoing clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    /// Send the clip without checking later rules
+    Allow,
+    /// Never send the clip
+    Block,
+    /// Replace what the pattern matches, then keep checking
+    Redact,
+}
+
+/// Regex checked against local clips before they leave the machine.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterRule {
+    pub pattern: String,
+    pub action: FilterAction,
+}
+
+/// How sync behaves after the system wakes from sleep.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResumePolicy {
+    /// Keep syncing as before
+    #[default]
+    Auto,
+    /// Pause and ask the user via a `system-resumed` event
+    Ask,
+    /// Pause until the user resumes sync
+    StayPaused,
+}
 
-    pub fn add_message(&mut self, message_id: Uuid) {
-        self.processed_messages.insert(message_id, Utc::now());
+/// Which way clips flow for this installation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncMode {
+    /// Send local changes and apply peers' clips
+    #[default]
+    Bidirectional,
+    /// Send local changes; never apply peers' clips
+    SendOnly,
+    /// Apply peers' clips; never send local changes
+    ReceiveOnly,
+}
+
+impl SyncMode {
+    pub fn sends(self) -> bool {
+        self != SyncMode::ReceiveOnly
     }
 
-    pub fn cleanup_old_messages(&mut self) {
-        let cutoff = Utc::now() - chrono::Duration::minutes(5);
-        self.processed_messages.retain(|_, timestamp| *timestamp > cutoff);
-        self.last_cleanup = Utc::now();
+    pub fn receives(self) -> bool {
+        self != SyncMode::SendOnly
     }
+}
+
+/// Cue override for a device and/or content kind. `None` fields match anything.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyCueRule {
+    /// Device name or IP address of the sender
+    pub device: Option<String>,
+    pub content_kind: Option<ContentKind>,
+    pub cue: ApplyCue,
+}
+
+/// Where local clips in a language or script may go. Clips in other
+/// languages are not affected.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LanguageRoute {
+    /// ISO 639-3 code (`jpn`), English name (`Japanese`), script
+    /// (`Cyrillic`, `Hangul`), or `cjk` for Chinese, Japanese, and Korean
+    pub language: String,
+    /// Device names or IP addresses sent matching clips; empty to keep
+    /// them on this device
+    pub devices: Vec<String>,
+}
+
+/// A device dialed by address, for networks where mDNS does not work.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManualPeer {
+    pub address: std::net::IpAddr,
+    pub port: u16,
+    /// Certificate fingerprint to pin; when set the peer is reached over TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+/// A folder whose new text files are sent to peers as clips, for tools
+/// that can write files but not use the local API.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WatchedFolder {
+    pub path: String,
+    /// Extensions sent, without the dot; empty for `txt` and `md`
+    #[serde(default)]
+    pub extensions: Vec<String>,
+    /// Larger files are left alone
+    #[serde(default = "default_watched_max_bytes")]
+    pub max_bytes: u64,
+    /// Delete files once they are sent
+    #[serde(default)]
+    pub delete_after_send: bool,
+}
+
+fn default_watched_max_bytes() -> u64 {
+    64 * 1024
+}
 
-    pub fn should_cleanup(&self) -> bool {
-        Utc::now() - self.last_cleanup > chrono::Duration::minutes(1)
+/// A device that is always dialed while sync runs, by host name or IP, for
+/// headless machines and networks that block mDNS.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerEndpoint {
+    pub host: String,
+    pub port: u16,
+    /// Certificate fingerprint to pin; when set the peer is reached over TLS
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+}
+
+impl From<&ManualPeer> for PeerEndpoint {
+    fn from(peer: &ManualPeer) -> Self {
+        Self {
+            host: peer.address.to_string(),
+            port: peer.port,
+            fingerprint: peer.fingerprint.clone(),
+        }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SyncMetrics {
-    pub messages_sent: u64,
-    pub messages_received: u64,
-    pub messages_failed: u64,
-    pub clipboard_updates_applied: u64,
-    pub clipboard_updates_failed: u64,
-    pub last_sync_time: Option<DateTime<Utc>>,
-    pub connected_peers: u32,
+#[serde(default)]
+pub struct Config {
+    pub websocket_port: u16,
+    pub mdns_service_name: String,
+    /// Name shown to peers in their device lists and notifications
+    pub device_name: String,
+    pub security_key: Option<String>,
+    /// Hours messages signed with the previous key are still accepted after
+    /// the key changes, while peers catch up (0 to stop at once)
+    pub key_rotation_grace_hours: u32,
+    /// Sync with builds that predate derived keys. They are sent messages
+    /// keyed with the passphrase itself, which is cheap to brute-force
+    pub legacy_key_peers: bool,
+    pub auto_start: bool,
+    /// Have the OS start the app, in the tray, when the user logs in
+    pub launch_at_login: bool,
+    pub sync_enabled: bool,
+    /// Let paired devices fetch recent (sanitized) log lines for troubleshooting
+    pub allow_remote_log_requests: bool,
+    /// Cue used when no rule in `apply_cue_rules` matches
+    pub apply_cue: ApplyCue,
+    pub apply_cue_rules: Vec<ApplyCueRule>,
+    /// DANGEROUS: write full clipboard contents to debug logs. Leaks copied
+    /// secrets into log files and remote log responses; never enable casually.
+    pub dangerously_log_clipboard_content: bool,
+    /// Only exchange clipboard content with explicitly trusted devices
+    pub trusted_only: bool,
+    /// Delete history entries older than this many days (0 keeps them forever)
+    pub history_retention_days: u32,
+    /// Keep at most this many history entries (0 for no limit)
+    pub history_max_entries: u32,
+    /// Days to keep entries carrying each tag, overriding both limits above
+    /// (0 keeps them forever)
+    pub history_tag_retention: std::collections::BTreeMap<String, u32>,
+    /// Largest size of the blob store, in MiB; the least recently written
+    /// blobs are evicted beyond it (0 for no limit)
+    pub blob_store_max_mb: u32,
+    /// Highest rate to send clips at, in KiB/s, by device address (e.g.
+    /// `192.168.1.20`); devices not listed are not throttled
+    pub bandwidth_caps: std::collections::BTreeMap<String, u32>,
+    /// Never send clipboard content that password managers mark as sensitive
+    pub skip_concealed_content: bool,
+    /// Ignore discovered services on this machine's addresses and our own
+    /// port, assuming they are this instance
+    pub filter_same_host: bool,
+    /// Largest clip sent or accepted, in bytes across all formats (0 for no limit)
+    pub max_content_bytes: u64,
+    pub oversize_policy: OversizePolicy,
+    pub conflict_policies: ConflictPolicies,
+    pub format_security: FormatSecurityPolicy,
+    /// Applied in order to local clips before they are sent
+    pub content_filters: Vec<FilterRule>,
+    /// Checked in order against the detected language of local text
+    /// clips; the first match decides which devices receive the clip
+    pub language_routes: Vec<LanguageRoute>,
+    /// Virtual desktops peer clips are applied on; clips arriving while
+    /// another desktop is active wait until the user returns. Empty
+    /// applies clips on every desktop
+    pub work_desktops: Vec<String>,
+    /// Folders whose new text files are sent to peers
+    pub watched_folders: Vec<WatchedFolder>,
+    pub resume_policy: ResumePolicy,
+    pub sync_mode: SyncMode,
+    /// Serve peers over `wss://` with a self-signed certificate
+    pub tls_enabled: bool,
+    /// Send local clipboard changes automatically; when off, clips are
+    /// only sent with `push_shortcut` or `push_clipboard_now`
+    pub monitor_clipboard: bool,
+    /// Global shortcut that sends the current clipboard, e.g. `CommandOrControl+Shift+C`
+    pub push_shortcut: Option<String>,
+    /// Tell the sending device when a clip from it is applied here
+    pub send_receipts: bool,
+    /// Show an OS notification when a peer's clip is applied
+    pub notifications_enabled: bool,
+    /// Short summaries of sync activity sent as `announcement` events,
+    /// for screen readers
+    pub announcement_verbosity: AnnouncementVerbosity,
+    /// Fastest heartbeat cadence, used for new or flaky links
+    pub heartbeat_min_secs: u32,
+    /// Slowest heartbeat cadence a stable link backs off to
+    pub heartbeat_max_secs: u32,
+    /// Devices to connect to directly, in addition to those found by mDNS
+    pub manual_peers: Vec<ManualPeer>,
+    /// Devices dialed on every start regardless of discovery
+    pub static_peers: Vec<PeerEndpoint>,
+    /// Extra source addresses (IPs or CIDR ranges) allowed to reach local
+    /// API endpoints; loopback is always allowed
+    pub api_allowed_sources: Vec<String>,
+    /// Browser origins allowed to open connections. Requests carrying any
+    /// other `Origin` header are refused on every endpoint.
+    pub api_allowed_origins: Vec<String>,
+    /// Serve Prometheus metrics at `/metrics` on this port, e.g. for
+    /// headless servers; `None` to turn it off. Reachable from the same
+    /// sources as the local API.
+    pub metrics_port: Option<u16>,
+    /// Send MessagePack in binary frames to peers that offer it, instead
+    /// of JSON text; takes effect on new connections
+    pub binary_framing: bool,
 }
 
-impl Default for SyncMetrics {
+impl Default for Config {
     fn default() -> Self {
         Self {
-            messages_sent: 0,
-            messages_received: 0,
-            messages_failed: 0,
-            clipboard_updates_applied: 0,
-            clipboard_updates_failed: 0,
-            last_sync_time: None,
-            connected_peers: 0,
+            websocket_port: 8765,
+            mdns_service_name: "unimesh-clip".to_string(),
+            device_name: crate::services::device_names::host_name(),
+            security_key: None,
+            key_rotation_grace_hours: 24,
+            legacy_key_peers: false,
+            auto_start: true,
+            launch_at_login: false,
+            sync_enabled: false,
+            allow_remote_log_requests: false,
+            apply_cue: ApplyCue::None,
+            apply_cue_rules: Vec::new(),
+            dangerously_log_clipboard_content: false,
+            trusted_only: false,
+            history_retention_days: 30,
+            history_max_entries: 10_000,
+            history_tag_retention: std::collections::BTreeMap::new(),
+            blob_store_max_mb: 512,
+            bandwidth_caps: std::collections::BTreeMap::new(),
+            skip_concealed_content: true,
+            filter_same_host: true,
+            max_content_bytes: 10 * 1024 * 1024,
+            oversize_policy: OversizePolicy::Skip,
+            conflict_policies: ConflictPolicies::default(),
+            format_security: FormatSecurityPolicy::default(),
+            content_filters: Vec::new(),
+            language_routes: Vec::new(),
+            work_desktops: Vec::new(),
+            resume_policy: ResumePolicy::Auto,
+            sync_mode: SyncMode::Bidirectional,
+            tls_enabled: false,
+            monitor_clipboard: true,
+            push_shortcut: None,
+            send_receipts: true,
+            notifications_enabled: false,
+            announcement_verbosity: AnnouncementVerbosity::Off,
+            heartbeat_min_secs: 5,
+            heartbeat_max_secs: 30,
+            manual_peers: Vec::new(),
+            static_peers: Vec::new(),
+            api_allowed_sources: Vec::new(),
+            api_allowed_origins: Vec::new(),
+            metrics_port: None,
+            binary_framing: true,
+            watched_folders: Vec::new(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl Config {
+    /// The shared security key, treating an empty string as unset.
+    pub fn effective_security_key(&self) -> Option<&str> {
+        self.security_key.as_deref().filter(|key| !key.is_empty())
+    }
+}
@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+/// How serious a version difference with a peer is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MismatchSeverity {
+    /// Still works, but one side is behind
+    Deprecated,
+    /// The devices cannot sync until one of them is upgraded
+    Incompatible,
+}
+
+/// Which device should be upgraded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpgradeSide {
+    Local,
+    Peer,
+}
+
+/// Payload of the `peer-version-mismatch` event, also shown on the device.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerVersionMismatch {
+    pub address: String,
+    pub peer_app_version: Option<String>,
+    pub peer_protocol: u32,
+    pub local_app_version: String,
+    pub local_protocol: u32,
+    pub severity: MismatchSeverity,
+    pub upgrade: UpgradeSide,
+    pub detected_at: DateTime<Utc>,
+}
+
+impl PeerVersionMismatch {
+    /// One-line explanation suitable for the user.
+    pub fn describe(&self) -> String {
+        let peer = self.peer_app_version.as_deref().unwrap_or("unknown");
+        let action = match (self.severity, self.upgrade) {
+            (MismatchSeverity::Incompatible, UpgradeSide::Local) => "upgrade this device to sync with it",
+            (MismatchSeverity::Incompatible, UpgradeSide::Peer) => "upgrade that device to sync with it",
+            (MismatchSeverity::Deprecated, UpgradeSide::Local) => "consider upgrading this device",
+            (MismatchSeverity::Deprecated, UpgradeSide::Peer) => "consider upgrading that device",
+        };
+        format!(
+            "{} runs version {} (protocol {}), this device {} (protocol {}); {}",
+            self.address, peer, self.peer_protocol, self.local_app_version, self.local_protocol, action
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredDevice {
+    pub name: String,
+    /// Best address to reach the device; IPv6 link-local ones carry a scope
+    pub address: String,
+    /// Every address the device announced, best first
+    #[serde(default)]
+    pub addresses: Vec<String>,
+    pub port: u16,
+    pub last_seen: DateTime<Utc>,
+    pub trusted: bool,
+    /// The device told us it has paused sync
+    #[serde(default)]
+    pub paused: bool,
+    /// Fingerprint of the certificate the device serves `wss://` with
+    #[serde(default)]
+    pub tls_fingerprint: Option<String>,
+    /// Stable identity from the `device_id` TXT record, when announced
+    #[serde(default)]
+    pub device_id: Option<Uuid>,
+    /// Friendly name from the device's hostname, for display
+    #[serde(default)]
+    pub display_name: String,
+    /// App version from the device's TXT record
+    #[serde(default)]
+    pub app_version: Option<String>,
+    /// Wire protocol from the device's TXT record
+    #[serde(default)]
+    pub protocol_version: Option<u32>,
+    /// Set when the device's version does not match ours, from its TXT
+    /// record or, once connected, its handshake
+    #[serde(default)]
+    pub version_mismatch: Option<PeerVersionMismatch>,
+    /// Combined liveness from discovery, sessions, and heartbeats
+    #[serde(default)]
+    pub liveness: DeviceLiveness,
+    /// The device asked us not to send it content until then
+    #[serde(default)]
+    pub muted_until: Option<DateTime<Utc>>,
+    /// We asked the device not to send us content until then
+    #[serde(default)]
+    pub mute_requested_until: Option<DateTime<Utc>>,
+    /// Paired as a guest until then
+    #[serde(default)]
+    pub guest_until: Option<DateTime<Utc>>,
+}
+
+/// This device's part in a presentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentationRole {
+    Idle,
+    Presenting,
+    Viewing,
+}
+
+/// Returned by the presentation commands and sent with the
+/// `presentation-changed` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresentationStatus {
+    pub role: PresentationRole,
+    /// Code viewers join with
+    pub code: Option<String>,
+    /// `address:port` of the presenter, while viewing
+    pub presenter: Option<String>,
+    /// Connected viewers, while presenting
+    pub viewers: Vec<String>,
+    pub started_at: Option<DateTime<Utc>>,
+}
+
+/// Whether LAN discovery is working.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoveryState {
+    #[default]
+    Stopped,
+    Active,
+    /// A transient failure; retrying with backoff
+    Retrying,
+    /// This network does not carry multicast, so devices have to be added
+    /// by address
+    MulticastUnavailable,
+}
+
+/// Returned by `get_discovery_status` and sent with the
+/// `discovery-status-changed` event.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DiscoveryStatus {
+    pub state: DiscoveryState,
+    /// Why discovery is not active
+    pub reason: Option<String>,
+    /// Failed attempts since discovery last worked
+    pub attempts: u32,
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// How sure we are that a device is still around, best first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceLiveness {
+    /// A session is open and answering heartbeats
+    Connected,
+    /// Announced or connected recently
+    #[default]
+    Online,
+    /// Quiet for a while; removed if nothing is heard soon
+    Stale,
+    Offline,
+}
+
+#[derive(Debug, Clone)]
+pub struct MessageCache {
+    pub processed_messages: std::collections::HashMap<Uuid, DateTime<Utc>>,
+    pub last_cleanup: DateTime<Utc>,
+}
+
+impl MessageCache {
+    pub fn new() -> Self {
+        Self {
+            processed_messages: std::collections::HashMap::new(),
+            last_cleanup: Utc::now(),
+        }
+    }
+
+    pub fn is_duplicate(&self, message_id: &Uuid) -> bool {
+        self.processed_messages.contains_key(message_id)
+    }
+
+    pub fn add_message(&mut self, message_id: Uuid) {
+        self.processed_messages.insert(message_id, Utc::now());
+    }
+
+    pub fn cleanup_old_messages(&mut self) {
+        let cutoff = Utc::now() - chrono::Duration::minutes(5);
+        self.processed_messages.retain(|_, timestamp| *timestamp > cutoff);
+        self.last_cleanup = Utc::now();
+    }
+
+    pub fn should_cleanup(&self) -> bool {
+        Utc::now() - self.last_cleanup > chrono::Duration::minutes(1)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncMetrics {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub messages_failed: u64,
+    pub messages_rejected: u64,
+    pub clipboard_updates_applied: u64,
+    pub clipboard_updates_failed: u64,
+    /// Writes the clipboard accepted but did not hold when read back
+    pub apply_verification_failures: u64,
+    pub last_sync_time: Option<DateTime<Utc>>,
+    pub connected_peers: u32,
+    /// Serialized message bytes sent to and received from peers
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// When the sync server started
+    pub started_at: Option<DateTime<Utc>>,
+    /// Seconds since `started_at`, filled in when metrics are read
+    pub uptime_secs: u64,
+    /// When the counters were last zeroed; `None` when they count from
+    /// `started_at`
+    pub reset_at: Option<DateTime<Utc>>,
+}
+
+impl SyncMetrics {
+    /// Zero the message and byte counters, keeping server state such as
+    /// `started_at` and the last sync time.
+    pub fn reset_counters(&mut self) {
+        *self = Self {
+            last_sync_time: self.last_sync_time,
+            started_at: self.started_at,
+            reset_at: Some(Utc::now()),
+            ..Self::default()
+        };
+    }
+}
+
+/// Compact sync state for menubar extras and widgets that poll often or
+/// listen for `status-summary-changed`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusSummary {
+    pub running: bool,
+    pub paused: bool,
+    pub connected_peers: u32,
+    pub last_sync_at: Option<DateTime<Utc>>,
+    pub last_sync_secs_ago: Option<u64>,
+    /// Local clips recorded in history since local midnight
+    pub copied_today: u32,
+    /// Clips received from peers since local midnight
+    pub received_today: u32,
+}
+
+/// Which side opened a peer connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionDirection {
+    /// The peer connected to our server
+    Inbound,
+    /// We dialed the peer
+    Outbound,
+}
+
+/// A peer with an open session, as opposed to one only discovered.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectedPeer {
+    pub peer_id: Uuid,
+    pub address: std::net::SocketAddr,
+    /// Friendly name of the device, when known
+    pub device: Option<String>,
+    pub direction: ConnectionDirection,
+    /// Our end of the connection
+    pub local_address: Option<std::net::SocketAddr>,
+    /// Network interface the connection runs over, e.g. `en0` or `wg0`
+    pub interface: Option<String>,
+    pub connected_since: DateTime<Utc>,
+    /// Last message or heartbeat in either direction
+    pub last_activity: DateTime<Utc>,
+    /// The peer told us it has paused sync
+    pub paused: bool,
+}
+
+/// Counters for one connected peer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerMetrics {
+    pub peer_id: Uuid,
+    pub address: std::net::SocketAddr,
+    pub direction: ConnectionDirection,
+    /// Our end of the connection
+    pub local_address: Option<std::net::SocketAddr>,
+    /// Network interface the connection runs over
+    pub interface: Option<String>,
+    /// Friendly name of the device, resolved when metrics are read
+    pub device: Option<String>,
+    pub connected_at: DateTime<Utc>,
+    pub last_activity: DateTime<Utc>,
+    /// Last `Heartbeat` message; never set for peers too old to send them
+    pub last_heartbeat: Option<DateTime<Utc>>,
+    /// Current heartbeat cadence for this link
+    pub heartbeat_interval_secs: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Messages from this peer that were rejected, plus sends that failed
+    pub failures: u64,
+    /// What the peer declared it takes; `None` until its `Hello`, and for
+    /// builds that send none
+    #[serde(default)]
+    pub capabilities: Option<PeerCapabilities>,
+}
+
+/// Capabilities a peer declared in its `Hello`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerCapabilities {
+    pub device_id: Option<Uuid>,
+    pub name: Option<String>,
+    /// Clip formats it reads, e.g. `["text", "html"]`
+    pub formats: Vec<String>,
+    /// Largest clip it accepts, in plaintext bytes
+    pub max_message_bytes: Option<u64>,
+    pub compression: Vec<String>,
+}
+
+impl PeerMetrics {
+    pub fn new(peer_id: Uuid, address: std::net::SocketAddr, direction: ConnectionDirection) -> Self {
+        let now = Utc::now();
+        Self {
+            peer_id,
+            address,
+            direction,
+            local_address: None,
+            interface: None,
+            device: None,
+            connected_at: now,
+            last_activity: now,
+            last_heartbeat: None,
+            heartbeat_interval_secs: 0,
+            messages_sent: 0,
+            messages_received: 0,
+            bytes_sent: 0,
+            bytes_received: 0,
+            failures: 0,
+            capabilities: None,
+        }
+    }
+}
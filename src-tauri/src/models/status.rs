@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use super::Config;
+
+/// Which services were up, as last reported by the service manager.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub running: bool,
+    pub paused: bool,
+    pub websocket: bool,
+    pub mdns: bool,
+    pub clipboard_monitoring: bool,
+    pub history: bool,
+}
+
+/// Whether the app started in safe mode after repeated early exits, and
+/// what it knows about them. Payload of the `safe-mode` event.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafeModeStatus {
+    pub active: bool,
+    /// Startups in a row that ended before running a minute
+    pub unfinished_startups: u32,
+    pub last_crash: Option<CrashReport>,
+}
+
+/// Written to disk when the app panics, for users to attach to bug reports.
+/// It is never uploaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub occurred_at: DateTime<Utc>,
+    pub app_version: String,
+    pub platform: String,
+    pub thread: Option<String>,
+    /// `file:line` of the panic
+    pub location: Option<String>,
+    /// Panic message, sanitized like shared log lines
+    pub message: String,
+    /// Latest warnings and errors from the log, sanitized
+    pub recent_errors: Vec<String>,
+    pub health: ComponentHealth,
+    pub backtrace: String,
+}
+
+/// Configuration as actually in effect, for the UI and bug reports.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveConfig {
+    /// Stored settings merged over defaults, without the security key
+    pub config: Config,
+    pub security_key_set: bool,
+    pub running: bool,
+    pub paused: bool,
+    /// Port the sync server is listening on, while running
+    pub bound_port: Option<u16>,
+    /// Address advertised over mDNS, while running
+    pub advertised_address: Option<String>,
+    pub tls_active: bool,
+    /// Whether the clipboard monitor started
+    pub clipboard_monitoring: bool,
+    pub history_available: bool,
+    /// Whether state is kept beside the executable instead of in app data
+    pub portable: bool,
+    /// Directory holding history, certificates, and crash reports
+    pub data_dir: Option<String>,
+    pub clipboard_backend: String,
+    pub discovery_backend: String,
+    pub app_version: String,
+    pub platform: String,
+}
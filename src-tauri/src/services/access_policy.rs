@@ -0,0 +1,239 @@
+use std::net::IpAddr;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use crate::models::Config;
+
+/// Kind of endpoint a connection is trying to reach. Every listener goes
+/// through `AccessPolicy::check` so new endpoints inherit the same rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endpoint {
+    /// Device-to-device sync; any LAN source may connect
+    PeerSync,
+    /// Local API for extensions; loopback plus `api_allowed_sources`
+    LocalApi,
+    /// Prometheus scrapes; same sources as the local API
+    Metrics,
+}
+
+/// Origin and source-address rules derived from the current config.
+#[derive(Debug, Clone)]
+pub struct AccessPolicy {
+    allowed_sources: Vec<(IpAddr, u8)>,
+    allowed_origins: Vec<String>,
+}
+
+impl AccessPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        let allowed_sources = config.api_allowed_sources.iter()
+            .filter_map(|entry| {
+                let range = parse_source(entry);
+                if range.is_none() {
+                    tracing::warn!("Ignoring invalid API source entry: {}", entry);
+                }
+                range
+            })
+            .collect();
+        let allowed_origins = config.api_allowed_origins.iter()
+            .map(|origin| normalize_origin(origin))
+            .collect();
+        Self { allowed_sources, allowed_origins }
+    }
+
+    /// Decide whether a connection may proceed.
+    ///
+    /// # Arguments
+    /// * `endpoint` - Endpoint being reached
+    /// * `source` - Remote address of the connection
+    /// * `origin` - `Origin` header, present only for browser clients
+    ///
+    /// # Errors
+    /// A human-readable reason when the connection is refused
+    pub fn check(&self, endpoint: Endpoint, source: IpAddr, origin: Option<&str>) -> Result<(), String> {
+        // Dual-stack listeners report IPv4 clients as IPv4-mapped IPv6
+        let source = source.to_canonical();
+        if let Some(origin) = origin {
+            if !self.allowed_origins.contains(&normalize_origin(origin)) {
+                return Err(format!("Origin {} is not allowed", origin));
+            }
+        }
+
+        match endpoint {
+            Endpoint::PeerSync => Ok(()),
+            Endpoint::LocalApi | Endpoint::Metrics if source.is_loopback() => Ok(()),
+            Endpoint::LocalApi | Endpoint::Metrics => {
+                let allowed = self.allowed_sources.iter()
+                    .any(|(network, prefix)| in_range(source, *network, *prefix));
+                if allowed {
+                    Ok(())
+                } else {
+                    Err(format!("Source address {} is not allowed", source))
+                }
+            }
+        }
+    }
+
+    /// Check a WebSocket upgrade request, answering 403 when refused.
+    // `ErrorResponse` is the rejection type tungstenite's handshake callback expects
+    #[allow(clippy::result_large_err)]
+    pub fn check_handshake(
+        &self,
+        endpoint: Endpoint,
+        source: IpAddr,
+        request: &Request,
+        response: Response,
+    ) -> Result<Response, ErrorResponse> {
+        let origin = request.headers().get("origin").and_then(|value| value.to_str().ok());
+        match self.check(endpoint, source, origin) {
+            Ok(()) => Ok(response),
+            Err(reason) => {
+                tracing::warn!("Refused connection from {}: {}", source, reason);
+                let mut refusal = ErrorResponse::new(Some(reason));
+                *refusal.status_mut() = StatusCode::FORBIDDEN;
+                Err(refusal)
+            }
+        }
+    }
+}
+
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_lowercase()
+}
+
+/// Parse `ip` or `ip/prefix` into a network and prefix length.
+fn parse_source(entry: &str) -> Option<(IpAddr, u8)> {
+    let entry = entry.trim();
+    match entry.split_once('/') {
+        Some((ip, prefix)) => {
+            let ip: IpAddr = ip.parse().ok()?;
+            let prefix: u8 = prefix.parse().ok()?;
+            let max = if ip.is_ipv4() { 32 } else { 128 };
+            (prefix <= max).then_some((ip, prefix))
+        }
+        None => {
+            let ip: IpAddr = entry.parse().ok()?;
+            Some((ip, if ip.is_ipv4() { 32 } else { 128 }))
+        }
+    }
+}
+
+fn in_range(source: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (source, network) {
+        (IpAddr::V4(source), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(source) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(source), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(source) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    fn policy(sources: &[&str], origins: &[&str]) -> AccessPolicy {
+        AccessPolicy::from_config(&Config {
+            api_allowed_sources: sources.iter().map(|source| source.to_string()).collect(),
+            api_allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn sources_parse_with_or_without_a_prefix() {
+        assert_eq!(parse_source("192.168.1.0/24"), Some((ip("192.168.1.0"), 24)));
+        assert_eq!(parse_source(" 10.0.0.7 "), Some((ip("10.0.0.7"), 32)));
+        assert_eq!(parse_source("fd00::/8"), Some((ip("fd00::"), 8)));
+        assert_eq!(parse_source("::1"), Some((ip("::1"), 128)));
+        assert_eq!(parse_source("0.0.0.0/0"), Some((ip("0.0.0.0"), 0)));
+    }
+
+    #[test]
+    fn malformed_sources_are_rejected() {
+        for entry in ["192.168.1.0/33", "fd00::/129", "10.0.0.0/", "10.0.0.0/-1", "10.0.0.0/8/8", "10.0.0/8", "lan", ""] {
+            assert_eq!(parse_source(entry), None, "{:?}", entry);
+        }
+        // And left out of the policy rather than failing it
+        let policy = policy(&["not an address", "192.168.1.0/24"], &[]);
+        assert_eq!(policy.allowed_sources, [(ip("192.168.1.0"), 24)]);
+    }
+
+    #[test]
+    fn ipv4_ranges_include_both_ends_only() {
+        let network = ip("192.168.1.0");
+        assert!(in_range(ip("192.168.1.0"), network, 24));
+        assert!(in_range(ip("192.168.1.255"), network, 24));
+        assert!(!in_range(ip("192.168.0.255"), network, 24));
+        assert!(!in_range(ip("192.168.2.0"), network, 24));
+        assert!(in_range(ip("192.168.1.7"), ip("192.168.1.7"), 32));
+        assert!(!in_range(ip("192.168.1.8"), ip("192.168.1.7"), 32));
+        assert!(in_range(ip("8.8.8.8"), ip("0.0.0.0"), 0));
+    }
+
+    #[test]
+    fn ipv6_ranges_include_both_ends_only() {
+        let network = ip("fd00:1::");
+        assert!(in_range(ip("fd00:1::"), network, 64));
+        assert!(in_range(ip("fd00:1::ffff:ffff:ffff:ffff"), network, 64));
+        assert!(!in_range(ip("fd00:1:0:1::"), network, 64));
+        assert!(in_range(ip("2001:db8::1"), ip("::"), 0));
+        assert!(!in_range(ip("::2"), ip("::1"), 128));
+    }
+
+    #[test]
+    fn families_never_match_each_other() {
+        assert!(!in_range(ip("::ffff:192.168.1.20"), ip("192.168.1.0"), 24));
+        assert!(!in_range(ip("192.168.1.20"), ip("::"), 0));
+    }
+
+    #[test]
+    fn ipv4_mapped_sources_are_checked_as_ipv4() {
+        let policy = policy(&["192.168.1.0/24"], &[]);
+        assert!(policy.check(Endpoint::LocalApi, ip("::ffff:192.168.1.20"), None).is_ok());
+        assert!(policy.check(Endpoint::Metrics, ip("::ffff:127.0.0.1"), None).is_ok());
+        assert!(policy.check(Endpoint::LocalApi, ip("::ffff:10.0.0.1"), None).is_err());
+    }
+
+    #[test]
+    fn api_sources_are_loopback_plus_allowed_ranges() {
+        let policy = policy(&["192.168.1.0/24"], &[]);
+        assert!(policy.check(Endpoint::LocalApi, ip("127.0.0.1"), None).is_ok());
+        assert!(policy.check(Endpoint::LocalApi, ip("::1"), None).is_ok());
+        assert!(policy.check(Endpoint::LocalApi, ip("192.168.1.20"), None).is_ok());
+        assert_eq!(
+            policy.check(Endpoint::Metrics, ip("10.0.0.1"), None),
+            Err("Source address 10.0.0.1 is not allowed".to_string())
+        );
+        // Peers sync from anywhere on the LAN
+        assert!(policy.check(Endpoint::PeerSync, ip("10.0.0.1"), None).is_ok());
+    }
+
+    #[test]
+    fn browser_origins_must_be_allowed_on_every_endpoint() {
+        let policy = policy(&[], &["https://app.example/"]);
+        assert!(policy.check(Endpoint::LocalApi, ip("127.0.0.1"), Some("HTTPS://App.Example")).is_ok());
+        for endpoint in [Endpoint::PeerSync, Endpoint::LocalApi, Endpoint::Metrics] {
+            assert_eq!(
+                policy.check(endpoint, ip("127.0.0.1"), Some("https://evil.example")),
+                Err("Origin https://evil.example is not allowed".to_string()),
+            );
+        }
+    }
+
+    #[test]
+    fn refused_handshakes_are_answered_with_403() {
+        let policy = policy(&[], &[]);
+        let request = Request::builder().header("origin", "https://evil.example").body(()).unwrap();
+        let refusal = policy.check_handshake(Endpoint::LocalApi, ip("127.0.0.1"), &request, Response::new(())).unwrap_err();
+        assert_eq!(refusal.status(), StatusCode::FORBIDDEN);
+        let request = Request::builder().body(()).unwrap();
+        assert!(policy.check_handshake(Endpoint::LocalApi, ip("127.0.0.1"), &request, Response::new(())).is_ok());
+    }
+}
@@ -0,0 +1,75 @@
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::AppHandle;
+use uuid::Uuid;
+use crate::models::{AnnouncementVerbosity, ClipOrigin, ContentKind};
+use super::connection::ConnectionContext;
+use super::events::{self, ANNOUNCEMENT_EVENT};
+
+/// Shortest gap between polite announcements, so a burst of clips does
+/// not keep a screen reader talking.
+const MIN_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How urgently a screen reader should speak, as in `aria-live`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Politeness {
+    /// Wait until the user is idle
+    Polite,
+    /// Interrupt; for problems only
+    Assertive,
+}
+
+/// Payload of the `announcement` event: one short sentence to speak.
+#[derive(Debug, Clone, Serialize)]
+pub struct Announcement {
+    pub message: String,
+    pub politeness: Politeness,
+    pub timestamp: DateTime<Utc>,
+}
+
+struct Limiter {
+    last: Option<Instant>,
+    /// Polite announcements dropped since the last one spoken
+    skipped: usize,
+}
+
+static LIMITER: Mutex<Limiter> = Mutex::new(Limiter { last: None, skipped: 0 });
+
+/// Announce `message` if `verbosity` asks for announcements at `level`.
+/// Polite announcements closer together than `MIN_INTERVAL` are dropped,
+/// and the next one spoken says how many were missed.
+pub fn announce(app: Option<&AppHandle>, verbosity: AnnouncementVerbosity, level: AnnouncementVerbosity, politeness: Politeness, message: String) {
+    if verbosity == AnnouncementVerbosity::Off || verbosity < level {
+        return;
+    }
+    let mut limiter = LIMITER.lock().unwrap_or_else(|e| e.into_inner());
+    let now = Instant::now();
+    if politeness == Politeness::Polite && limiter.last.is_some_and(|last| now.duration_since(last) < MIN_INTERVAL) {
+        limiter.skipped += 1;
+        return;
+    }
+    let message = match std::mem::take(&mut limiter.skipped) {
+        0 => message,
+        1 => format!("{}, and 1 more update", message),
+        skipped => format!("{}, and {} more updates", message, skipped),
+    };
+    limiter.last = Some(now);
+    drop(limiter);
+    events::emit(app, ANNOUNCEMENT_EVENT, Announcement { message, politeness, timestamp: Utc::now() });
+}
+
+/// Announce a peer connecting (verbose only) or going away.
+pub(crate) async fn peer(ctx: &ConnectionContext, connected: bool, peer_id: Uuid, address: SocketAddr) {
+    let verbosity = ctx.config.read().await.announcement_verbosity;
+    let origin = ClipOrigin { peer_id, address, device: None, message_id: None, sent_at: None, received_at: None, kind: ContentKind::Text };
+    let device = ctx.directory.name_for(&origin);
+    if connected {
+        announce(ctx.app.as_ref(), verbosity, AnnouncementVerbosity::Verbose, Politeness::Polite, format!("{} connected", device));
+    } else {
+        announce(ctx.app.as_ref(), verbosity, AnnouncementVerbosity::Essential, Politeness::Polite, format!("{} disconnected", device));
+    }
+}
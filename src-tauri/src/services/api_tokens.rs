@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::{ApiScope, ApiTokenInfo, CreatedApiToken};
+
+/// Key under which API tokens are persisted in the settings store.
+pub const API_TOKENS_KEY: &str = "api_tokens";
+/// Prefix that makes leaked tokens easy to recognize.
+const TOKEN_PREFIX: &str = "umc_";
+
+/// A persisted token. Only a BLAKE3 hash of the secret is stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ApiTokenEntry {
+    scope: ApiScope,
+    secret_hash: String,
+    created_at: DateTime<Utc>,
+}
+
+/// Local API tokens keyed by id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ApiTokenStore {
+    entries: HashMap<Uuid, ApiTokenEntry>,
+}
+
+fn hash_secret(secret: &str) -> String {
+    blake3::hash(secret.as_bytes()).to_hex().to_string()
+}
+
+impl ApiTokenStore {
+    /// Mint a token with the given scope. The secret is returned once and
+    /// cannot be recovered later.
+    pub fn create(&mut self, scope: ApiScope) -> CreatedApiToken {
+        let id = Uuid::new_v4();
+        let token = format!(
+            "{}{}{}",
+            TOKEN_PREFIX,
+            Uuid::new_v4().simple(),
+            Uuid::new_v4().simple()
+        );
+        let entry = ApiTokenEntry {
+            scope,
+            secret_hash: hash_secret(&token),
+            created_at: Utc::now(),
+        };
+        let info = ApiTokenInfo {
+            id,
+            scope,
+            created_at: entry.created_at,
+        };
+        self.entries.insert(id, entry);
+        CreatedApiToken { info, token }
+    }
+
+    /// Remove a token. Returns whether it existed.
+    pub fn revoke(&mut self, id: Uuid) -> bool {
+        self.entries.remove(&id).is_some()
+    }
+
+    /// Resolve a presented secret to its token id and scope.
+    pub fn authenticate(&self, secret: &str) -> Option<(Uuid, ApiScope)> {
+        let hash = hash_secret(secret);
+        self.entries
+            .iter()
+            .find(|(_, entry)| entry.secret_hash == hash)
+            .map(|(id, entry)| (*id, entry.scope))
+    }
+
+    pub fn is_active(&self, id: Uuid) -> bool {
+        self.entries.contains_key(&id)
+    }
+
+    pub fn list(&self) -> Vec<ApiTokenInfo> {
+        let mut tokens: Vec<ApiTokenInfo> = self.entries
+            .iter()
+            .map(|(id, entry)| ApiTokenInfo {
+                id: *id,
+                scope: entry.scope,
+                created_at: entry.created_at,
+            })
+            .collect();
+        tokens.sort_by_key(|token| token.created_at);
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minted_tokens_are_prefixed_and_unique() {
+        let mut store = ApiTokenStore::default();
+        let first = store.create(ApiScope::Read);
+        let second = store.create(ApiScope::Read);
+        assert!(first.token.starts_with(TOKEN_PREFIX));
+        assert_ne!(first.token, second.token);
+        assert_ne!(first.info.id, second.info.id);
+        assert_eq!(store.list().len(), 2);
+    }
+
+    #[test]
+    fn only_a_hash_of_the_secret_is_kept() {
+        let mut store = ApiTokenStore::default();
+        let created = store.create(ApiScope::ReadWrite);
+        let stored = serde_json::to_string(&store).unwrap();
+        assert!(!stored.contains(&created.token));
+        assert!(stored.contains(&hash_secret(&created.token)));
+
+        // A store read back still knows the token
+        let restored: ApiTokenStore = serde_json::from_str(&stored).unwrap();
+        assert_eq!(restored.authenticate(&created.token), Some((created.info.id, ApiScope::ReadWrite)));
+    }
+
+    #[test]
+    fn only_the_exact_secret_authenticates() {
+        let mut store = ApiTokenStore::default();
+        let created = store.create(ApiScope::Read);
+        assert_eq!(store.authenticate(&created.token), Some((created.info.id, ApiScope::Read)));
+        let mut altered = created.token.clone();
+        altered.pop();
+        for secret in [altered.as_str(), &created.token.to_uppercase(), &format!("{} ", created.token), "", TOKEN_PREFIX] {
+            assert_eq!(store.authenticate(secret), None, "{:?}", secret);
+        }
+    }
+
+    #[test]
+    fn scopes_decide_who_may_push() {
+        let mut store = ApiTokenStore::default();
+        let read = store.create(ApiScope::Read);
+        let read_write = store.create(ApiScope::ReadWrite);
+        let scope = |token: &str| store.authenticate(token).map(|(_, scope)| scope).unwrap();
+        assert!(!scope(&read.token).can_push());
+        assert!(scope(&read_write.token).can_push());
+    }
+
+    #[test]
+    fn revoked_tokens_no_longer_authenticate() {
+        let mut store = ApiTokenStore::default();
+        let revoked = store.create(ApiScope::ReadWrite);
+        let kept = store.create(ApiScope::Read);
+        assert!(store.revoke(revoked.info.id));
+        assert!(!store.is_active(revoked.info.id));
+        assert_eq!(store.authenticate(&revoked.token), None);
+        assert!(store.is_active(kept.info.id));
+        assert_eq!(store.list().iter().map(|token| token.id).collect::<Vec<_>>(), [kept.info.id]);
+    }
+
+    #[test]
+    fn a_revoked_token_presented_again_stays_refused() {
+        let mut store = ApiTokenStore::default();
+        let created = store.create(ApiScope::ReadWrite);
+        store.revoke(created.info.id);
+        assert!(!store.revoke(created.info.id));
+        // Minting anew does not bring the old secret back
+        store.create(ApiScope::ReadWrite);
+        assert_eq!(store.authenticate(&created.token), None);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// File under the data dir recording applied peer clips, one JSON mark
+/// per line.
+pub const APPLIED_LOG_FILE: &str = "applied_messages.log";
+/// Most recent applied clips remembered; peers retry well within this.
+const WINDOW: usize = 1024;
+
+/// A peer clip that reached the clipboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AppliedMark {
+    message_id: Uuid,
+    /// When the sender says it sent the clip, which orders its clips
+    sent_at: Option<DateTime<Utc>>,
+    applied_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Window {
+    marks: VecDeque<AppliedMark>,
+    ids: HashSet<Uuid>,
+    /// Lines in the file, compacted once it holds twice the window
+    lines: usize,
+}
+
+impl Window {
+    fn push(&mut self, mark: AppliedMark) {
+        self.ids.insert(mark.message_id);
+        self.marks.push_back(mark);
+        while self.marks.len() > WINDOW {
+            if let Some(old) = self.marks.pop_front() {
+                self.ids.remove(&old.message_id);
+            }
+        }
+    }
+}
+
+/// Clips applied recently, kept on disk so a peer retrying right after a
+/// restart or crash is not applied twice. The in-memory dedupe cache only
+/// covers the current run.
+#[derive(Default)]
+pub struct AppliedLog {
+    /// `None` keeps the log in memory only
+    path: Option<PathBuf>,
+    window: Mutex<Window>,
+}
+
+impl AppliedLog {
+    /// Load the window kept at `path`. Unreadable lines are skipped.
+    pub fn open(path: PathBuf) -> Self {
+        let mut window = Window::default();
+        if let Ok(file) = File::open(&path) {
+            for line in BufReader::new(file).lines().map_while(Result::ok) {
+                window.lines += 1;
+                if let Ok(mark) = serde_json::from_str::<AppliedMark>(&line) {
+                    window.push(mark);
+                }
+            }
+        }
+        tracing::info!("Loaded {} applied message marks", window.marks.len());
+        Self { path: Some(path), window: Mutex::new(window) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Window> {
+        self.window.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Whether clip `message_id` was already applied, in this run or an
+    /// earlier one.
+    pub fn contains(&self, message_id: Uuid) -> bool {
+        self.lock().ids.contains(&message_id)
+    }
+
+    /// Remember that clip `message_id` reached the clipboard. The mark is
+    /// synced to disk, on the blocking pool, before returning, so it
+    /// survives a crash right after.
+    pub async fn record(self: Arc<Self>, message_id: Uuid, sent_at: Option<DateTime<Utc>>) {
+        if let Err(e) = tokio::task::spawn_blocking(move || self.persist(message_id, sent_at)).await {
+            tracing::warn!("Failed to record applied message {}: {}", message_id, e);
+        }
+    }
+
+    fn persist(&self, message_id: Uuid, sent_at: Option<DateTime<Utc>>) {
+        let mark = AppliedMark { message_id, sent_at, applied_at: Utc::now() };
+        let mut window = self.lock();
+        window.push(mark.clone());
+        let Some(ref path) = self.path else {
+            return;
+        };
+        let result = if window.lines >= 2 * WINDOW {
+            compact(path, &window.marks).map(|()| window.lines = window.marks.len())
+        } else {
+            append(path, &mark).map(|()| window.lines += 1)
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to persist applied message {}: {}", message_id, e);
+        }
+    }
+}
+
+fn append(path: &Path, mark: &AppliedMark) -> Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(mark)?)?;
+    file.sync_data()?;
+    Ok(())
+}
+
+/// Rewrite the file with just the window, replacing it atomically.
+fn compact(path: &Path, marks: &VecDeque<AppliedMark>) -> Result<()> {
+    let tmp = path.with_extension("log.tmp");
+    let mut file = File::create(&tmp)?;
+    for mark in marks {
+        writeln!(file, "{}", serde_json::to_string(mark)?)?;
+    }
+    file.sync_all()?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
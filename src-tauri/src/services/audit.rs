@@ -0,0 +1,83 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// File under the data dir the audit trail is appended to, one JSON entry
+/// per line.
+pub const AUDIT_FILE: &str = "audit.log";
+/// Size at which the trail is rotated to `audit.log.1`.
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+static AUDIT_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Serializes appends and rotation.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// A security-relevant action taken on received data.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub timestamp: DateTime<Utc>,
+    /// What happened, e.g. `format_sanitized`
+    pub action: String,
+    /// Device the data came from
+    pub device: Option<String>,
+    pub details: Vec<String>,
+}
+
+impl AuditEntry {
+    pub fn new(action: &str, device: Option<String>, details: Vec<String>) -> Self {
+        Self { timestamp: Utc::now(), action: action.to_string(), device, details }
+    }
+}
+
+/// Where to keep the trail. Entries recorded before this is called are
+/// only logged.
+pub fn set_dir(dir: PathBuf) {
+    let _ = AUDIT_DIR.set(dir);
+}
+
+/// Append `entry` to the trail. Failures are logged; auditing must never
+/// stop a clip from being applied.
+pub fn record(entry: AuditEntry) {
+    tracing::info!("Audit: {} from {}: {}", entry.action, entry.device.as_deref().unwrap_or("unknown device"), entry.details.join("; "));
+    let Some(dir) = AUDIT_DIR.get() else {
+        return;
+    };
+    if let Err(e) = append(dir, &entry) {
+        tracing::warn!("Failed to write audit entry: {}", e);
+    }
+}
+
+fn append(dir: &Path, entry: &AuditEntry) -> Result<()> {
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    fs::create_dir_all(dir)?;
+    let path = dir.join(AUDIT_FILE);
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() > MAX_FILE_BYTES) {
+        fs::rename(&path, dir.join(format!("{}.1", AUDIT_FILE)))?;
+    }
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    OpenOptions::new().create(true).append(true).open(&path)?.write_all(&line)?;
+    Ok(())
+}
+
+/// The last `limit` entries, oldest first.
+pub fn recent(limit: usize) -> Result<Vec<AuditEntry>> {
+    let Some(dir) = AUDIT_DIR.get() else {
+        return Ok(Vec::new());
+    };
+    let path = dir.join(AUDIT_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(path)?;
+    let entries: Vec<AuditEntry> = text
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let skip = entries.len().saturating_sub(limit);
+    Ok(entries.into_iter().skip(skip).collect())
+}
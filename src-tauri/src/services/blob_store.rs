@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use anyhow::Result;
+
+/// Directory under the data dir holding clip payloads kept out of the
+/// history database.
+pub const BLOBS_DIR: &str = "blobs";
+
+/// What one eviction pass removed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Eviction {
+    pub blobs_removed: usize,
+    pub bytes_reclaimed: u64,
+}
+
+/// Content-addressed blobs, each a file named by the SHA-256 of its bytes.
+#[derive(Debug, Clone)]
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Remove the least recently written blobs until the rest fit in
+    /// `max_bytes`. Files not named like a blob are left alone.
+    ///
+    /// # Errors
+    /// When the directory cannot be listed or a blob cannot be removed
+    pub fn evict(&self, max_bytes: u64) -> Result<Eviction> {
+        let mut blobs = match blobs_in(&self.dir) {
+            Ok(blobs) => blobs,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Eviction::default()),
+            Err(e) => return Err(e.into()),
+        };
+        // Oldest first; the name breaks ties so passes are repeatable
+        blobs.sort_by(|a, b| (a.modified, &a.path).cmp(&(b.modified, &b.path)));
+
+        let mut total: u64 = blobs.iter().map(|blob| blob.bytes).sum();
+        let mut eviction = Eviction::default();
+        for blob in blobs {
+            if total <= max_bytes {
+                break;
+            }
+            fs::remove_file(&blob.path)?;
+            total -= blob.bytes;
+            eviction.blobs_removed += 1;
+            eviction.bytes_reclaimed += blob.bytes;
+        }
+        Ok(eviction)
+    }
+}
+
+struct Blob {
+    path: PathBuf,
+    bytes: u64,
+    modified: SystemTime,
+}
+
+fn blobs_in(dir: &Path) -> std::io::Result<Vec<Blob>> {
+    let mut blobs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() || !is_blob_name(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        blobs.push(Blob {
+            path: entry.path(),
+            bytes: metadata.len(),
+            modified: metadata.modified()?,
+        });
+    }
+    Ok(blobs)
+}
+
+/// Whether `name` is a lowercase hex SHA-256, as blobs are named.
+fn is_blob_name(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use sha2::{Digest, Sha256};
+    use uuid::Uuid;
+    use super::*;
+
+    /// A blob directory removed when the test ends.
+    struct TempBlobs(PathBuf);
+
+    impl TempBlobs {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("unimesh-blobs-{}", Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        /// Store `bytes` as a blob last written `age` ago.
+        fn put(&self, bytes: &[u8], age: Duration) -> PathBuf {
+            let name: String = Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect();
+            let path = self.0.join(name);
+            fs::write(&path, bytes).unwrap();
+            fs::File::options().write(true).open(&path).unwrap().set_modified(SystemTime::now() - age).unwrap();
+            path
+        }
+
+        fn store(&self) -> BlobStore {
+            BlobStore::new(self.0.clone())
+        }
+    }
+
+    impl Drop for TempBlobs {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    #[test]
+    fn oldest_blobs_go_first_until_the_rest_fit() {
+        let blobs = TempBlobs::new();
+        let oldest = blobs.put(&[1; 100], 3 * HOUR);
+        let older = blobs.put(&[2; 100], 2 * HOUR);
+        let newest = blobs.put(&[3; 100], HOUR);
+
+        let eviction = blobs.store().evict(150).unwrap();
+        assert_eq!(eviction, Eviction { blobs_removed: 2, bytes_reclaimed: 200 });
+        assert!(!oldest.exists());
+        assert!(!older.exists());
+        assert!(newest.exists());
+    }
+
+    #[test]
+    fn nothing_is_evicted_at_the_cap() {
+        let blobs = TempBlobs::new();
+        let kept = [blobs.put(&[1; 100], 2 * HOUR), blobs.put(&[2; 100], HOUR)];
+        assert_eq!(blobs.store().evict(200).unwrap(), Eviction::default());
+        assert!(kept.iter().all(|path| path.exists()));
+    }
+
+    #[test]
+    fn files_not_named_like_blobs_are_left_alone() {
+        let blobs = TempBlobs::new();
+        let other = blobs.0.join("notes.txt");
+        fs::write(&other, [0; 500]).unwrap();
+        let blob = blobs.put(&[1; 100], HOUR);
+
+        let eviction = blobs.store().evict(0).unwrap();
+        assert_eq!(eviction, Eviction { blobs_removed: 1, bytes_reclaimed: 100 });
+        assert!(other.exists());
+        assert!(!blob.exists());
+    }
+
+    #[test]
+    fn a_missing_directory_has_nothing_to_evict() {
+        let store = BlobStore::new(std::env::temp_dir().join(format!("unimesh-blobs-{}", Uuid::new_v4())));
+        assert_eq!(store.evict(0).unwrap(), Eviction::default());
+    }
+
+    #[test]
+    fn blob_names_are_lowercase_sha256_hex() {
+        assert!(is_blob_name(&"ab".repeat(32)));
+        assert!(!is_blob_name(&"AB".repeat(32)));
+        assert!(!is_blob_name(&"ab".repeat(31)));
+        assert!(!is_blob_name(&"zz".repeat(32)));
+    }
+}
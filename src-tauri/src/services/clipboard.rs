@@ -3,11 +3,51 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use tokio::time::{interval, Duration, timeout};
 use anyhow::Result;
+use crate::models::ClipboardContent;
+use crate::utils::{concealed, hashing, sync_marker};
+use super::shutdown::Shutdown;
+
+/// Read plain text plus whatever rich formats the platform exposes.
+/// arboard has no RTF support, so `rtf` is only ever carried through.
+fn read_content(clipboard: &mut Clipboard) -> Result<ClipboardContent, arboard::Error> {
+    let text = clipboard.get_text()?;
+    let html = clipboard.get().html().ok().filter(|html| !html.is_empty());
+    Ok(ClipboardContent { text, html, rtf: None })
+}
+
+/// A clipboard write reported success but reading back returned something
+/// else; some Linux setups silently drop writes without an event loop.
+#[derive(Debug, Clone, Copy)]
+pub struct ApplyVerificationFailed;
+
+impl std::fmt::Display for ApplyVerificationFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "clipboard write did not take effect")
+    }
+}
+
+impl std::error::Error for ApplyVerificationFailed {}
+
+/// Read the text back and check it is what was just written.
+fn verify_write(clipboard: &mut Clipboard, expected: blake3::Hash) -> Result<()> {
+    match clipboard.get_text() {
+        Ok(text) if hashing::hash_text(&text) == expected => Ok(()),
+        Ok(_) => Err(ApplyVerificationFailed.into()),
+        Err(e) => {
+            tracing::debug!("Failed to read clipboard back: {}", e);
+            Err(ApplyVerificationFailed.into())
+        }
+    }
+}
 
 pub struct ClipboardMonitor {
     clipboard: Arc<Mutex<Clipboard>>,
-    last_content: Arc<Mutex<Option<String>>>,
+    last_hash: Arc<Mutex<Option<blake3::Hash>>>,
     sync_in_progress: Arc<Mutex<bool>>,
+    /// While set the clipboard is not read at all
+    capture_paused: Arc<Mutex<bool>>,
+    /// Ignore content its source marked as sensitive
+    skip_concealed: Arc<Mutex<bool>>,
 }
 
 impl ClipboardMonitor {
@@ -16,7 +56,7 @@ impl ClipboardMonitor {
         
         // Add timeout to prevent hanging on permission requests
         let clipboard_result = timeout(Duration::from_secs(5), async {
-            tokio::task::spawn_blocking(|| Clipboard::new()).await
+            tokio::task::spawn_blocking(Clipboard::new).await
         }).await;
         
         let clipboard = match clipboard_result {
@@ -33,41 +73,62 @@ impl ClipboardMonitor {
         tracing::info!("Clipboard monitor initialized successfully");
         Ok(Self {
             clipboard: Arc::new(Mutex::new(clipboard)),
-            last_content: Arc::new(Mutex::new(None)),
+            last_hash: Arc::new(Mutex::new(None)),
             sync_in_progress: Arc::new(Mutex::new(false)),
+            capture_paused: Arc::new(Mutex::new(false)),
+            skip_concealed: Arc::new(Mutex::new(true)),
         })
     }
 
-    pub async fn start_monitoring<F>(&self, on_change: F) -> Result<()>
+    /// Poll the clipboard and call `on_change` with each new clip until
+    /// `shutdown`. A read in progress finishes first, so the clipboard is
+    /// never left locked.
+    pub async fn start_monitoring<F>(&self, on_change: F, shutdown: Shutdown) -> Result<()>
     where
-        F: Fn(String) + Send + Sync + 'static,
+        F: Fn(ClipboardContent) + Send + Sync + 'static,
     {
         let clipboard = self.clipboard.clone();
-        let last_content = self.last_content.clone();
+        let last_hash = self.last_hash.clone();
         let sync_in_progress = self.sync_in_progress.clone();
+        let capture_paused = self.capture_paused.clone();
+        let skip_concealed = self.skip_concealed.clone();
         let on_change = Arc::new(on_change);
         
-        tokio::spawn(async move {
+        shutdown.clone().spawn(async move {
             let mut interval = interval(Duration::from_millis(500));
             
             loop {
-                interval.tick().await;
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.cancelled() => break,
+                }
                 
                 // Skip monitoring if sync is in progress
-                if *sync_in_progress.lock().await {
+                if *sync_in_progress.lock().await || *capture_paused.lock().await {
                     continue;
                 }
                 
                 let mut clipboard = clipboard.lock().await;
-                match clipboard.get_text() {
-                    Ok(text) => {
-                        let mut last = last_content.lock().await;
+                match read_content(&mut clipboard) {
+                    Ok(content) => {
+                        let mut last = last_hash.lock().await;
                         
-                        if last.as_ref() != Some(&text) && !text.is_empty() {
-                            *last = Some(text.clone());
+                        // Changes are keyed on the text; rich formats may not
+                        // read back byte-for-byte after we write them
+                        let hash = hashing::hash_text(&content.text);
+                        if *last != Some(hash) && !content.text.is_empty() {
+                            *last = Some(hash);
                             drop(last);
                             drop(clipboard);
-                            on_change(text);
+                            if sync_marker::is_marked() {
+                                tracing::debug!("Clipboard content is marked as not to be synced");
+                                continue;
+                            }
+                            if *skip_concealed.lock().await && concealed::is_concealed() {
+                                tracing::debug!("Clipboard content is marked as sensitive, not sharing it");
+                                continue;
+                            }
+                            on_change(content);
                         }
                     }
                     Err(e) => {
@@ -80,7 +141,56 @@ impl ClipboardMonitor {
         Ok(())
     }
 
-    pub async fn set_clipboard(&self, content: String) -> Result<()> {
+    /// Stop or restart reading the clipboard. On resume, whatever was copied
+    /// during the pause counts as already seen, so it is never sent.
+    pub async fn set_capture_paused(&self, paused: bool) {
+        let mut capture_paused = self.capture_paused.lock().await;
+        if !paused && *capture_paused {
+            match read_content(&mut *self.clipboard.lock().await) {
+                Ok(content) => *self.last_hash.lock().await = Some(hashing::hash_text(&content.text)),
+                Err(e) => tracing::debug!("Failed to read clipboard on resume: {}", e),
+            }
+        }
+        *capture_paused = paused;
+    }
+
+    /// Whether content marked as sensitive by its source (password
+    /// managers) is ignored.
+    pub async fn set_skip_concealed(&self, skip: bool) {
+        *self.skip_concealed.lock().await = skip;
+    }
+
+    /// Read the clipboard once. The monitor treats what was read as seen,
+    /// so it is not reported again as a change.
+    ///
+    /// # Errors
+    /// Also when the content is marked as not to be synced, or as sensitive
+    /// and such content is skipped
+    pub async fn read_now(&self) -> Result<ClipboardContent> {
+        let content = read_content(&mut *self.clipboard.lock().await)
+            .map_err(|e| anyhow::anyhow!("Failed to read clipboard: {}", e))?;
+        *self.last_hash.lock().await = Some(hashing::hash_text(&content.text));
+        if sync_marker::is_marked() {
+            return Err(anyhow::anyhow!("Clipboard content is marked as not to be synced"));
+        }
+        if *self.skip_concealed.lock().await && concealed::is_concealed() {
+            return Err(anyhow::anyhow!("Clipboard content is marked as sensitive"));
+        }
+        Ok(content)
+    }
+
+    /// Write content to the clipboard, including HTML when present
+    /// (with the plain text as its fallback). Each write is read back, and
+    /// one that did not take effect is retried like a failed write; if none
+    /// does, the error is `ApplyVerificationFailed`.
+    pub async fn set_clipboard(&self, content: ClipboardContent) -> Result<()> {
+        // Already on the clipboard, e.g. the same clip relayed by two peers
+        let hash = hashing::hash_text(&content.text);
+        if *self.last_hash.lock().await == Some(hash) {
+            tracing::debug!("Clipboard already holds this content, skipping write");
+            return Ok(());
+        }
+        
         // Set sync in progress to prevent triggering our own change detection
         *self.sync_in_progress.lock().await = true;
         
@@ -90,7 +200,12 @@ impl ClipboardMonitor {
             // Retry clipboard operation up to 3 times
             let mut retry_count = 0;
             loop {
-                match clipboard.set_text(&content) {
+                let written = match content.html {
+                    Some(ref html) => clipboard.set_html(html.as_str(), Some(content.text.as_str())),
+                    None => clipboard.set_text(content.text.as_str()),
+                };
+                let written = written.map_err(anyhow::Error::from).and_then(|_| verify_write(&mut clipboard, hash));
+                match written {
                     Ok(_) => {
                         tracing::debug!("Clipboard set successfully on attempt {}", retry_count + 1);
                         break Ok(());
@@ -99,6 +214,9 @@ impl ClipboardMonitor {
                         retry_count += 1;
                         if retry_count >= 3 {
                             tracing::error!("Failed to set clipboard after {} attempts: {}", retry_count, e);
+                            if e.is::<ApplyVerificationFailed>() {
+                                break Err(e);
+                            }
                             break Err(anyhow::anyhow!("Failed to set clipboard: {}", e));
                         } else {
                             tracing::warn!("Clipboard set attempt {} failed, retrying: {}", retry_count, e);
@@ -111,9 +229,11 @@ impl ClipboardMonitor {
             }
         };
         
-        // Update our last_content to prevent detection on success
+        // Update our last hash to prevent detection on success, and tell
+        // other sync tools not to pass the clip on
         if result.is_ok() {
-            *self.last_content.lock().await = Some(content);
+            *self.last_hash.lock().await = Some(hash);
+            sync_marker::mark_applied();
         }
         
         // Brief delay to ensure clipboard is set before re-enabling monitoring
@@ -0,0 +1,320 @@
+use std::sync::Arc;
+use anyhow::Result;
+use tauri::AppHandle;
+use tokio::sync::{oneshot, RwLock};
+use uuid::Uuid;
+use crate::models::{AnnouncementVerbosity, ClipOrigin, ClipboardContent, ClipboardMessage, Config, HistoryOrigin};
+use super::announcements::{self, Politeness};
+use super::audit::{self, AuditEntry};
+use super::clipboard::{ApplyVerificationFailed, ClipboardMonitor};
+use super::conflicts::{self, ApplyDecision, ConflictGate};
+use super::content_filter::{self, FilterOutcome};
+use super::cues;
+use super::format_filter;
+use super::events::{self, ApplyVerificationFailedEvent, ClipboardConflictEvent, ClipboardSyncedEvent, SyncDirection, APPLY_VERIFICATION_FAILED_EVENT, CLIPBOARD_CONFLICT_EVENT, CLIPBOARD_SYNCED_EVENT};
+use super::device_names::{self, DeviceDirectory};
+use super::history::HistoryStore;
+use super::notifications;
+use super::previews::PreviewCache;
+use super::receipts::ApplyReport;
+use super::size_policy::{self, Direction, OversizeGate, SizeDecision};
+use super::workspaces::{self, DesktopGate, HeldClip};
+use super::websocket::WebSocketServer;
+use super::connection::ApplyOutcome;
+use super::shutdown::Shutdown;
+
+/// How a clip is named in announcements.
+fn describe(content: &ClipboardContent) -> &'static str {
+    if content.is_rich() { "rich text" } else { "text" }
+}
+
+/// Pass a local clip through the content filters and size policy before
+/// it is sent. `None` when it must not go out now; clips held for the user
+/// to confirm go out from the oversize gate once accepted.
+pub(crate) async fn gate_outgoing(config: &Config, oversize: &OversizeGate, content: ClipboardContent) -> Option<ClipboardContent> {
+    let content = match content_filter::apply(&config.content_filters, content) {
+        FilterOutcome::Send { content, changes } => {
+            if !changes.is_empty() {
+                audit::record(AuditEntry::new("content_redacted", None, changes));
+            }
+            content
+        }
+        FilterOutcome::Block(pattern) => {
+            audit::record(AuditEntry::new("content_blocked", None, vec![format!("matched {:?}", pattern)]));
+            return None;
+        }
+    };
+    match size_policy::check(config, content) {
+        SizeDecision::Allow(content) => Some(content),
+        SizeDecision::Drop => {
+            tracing::info!("Not syncing local clip: larger than {} bytes", config.max_content_bytes);
+            None
+        }
+        SizeDecision::Prompt(content) => {
+            oversize.hold(Direction::Outgoing, content, None, None, config.max_content_bytes).await;
+            None
+        }
+    }
+}
+
+/// Glue between the clipboard monitor and the sync server: what happens
+/// when a peer's clip arrives and when the local clipboard changes.
+///
+/// The server is passed in rather than stored, so the callback the server
+/// holds does not keep the server itself alive.
+#[derive(Clone)]
+pub struct ClipboardSync {
+    pub clipboard: Arc<ClipboardMonitor>,
+    pub config: Arc<RwLock<Config>>,
+    pub history: Option<Arc<HistoryStore>>,
+    pub app: Option<AppHandle>,
+    pub oversize: Arc<OversizeGate>,
+    pub directory: Arc<DeviceDirectory>,
+    pub previews: Arc<PreviewCache>,
+    pub conflicts: Arc<ConflictGate>,
+    pub desktops: Arc<DesktopGate>,
+}
+
+impl ClipboardSync {
+    /// Route remote clips to the clipboard and, unless disabled, start
+    /// watching for local changes.
+    pub async fn attach(&self, ws: &Arc<WebSocketServer>) -> Result<()> {
+        let remote = self.clone();
+        let report = ws.apply_report();
+        let shutdown = ws.shutdown_signal();
+        ws.set_clipboard_callback(move |content, origin| {
+            let sync = remote.clone();
+            let report = report.clone();
+            let tasks = shutdown.clone();
+            let (outcome_tx, outcome) = oneshot::channel();
+            shutdown.spawn(async move {
+                let _ = outcome_tx.send(sync.apply_remote(content, origin, report, tasks).await);
+            });
+            outcome
+        }).await;
+
+        if !self.config.read().await.monitor_clipboard {
+            tracing::info!("Clipboard monitoring disabled; local clips are sent on demand");
+            return Ok(());
+        }
+        let local = self.clone();
+        let shutdown = ws.shutdown_signal();
+        let tasks = shutdown.clone();
+        let ws = ws.clone();
+        self.clipboard.start_monitoring(move |content| {
+            let sync = local.clone();
+            let ws = ws.clone();
+            tasks.spawn(async move {
+                sync.share_local(&ws, content).await;
+            });
+        }, shutdown).await
+    }
+
+    /// Take a peer's clip, holding it while the user is away from their
+    /// work desktops.
+    async fn apply_remote(&self, content: ClipboardContent, origin: ClipOrigin, report: ApplyReport, shutdown: Shutdown) -> ApplyOutcome {
+        let (mode, work_desktops) = {
+            let config = self.config.read().await;
+            (config.sync_mode, config.work_desktops.clone())
+        };
+        if !mode.receives() {
+            tracing::debug!("Send-only mode, ignoring clip from {}", self.directory.name_for(&origin));
+            return ApplyOutcome::Declined;
+        }
+        if !work_desktops.is_empty() {
+            let desktop = workspaces::current_desktop();
+            if !workspaces::is_work(&work_desktops, desktop.as_deref()) {
+                tracing::info!("Holding clip from {} until a work desktop is active (on {:?})", self.directory.name_for(&origin), desktop);
+                if self.desktops.hold(HeldClip { content, origin, arrived_on: desktop, report }) {
+                    self.spawn_desktop_wait(shutdown);
+                }
+                return ApplyOutcome::Held;
+            }
+        }
+        self.resolve_conflict(content, origin, &report).await
+    }
+
+    /// Apply the held clip once the user is back on a work desktop. Sync
+    /// stopping ends the wait and leaves the clip held.
+    fn spawn_desktop_wait(&self, shutdown: Shutdown) {
+        let sync = self.clone();
+        shutdown.clone().spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(workspaces::POLL_INTERVAL) => {}
+                    _ = shutdown.cancelled() => {
+                        sync.desktops.stop_waiting();
+                        return;
+                    }
+                }
+                let work_desktops = sync.config.read().await.work_desktops.clone();
+                if workspaces::is_work(&work_desktops, workspaces::current_desktop().as_deref()) {
+                    break;
+                }
+            }
+            let Some(held) = sync.desktops.release() else {
+                return;
+            };
+            tracing::info!(
+                "Back on a work desktop; applying clip from {} that arrived on {:?}",
+                sync.directory.name_for(&held.origin), held.arrived_on
+            );
+            sync.resolve_conflict(held.content, held.origin, &held.report).await;
+        });
+    }
+
+    /// Decide what a peer's clip may do given the conflict policy for its
+    /// kind, then apply, hold, or record it.
+    async fn resolve_conflict(&self, content: ClipboardContent, origin: ClipOrigin, report: &ApplyReport) -> ApplyOutcome {
+        let kind = origin.kind;
+        let policy = self.config.read().await.conflict_policies.for_kind(kind);
+        match conflicts::decide(policy, self.conflicts.local_changed_at(), origin.received_at) {
+            ApplyDecision::Apply => self.apply_now(content, origin, report).await,
+            ApplyDecision::Discard => {
+                tracing::info!("Keeping newer local clip over {}'s", self.directory.name_for(&origin));
+                ApplyOutcome::Declined
+            }
+            ApplyDecision::Confirm => {
+                let device = self.directory.name_for(&origin);
+                let bytes = size_policy::content_size(&content);
+                let id = self.conflicts.hold(content, origin);
+                tracing::info!("Holding conflicting clip {} from {} for confirmation", id, device);
+                events::emit(self.app.as_ref(), CLIPBOARD_CONFLICT_EVENT, ClipboardConflictEvent {
+                    id,
+                    device,
+                    kind,
+                    bytes,
+                    timestamp: chrono::Utc::now(),
+                });
+                ApplyOutcome::Held
+            }
+            ApplyDecision::HistoryOnly => {
+                let device = self.directory.name_for(&origin);
+                tracing::info!("Recording conflicting clip from {} in history only", device);
+                if let Some(ref history) = self.history {
+                    if let Err(e) = history.record(content.text, HistoryOrigin::Remote, Some(device)).await {
+                        tracing::warn!("Failed to record clipboard history: {}", e);
+                    }
+                }
+                ApplyOutcome::Held
+            }
+        }
+    }
+
+    /// Write a peer's clip to the local clipboard, then acknowledge, cue,
+    /// notify, and record it.
+    pub async fn apply_now(&self, mut content: ClipboardContent, origin: ClipOrigin, report: &ApplyReport) -> ApplyOutcome {
+        let device = self.directory.name_for(&origin);
+        let changes = format_filter::apply(&self.config.read().await.format_security, &mut content);
+        if !changes.is_empty() {
+            audit::record(AuditEntry::new("format_sanitized", Some(device.clone()), changes));
+        }
+        if let Err(e) = self.clipboard.set_clipboard(content.clone()).await {
+            tracing::error!("Failed to update clipboard from network: {}", e);
+            let verbosity = self.config.read().await.announcement_verbosity;
+            announcements::announce(self.app.as_ref(), verbosity, AnnouncementVerbosity::Essential, Politeness::Assertive, format!("Could not paste the clip from {}", device));
+            if e.is::<ApplyVerificationFailed>() {
+                report.metrics.write().await.apply_verification_failures += 1;
+                events::emit(self.app.as_ref(), APPLY_VERIFICATION_FAILED_EVENT, ApplyVerificationFailedEvent {
+                    device,
+                    timestamp: chrono::Utc::now(),
+                });
+            }
+            return ApplyOutcome::Failed;
+        }
+        report.applied(&origin).await;
+        if let Some(id) = origin.message_id {
+            self.previews.record(id, SyncDirection::Received, &content);
+        }
+        self.emit_synced(SyncDirection::Received, origin.message_id, Some(&origin), Some(device.clone()), size_policy::content_size(&content));
+        if let Some(ref app) = self.app {
+            let config = self.config.read().await;
+            cues::emit_apply_cue(app, &config, &origin, &device, origin.kind);
+            if config.notifications_enabled {
+                notifications::notify_remote_clip(app, &device, origin.device.as_ref(), &content.text);
+            }
+        }
+        let verbosity = self.config.read().await.announcement_verbosity;
+        announcements::announce(self.app.as_ref(), verbosity, AnnouncementVerbosity::Essential, Politeness::Polite, format!("Received {} from {}", describe(&content), device));
+        if let Some(ref history) = self.history {
+            if let Err(e) = history.record(content.text.clone(), HistoryOrigin::Remote, Some(device)).await {
+                tracing::warn!("Failed to record clipboard history: {}", e);
+            }
+        }
+        ApplyOutcome::Applied(content)
+    }
+
+    /// Apply the size policy to a local change before sending it.
+    pub async fn share_local(&self, ws: &WebSocketServer, content: ClipboardContent) {
+        self.conflicts.note_local_change();
+        if !self.config.read().await.sync_mode.sends() {
+            tracing::debug!("Receive-only mode, not broadcasting local clipboard change");
+            return;
+        }
+        if ws.is_paused().await {
+            tracing::debug!("Sync paused, not broadcasting local clipboard change");
+            return;
+        }
+
+        let config = self.config.read().await.clone();
+        if let Some(content) = gate_outgoing(&config, &self.oversize, content).await {
+            self.send_local(ws, content).await;
+        }
+    }
+
+    /// Record a local clip and send it to peers and local API clients.
+    pub async fn send_local(&self, ws: &WebSocketServer, content: ClipboardContent) {
+        let mut entry_id = None;
+        if let Some(ref history) = self.history {
+            match history.record(content.text.clone(), HistoryOrigin::Local, None).await {
+                Ok(id) => entry_id = Some(id),
+                Err(e) => tracing::warn!("Failed to record clipboard history: {}", e),
+            }
+        }
+
+        ws.publish_local_change(content.clone());
+        let bytes = size_policy::content_size(&content);
+
+        let mut message = match ClipboardMessage::clipboard_update(content.clone()) {
+            Ok(message) => message,
+            Err(e) => {
+                tracing::error!("Failed to encode clipboard update: {}", e);
+                return;
+            }
+        };
+
+        message.device = Some(device_names::local_device_info());
+        self.previews.record(message.id, SyncDirection::Sent, &content);
+
+        // Before sending, so receipts from fast peers find the entry
+        if let (Some(history), Some(id)) = (self.history.as_ref(), entry_id) {
+            let recipients = ws.data_recipients().await;
+            if let Err(e) = history.mark_sent(id, message.id, recipients).await {
+                tracing::warn!("Failed to record clip recipients: {}", e);
+            }
+        }
+
+        // Protected with the security key (if set) by the transport
+        let message_id = message.id;
+        match ws.broadcast_message(message).await {
+            Ok(()) => {
+                self.emit_synced(SyncDirection::Sent, Some(message_id), None, None, bytes);
+                let verbosity = self.config.read().await.announcement_verbosity;
+                announcements::announce(self.app.as_ref(), verbosity, AnnouncementVerbosity::Verbose, Politeness::Polite, format!("Sent {}", describe(&content)));
+            }
+            Err(e) => tracing::error!("Failed to broadcast clipboard update: {}", e),
+        }
+    }
+
+    /// Tell the UI a clip went out or came in.
+    fn emit_synced(&self, direction: SyncDirection, message_id: Option<Uuid>, origin: Option<&ClipOrigin>, device: Option<String>, bytes: u64) {
+        events::emit(self.app.as_ref(), CLIPBOARD_SYNCED_EVENT, ClipboardSyncedEvent {
+            direction,
+            message_id,
+            device,
+            sender: origin.and_then(|origin| origin.device.clone()),
+            bytes,
+            timestamp: chrono::Utc::now(),
+        });
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::models::{ClipOrigin, ClipboardContent, ConflictPolicy};
+
+/// What to do with an incoming clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyDecision {
+    /// Write it to the clipboard
+    Apply,
+    /// Drop it; the local clip is newer
+    Discard,
+    /// Hold it until the user confirms
+    Confirm,
+    /// Record it in history without touching the clipboard
+    HistoryOnly,
+}
+
+/// Whether a clip received at `remote_received_at` races a local change.
+/// The local clipboard changed at or after the clip arrived, e.g. while it
+/// was queued or held, so applying it would replace something newer. Both
+/// times are on our clock; the sender's is never compared, as it may be
+/// skewed. Clips without a receive time never conflict.
+pub fn is_conflict(local_changed_at: Option<DateTime<Utc>>, remote_received_at: Option<DateTime<Utc>>) -> bool {
+    matches!((local_changed_at, remote_received_at), (Some(local), Some(remote)) if local >= remote)
+}
+
+/// The apply decision for an incoming clip under `policy`.
+pub fn decide(policy: ConflictPolicy, local_changed_at: Option<DateTime<Utc>>, remote_received_at: Option<DateTime<Utc>>) -> ApplyDecision {
+    if !is_conflict(local_changed_at, remote_received_at) {
+        return ApplyDecision::Apply;
+    }
+    match policy {
+        ConflictPolicy::LastWriterWins => ApplyDecision::Discard,
+        ConflictPolicy::NeverOverwrite => ApplyDecision::Confirm,
+        ConflictPolicy::HistoryOnly => ApplyDecision::HistoryOnly,
+    }
+}
+
+/// A conflicting clip waiting for the user.
+pub struct HeldConflict {
+    pub content: ClipboardContent,
+    pub origin: ClipOrigin,
+}
+
+/// When the local clipboard last changed, and conflicting clips held for
+/// confirmation. Shared across restarts of the sync services.
+#[derive(Default)]
+pub struct ConflictGate {
+    local_changed_at: Mutex<Option<DateTime<Utc>>>,
+    pending: Mutex<HashMap<Uuid, HeldConflict>>,
+}
+
+impl ConflictGate {
+    pub fn note_local_change(&self) {
+        *self.local_changed_at.lock().unwrap_or_else(|e| e.into_inner()) = Some(Utc::now());
+    }
+
+    pub fn local_changed_at(&self) -> Option<DateTime<Utc>> {
+        *self.local_changed_at.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Hold a clip and return the id to confirm it with.
+    pub fn hold(&self, content: ClipboardContent, origin: ClipOrigin) -> Uuid {
+        let id = Uuid::new_v4();
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).insert(id, HeldConflict { content, origin });
+        id
+    }
+
+    pub fn take(&self, id: Uuid) -> Option<HeldConflict> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).remove(&id)
+    }
+
+    /// Drop every clip held from `ip`. Returns how many there were.
+    pub fn purge_from(&self, ip: IpAddr) -> usize {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let before = pending.len();
+        pending.retain(|_, held| held.origin.address.ip() != ip);
+        before - pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use crate::models::{ConflictPolicies, ContentKind, DescriptorKind};
+
+    fn at(seconds: i64) -> Option<DateTime<Utc>> {
+        Some(DateTime::<Utc>::UNIX_EPOCH + Duration::seconds(seconds))
+    }
+
+    const ALL: [ConflictPolicy; 3] = [
+        ConflictPolicy::LastWriterWins,
+        ConflictPolicy::NeverOverwrite,
+        ConflictPolicy::HistoryOnly,
+    ];
+
+    #[test]
+    fn remote_clip_after_local_change_applies_under_every_policy() {
+        for policy in ALL {
+            assert_eq!(decide(policy, at(10), at(11)), ApplyDecision::Apply, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn without_local_change_remote_clip_applies() {
+        for policy in ALL {
+            assert_eq!(decide(policy, None, at(11)), ApplyDecision::Apply, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn remote_clip_without_timestamp_applies() {
+        for policy in ALL {
+            assert_eq!(decide(policy, at(10), None), ApplyDecision::Apply, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn concurrent_text_keeps_last_writer() {
+        // Copied locally at 12 while a peer's clip received at 11 was queued
+        assert_eq!(decide(ConflictPolicy::LastWriterWins, at(12), at(11)), ApplyDecision::Discard);
+    }
+
+    #[test]
+    fn concurrent_image_waits_for_confirmation() {
+        assert_eq!(decide(ConflictPolicy::NeverOverwrite, at(12), at(11)), ApplyDecision::Confirm);
+    }
+
+    #[test]
+    fn concurrent_file_goes_to_history_only() {
+        assert_eq!(decide(ConflictPolicy::HistoryOnly, at(12), at(11)), ApplyDecision::HistoryOnly);
+    }
+
+    #[test]
+    fn non_text_clips_follow_the_policy_for_their_descriptor() {
+        let policies = ConflictPolicies::default();
+        let decided = |kind: DescriptorKind| decide(policies.for_kind(kind.into()), at(12), at(11));
+        assert_eq!(decided(DescriptorKind::Image), ApplyDecision::Confirm);
+        assert_eq!(decided(DescriptorKind::File), ApplyDecision::HistoryOnly);
+        // Rich text is still text
+        assert_eq!(decided(DescriptorKind::Rich), ApplyDecision::Discard);
+    }
+
+    #[test]
+    fn simultaneous_writes_keep_the_local_clip() {
+        assert!(is_conflict(at(11), at(11)));
+        assert_eq!(decide(ConflictPolicy::LastWriterWins, at(11), at(11)), ApplyDecision::Discard);
+    }
+
+    #[test]
+    fn held_conflict_is_taken_once() {
+        let gate = ConflictGate::default();
+        let origin = ClipOrigin {
+            peer_id: Uuid::new_v4(),
+            address: "192.0.2.1:8765".parse().unwrap(),
+            device: None,
+            message_id: None,
+            sent_at: None,
+            received_at: at(11),
+            kind: ContentKind::Text,
+        };
+        let id = gate.hold(ClipboardContent::plain("remote".to_string()), origin);
+        assert_eq!(gate.take(id).map(|held| held.content.text), Some("remote".to_string()));
+        assert!(gate.take(id).is_none());
+    }
+
+    #[test]
+    fn local_change_is_remembered() {
+        let gate = ConflictGate::default();
+        assert!(gate.local_changed_at().is_none());
+        gate.note_local_change();
+        let changed = gate.local_changed_at();
+        assert!(changed.is_some());
+        // A clip received before the change now conflicts
+        assert!(is_conflict(changed, changed.map(|t| t - Duration::seconds(1))));
+    }
+}
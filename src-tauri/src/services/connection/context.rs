@@ -0,0 +1,104 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::{RwLock, broadcast, watch};
+use uuid::Uuid;
+use tauri::AppHandle;
+use crate::models::{Config, MessageCache, PeerEndpoint, StatusSummary, SyncMetrics};
+use crate::services::remote_logs::PendingLogRequests;
+use crate::services::trust::TrustStore;
+use crate::services::api_tokens::ApiTokenStore;
+use crate::services::size_policy::OversizeGate;
+use crate::services::tombstones::TombstoneTx;
+use crate::services::device_names::DeviceDirectory;
+use crate::services::heartbeat::LinkHistory;
+use crate::services::shutdown::Shutdown;
+use crate::services::applied_log::AppliedLog;
+use crate::services::liveness::LivenessRegistry;
+use crate::services::sessions::SessionRegistry;
+use crate::services::peer_capabilities::CapabilityRegistry;
+use crate::services::key_rotation::SharedKeyRing;
+use crate::services::key_derivation::KeyDerivation;
+use crate::services::peer_metrics::PeerMetricsMap;
+use crate::services::receipts::ReceiptTx;
+use crate::services::presentation::SharedPresentation;
+use crate::services::delta_sync::DeltaState;
+use crate::services::remote_mute::MuteRegistry;
+use crate::services::versions::VersionMismatches;
+use super::{ApiTx, ClipboardCallback, PeerMap, Tx};
+
+/// Shared state handed to every connection task.
+#[derive(Clone)]
+pub(crate) struct ConnectionContext {
+    pub peers: PeerMap,
+    pub tx: Tx,
+    pub message_cache: Arc<RwLock<MessageCache>>,
+    pub clipboard_callback: ClipboardCallback,
+    pub config: Arc<RwLock<Config>>,
+    pub pending_log_requests: PendingLogRequests,
+    pub api_tx: ApiTx,
+    /// Peer connections to close, by connection id
+    pub peer_close: broadcast::Sender<Uuid>,
+    /// Peers to redial on another interface after theirs went away
+    pub migrations: Arc<RwLock<Vec<PeerEndpoint>>>,
+    pub tombstone_tx: TombstoneTx,
+    pub receipt_tx: ReceiptTx,
+    /// Clips applied here, across restarts
+    pub applied: Arc<AppliedLog>,
+    /// Friendly names of senders, resolved when events are emitted
+    pub directory: Arc<DeviceDirectory>,
+    pub security: SecurityContext,
+    pub policy: PolicyContext,
+    pub telemetry: TelemetryContext,
+    /// Where UI events go, when running inside the app
+    pub app: Option<AppHandle>,
+    /// Stops the server's tasks, including each connection's own
+    pub shutdown: Shutdown,
+}
+
+/// Who may connect and which keys their messages are checked against.
+#[derive(Clone)]
+pub(crate) struct SecurityContext {
+    pub trust: Arc<RwLock<TrustStore>>,
+    pub api_tokens: Arc<RwLock<ApiTokenStore>>,
+    /// Ids of revoked tokens, so live connections using them are closed
+    pub api_revoked: broadcast::Sender<Uuid>,
+    /// Session agreed with each connected peer in its handshake
+    pub sessions: Arc<SessionRegistry>,
+    /// The previous security key, accepted for a while after a change
+    pub key_ring: SharedKeyRing,
+    /// Our salt and the keys derived from the passphrase with it and peers' salts
+    pub keys: Arc<KeyDerivation>,
+}
+
+/// What flows to and from each peer right now.
+#[derive(Clone)]
+pub(crate) struct PolicyContext {
+    /// Sync is paused on this device: nothing is sent or applied
+    pub paused: Arc<RwLock<bool>>,
+    /// Peers that told us they paused sync
+    pub paused_peers: Arc<RwLock<HashSet<Uuid>>>,
+    /// Presenter or viewer role, while a presentation runs
+    pub presentation: SharedPresentation,
+    /// Which peers take deltas, and the clips they are diffed against
+    pub delta: Arc<DeltaState>,
+    /// Peers that asked not to be sent content for a while, and the reverse
+    pub mutes: Arc<MuteRegistry>,
+    /// Oversized clips held for the user's decision
+    pub oversize: Arc<OversizeGate>,
+    /// What each connected peer declared it takes in its `Hello`
+    pub capabilities: Arc<CapabilityRegistry>,
+}
+
+/// Counters and link state reported to the UI and local API clients.
+#[derive(Clone)]
+pub(crate) struct TelemetryContext {
+    pub sync_metrics: Arc<RwLock<SyncMetrics>>,
+    pub peer_metrics: PeerMetricsMap,
+    /// Latest status summary, pushed to local API clients that ask
+    pub status: Arc<watch::Sender<StatusSummary>>,
+    /// How recent connections to each device ended, for heartbeat cadence
+    pub link_history: LinkHistory,
+    pub liveness: Arc<LivenessRegistry>,
+    /// Peers whose app version differs from ours, by address
+    pub version_mismatches: VersionMismatches,
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use uuid::Uuid;
+use crate::models::{ClipboardMessage, LogRequest, LogResponse, MessageType, Protection, SyncStateNotice};
+use crate::services::peer_metrics;
+use crate::services::remote_logs;
+use super::ConnectionContext;
+
+impl ConnectionContext {
+    /// Answer a peer's request for recent log lines. Logs are only shared
+    /// with paired devices (a security key is set) and with local consent.
+    pub(super) async fn handle_log_request(&self, peer_id: Uuid, request_id: Uuid, content: Option<&str>) {
+        if !self.is_peer_allowed(peer_id).await {
+            tracing::info!("Ignoring log request from untrusted peer {}", peer_id);
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+        let Some(request) = content.and_then(|c| serde_json::from_str::<LogRequest>(c).ok()) else {
+            tracing::warn!("Malformed log request from {}", peer_id);
+            return;
+        };
+
+        let allowed = {
+            let config = self.config.read().await;
+            config.effective_security_key().is_some() && config.allow_remote_log_requests
+        };
+        tracing::info!(
+            "Log request from {} for {} lines: {}",
+            peer_id, request.lines, if allowed { "granted" } else { "denied" }
+        );
+
+        let response = remote_logs::build_response(request_id, &request, allowed);
+        let message = match serde_json::to_string(&response) {
+            Ok(payload) => ClipboardMessage::new(MessageType::LogResponse, Some(payload)),
+            Err(e) => {
+                tracing::error!("Failed to encode log response: {}", e);
+                return;
+            }
+        };
+        if let Err(e) = self.send_to_peer(peer_id, message).await {
+            tracing::warn!("Failed to send log response to {}: {}", peer_id, e);
+        }
+    }
+
+    pub(super) async fn handle_log_response(&self, peer_id: Uuid, content: Option<&str>) {
+        let Some(response) = content.and_then(|c| serde_json::from_str::<LogResponse>(c).ok()) else {
+            tracing::warn!("Malformed log response from {}", peer_id);
+            return;
+        };
+
+        // Only responses to requests we actually made are accepted
+        match self.pending_log_requests.write().await.remove(&response.request_id) {
+            Some(waiter) => {
+                let _ = waiter.send(response);
+            }
+            None => {
+                tracing::debug!("Ignoring unsolicited log response from {}", peer_id);
+            }
+        }
+    }
+
+    /// Tell a single peer whether sync is paused on this device.
+    pub async fn send_sync_state(&self, peer_id: Uuid, paused: bool) -> Result<()> {
+        let payload = serde_json::to_string(&SyncStateNotice { paused })?;
+        let message = ClipboardMessage::new(MessageType::SyncState, Some(payload))
+            .with_protection(Protection::Signed);
+        self.send_to_peer(peer_id, message).await
+    }
+
+    pub(super) async fn handle_sync_state(&self, peer_id: Uuid, content: Option<&str>) {
+        let Some(notice) = content.and_then(|c| serde_json::from_str::<SyncStateNotice>(c).ok()) else {
+            tracing::warn!("Malformed sync state from {}", peer_id);
+            return;
+        };
+
+        tracing::info!("Peer {} {} sync", peer_id, if notice.paused { "paused" } else { "resumed" });
+        let mut paused_peers = self.policy.paused_peers.write().await;
+        if notice.paused {
+            paused_peers.insert(peer_id);
+        } else {
+            paused_peers.remove(&peer_id);
+        }
+    }
+}
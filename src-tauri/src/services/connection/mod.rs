@@ -0,0 +1,426 @@
+use tokio_tungstenite::tungstenite::Message;
+use std::sync::Arc;
+use tokio::sync::{RwLock, broadcast, oneshot};
+use std::collections::HashMap;
+use uuid::Uuid;
+use anyhow::Result;
+use std::net::SocketAddr;
+use crate::models::{ClipOrigin, ClipboardContent, ClipboardMessage, MessageType, RichFormats};
+use crate::utils::{crypto, hashing};
+use super::size_policy;
+use super::tombstones;
+use super::announcements;
+use super::events::{self, PeerEvent, PEER_CONNECTED_EVENT, PEER_DISCONNECTED_EVENT};
+use super::key_rotation;
+use super::key_derivation;
+use super::lanes::{Lane, PeerLink};
+use super::language_routes::{self, Detected};
+use super::peer_metrics;
+use super::receipts;
+use super::protocol_trace;
+use super::delta_sync;
+use super::remote_mute;
+use super::versions;
+
+mod context;
+mod control;
+mod outgoing;
+mod updates;
+#[cfg(test)]
+mod tests;
+
+pub(crate) use context::{ConnectionContext, PolicyContext, SecurityContext, TelemetryContext};
+
+/// A serialized message on its way to every peer.
+#[derive(Debug, Clone)]
+pub(crate) struct Outbound {
+    /// Peer the message came from; it is skipped so nothing echoes back
+    pub origin: Option<Uuid>,
+    /// Message id, used to key chunk frames when the message is streamed
+    pub id: Uuid,
+    pub lane: Lane,
+    pub json: String,
+    /// The message `json` holds, when it parsed; each connection changes
+    /// its own copy rather than parsing `json` again
+    pub message: Option<Arc<ClipboardMessage>>,
+    /// Detected language of a local text clip, for `language_routes`
+    pub language: Option<Detected>,
+    /// Type of the message, for the protocol trace; unknown for relayed
+    /// frames that did not parse
+    pub msg_type: Option<MessageType>,
+}
+
+/// Outbound fan-out channel shared by all connection tasks.
+pub(crate) type Tx = broadcast::Sender<Outbound>;
+pub(crate) type PeerMap = Arc<RwLock<HashMap<Uuid, (SocketAddr, PeerLink)>>>;
+/// Plaintext events for local API clients: (connection to skip, event JSON).
+pub(crate) type ApiTx = broadcast::Sender<(Option<Uuid>, String)>;
+/// Hands a peer's clip to the clipboard, answering once it is written,
+/// held, or turned down.
+pub(crate) type ClipboardCallback = Arc<RwLock<Option<Box<dyn Fn(ClipboardContent, ClipOrigin) -> oneshot::Receiver<ApplyOutcome> + Send + Sync>>>>;
+
+/// What became of a peer's clip handed to the clipboard callback.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    /// Written to the clipboard, as it was after format filtering
+    Applied(ClipboardContent),
+    /// Held for the user, or recorded in history only
+    Held,
+    /// Turned down: the local clip is newer, or this device only sends
+    Declined,
+    /// The clipboard could not be written
+    Failed,
+}
+
+impl ConnectionContext {
+    /// Tell the UI a peer connected or disconnected.
+    pub async fn emit_peer_event(&self, connected: bool, peer_id: Uuid, address: SocketAddr) {
+        let event = if connected { PEER_CONNECTED_EVENT } else { PEER_DISCONNECTED_EVENT };
+        let connected_peers = self.peers.read().await.len() as u32;
+        events::emit(self.app.as_ref(), event, PeerEvent { peer_id, address, connected_peers });
+        announcements::peer(self, connected, peer_id, address).await;
+    }
+
+    async fn security_key(&self) -> Option<String> {
+        self.config.read().await.effective_security_key().map(str::to_string)
+    }
+
+    /// Whether clipboard content may flow to/from this peer. With
+    /// `trusted_only` enabled, only peers whose IP was explicitly trusted
+    /// pass, and the presenter this device joined.
+    pub async fn is_peer_allowed(&self, peer_id: Uuid) -> bool {
+        if !self.config.read().await.trusted_only {
+            return true;
+        }
+        let Some(address) = self.peers.read().await.get(&peer_id).map(|(addr, _)| *addr) else {
+            return false;
+        };
+        self.policy.presentation.read().await.is_presenter(address)
+            || self.security.trust.read().await.is_trusted_ip(address.ip())
+    }
+
+    /// Whether clipboard content should be forwarded to this peer right now.
+    /// Viewers that joined with the session code always receive; a
+    /// presenter being viewed never does.
+    pub async fn should_send_to(&self, peer_id: Uuid) -> bool {
+        if *self.policy.paused.read().await || self.policy.paused_peers.read().await.contains(&peer_id) {
+            return false;
+        }
+        let address = self.peers.read().await.get(&peer_id).map(|(addr, _)| *addr);
+        if let Some(address) = address {
+            if self.policy.mutes.is_muted(address.ip()) {
+                return false;
+            }
+            let presentation = self.policy.presentation.read().await;
+            if presentation.is_viewer(address) {
+                return true;
+            }
+            if presentation.is_presenter(address) {
+                return false;
+            }
+        }
+        self.is_peer_allowed(peer_id).await
+    }
+
+    /// Check a message against the configured key. Without a key every
+    /// message is accepted; with one, the signature must be present and valid.
+    fn is_authentic(key: Option<&str>, message: &ClipboardMessage) -> bool {
+        match key {
+            None => true,
+            Some(key) => message.signature.as_deref().is_some_and(|signature| {
+                crypto::verify_signature(key, &message.signing_payload(), signature)
+            }),
+        }
+    }
+
+    /// Plaintext of a message field, decrypting it when the message is flagged as encrypted.
+    fn decrypt_field(key: Option<&str>, field: &Option<String>, encrypted: bool) -> Result<Option<String>> {
+        match (field, encrypted) {
+            (Some(value), true) => {
+                let key = key.ok_or_else(|| anyhow::anyhow!("encrypted content but no security key configured"))?;
+                crypto::decrypt_content(key, value).map(Some)
+            }
+            (value, _) => Ok(value.clone()),
+        }
+    }
+
+    /// Clipboard content of an update, with rich formats when the sender included them.
+    pub fn clipboard_content(text: String, formats: Option<&str>) -> ClipboardContent {
+        let formats = formats
+            .and_then(|f| serde_json::from_str::<RichFormats>(f).ok())
+            .unwrap_or_default();
+        ClipboardContent {
+            text,
+            html: formats.html,
+            rtf: formats.rtf,
+        }
+    }
+
+    /// Verify, deduplicate, decrypt, and dispatch one text frame from a peer.
+    pub async fn handle_text(&self, peer_id: Uuid, text: &str) {
+        let address = self.peers.read().await.get(&peer_id).map(|(addr, _)| *addr);
+        // Try to parse as ClipboardMessage
+        let message = match serde_json::from_str::<ClipboardMessage>(text) {
+            Ok(message) => message,
+            Err(e) => {
+                if versions::refuses(self, address, versions::written_in(text)).await {
+                    protocol_trace::received(address, None, text.len(), "unsupported protocol");
+                    peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+                    return;
+                }
+                tracing::warn!("Failed to parse clipboard message from {}: {}", peer_id, e);
+                // Unparseable messages can't be verified or carry a session,
+                // so drop them when a key is set or a session was agreed
+                if self.security_key().await.is_some() || self.security.sessions.get(peer_id).is_some() {
+                    protocol_trace::received(address, None, text.len(), "unparseable");
+                    peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+                    return;
+                }
+                // Still broadcast raw message for compatibility
+                protocol_trace::received(address, None, text.len(), "unparseable, relayed");
+                self.rebroadcast(Some(peer_id), Uuid::new_v4(), None, text).await;
+                return;
+            }
+        };
+
+        // Update metrics for received message
+        {
+            let mut metrics = self.telemetry.sync_metrics.write().await;
+            metrics.messages_received += 1;
+            metrics.bytes_received += text.len() as u64;
+            metrics.last_sync_time = Some(chrono::Utc::now());
+        }
+        peer_metrics::record_received(&self.telemetry, peer_id, text.len()).await;
+
+        // Structure only: never the payload itself
+        tracing::debug!(
+            peer = %peer_id,
+            id = %message.id,
+            msg_type = ?message.msg_type,
+            bytes = text.len(),
+            hash = %hashing::short_hash(text.as_bytes()),
+            encrypted = message.encrypted,
+            "Received message"
+        );
+
+        let (key, log_content) = {
+            let config = self.config.read().await;
+            (
+                config.effective_security_key().map(str::to_string),
+                config.dangerously_log_clipboard_content,
+            )
+        };
+
+        if versions::refuses(self, address, message.protocol_version).await {
+            protocol_trace::received(address, Some(&message), text.len(), "unsupported protocol");
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+
+        // A message from another connection is dropped before its
+        // signature is checked
+        if !self.security.sessions.admits(peer_id, &message) {
+            tracing::warn!("Rejecting message {} from {}: not bound to this session", message.id, peer_id);
+            protocol_trace::received(address, Some(&message), text.len(), "wrong session");
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+
+        // Reject unsigned or forged messages before they touch the cache
+        let key = match key_rotation::verifying_key(&self.security, peer_id, key, &message).await {
+            Ok(key) => key,
+            Err(reason) => {
+                tracing::warn!("Rejecting message {} from {}: {}", message.id, peer_id, reason);
+                protocol_trace::received(address, Some(&message), text.len(), reason);
+                peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+                return;
+            }
+        };
+        if !Self::is_authentic(key.as_deref(), &message) {
+            tracing::warn!(
+                "Rejecting message {} from {}: missing or invalid signature",
+                message.id, peer_id
+            );
+            protocol_trace::received(address, Some(&message), text.len(), "invalid signature");
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+
+        // Check for duplicate message
+        {
+            let mut cache = self.message_cache.write().await;
+            if cache.is_duplicate(&message.id) {
+                tracing::debug!("Ignoring duplicate message {}", message.id);
+                protocol_trace::received(address, Some(&message), text.len(), "duplicate");
+                return;
+            }
+            cache.add_message(message.id);
+            if cache.should_cleanup() {
+                cache.cleanup_old_messages();
+            }
+        }
+
+        // Clips too big to take are dropped on their descriptor, without
+        // decrypting the body
+        let oversized = match message.descriptor {
+            Some(ref descriptor) => size_policy::rejects(&*self.config.read().await, descriptor),
+            None => false,
+        };
+        if oversized {
+            tracing::info!("Dropping oversized clipboard update {} from {} before decrypting it", message.id, peer_id);
+            protocol_trace::received(address, Some(&message), text.len(), "oversized");
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+
+        // Decrypt before anything reaches the clipboard
+        let decrypted = Self::decrypt_field(key.as_deref(), &message.content, message.encrypted)
+            .and_then(|content| {
+                let formats = Self::decrypt_field(key.as_deref(), &message.formats, message.encrypted)?;
+                let hash = Self::decrypt_field(key.as_deref(), &message.content_hash, message.encrypted)?;
+                Ok((content, formats, hash))
+            });
+        let (content, formats, content_hash) = match decrypted {
+            Ok(decrypted) => decrypted,
+            Err(e) => {
+                tracing::warn!("Rejecting message {} from {}: {}", message.id, peer_id, e);
+                protocol_trace::received(address, Some(&message), text.len(), "undecryptable");
+                peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+                return;
+            }
+        };
+        protocol_trace::received(address, Some(&message), text.len(), "accepted");
+
+        if log_content {
+            tracing::debug!(peer = %peer_id, id = %message.id, "Message content: {:?}", content);
+        }
+
+        match message.msg_type {
+            MessageType::ClipboardUpdate => {
+                if let Some(text_content) = content {
+                    let clip = Self::clipboard_content(text_content, formats.as_deref());
+                    delta_sync::note_full(self, peer_id, message.id, &clip, content_hash.as_deref()).await;
+                    self.handle_clipboard_update(peer_id, &message, clip, content_hash, text).await;
+                }
+            }
+            MessageType::LogRequest => {
+                self.handle_log_request(peer_id, message.id, content.as_deref()).await;
+            }
+            MessageType::LogResponse => {
+                self.handle_log_response(peer_id, content.as_deref()).await;
+            }
+            MessageType::SyncState => {
+                self.handle_sync_state(peer_id, content.as_deref()).await;
+            }
+            MessageType::DeviceRemoved => {
+                tombstones::handle(self, peer_id, message.id, content.as_deref()).await;
+            }
+            MessageType::Receipt => {
+                receipts::handle(self, peer_id, message.id, content.as_deref()).await;
+            }
+            MessageType::ClipboardDelta => {
+                delta_sync::handle(self, peer_id, &message, content.as_deref()).await;
+            }
+            MessageType::DeltaAck => {
+                delta_sync::handle_ack(self, peer_id, content.as_deref()).await;
+            }
+            MessageType::MuteRequest => {
+                remote_mute::handle(self, peer_id, content.as_deref()).await;
+            }
+            MessageType::Hello => {
+                versions::handle_hello(self, peer_id, content.as_deref()).await;
+            }
+            MessageType::KeyRotation => {
+                key_rotation::handle(self, peer_id, &message, content.as_deref()).await;
+            }
+            MessageType::Unknown => {
+                tracing::debug!("Skipping message {} from {}: a type this build does not know", message.id, peer_id);
+            }
+            MessageType::Heartbeat => {
+                peer_metrics::record_heartbeat(&self.telemetry, peer_id).await;
+            }
+            MessageType::DeviceInfo => {
+                tracing::debug!("Ignoring {:?} message from {}", message.msg_type, peer_id);
+            }
+        }
+    }
+
+    /// Protect a message for the wire according to the current config.
+    /// Every outgoing peer message passes through here.
+    pub(crate) async fn protect(&self, message: &mut ClipboardMessage) -> Result<()> {
+        self.security.keys.protect(message, self.security_key().await.as_deref(), true).await
+    }
+
+    /// Protect and fan a message out to every peer.
+    pub async fn broadcast(&self, mut message: ClipboardMessage) -> Result<()> {
+        self.policy.delta.remember_sent(&message);
+        let language = language_routes::detect_message(&self.config.read().await.language_routes, &message);
+        self.protect(&mut message).await?;
+        {
+            let mut cache = self.message_cache.write().await;
+            cache.add_message(message.id);
+            if cache.should_cleanup() {
+                cache.cleanup_old_messages();
+            }
+        }
+        let json = serde_json::to_string(&message)?;
+        {
+            let mut metrics = self.telemetry.sync_metrics.write().await;
+            metrics.messages_sent += 1;
+            metrics.bytes_sent += json.len() as u64;
+            metrics.last_sync_time = Some(chrono::Utc::now());
+        }
+        // No receivers is normal when no peers are connected
+        let lane = Lane::of(&message.msg_type);
+        let _ = self.tx.send(Outbound { origin: None, id: message.id, lane, json, language, msg_type: Some(message.msg_type.clone()), message: Some(Arc::new(message)) });
+        Ok(())
+    }
+
+    /// Relay a peer's clip (or unparseable frame) to the other peers,
+    /// protected with our key rather than the sender's.
+    async fn rebroadcast(&self, origin: Option<Uuid>, id: Uuid, msg_type: Option<MessageType>, text: &str) {
+        let Some(json) = key_derivation::relay(self, origin, text).await else {
+            return;
+        };
+        let message = serde_json::from_str(&json).ok().map(Arc::new);
+        if let Err(e) = self.tx.send(Outbound { origin, id, lane: Lane::Data, json, language: None, msg_type, message }) {
+            tracing::error!("Failed to broadcast message: {}", e);
+            let mut metrics = self.telemetry.sync_metrics.write().await;
+            metrics.messages_failed += 1;
+        }
+    }
+
+    /// Protect and send a message to a single peer.
+    pub async fn send_to_peer(&self, peer_id: Uuid, message: ClipboardMessage) -> Result<()> {
+        let key = self.security_key().await;
+        self.send_to_peer_with_key(peer_id, message, key.as_deref()).await
+    }
+
+    /// Send a message to a single peer, protected under the `passphrase`
+    /// given rather than the configured one.
+    pub(crate) async fn send_to_peer_with_key(&self, peer_id: Uuid, mut message: ClipboardMessage, passphrase: Option<&str>) -> Result<()> {
+        message.session = self.security.sessions.get(peer_id);
+        self.security.keys.protect(&mut message, passphrase, self.security.keys.takes_derived(peer_id)).await?;
+        let json = serde_json::to_string(&message)?;
+        let json_len = json.len();
+
+        let peers = self.peers.read().await;
+        let (address, link) = peers
+            .get(&peer_id)
+            .ok_or_else(|| anyhow::anyhow!("Peer {} is not connected", peer_id))?;
+        link
+            .send(Lane::of(&message.msg_type), Message::Text(json.into()))
+            .map_err(|_| anyhow::anyhow!("Connection to peer {} is closing", peer_id))?;
+        protocol_trace::sent(Some(*address), message.id, Some(message.msg_type), json_len);
+
+        drop(peers);
+
+        {
+            let mut metrics = self.telemetry.sync_metrics.write().await;
+            metrics.messages_sent += 1;
+            metrics.bytes_sent += json_len as u64;
+        }
+        peer_metrics::record_sent(&self.telemetry, peer_id, json_len).await;
+        Ok(())
+    }
+}
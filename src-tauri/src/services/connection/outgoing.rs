@@ -0,0 +1,54 @@
+use uuid::Uuid;
+use crate::services::lanes::Lane;
+use crate::services::peer_capabilities;
+use super::{ConnectionContext, Outbound};
+
+impl ConnectionContext {
+    /// The frame `peer_id` gets for `outbound`, or `None` when the peer
+    /// does not take it. Our clips go as a delta when one pays off; clips
+    /// are fitted to what the peer declared, rewritten for a peer admitted
+    /// without a salt, and bound to its session. Those changes are made to
+    /// a copy of the typed message, which is then protected and serialized
+    /// once; without any the shared frame goes as is.
+    pub(crate) async fn frame_for(&self, peer_id: Uuid, outbound: &Outbound) -> Option<String> {
+        let Some(ref shared) = outbound.message else {
+            return Some(outbound.json.clone());
+        };
+        let session = self.security.sessions.get(peer_id);
+        let passphrase = self.security_key().await;
+
+        if outbound.lane == Lane::Data && outbound.origin.is_none() {
+            if let Some(mut delta) = self.policy.delta.encode_for(peer_id, outbound.id) {
+                delta.session = session;
+                let derived = self.security.keys.takes_derived(peer_id);
+                match self.security.keys.protect(&mut delta, passphrase.as_deref(), derived).await {
+                    Ok(()) => return serde_json::to_string(&delta).ok(),
+                    Err(e) => tracing::warn!("Failed to protect delta for {}: {}", peer_id, e),
+                }
+            }
+        }
+
+        let mut message = (**shared).clone();
+        let mut changed = false;
+        if outbound.lane == Lane::Data {
+            if let Some(capabilities) = self.policy.capabilities.get(peer_id) {
+                if !peer_capabilities::fits(&capabilities, &message) {
+                    tracing::info!("Not sending {} clip {}: it takes up to {:?} bytes", peer_id, message.id, capabilities.max_message_bytes);
+                    return None;
+                }
+                changed |= peer_capabilities::tailor(&capabilities, &mut message);
+            }
+        }
+        changed |= self.security.sessions.bind(peer_id, &mut message);
+        if !changed && !self.security.keys.rewrites_for(peer_id, &message) {
+            return Some(outbound.json.clone());
+        }
+        if let Some(passphrase) = passphrase {
+            if let Err(e) = self.security.keys.for_peer(peer_id, &mut message, &passphrase).await {
+                tracing::debug!("Failed to protect message {} for {}: {}", message.id, peer_id, e);
+                return Some(outbound.json.clone());
+            }
+        }
+        serde_json::to_string(&message).ok()
+    }
+}
@@ -0,0 +1,261 @@
+use super::*;
+use tokio::sync::mpsc;
+use crate::models::{Config, ContentKind, DescriptorKind, PeerCapabilities, Protection, SyncStateNotice};
+use crate::services::lanes::PeerLink;
+use crate::services::websocket::WebSocketServer;
+
+const KEY: &str = "shared passphrase for tests";
+
+fn context() -> ConnectionContext {
+    let config = Config { security_key: Some(KEY.to_string()), ..Config::default() };
+    let server = WebSocketServer::new(
+        0,
+        Arc::new(RwLock::new(config)),
+        Arc::default(),
+        Arc::default(),
+        Arc::default(),
+        Arc::default(),
+    );
+    server.context()
+}
+
+/// A sync state notice saying the sender paused: once dispatched, the
+/// sender shows up in `paused_peers`.
+fn pause_notice() -> ClipboardMessage {
+    let payload = serde_json::to_string(&SyncStateNotice { paused: true }).unwrap();
+    ClipboardMessage::new(MessageType::SyncState, Some(payload)).with_protection(Protection::Signed)
+}
+
+fn signed(mut message: ClipboardMessage, key: &str) -> String {
+    message.protect(Some(key)).unwrap();
+    serde_json::to_string(&message).unwrap()
+}
+
+async fn dispatched(ctx: &ConnectionContext, peer_id: Uuid) -> bool {
+    ctx.policy.paused_peers.read().await.contains(&peer_id)
+}
+
+async fn rejected(ctx: &ConnectionContext) -> u64 {
+    ctx.telemetry.sync_metrics.read().await.messages_rejected
+}
+
+#[tokio::test]
+async fn signed_messages_are_dispatched() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    ctx.handle_text(peer_id, &signed(pause_notice(), KEY)).await;
+    assert!(dispatched(&ctx, peer_id).await);
+    assert_eq!(rejected(&ctx).await, 0);
+}
+
+#[tokio::test]
+async fn unsigned_messages_are_not_dispatched() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    let message = pause_notice();
+    ctx.handle_text(peer_id, &serde_json::to_string(&message).unwrap()).await;
+    assert!(!dispatched(&ctx, peer_id).await);
+    assert_eq!(rejected(&ctx).await, 1);
+    // Rejected before the cache, so a signed copy is not taken for a duplicate
+    assert!(!ctx.message_cache.write().await.is_duplicate(&message.id));
+}
+
+#[tokio::test]
+async fn messages_signed_with_another_key_are_not_dispatched() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    ctx.handle_text(peer_id, &signed(pause_notice(), "some other passphrase")).await;
+    assert!(!dispatched(&ctx, peer_id).await);
+    assert_eq!(rejected(&ctx).await, 1);
+}
+
+#[tokio::test]
+async fn tampered_messages_are_not_dispatched() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    let json = signed(pause_notice(), KEY);
+    let mut message: ClipboardMessage = serde_json::from_str(&json).unwrap();
+    message.timestamp += chrono::Duration::seconds(1);
+    ctx.handle_text(peer_id, &serde_json::to_string(&message).unwrap()).await;
+    assert!(!dispatched(&ctx, peer_id).await);
+    assert_eq!(rejected(&ctx).await, 1);
+}
+
+#[tokio::test]
+async fn messages_bound_to_another_session_are_not_dispatched() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    ctx.security.sessions.open(peer_id, Uuid::new_v4());
+    let mut message = pause_notice();
+    message.bind_session(Uuid::new_v4(), None);
+    ctx.handle_text(peer_id, &signed(message, KEY)).await;
+    assert!(!dispatched(&ctx, peer_id).await);
+    assert_eq!(rejected(&ctx).await, 1);
+}
+
+#[tokio::test]
+async fn messages_without_a_session_are_not_dispatched_on_a_session() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    ctx.security.sessions.open(peer_id, Uuid::new_v4());
+    ctx.handle_text(peer_id, &signed(pause_notice(), KEY)).await;
+    assert!(!dispatched(&ctx, peer_id).await);
+    assert_eq!(rejected(&ctx).await, 1);
+}
+
+#[tokio::test]
+async fn messages_bound_to_the_session_are_dispatched() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    let session = Uuid::new_v4();
+    ctx.security.sessions.open(peer_id, session);
+    let mut message = pause_notice();
+    message.bind_session(session, None);
+    ctx.handle_text(peer_id, &signed(message, KEY)).await;
+    assert!(dispatched(&ctx, peer_id).await);
+}
+
+#[tokio::test]
+async fn unparseable_frames_are_dropped_when_a_key_is_set() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    let mut outbound = ctx.tx.subscribe();
+    ctx.handle_text(peer_id, "not a message").await;
+    assert_eq!(rejected(&ctx).await, 1);
+    assert!(outbound.try_recv().is_err(), "unparseable frame was relayed");
+}
+
+/// Register `peer_id` as a connected peer.
+async fn connect(ctx: &ConnectionContext, peer_id: Uuid) {
+    let (link, _, _) = PeerLink::new();
+    ctx.peers.write().await.insert(peer_id, ("192.168.1.20:7878".parse().unwrap(), link));
+}
+
+/// Answer every clip handed to the clipboard with `outcome`.
+async fn answer(ctx: &ConnectionContext, outcome: ApplyOutcome) {
+    *ctx.clipboard_callback.write().await = Some(Box::new(move |_, _| {
+        let (answer, outcome_rx) = oneshot::channel();
+        let _ = answer.send(outcome.clone());
+        outcome_rx
+    }));
+}
+
+fn clip(text: &str) -> String {
+    let message = ClipboardMessage::clipboard_update(ClipboardContent::plain(text.to_string())).unwrap();
+    signed(message, KEY)
+}
+
+#[tokio::test]
+async fn applied_clips_are_published_and_relayed() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    connect(&ctx, peer_id).await;
+    answer(&ctx, ApplyOutcome::Applied(ClipboardContent::plain("as written".to_string()))).await;
+    let (mut api, mut relayed) = (ctx.api_tx.subscribe(), ctx.tx.subscribe());
+    ctx.handle_text(peer_id, &clip("hello")).await;
+    // API clients get the clip as the clipboard took it
+    assert!(api.try_recv().unwrap().1.contains("as written"));
+    assert_eq!(relayed.try_recv().unwrap().origin, Some(peer_id));
+}
+
+#[tokio::test]
+async fn clips_not_applied_are_neither_published_nor_relayed() {
+    for outcome in [ApplyOutcome::Held, ApplyOutcome::Declined, ApplyOutcome::Failed] {
+        let ctx = context();
+        let peer_id = Uuid::new_v4();
+        connect(&ctx, peer_id).await;
+        answer(&ctx, outcome.clone()).await;
+        let (mut api, mut relayed) = (ctx.api_tx.subscribe(), ctx.tx.subscribe());
+        ctx.handle_text(peer_id, &clip("hello")).await;
+        assert!(api.try_recv().is_err(), "{:?} clip was published", outcome);
+        assert!(relayed.try_recv().is_err(), "{:?} clip was relayed", outcome);
+    }
+}
+
+#[tokio::test]
+async fn clips_carry_the_kind_from_their_descriptor() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    connect(&ctx, peer_id).await;
+    let (kind_tx, mut kind_rx) = mpsc::unbounded_channel();
+    *ctx.clipboard_callback.write().await = Some(Box::new(move |_, origin| {
+        let _ = kind_tx.send(origin.kind);
+        oneshot::channel().1
+    }));
+    let mut message = ClipboardMessage::clipboard_update(ClipboardContent::plain("photo.png".to_string())).unwrap();
+    message.descriptor.as_mut().unwrap().kind = DescriptorKind::Image;
+    ctx.handle_text(peer_id, &signed(message, KEY)).await;
+    assert_eq!(kind_rx.try_recv().unwrap(), ContentKind::Image);
+
+    ctx.handle_text(peer_id, &clip("hello")).await;
+    assert_eq!(kind_rx.try_recv().unwrap(), ContentKind::Text);
+}
+
+#[tokio::test]
+async fn clips_the_clipboard_never_answers_for_count_as_failed() {
+    let ctx = context();
+    *ctx.clipboard_callback.write().await = Some(Box::new(|_, _| oneshot::channel().1));
+    let origin = ClipOrigin { peer_id: Uuid::new_v4(), address: "192.168.1.20:7878".parse().unwrap(), device: None, message_id: None, sent_at: None, received_at: None, kind: ContentKind::Text };
+    let outcome = ctx.apply_clipboard_update(&origin, &ClipboardContent::plain("hello".to_string())).await;
+    assert_eq!(outcome, ApplyOutcome::Failed);
+    assert_eq!(ctx.telemetry.sync_metrics.read().await.clipboard_updates_failed, 1);
+}
+
+/// Our clip as it is fanned out to every connection.
+async fn outbound_clip(ctx: &ConnectionContext, text: &str) -> Outbound {
+    let mut outbound = ctx.tx.subscribe();
+    ctx.broadcast(ClipboardMessage::clipboard_update(ClipboardContent::plain(text.to_string())).unwrap()).await.unwrap();
+    outbound.try_recv().unwrap()
+}
+
+async fn verifies(ctx: &ConnectionContext, frame: &ClipboardMessage) -> bool {
+    let key = ctx.security.keys.sending_key(KEY, frame).await;
+    crypto::verify_signature(&key, &frame.signing_payload(), frame.signature.as_deref().unwrap_or_default())
+}
+
+#[tokio::test]
+async fn frames_are_bound_to_each_peers_session_and_signed_for_it() {
+    let ctx = context();
+    let (derived, legacy) = (Uuid::new_v4(), Uuid::new_v4());
+    ctx.security.keys.open(derived, [9; 16]);
+    for peer in [derived, legacy] {
+        ctx.security.sessions.open(peer, Uuid::new_v4());
+    }
+    let outbound = outbound_clip(&ctx, "hello").await;
+
+    for peer in [derived, legacy] {
+        let frame: ClipboardMessage = serde_json::from_str(&ctx.frame_for(peer, &outbound).await.unwrap()).unwrap();
+        assert_eq!(frame.session, ctx.security.sessions.get(peer));
+        assert!(verifies(&ctx, &frame).await, "frame for {} does not verify", peer);
+    }
+    // A peer admitted without a salt gets the clip under the passphrase
+    let frame: ClipboardMessage = serde_json::from_str(&ctx.frame_for(legacy, &outbound).await.unwrap()).unwrap();
+    assert_eq!(frame.key_salt, None);
+    let mut opened = frame.clone();
+    opened.open(KEY).unwrap();
+    assert_eq!(opened.content.as_deref(), Some("hello"));
+}
+
+#[tokio::test]
+async fn peers_needing_no_changes_get_the_shared_frame() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    ctx.security.keys.open(peer_id, [9; 16]);
+    let outbound = outbound_clip(&ctx, "hello").await;
+    assert_eq!(ctx.frame_for(peer_id, &outbound).await, Some(outbound.json.clone()));
+}
+
+#[tokio::test]
+async fn clips_larger_than_a_peer_takes_are_not_sent() {
+    let ctx = context();
+    let peer_id = Uuid::new_v4();
+    ctx.policy.capabilities.record(peer_id, PeerCapabilities {
+        device_id: None,
+        name: None,
+        formats: vec!["text".to_string()],
+        max_message_bytes: Some(4),
+        compression: Vec::new(),
+    });
+    assert_eq!(ctx.frame_for(peer_id, &outbound_clip(&ctx, "hello").await).await, None);
+    assert!(ctx.frame_for(peer_id, &outbound_clip(&ctx, "hi").await).await.is_some());
+}
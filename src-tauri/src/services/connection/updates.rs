@@ -0,0 +1,129 @@
+use tokio::time::Duration;
+use uuid::Uuid;
+use crate::models::{ApiClipboardEvent, ClipOrigin, ClipboardContent, ClipboardMessage, ContentKind, HistoryOrigin};
+use crate::services::peer_metrics;
+use crate::services::receipts;
+use crate::services::size_policy::{self, Direction, SizeDecision};
+use crate::utils::hashing;
+use super::{ApplyOutcome, ConnectionContext};
+
+/// How long a peer's clip may take to be written to the clipboard.
+const APPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+impl ConnectionContext {
+    /// Check and apply a peer's clipboard update, then relay it to other peers.
+    pub(crate) async fn handle_clipboard_update(
+        &self,
+        peer_id: Uuid,
+        message: &ClipboardMessage,
+        content: ClipboardContent,
+        content_hash: Option<String>,
+        text: &str,
+    ) {
+        if !self.is_peer_allowed(peer_id).await {
+            tracing::info!("Ignoring clipboard update from untrusted peer {}", peer_id);
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+        if *self.policy.paused.read().await {
+            tracing::debug!("Sync paused, not applying clipboard update from {}", peer_id);
+            return;
+        }
+        if content_hash.is_some_and(|hash| hash != hashing::hash_content(&content)) {
+            tracing::warn!("Rejecting message {} from {}: content hash mismatch", message.id, peer_id);
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+        let Some(address) = self.peers.read().await.get(&peer_id).map(|(addr, _)| *addr) else {
+            return;
+        };
+        if self.policy.presentation.read().await.is_viewer(address) {
+            tracing::info!("Ignoring clipboard update from viewer {}: viewers are read-only", peer_id);
+            peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            return;
+        }
+        // A peer retrying a clip we applied before a restart
+        if self.applied.contains(message.id) {
+            tracing::debug!("Clip {} from {} was already applied", message.id, peer_id);
+            if self.config.read().await.send_receipts {
+                receipts::acknowledge(self, message.id).await;
+            }
+            return;
+        }
+        let origin = ClipOrigin { peer_id, address, device: message.device.clone(), message_id: Some(message.id), sent_at: Some(message.timestamp), received_at: Some(chrono::Utc::now()), kind: message.descriptor.as_ref().map_or(ContentKind::Text, |descriptor| descriptor.kind.into()) };
+        let device = self.directory.name_for(&origin);
+
+        let config = self.config.read().await.clone();
+        let oversized = config.max_content_bytes > 0
+            && size_policy::content_size(&content) > config.max_content_bytes;
+        match size_policy::check(&config, content) {
+            SizeDecision::Allow(content) => {
+                // API clients get the clip as written, after format filtering
+                let ApplyOutcome::Applied(content) = self.apply_clipboard_update(&origin, &content).await else {
+                    return;
+                };
+                self.publish_api_event(None, content, HistoryOrigin::Remote, Some(device));
+                // Relay once applied here, unless it is too big for this
+                // device to pass on
+                if !oversized {
+                    self.rebroadcast(Some(peer_id), message.id, Some(message.msg_type.clone()), text).await;
+                }
+            }
+            SizeDecision::Drop => {
+                tracing::info!("Dropping oversized clipboard update {} from {}", message.id, peer_id);
+                peer_metrics::record_rejected(&self.telemetry, peer_id).await;
+            }
+            SizeDecision::Prompt(content) => {
+                self.policy.oversize.hold(Direction::Incoming, content, Some(origin), Some(device), config.max_content_bytes).await;
+            }
+        }
+    }
+
+    /// Hand remote content to the clipboard callback and wait for what
+    /// became of it.
+    pub async fn apply_clipboard_update(&self, origin: &ClipOrigin, content: &ClipboardContent) -> ApplyOutcome {
+        let outcome = {
+            let callback_guard = self.clipboard_callback.read().await;
+            let Some(ref callback) = *callback_guard else {
+                return ApplyOutcome::Declined;
+            };
+            tracing::info!(
+                "Applying clipboard update from {}: {} chars{}",
+                origin.peer_id, content.text.len(), if content.is_rich() { " with rich formats" } else { "" }
+            );
+            callback(content.clone(), origin.clone())
+        };
+        let outcome = match tokio::time::timeout(APPLY_TIMEOUT, outcome).await {
+            Ok(Ok(outcome)) => outcome,
+            // The apply task ended without answering, as when sync stops
+            Ok(Err(_)) => ApplyOutcome::Failed,
+            Err(_) => {
+                tracing::error!("Clipboard update from {} was not applied within {:?}", origin.peer_id, APPLY_TIMEOUT);
+                ApplyOutcome::Failed
+            }
+        };
+        // Clips that reach the clipboard are counted, and marked applied,
+        // once written
+        if outcome == ApplyOutcome::Failed {
+            self.telemetry.sync_metrics.write().await.clipboard_updates_failed += 1;
+        }
+        outcome
+    }
+
+    /// Tell local API clients about a clipboard change, skipping `skip`.
+    pub fn publish_api_event(&self, skip: Option<Uuid>, content: ClipboardContent, origin: HistoryOrigin, device: Option<String>) {
+        let event = ApiClipboardEvent {
+            content,
+            origin,
+            device,
+            timestamp: chrono::Utc::now(),
+        };
+        match serde_json::to_string(&event) {
+            // No subscribers just means no API clients are connected
+            Ok(json) => {
+                let _ = self.api_tx.send((skip, json));
+            }
+            Err(e) => tracing::error!("Failed to encode API event: {}", e),
+        }
+    }
+}
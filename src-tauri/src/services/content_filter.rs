@@ -0,0 +1,138 @@
+use anyhow::Result;
+use regex::Regex;
+use crate::models::{ClipboardContent, FilterAction, FilterRule};
+
+/// What redacted matches are replaced with.
+pub const REDACTION: &str = "[REDACTED]";
+
+/// What the content filters decided for an outgoing clip.
+#[derive(Debug)]
+pub enum FilterOutcome {
+    /// Send this, possibly redacted. `changes` describe any redaction.
+    Send { content: ClipboardContent, changes: Vec<String> },
+    /// Never send it; the pattern of the rule that blocked it
+    Block(String),
+}
+
+/// Check that every rule's pattern compiles.
+///
+/// # Errors
+/// Names the first invalid pattern
+pub fn validate(rules: &[FilterRule]) -> Result<()> {
+    for rule in rules {
+        Regex::new(&rule.pattern)
+            .map_err(|e| anyhow::anyhow!("Invalid content filter pattern {:?}: {}", rule.pattern, e))?;
+    }
+    Ok(())
+}
+
+/// Run a local clip through `rules` in order. The first matching `allow`
+/// rule sends it as it is at that point, a matching `block` rule stops it,
+/// and `redact` rules replace what they match and let later rules run.
+/// Redacting drops the rich formats, which would still carry the match.
+pub fn apply(rules: &[FilterRule], mut content: ClipboardContent) -> FilterOutcome {
+    let mut changes = Vec::new();
+    for rule in rules {
+        let pattern = match Regex::new(&rule.pattern) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                tracing::warn!("Skipping invalid content filter {:?}: {}", rule.pattern, e);
+                continue;
+            }
+        };
+        let matches = pattern.is_match(&content.text)
+            || content.html.as_deref().is_some_and(|html| pattern.is_match(html));
+        if !matches {
+            continue;
+        }
+        match rule.action {
+            FilterAction::Allow => break,
+            FilterAction::Block => return FilterOutcome::Block(rule.pattern.clone()),
+            FilterAction::Redact => {
+                let count = pattern.find_iter(&content.text).count();
+                content.text = pattern.replace_all(&content.text, REDACTION).into_owned();
+                content.html = None;
+                content.rtf = None;
+                changes.push(format!("redacted {} match(es) of {:?}", count, rule.pattern));
+            }
+        }
+    }
+    FilterOutcome::Send { content, changes }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, action: FilterAction) -> FilterRule {
+        FilterRule { pattern: pattern.to_string(), action }
+    }
+
+    fn rich(text: &str) -> ClipboardContent {
+        ClipboardContent { text: text.to_string(), html: Some(format!("<b>{}</b>", text)), rtf: Some(r"{\rtf1 x}".to_string()) }
+    }
+
+    fn sent(outcome: FilterOutcome) -> (ClipboardContent, Vec<String>) {
+        match outcome {
+            FilterOutcome::Send { content, changes } => (content, changes),
+            FilterOutcome::Block(pattern) => panic!("blocked by {}", pattern),
+        }
+    }
+
+    const CARD: &str = r"\b\d{4}-\d{4}-\d{4}-\d{4}\b";
+
+    #[test]
+    fn clips_no_rule_matches_are_sent_unchanged() {
+        let rules = [rule(CARD, FilterAction::Block), rule("password", FilterAction::Redact)];
+        let (content, changes) = sent(apply(&rules, rich("meeting at noon")));
+        assert_eq!(content, rich("meeting at noon"));
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn matching_block_rules_stop_the_clip() {
+        let rules = [rule(CARD, FilterAction::Block)];
+        assert!(matches!(apply(&rules, rich("card 1234-5678-9012-3456")), FilterOutcome::Block(pattern) if pattern == CARD));
+    }
+
+    #[test]
+    fn matches_in_the_html_alone_count() {
+        let content = ClipboardContent { text: "see link".to_string(), html: Some("<a href=\"https://intranet\">see link</a>".to_string()), rtf: None };
+        assert!(matches!(apply(&[rule("intranet", FilterAction::Block)], content), FilterOutcome::Block(_)));
+    }
+
+    #[test]
+    fn redaction_replaces_every_match_and_drops_rich_formats() {
+        let rules = [rule(r"token=\w+", FilterAction::Redact)];
+        let (content, changes) = sent(apply(&rules, rich("token=abc and token=def")));
+        assert_eq!(content.text, format!("{} and {}", REDACTION, REDACTION));
+        assert_eq!((content.html, content.rtf), (None, None));
+        assert_eq!(changes, [format!("redacted 2 match(es) of {:?}", r"token=\w+")]);
+    }
+
+    #[test]
+    fn later_rules_see_the_redacted_clip() {
+        let rules = [rule("secret", FilterAction::Redact), rule("secret", FilterAction::Block)];
+        let (content, _) = sent(apply(&rules, ClipboardContent::plain("a secret".to_string())));
+        assert_eq!(content.text, format!("a {}", REDACTION));
+    }
+
+    #[test]
+    fn an_allow_rule_stops_later_rules() {
+        let rules = [rule("^public:", FilterAction::Allow), rule(CARD, FilterAction::Block)];
+        let (content, changes) = sent(apply(&rules, ClipboardContent::plain("public: 1234-5678-9012-3456".to_string())));
+        assert_eq!(content.text, "public: 1234-5678-9012-3456");
+        assert!(changes.is_empty());
+        assert!(matches!(apply(&rules, ClipboardContent::plain("1234-5678-9012-3456".to_string())), FilterOutcome::Block(_)));
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected_and_skipped() {
+        let rules = [rule("(unclosed", FilterAction::Block), rule("secret", FilterAction::Block)];
+        let error = validate(&rules).unwrap_err().to_string();
+        assert!(error.contains("(unclosed"), "{}", error);
+        assert!(validate(&rules[1..]).is_ok());
+        assert!(matches!(apply(&rules, ClipboardContent::plain("(unclosed".to_string())), FilterOutcome::Send { .. }));
+        assert!(matches!(apply(&rules, ClipboardContent::plain("secret".to_string())), FilterOutcome::Block(_)));
+    }
+}
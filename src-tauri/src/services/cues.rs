@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use crate::models::{ApplyCue, ApplyCueRule, ClipOrigin, Config, ContentKind};
+
+pub const APPLY_CUE_EVENT: &str = "apply-cue";
+
+/// Payload of the `apply-cue` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApplyCueEvent {
+    pub cue: ApplyCue,
+    pub device: String,
+    pub content_kind: ContentKind,
+    pub timestamp: DateTime<Utc>,
+}
+
+fn rule_matches_device(rule: &ApplyCueRule, origin: &ClipOrigin, device: &str) -> Option<bool> {
+    let wanted = rule.device.as_deref()?;
+    Some(device.eq_ignore_ascii_case(wanted) || origin.address.ip().to_string() == wanted)
+}
+
+/// Pick the cue for a clip from `device` (its sender's resolved name). The
+/// most specific matching rule wins (device + kind, then device, then kind);
+/// otherwise the default applies.
+pub fn resolve_cue(config: &Config, origin: &ClipOrigin, device: &str, kind: ContentKind) -> ApplyCue {
+    config
+        .apply_cue_rules
+        .iter()
+        .filter_map(|rule| {
+            let device_score = match rule_matches_device(rule, origin, device) {
+                Some(true) => 2,
+                Some(false) => return None,
+                None => 0,
+            };
+            let kind_score = match rule.content_kind {
+                Some(rule_kind) if rule_kind == kind => 1,
+                Some(_) => return None,
+                None => 0,
+            };
+            Some((device_score + kind_score, rule.cue))
+        })
+        .max_by_key(|(score, _)| *score)
+        .map(|(_, cue)| cue)
+        .unwrap_or(config.apply_cue)
+}
+
+/// Emit `apply-cue` for an applied remote clip unless the resolved cue is `None`.
+pub fn emit_apply_cue(app: &AppHandle, config: &Config, origin: &ClipOrigin, device: &str, kind: ContentKind) {
+    let cue = resolve_cue(config, origin, device, kind);
+    if cue == ApplyCue::None {
+        return;
+    }
+
+    let event = ApplyCueEvent {
+        cue,
+        device: device.to_string(),
+        content_kind: kind,
+        timestamp: Utc::now(),
+    };
+    if let Err(e) = app.emit(APPLY_CUE_EVENT, event) {
+        tracing::warn!("Failed to emit apply cue: {}", e);
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue};
+use uuid::Uuid;
+use crate::models::{ClipDelta, ClipboardContent, ClipboardMessage, ContentDescriptor, DeltaAck, MessageType, Protection};
+use crate::utils::hashing;
+use super::connection::ConnectionContext;
+
+const FEATURES_HEADER: &str = "x-unimesh-features";
+const DELTA_FEATURE: &str = "delta";
+/// Clips shorter than this are always sent whole.
+pub const MIN_DELTA_BYTES: usize = 16 * 1024;
+/// Local clips kept to diff against and to resend after a rejected delta.
+const SENT_CLIPS: usize = 8;
+/// Clips kept per peer as bases for its deltas.
+const BASES_PER_PEER: usize = 4;
+
+/// Offer delta sync in a handshake request or response.
+pub fn advertise(headers: &mut HeaderMap) {
+    headers.insert(FEATURES_HEADER, HeaderValue::from_static(DELTA_FEATURE));
+}
+
+/// Whether the other side offered delta sync in its handshake.
+pub fn offered(headers: &HeaderMap) -> bool {
+    headers
+        .get(FEATURES_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|feature| feature.trim() == DELTA_FEATURE))
+}
+
+/// Split `new` into the bytes it shares with the start and end of `base`
+/// and the text in between, on character boundaries.
+pub fn splice<'a>(base: &str, new: &'a str) -> (usize, usize, &'a str) {
+    let mut prefix = base.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    while !new.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let room = (base.len() - prefix).min(new.len() - prefix);
+    let mut suffix = base.bytes().rev().zip(new.bytes().rev()).take(room).take_while(|(a, b)| a == b).count();
+    while !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    (prefix, suffix, &new[prefix..new.len() - suffix])
+}
+
+/// Rebuild the text a delta stands for, or `None` when it does not fit `base`.
+pub fn apply(base: &str, delta: &ClipDelta) -> Option<String> {
+    if delta.prefix + delta.suffix > base.len() {
+        return None;
+    }
+    let head = base.get(..delta.prefix)?;
+    let tail = base.get(base.len() - delta.suffix..)?;
+    Some([head, &delta.insert, tail].concat())
+}
+
+/// Clips a peer sent us, oldest first, by message id.
+type Bases = VecDeque<(Uuid, Arc<String>)>;
+
+/// Per-session delta bookkeeping, shared by every connection.
+#[derive(Default)]
+pub struct DeltaState {
+    /// Sessions whose peer offered delta sync
+    peers: Mutex<HashSet<Uuid>>,
+    /// Recent text-only clips sent from here, unprotected
+    sent: Mutex<VecDeque<ClipboardMessage>>,
+    /// Per peer, our clip it last confirmed holding
+    acked: Mutex<HashMap<Uuid, Uuid>>,
+    /// Per peer, recent clips it sent us, by message id
+    bases: Mutex<HashMap<Uuid, Bases>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl DeltaState {
+    pub fn connected(&self, peer_id: Uuid) {
+        lock(&self.peers).insert(peer_id);
+    }
+
+    pub fn disconnected(&self, peer_id: Uuid) {
+        lock(&self.peers).remove(&peer_id);
+        lock(&self.acked).remove(&peer_id);
+        lock(&self.bases).remove(&peer_id);
+    }
+
+    fn supports(&self, peer_id: Uuid) -> bool {
+        lock(&self.peers).contains(&peer_id)
+    }
+
+    /// Keep a local text clip that is about to be broadcast.
+    pub fn remember_sent(&self, message: &ClipboardMessage) {
+        if !matches!(message.msg_type, MessageType::ClipboardUpdate) || message.formats.is_some() {
+            return;
+        }
+        let mut sent = lock(&self.sent);
+        sent.push_back(message.clone());
+        while sent.len() > SENT_CLIPS {
+            sent.pop_front();
+        }
+    }
+
+    fn sent(&self, id: Uuid) -> Option<ClipboardMessage> {
+        lock(&self.sent).iter().find(|message| message.id == id).cloned()
+    }
+
+    fn remember_base(&self, peer_id: Uuid, id: Uuid, text: Arc<String>) {
+        let mut bases = lock(&self.bases);
+        let bases = bases.entry(peer_id).or_default();
+        bases.push_back((id, text));
+        while bases.len() > BASES_PER_PEER {
+            bases.pop_front();
+        }
+    }
+
+    fn base(&self, peer_id: Uuid, id: Uuid) -> Option<Arc<String>> {
+        lock(&self.bases).get(&peer_id)?.iter().find(|(base, _)| *base == id).map(|(_, text)| text.clone())
+    }
+
+    /// What to send `peer_id` for local clip `id`: a delta against what the
+    /// peer last confirmed, when it takes deltas and one is much smaller
+    /// than the clip, else `None` to send the clip whole. The delta is
+    /// protected per peer by the caller.
+    pub fn encode_for(&self, peer_id: Uuid, id: Uuid) -> Option<ClipboardMessage> {
+        if !self.supports(peer_id) {
+            return None;
+        }
+        let full = self.sent(id)?;
+        let text = full.content.as_deref()?;
+        if text.len() < MIN_DELTA_BYTES {
+            return None;
+        }
+        let base_id = *lock(&self.acked).get(&peer_id)?;
+        let base = self.sent(base_id)?;
+        let (prefix, suffix, insert) = splice(base.content.as_deref()?, text);
+        if insert.len() > text.len() / 2 {
+            return None;
+        }
+        let delta = ClipDelta {
+            message_id: id,
+            base_id,
+            prefix,
+            suffix,
+            insert: insert.to_string(),
+            content_hash: full.content_hash.clone()?,
+        };
+        let mut message = ClipboardMessage::new(MessageType::ClipboardDelta, serde_json::to_string(&delta).ok());
+        message.timestamp = full.timestamp;
+        message.device = full.device.clone();
+        Some(message)
+    }
+}
+
+async fn send_ack(ctx: &ConnectionContext, peer_id: Uuid, message_id: Uuid, accepted: bool) {
+    let payload = match serde_json::to_string(&DeltaAck { message_id, accepted }) {
+        Ok(payload) => payload,
+        Err(e) => {
+            tracing::error!("Failed to encode delta ack: {}", e);
+            return;
+        }
+    };
+    let message = ClipboardMessage::new(MessageType::DeltaAck, Some(payload)).with_protection(Protection::Signed);
+    if let Err(e) = ctx.send_to_peer(peer_id, message).await {
+        tracing::debug!("Failed to send delta ack to {}: {}", peer_id, e);
+    }
+}
+
+/// Keep a whole text clip from a delta-capable peer as a base for its
+/// next deltas, and tell the peer it may diff against it.
+pub(crate) async fn note_full(ctx: &ConnectionContext, peer_id: Uuid, message_id: Uuid, content: &ClipboardContent, content_hash: Option<&str>) {
+    if !ctx.policy.delta.supports(peer_id) || content.is_rich() || !ctx.is_peer_allowed(peer_id).await {
+        return;
+    }
+    if content_hash.is_some_and(|hash| hash != hashing::hash_content(content)) {
+        return;
+    }
+    ctx.policy.delta.remember_base(peer_id, message_id, Arc::new(content.text.clone()));
+    send_ack(ctx, peer_id, message_id, true).await;
+}
+
+/// Rebuild a peer's clip from a delta and handle it like a whole update.
+/// A delta that does not fit is refused, and the peer resends the clip.
+pub(crate) async fn handle(ctx: &ConnectionContext, peer_id: Uuid, message: &ClipboardMessage, content: Option<&str>) {
+    let Some(delta) = content.and_then(|c| serde_json::from_str::<ClipDelta>(c).ok()) else {
+        tracing::warn!("Malformed delta from {}", peer_id);
+        return;
+    };
+    let rebuilt = ctx.policy.delta.base(peer_id, delta.base_id)
+        .and_then(|base| apply(&base, &delta))
+        .map(|text| ClipboardContent { text, html: None, rtf: None })
+        .filter(|content| hashing::hash_content(content) == delta.content_hash);
+    let Some(content) = rebuilt else {
+        tracing::info!("Delta {} from {} does not match our copy of {}, asking for the whole clip", message.id, peer_id, delta.base_id);
+        send_ack(ctx, peer_id, delta.message_id, false).await;
+        return;
+    };
+    ctx.policy.delta.remember_base(peer_id, delta.message_id, Arc::new(content.text.clone()));
+    send_ack(ctx, peer_id, delta.message_id, true).await;
+
+    {
+        let mut cache = ctx.message_cache.write().await;
+        if cache.is_duplicate(&delta.message_id) {
+            return;
+        }
+        cache.add_message(delta.message_id);
+    }
+
+    // The whole update, so it is applied, acknowledged, and relayed as usual
+    let mut full = ClipboardMessage::new(MessageType::ClipboardUpdate, Some(content.text.clone()));
+    full.id = delta.message_id;
+    full.timestamp = message.timestamp;
+    full.device = message.device.clone();
+    full.sender_id = message.sender_id;
+    full.content_hash = Some(delta.content_hash.clone());
+    full.descriptor = Some(ContentDescriptor::of(&content, &delta.content_hash));
+    let mut relay = full.clone();
+    if let Err(e) = ctx.protect(&mut relay).await {
+        tracing::warn!("Failed to protect rebuilt clip {}: {}", delta.message_id, e);
+        return;
+    }
+    let Ok(json) = serde_json::to_string(&relay) else {
+        return;
+    };
+    ctx.handle_clipboard_update(peer_id, &full, content, None, &json).await;
+}
+
+/// A peer confirmed holding one of our clips, or refused a delta; in that
+/// case the clip is sent to it whole.
+pub(crate) async fn handle_ack(ctx: &ConnectionContext, peer_id: Uuid, content: Option<&str>) {
+    let Some(ack) = content.and_then(|c| serde_json::from_str::<DeltaAck>(c).ok()) else {
+        tracing::warn!("Malformed delta ack from {}", peer_id);
+        return;
+    };
+    let Some(full) = ctx.policy.delta.sent(ack.message_id) else {
+        return;
+    };
+    if ack.accepted {
+        lock(&ctx.policy.delta.acked).insert(peer_id, ack.message_id);
+        return;
+    }
+    lock(&ctx.policy.delta.acked).remove(&peer_id);
+    if let Err(e) = ctx.send_to_peer(peer_id, full).await {
+        tracing::warn!("Failed to resend clip {} to {}: {}", ack.message_id, peer_id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A local clip of `text`, kept as sent.
+    fn sent(state: &DeltaState, text: &str) -> ClipboardMessage {
+        let message = ClipboardMessage::clipboard_update(ClipboardContent::plain(text.to_string())).unwrap();
+        state.remember_sent(&message);
+        message
+    }
+
+    fn delta_of(message: &ClipboardMessage) -> ClipDelta {
+        assert!(matches!(message.msg_type, MessageType::ClipboardDelta));
+        serde_json::from_str(message.content.as_deref().unwrap()).unwrap()
+    }
+
+    fn long_text() -> String {
+        "line of clipboard text\n".repeat(MIN_DELTA_BYTES / 16)
+    }
+
+    /// A peer that takes deltas and holds `base`.
+    fn peer_holding(state: &DeltaState, base: &ClipboardMessage) -> Uuid {
+        let peer_id = Uuid::new_v4();
+        state.connected(peer_id);
+        lock(&state.acked).insert(peer_id, base.id);
+        peer_id
+    }
+
+    #[test]
+    fn deltas_rebuild_the_clip_from_the_base() {
+        let state = DeltaState::default();
+        let base = sent(&state, &long_text());
+        let edited = sent(&state, &format!("a new first line\n{}", long_text().replacen("clipboard", "edited", 1)));
+        let peer_id = peer_holding(&state, &base);
+
+        let message = state.encode_for(peer_id, edited.id).unwrap();
+        let delta = delta_of(&message);
+        assert_eq!((delta.message_id, delta.base_id), (edited.id, base.id));
+        assert!(delta.insert.len() < 64);
+        let rebuilt = apply(base.content.as_deref().unwrap(), &delta).unwrap();
+        assert_eq!(Some(rebuilt.as_str()), edited.content.as_deref());
+        assert_eq!(hashing::hash_content(&ClipboardContent::plain(rebuilt)), delta.content_hash);
+        assert_eq!(message.timestamp, edited.timestamp);
+    }
+
+    #[test]
+    fn deltas_against_another_base_do_not_rebuild_the_clip() {
+        let state = DeltaState::default();
+        let base = sent(&state, &long_text());
+        let edited = sent(&state, &format!("{}one more line\n", long_text()));
+        let delta = delta_of(&state.encode_for(peer_holding(&state, &base), edited.id).unwrap());
+
+        // A receiver holding other text finds the delta out of range or
+        // the result under another hash, and refuses it for the whole clip
+        assert_eq!(apply("short", &delta), None);
+        let other = apply(&long_text().to_uppercase(), &delta).unwrap();
+        assert_ne!(hashing::hash_content(&ClipboardContent::plain(other)), delta.content_hash);
+    }
+
+    #[test]
+    fn clips_go_whole_without_a_base_the_peer_holds() {
+        let state = DeltaState::default();
+        let base = sent(&state, &long_text());
+        let edited = sent(&state, &format!("{}one more line\n", long_text()));
+
+        let peer_id = Uuid::new_v4();
+        state.connected(peer_id);
+        assert!(state.encode_for(peer_id, edited.id).is_none());
+
+        // The confirmed base has since dropped out of the sent clips
+        let peer_id = peer_holding(&state, &base);
+        for i in 0..SENT_CLIPS {
+            sent(&state, &format!("filler {}", i));
+        }
+        assert!(state.encode_for(peer_id, edited.id).is_none());
+    }
+
+    #[test]
+    fn peers_without_delta_support_get_the_clip_whole() {
+        let state = DeltaState::default();
+        let base = sent(&state, &long_text());
+        let edited = sent(&state, &format!("{}one more line\n", long_text()));
+        let peer_id = peer_holding(&state, &base);
+        state.disconnected(peer_id);
+        lock(&state.acked).insert(peer_id, base.id);
+        assert!(state.encode_for(peer_id, edited.id).is_none());
+    }
+
+    #[test]
+    fn short_or_mostly_changed_clips_go_whole() {
+        let state = DeltaState::default();
+        let base = sent(&state, "short base");
+        let short = sent(&state, "short base, edited");
+        let peer_id = peer_holding(&state, &base);
+        assert!(state.encode_for(peer_id, short.id).is_none());
+
+        let base = sent(&state, &long_text());
+        let rewritten = sent(&state, &"x".repeat(MIN_DELTA_BYTES * 2));
+        let peer_id = peer_holding(&state, &base);
+        assert!(state.encode_for(peer_id, rewritten.id).is_none());
+    }
+
+    #[test]
+    fn splices_fall_on_character_boundaries() {
+        let (prefix, suffix, insert) = splice("aé", "aè");
+        assert_eq!((prefix, suffix, insert), (1, 0, "è"));
+        let delta = ClipDelta { message_id: Uuid::nil(), base_id: Uuid::nil(), prefix, suffix, insert: insert.to_string(), content_hash: String::new() };
+        assert_eq!(apply("aé", &delta).as_deref(), Some("aè"));
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::RwLock;
+use uuid::Uuid;
+use crate::models::{ClipOrigin, DeviceInfo};
+
+/// Settings store key of this device's stable id.
+pub const DEVICE_ID_KEY: &str = "device_id";
+
+/// Friendly names of LAN devices by IP, kept current by mDNS discovery so
+/// events and history can name a sender when they are emitted.
+#[derive(Debug, Default)]
+pub struct DeviceDirectory {
+    names: RwLock<HashMap<IpAddr, String>>,
+}
+
+/// Readable name from an mDNS hostname such as `studio-mac.local.`.
+pub fn friendly_name(hostname: &str) -> String {
+    hostname.trim_end_matches('.').trim_end_matches(".local").to_string()
+}
+
+/// Name set in the config, if any; see [`set_local_device_name`].
+static CONFIGURED_NAME: RwLock<Option<String>> = RwLock::new(None);
+
+/// This machine's hostname, without the `.local` suffix.
+pub fn host_name() -> String {
+    hostname::get()
+        .map(|name| friendly_name(&name.to_string_lossy()))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// Use `name` as this device's name from now on. A blank name falls back
+/// to the hostname.
+pub fn set_local_device_name(name: &str) {
+    let name = name.trim();
+    *CONFIGURED_NAME.write().unwrap_or_else(|e| e.into_inner()) = (!name.is_empty()).then(|| name.to_string());
+}
+
+/// Name this device reports to peers.
+pub fn local_device_name() -> String {
+    CONFIGURED_NAME
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(host_name)
+}
+
+/// Stable id set at startup; see [`set_local_device_id`].
+static DEVICE_ID: RwLock<Option<Uuid>> = RwLock::new(None);
+
+pub fn set_local_device_id(id: Uuid) {
+    *DEVICE_ID.write().unwrap_or_else(|e| e.into_inner()) = Some(id);
+}
+
+/// Id that identifies this device across restarts and address changes,
+/// once it has been set.
+pub fn local_device_id() -> Option<Uuid> {
+    *DEVICE_ID.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Identity attached to clips this device sends.
+pub fn local_device_info() -> DeviceInfo {
+    DeviceInfo {
+        name: local_device_name(),
+        platform: std::env::consts::OS.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
+impl DeviceDirectory {
+    pub fn record(&self, ip: IpAddr, name: String) {
+        if name.is_empty() {
+            return;
+        }
+        self.names.write().unwrap_or_else(|e| e.into_inner()).insert(ip, name);
+    }
+
+    /// Name discovery knows for a device address.
+    pub fn name_of(&self, ip: IpAddr) -> Option<String> {
+        self.names.read().unwrap_or_else(|e| e.into_inner()).get(&ip).cloned()
+    }
+
+    /// Name to show for a clip's sender: the name it announced, else the
+    /// name discovery knows for its address, else the address itself.
+    pub fn name_for(&self, origin: &ClipOrigin) -> String {
+        if let Some(ref device) = origin.device {
+            return device.name.clone();
+        }
+        self.name_of(origin.address.ip()).unwrap_or_else(|| origin.display_name())
+    }
+}
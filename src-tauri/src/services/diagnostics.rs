@@ -0,0 +1,126 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::time::Duration;
+use tracing_subscriber::filter::LevelFilter;
+use crate::models::{ComponentHealth, ConnectedPeer, EffectiveConfig, SyncMetrics};
+use crate::utils::{crash_report, log_buffer, log_level, zip::ZipBuilder};
+use super::events;
+use super::websocket::WebSocketServer;
+
+/// Directory under the data dir that bundles are written to.
+pub const DIAGNOSTICS_DIR: &str = "diagnostics";
+/// Longest capture allowed, so a forgotten capture ends on its own.
+pub const MAX_CAPTURE_SECS: u64 = 300;
+/// How often metrics and peer states are sampled during a capture.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+/// Warning and error lines kept in `recent_errors.txt`.
+const RECENT_ERRORS: usize = 100;
+
+/// What a capture needs from the running services, taken without holding
+/// the service manager for the whole capture.
+pub struct DiagnosticsProbe {
+    pub websocket: Option<Arc<WebSocketServer>>,
+    pub config: EffectiveConfig,
+    pub data_dir: PathBuf,
+}
+
+/// Metrics and peers at one point during a capture.
+#[derive(Debug, Serialize)]
+struct Sample {
+    at: DateTime<Utc>,
+    metrics: Option<SyncMetrics>,
+    peers: Vec<ConnectedPeer>,
+}
+
+#[derive(Debug, Serialize)]
+struct HealthSnapshot {
+    captured_at: DateTime<Utc>,
+    duration_secs: u64,
+    log_level_before: String,
+    health: ComponentHealth,
+    peers: Vec<ConnectedPeer>,
+    metrics: Option<SyncMetrics>,
+}
+
+/// Log at debug level for `duration`, recording events, metrics, and peer
+/// states, then write a sanitized bundle and return its path. The previous
+/// log level is restored even if writing the bundle fails.
+pub async fn capture(probe: DiagnosticsProbe, duration: Duration) -> Result<PathBuf> {
+    if !events::start_recording() {
+        anyhow::bail!("A diagnostic capture is already running");
+    }
+    let previous = log_level::current();
+    if previous < LevelFilter::DEBUG {
+        if let Err(e) = log_level::set(LevelFilter::DEBUG) {
+            tracing::warn!("Could not raise the log level for diagnostics: {}", e);
+        }
+    }
+    tracing::info!("Capturing diagnostics for {}s", duration.as_secs());
+
+    let samples = record_samples(probe.websocket.as_deref(), duration).await;
+    let recorded = events::stop_recording();
+    if let Err(e) = log_level::set(previous) {
+        tracing::warn!("Could not restore the log level after diagnostics: {}", e);
+    }
+
+    let (peers, metrics) = sample(probe.websocket.as_deref()).await;
+    let snapshot = HealthSnapshot {
+        captured_at: Utc::now(),
+        duration_secs: duration.as_secs(),
+        log_level_before: previous.to_string(),
+        health: crash_report::health(),
+        peers,
+        metrics,
+    };
+    let logs: Vec<String> = log_buffer::recent_lines(log_buffer::CAPACITY)
+        .iter()
+        .map(|line| log_buffer::sanitize_line(line))
+        .collect();
+
+    let mut bundle = ZipBuilder::default();
+    bundle.add("logs.txt", logs.join("\n").as_bytes());
+    bundle.add("recent_errors.txt", log_buffer::recent_errors(log_buffer::CAPACITY, RECENT_ERRORS).join("\n").as_bytes());
+    // Effective config already leaves the security key out
+    bundle.add("config.json", &serde_json::to_vec_pretty(&probe.config)?);
+    bundle.add("health.json", &serde_json::to_vec_pretty(&snapshot)?);
+    bundle.add("samples.json", &serde_json::to_vec_pretty(&samples)?);
+    bundle.add("events.json", &serde_json::to_vec_pretty(&recorded)?);
+    write_bundle(&probe.data_dir, bundle.finish())
+}
+
+async fn record_samples(ws: Option<&WebSocketServer>, duration: Duration) -> Vec<Sample> {
+    let deadline = tokio::time::Instant::now() + duration;
+    let mut samples = Vec::new();
+    let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+    while tokio::time::Instant::now() < deadline {
+        tokio::select! {
+            _ = interval.tick() => {
+                let (peers, metrics) = sample(ws).await;
+                samples.push(Sample { at: Utc::now(), metrics, peers });
+            }
+            _ = tokio::time::sleep_until(deadline) => break,
+        }
+    }
+    samples
+}
+
+async fn sample(ws: Option<&WebSocketServer>) -> (Vec<ConnectedPeer>, Option<SyncMetrics>) {
+    match ws {
+        Some(ws) => (ws.get_connected_peers().await, Some(ws.get_sync_metrics().await)),
+        None => (Vec::new(), None),
+    }
+}
+
+fn write_bundle(data_dir: &Path, bytes: Vec<u8>) -> Result<PathBuf> {
+    let dir = data_dir.join(DIAGNOSTICS_DIR);
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Cannot create {}", dir.display()))?;
+    let path = dir.join(format!("diagnostics-{}.zip", Utc::now().format("%Y%m%d-%H%M%S")));
+    std::fs::write(&path, bytes)
+        .with_context(|| format!("Cannot write {}", path.display()))?;
+    tracing::info!("Diagnostic bundle written to {}", path.display());
+    Ok(path)
+}
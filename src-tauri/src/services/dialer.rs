@@ -0,0 +1,233 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{Context, Result};
+use tokio::net::TcpStream;
+use tokio::sync::Notify;
+use tokio::task::JoinSet;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::client_async;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Error as WsError;
+use crate::models::{Config, ConnectionDirection, PeerEndpoint};
+use crate::utils::net;
+use super::connection::ConnectionContext;
+use super::delta_sync;
+use super::framing;
+use super::key_derivation;
+use super::peer_session::{self, Route};
+use super::presentation;
+use super::protocol_trace;
+use super::sessions;
+use super::reconnect::{self, Backoff};
+use super::tls;
+use super::versions::{self, PeerVersion};
+use super::shutdown::{Shutdown, DRAIN_TIMEOUT};
+
+/// How often an endpoint reached through an inbound session is checked.
+pub const REDIAL_INTERVAL: Duration = Duration::from_secs(15);
+/// How often endpoints are checked for a retry that has come due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long a dial may take before the attempt is given up.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Static peers followed by manual ones, without duplicates.
+fn endpoints(config: &Config) -> Vec<PeerEndpoint> {
+    let mut endpoints = config.static_peers.clone();
+    for peer in config.manual_peers.iter().map(PeerEndpoint::from) {
+        if !endpoints.iter().any(|known| known.host == peer.host && known.port == peer.port) {
+            endpoints.push(peer);
+        }
+    }
+    endpoints
+}
+
+/// Result of one dial: the address the endpoint resolved to, and whether a
+/// session ran (`true`) or the peer was already connected (`false`).
+type DialOutcome = (String, Option<IpAddr>, Result<bool>);
+
+/// Keep a connection open to every static and manual peer in the config,
+/// and to peers being migrated off a lost interface, until `shutdown`
+/// fires. Dropped sessions are redialed and failures retried with
+/// jittered exponential backoff, up to [`reconnect::MAX_ATTEMPTS`] in a
+/// row; an mDNS announcement from the device starts its backoff over. `wake` retries every endpoint right
+/// away, e.g. after a peer was added. Connections opened here end with
+/// the task, which gives them `DRAIN_TIMEOUT` to close.
+pub(crate) fn spawn(ctx: ConnectionContext, wake: Arc<Notify>, shutdown: Shutdown) {
+    shutdown.clone().spawn(async move {
+        let mut sessions: JoinSet<DialOutcome> = JoinSet::new();
+        let mut dialing: HashSet<String> = HashSet::new();
+        let mut backoff: HashMap<String, Backoff> = HashMap::new();
+        let mut check = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = check.tick() => {}
+                _ = wake.notified() => backoff.clear(),
+                Some(Ok((key, address, result))) = sessions.join_next() => {
+                    dialing.remove(&key);
+                    let state = backoff.entry(key.clone()).or_default();
+                    let connected = result.is_ok();
+                    match (result, address) {
+                        (Ok(true), Some(ip)) => {
+                            tracing::info!("Connection to {} closed, reconnecting", key);
+                            state.connected(ip, reconnect::BASE_DELAY);
+                        }
+                        (Ok(_), Some(ip)) => state.connected(ip, REDIAL_INTERVAL),
+                        (Ok(_), None) => {}
+                        (Err(e), address) => {
+                            let delay = state.failed(address);
+                            if state.gave_up() {
+                                tracing::warn!("Giving up on {} after {} attempts: {:#}", key, reconnect::MAX_ATTEMPTS, e);
+                            } else {
+                                tracing::info!("Could not connect to {}: {:#}; retrying in {:?}", key, e, delay);
+                            }
+                        }
+                    }
+                    // A migration is done once it connected or ran out of tries
+                    if connected || state.gave_up() {
+                        ctx.migrations.write().await.retain(|m| format!("{}:{}", m.host, m.port) != key);
+                    }
+                    continue;
+                }
+                _ = shutdown.cancelled() => break,
+            }
+
+            let mut endpoints = endpoints(&*ctx.config.read().await);
+            if let Some(presenter) = ctx.policy.presentation.read().await.presenter() {
+                endpoints.push(presenter.clone());
+            }
+            for migration in ctx.migrations.read().await.iter() {
+                if !endpoints.iter().any(|known| known.host == migration.host && known.port == migration.port) {
+                    endpoints.push(migration.clone());
+                }
+            }
+            let keys: HashSet<String> = endpoints.iter().map(|e| format!("{}:{}", e.host, e.port)).collect();
+            backoff.retain(|key, _| keys.contains(key));
+            for endpoint in endpoints {
+                let key = format!("{}:{}", endpoint.host, endpoint.port);
+                if dialing.contains(&key) {
+                    continue;
+                }
+                let state = backoff.entry(key.clone()).or_default();
+                if state.reset_if_seen(&ctx.telemetry.liveness) {
+                    tracing::info!("{} announced itself again, retrying now", key);
+                }
+                if !state.is_due() {
+                    continue;
+                }
+                dialing.insert(key.clone());
+                let ctx = ctx.clone();
+                sessions.spawn(async move {
+                    match resolve(&endpoint).await {
+                        Ok(addr) => (key, Some(addr.ip()), dial(ctx, addr, endpoint.fingerprint.as_deref()).await),
+                        Err(e) => (key, None, Err(e)),
+                    }
+                });
+            }
+        }
+        let drained = tokio::time::timeout(DRAIN_TIMEOUT, async {
+            while sessions.join_next().await.is_some() {}
+        }).await;
+        if drained.is_err() {
+            tracing::warn!("Aborting {} outbound connections that did not close", sessions.len());
+        }
+    });
+}
+
+/// Whether a peer at `ip` is already connected, in either direction.
+async fn is_connected(ctx: &ConnectionContext, ip: IpAddr) -> bool {
+    ctx.peers.read().await.values().any(|(addr, _)| addr.ip() == ip)
+}
+
+async fn resolve(endpoint: &PeerEndpoint) -> Result<SocketAddr> {
+    // Literal addresses, including scoped IPv6 ones like `fe80::1%3`
+    if let Some(addr) = net::parse_socket_addr(&endpoint.host, endpoint.port) {
+        return Ok(addr);
+    }
+    tokio::net::lookup_host((endpoint.host.as_str(), endpoint.port))
+        .await?
+        .next()
+        .with_context(|| format!("{} did not resolve", endpoint.host))
+}
+
+/// Connect to `addr`, unless it is already connected, and run the session
+/// until it closes. Returns whether a session ran. Peers with a known
+/// fingerprint, pinned when they were trusted or set on the endpoint, are
+/// reached over `wss://`. The rest are reached over `ws://`, except with TLS
+/// on, when they are refused rather than reached in plaintext.
+async fn dial(ctx: ConnectionContext, addr: SocketAddr, fingerprint: Option<&str>) -> Result<bool> {
+    if is_connected(&ctx, addr.ip()).await {
+        return Ok(false);
+    }
+    let pinned = ctx.security.trust.read().await.pinned_fingerprint(addr.ip());
+    let fingerprint = pinned.as_deref().or(fingerprint);
+    if fingerprint.is_none() && ctx.config.read().await.tls_enabled {
+        anyhow::bail!("No certificate fingerprint known for {}; not connecting in plaintext with TLS on", addr);
+    }
+    match fingerprint {
+        Some(fingerprint) => {
+            let stream = tokio::time::timeout(CONNECT_TIMEOUT, tls::connect_pinned(addr, fingerprint)).await??;
+            let local = stream.get_ref().0.local_addr().ok();
+            handshake(ctx, addr, local, net::ws_url(true, addr), stream).await
+        }
+        None => {
+            let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await??;
+            let local = stream.local_addr().ok();
+            handshake(ctx, addr, local, net::ws_url(false, addr), stream).await
+        }
+    }
+}
+
+/// Open the WebSocket with our version headers, note how the peer's
+/// version compares, and run the session. Errors once the session is up
+/// are logged rather than returned, as the connection did succeed.
+async fn handshake<S>(ctx: ConnectionContext, addr: SocketAddr, local: Option<SocketAddr>, url: String, stream: S) -> Result<bool>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut request = url.into_client_request()?;
+    versions::advertise(request.headers_mut());
+    delta_sync::advertise(request.headers_mut());
+    let binary_framing = ctx.config.read().await.binary_framing;
+    if binary_framing {
+        framing::advertise(request.headers_mut());
+    }
+    let nonce = sessions::offer(request.headers_mut());
+    key_derivation::advertise(request.headers_mut(), ctx.security.keys.salt());
+    if let Some(code) = ctx.policy.presentation.read().await.code_for(addr) {
+        presentation::present_code(request.headers_mut(), &code);
+    }
+    match client_async(request, stream).await {
+        Ok((ws_stream, response)) => {
+            protocol_trace::handshake(ConnectionDirection::Outbound, addr, "accepted");
+            versions::record(&ctx, addr, PeerVersion::from_headers(response.headers()).as_ref()).await;
+            let route = Route {
+                direction: ConnectionDirection::Outbound,
+                local,
+                delta: delta_sync::offered(response.headers()),
+                session: sessions::offered(response.headers()).map(|server| sessions::derive(nonce, server)),
+                hello: versions::offers_hello(response.headers()),
+                framing: framing::negotiate(binary_framing, response.headers()),
+                key_salt: key_derivation::offered(response.headers()),
+            };
+            if route.key_salt.is_none() && !key_derivation::admits_legacy(&*ctx.config.read().await) {
+                anyhow::bail!("{} predates derived keys; enable syncing with older versions to connect", addr);
+            }
+            if let Err(e) = peer_session::run(ws_stream, addr, route, ctx).await {
+                tracing::debug!("Session with {} ended: {:#}", addr, e);
+            }
+            Ok(true)
+        }
+        Err(WsError::Http(response)) => {
+            protocol_trace::handshake(ConnectionDirection::Outbound, addr, &format!("refused with status {}", response.status()));
+            // A refusal over versions still carries the peer's headers
+            versions::record(&ctx, addr, PeerVersion::from_headers(response.headers()).as_ref()).await;
+            anyhow::bail!("Handshake refused with status {}", response.status())
+        }
+        Err(e) => {
+            protocol_trace::handshake(ConnectionDirection::Outbound, addr, &format!("failed: {}", e));
+            Err(e.into())
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use uuid::Uuid;
+use crate::models::{ContentKind, DeviceInfo};
+
+pub const DEVICE_DISCOVERED_EVENT: &str = "device-discovered";
+pub const DEVICE_LOST_EVENT: &str = "device-lost";
+pub const PEER_CONNECTED_EVENT: &str = "peer-connected";
+pub const PEER_DISCONNECTED_EVENT: &str = "peer-disconnected";
+pub const CLIPBOARD_SYNCED_EVENT: &str = "clipboard-synced";
+pub const METRICS_UPDATED_EVENT: &str = "metrics-updated";
+pub const SYNC_STATUS_EVENT: &str = "sync-status-changed";
+pub const APPLY_VERIFICATION_FAILED_EVENT: &str = "apply-verification-failed";
+pub const PEER_VERSION_MISMATCH_EVENT: &str = "peer-version-mismatch";
+pub const CLIPBOARD_CONFLICT_EVENT: &str = "clipboard-conflict";
+pub const PRESENTATION_CHANGED_EVENT: &str = "presentation-changed";
+pub const NETWORK_INTERFACES_CHANGED_EVENT: &str = "network-interfaces-changed";
+pub const DISCOVERY_STATUS_EVENT: &str = "discovery-status-changed";
+pub const STATUS_SUMMARY_EVENT: &str = "status-summary-changed";
+pub const SAFE_MODE_EVENT: &str = "safe-mode";
+pub const ANNOUNCEMENT_EVENT: &str = "announcement";
+pub const GUEST_EXPIRED_EVENT: &str = "guest-expired";
+pub const KEY_ROTATED_EVENT: &str = "key-rotated";
+
+/// Payload of the `device-lost` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceLostEvent {
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+}
+
+/// Payload of the `peer-connected` and `peer-disconnected` events.
+#[derive(Debug, Clone, Serialize)]
+pub struct PeerEvent {
+    pub peer_id: Uuid,
+    pub address: SocketAddr,
+    /// Peers connected after the change
+    pub connected_peers: u32,
+}
+
+/// Which way a synced clip travelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncDirection {
+    Sent,
+    Received,
+}
+
+/// Payload of the `clipboard-synced` event. Carries no clipboard content.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardSyncedEvent {
+    pub direction: SyncDirection,
+    /// Key for `get_message_preview`
+    pub message_id: Option<Uuid>,
+    /// Sender of a received clip
+    pub device: Option<String>,
+    /// Platform and version the sender announced, if it did
+    pub sender: Option<DeviceInfo>,
+    pub bytes: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Payload of the `clipboard-conflict` event: a peer's clip raced a local
+/// change and is held until `resolve_clipboard_conflict` is called.
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipboardConflictEvent {
+    pub id: Uuid,
+    pub device: String,
+    pub kind: ContentKind,
+    pub bytes: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Payload of the `apply-verification-failed` event: a received clip was
+/// written but the clipboard did not keep it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ApplyVerificationFailedEvent {
+    pub device: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Payload of the `sync-status-changed` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncStatusEvent {
+    pub running: bool,
+    pub paused: bool,
+}
+
+/// An event captured while a diagnostic bundle is being recorded.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordedEvent {
+    pub event: String,
+    pub payload: serde_json::Value,
+    pub timestamp: DateTime<Utc>,
+}
+
+static RECORDING: Mutex<Option<Vec<RecordedEvent>>> = Mutex::new(None);
+
+/// Keep a copy of every emitted event until `stop_recording`. Returns
+/// `false` if a recording is already running.
+pub fn start_recording() -> bool {
+    let mut recording = RECORDING.lock().unwrap_or_else(|e| e.into_inner());
+    if recording.is_some() {
+        return false;
+    }
+    *recording = Some(Vec::new());
+    true
+}
+
+/// End the recording and return what it captured.
+pub fn stop_recording() -> Vec<RecordedEvent> {
+    RECORDING.lock().unwrap_or_else(|e| e.into_inner()).take().unwrap_or_default()
+}
+
+/// Emit `event` to the frontend if an app handle is available. Failures are
+/// logged; the UI missing an update must never disturb sync.
+pub fn emit<S: Serialize + Clone>(app: Option<&AppHandle>, event: &str, payload: S) {
+    if let Some(ref mut recording) = *RECORDING.lock().unwrap_or_else(|e| e.into_inner()) {
+        recording.push(RecordedEvent {
+            event: event.to_string(),
+            payload: serde_json::to_value(&payload).unwrap_or_default(),
+            timestamp: Utc::now(),
+        });
+    }
+    if let Some(app) = app {
+        if let Err(e) = app.emit(event, payload) {
+            tracing::warn!("Failed to emit {} event: {}", event, e);
+        }
+    }
+}
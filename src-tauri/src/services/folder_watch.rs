@@ -0,0 +1,160 @@
+//! Sending text files dropped into watched folders, for tools that can
+//! write files but not use the local API. Folders are watched with the
+//! platform's notifications: inotify, FSEvents, or ReadDirectoryChangesW.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use crate::models::{ClipboardContent, WatchedFolder};
+use super::clipboard_sync::ClipboardSync;
+use super::websocket::WebSocketServer;
+use super::shutdown::Shutdown;
+
+/// How long a file has to stay unchanged before it is read, so files
+/// written in several steps are not sent half done.
+const SETTLE: Duration = Duration::from_millis(500);
+/// How often settled files are looked for.
+const TICK: Duration = Duration::from_millis(250);
+/// Extensions sent from folders that name none.
+const DEFAULT_EXTENSIONS: &[&str] = &["txt", "md"];
+
+/// A watched folder, with its path as the watcher reports it.
+struct Rule {
+    dir: PathBuf,
+    folder: WatchedFolder,
+}
+
+impl Rule {
+    fn accepts(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        // Editors and downloads write to hidden or temporary names first
+        if name.starts_with('.') || name.ends_with('~') {
+            return false;
+        }
+        let Some(extension) = path.extension().and_then(|ext| ext.to_str()) else {
+            return false;
+        };
+        if self.folder.extensions.is_empty() {
+            DEFAULT_EXTENSIONS.iter().any(|allowed| allowed.eq_ignore_ascii_case(extension))
+        } else {
+            self.folder.extensions.iter().any(|allowed| allowed.trim_start_matches('.').eq_ignore_ascii_case(extension))
+        }
+    }
+}
+
+/// Watch `folders` until `shutdown`, sending each new or rewritten file
+/// that matches its folder's rules. Folders that cannot be watched are
+/// reported and skipped.
+pub fn spawn(folders: &[WatchedFolder], sync: ClipboardSync, ws: Arc<WebSocketServer>, shutdown: Shutdown) {
+    if folders.is_empty() {
+        return;
+    }
+    let (tx, mut rx) = mpsc::unbounded_channel::<PathBuf>();
+    let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| match result {
+        Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+            for path in event.paths {
+                let _ = tx.send(path);
+            }
+        }
+        Ok(_) => {}
+        Err(e) => tracing::warn!("Folder watcher error: {}", e),
+    });
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            tracing::error!("Failed to start the folder watcher: {}", e);
+            return;
+        }
+    };
+
+    let mut rules = Vec::new();
+    for folder in folders {
+        // Watchers report resolved paths, e.g. /private/var on macOS
+        let dir = match std::fs::canonicalize(&folder.path) {
+            Ok(dir) => dir,
+            Err(e) => {
+                tracing::warn!("Not watching {}: {}", folder.path, e);
+                continue;
+            }
+        };
+        match watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            Ok(()) => {
+                tracing::info!("Watching {} for files to send", dir.display());
+                rules.push(Rule { dir, folder: folder.clone() });
+            }
+            Err(e) => tracing::warn!("Not watching {}: {}", folder.path, e),
+        }
+    }
+    if rules.is_empty() {
+        return;
+    }
+
+    shutdown.clone().spawn(async move {
+        // Watching stops when the watcher is dropped
+        let _watcher = watcher;
+        let mut changed: HashMap<PathBuf, Instant> = HashMap::new();
+        let mut tick = tokio::time::interval(TICK);
+        loop {
+            tokio::select! {
+                Some(path) = rx.recv() => {
+                    changed.insert(path, Instant::now());
+                }
+                _ = tick.tick() => {
+                    let settled: Vec<PathBuf> = changed.iter()
+                        .filter(|(_, at)| at.elapsed() >= SETTLE)
+                        .map(|(path, _)| path.clone())
+                        .collect();
+                    for path in settled {
+                        changed.remove(&path);
+                        let rule = rules.iter().find(|rule| path.parent() == Some(rule.dir.as_path()));
+                        if let Some(rule) = rule.filter(|rule| rule.accepts(&path)) {
+                            send_file(&rule.folder, &path, &sync, &ws).await;
+                        }
+                    }
+                }
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    });
+}
+
+/// Send one file as a plain text clip, then delete it if the folder asks.
+async fn send_file(folder: &WatchedFolder, path: &Path, sync: &ClipboardSync, ws: &WebSocketServer) {
+    // Gone already, e.g. deleted after an earlier send
+    let Ok(metadata) = tokio::fs::metadata(path).await else {
+        return;
+    };
+    if !metadata.is_file() {
+        return;
+    }
+    if metadata.len() > folder.max_bytes {
+        tracing::info!("Not sending {}: larger than {} bytes", path.display(), folder.max_bytes);
+        return;
+    }
+    let text = match tokio::fs::read(path).await.map(String::from_utf8) {
+        Ok(Ok(text)) => text,
+        Ok(Err(_)) => {
+            tracing::info!("Not sending {}: not UTF-8 text", path.display());
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Failed to read {}: {}", path.display(), e);
+            return;
+        }
+    };
+    if text.trim().is_empty() {
+        return;
+    }
+    tracing::info!("Sending {} from a watched folder", path.display());
+    sync.share_local(ws, ClipboardContent::plain(text)).await;
+    if folder.delete_after_send {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            tracing::warn!("Failed to delete {} after sending it: {}", path.display(), e);
+        }
+    }
+}
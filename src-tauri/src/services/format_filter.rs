@@ -0,0 +1,326 @@
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::models::{ClipboardContent, FormatSecurityPolicy};
+
+/// Elements removed together with everything inside them.
+const DROPPED_BLOCKS: &[&str] = &["script", "iframe", "object", "embed", "applet", "frameset", "noscript"];
+/// Elements removed on their own; they load or redirect to other documents.
+const DROPPED_TAGS: &[&str] = &["link", "meta", "base", "frame"];
+/// Attributes holding a URL.
+const URL_ATTRIBUTES: &[&str] = &[
+    "href", "src", "srcset", "action", "formaction", "xlink:href", "background", "poster",
+    "data", "cite", "longdesc", "lowsrc", "dynsrc", "ping",
+];
+/// URL attributes that navigate on click rather than load a resource.
+const NAVIGATION_ATTRIBUTES: &[&str] = &["href", "xlink:href", "cite", "longdesc"];
+/// Schemes that run code when followed.
+const SCRIPT_SCHEMES: &[&str] = &["javascript:", "vbscript:", "livescript:", "data:text/html"];
+
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"([^\s=/>]+)(?:\s*=\s*("[^"]*"|'[^']*'|[^\s>]+))?"#).expect("valid attribute pattern")
+});
+static EXTERNAL_CSS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)url\s*\(|@import|expression\s*\(").expect("valid css pattern")
+});
+
+/// Make received rich formats safe to put on the clipboard. Returns what
+/// was changed, for the audit trail; empty when nothing was.
+pub fn apply(policy: &FormatSecurityPolicy, content: &mut ClipboardContent) -> Vec<String> {
+    let mut changes = Vec::new();
+    if policy.sanitize_html {
+        if let Some(html) = content.html.take() {
+            let (clean, removed) = sanitize_html(&html, &policy.blocked_file_extensions);
+            changes.extend(removed.into_iter().map(|what| format!("html: removed {}", what)));
+            content.html = Some(clean);
+        }
+    }
+    if policy.drop_rtf_objects && content.rtf.as_deref().is_some_and(rtf_embeds_objects) {
+        content.rtf = None;
+        changes.push("rtf: dropped, it embeds objects".to_string());
+    }
+    changes
+}
+
+/// Whether a file with this name may be received under `blocked`, a list
+/// of lowercase extensions without the dot.
+pub fn is_allowed_file(name: &str, blocked: &[String]) -> bool {
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext.trim().to_ascii_lowercase());
+    !extension.is_some_and(|ext| blocked.iter().any(|b| b.trim_start_matches('.').eq_ignore_ascii_case(&ext)))
+}
+
+/// Embedded OLE objects are a long-standing exploit vector in RTF readers.
+fn rtf_embeds_objects(rtf: &str) -> bool {
+    rtf.contains("\\object") || rtf.contains("\\objdata")
+}
+
+/// Strip scripts, event handlers, script URLs, and references that load
+/// external resources, keeping the markup otherwise as it was.
+fn sanitize_html(html: &str, blocked: &[String]) -> (String, Vec<String>) {
+    let mut out = String::with_capacity(html.len());
+    let mut removed = Vec::new();
+    let lower = html.to_ascii_lowercase();
+    let mut pos = 0;
+
+    while let Some(offset) = html[pos..].find('<') {
+        let start = pos + offset;
+        out.push_str(&html[pos..start]);
+        if lower[start..].starts_with("<!--") {
+            let end = lower[start..].find("-->").map_or(html.len(), |i| start + i + 3);
+            out.push_str(&html[start..end]);
+            pos = end;
+            continue;
+        }
+        let Some(end) = tag_end(html, start) else {
+            // Unterminated tag: drop the rest rather than guess
+            removed.push("unterminated tag".to_string());
+            pos = html.len();
+            break;
+        };
+        let tag = &html[start..end];
+        let closing = tag.starts_with("</");
+        let name = tag_name(tag);
+
+        if DROPPED_BLOCKS.contains(&name.as_str()) || (name == "style" && !closing && style_block_is_external(&lower, end)) {
+            removed.push(format!("<{}>", name));
+            pos = if closing { end } else { block_end(&lower, end, &name) };
+            continue;
+        }
+        if DROPPED_TAGS.contains(&name.as_str()) {
+            removed.push(format!("<{}>", name));
+            pos = end;
+            continue;
+        }
+        if closing || name.is_empty() {
+            out.push_str(tag);
+        } else {
+            out.push_str(&clean_tag(tag, &name, blocked, &mut removed));
+        }
+        pos = end;
+    }
+    out.push_str(&html[pos..]);
+    (out, removed)
+}
+
+/// Index just past the `>` closing the tag at `start`, skipping quoted values.
+fn tag_end(html: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in html[start + 1..].char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '>') => return Some(start + 1 + i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.trim_start_matches('<')
+        .trim_start_matches('/')
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == ':')
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+/// Index just past the closing tag of the block opened before `from`, or
+/// the end of the document when it is never closed.
+fn block_end(lower: &str, from: usize, name: &str) -> usize {
+    let closing = format!("</{}", name);
+    match lower[from..].find(&closing) {
+        Some(i) => {
+            let close_start = from + i;
+            lower[close_start..].find('>').map_or(lower.len(), |j| close_start + j + 1)
+        }
+        None => lower.len(),
+    }
+}
+
+fn style_block_is_external(lower: &str, from: usize) -> bool {
+    let end = lower[from..].find("</style").map_or(lower.len(), |i| from + i);
+    EXTERNAL_CSS.is_match(&lower[from..end])
+}
+
+/// Rebuild an opening tag with only the attributes that are safe to keep.
+fn clean_tag(tag: &str, name: &str, blocked: &[String], removed: &mut Vec<String>) -> String {
+    let inner = tag[1 + name.len()..tag.len() - 1].trim_end();
+    let self_closing = inner.ends_with('/');
+    let mut rebuilt = format!("<{}", &tag[1..1 + name.len()]);
+    for attribute in ATTRIBUTE.captures_iter(inner.trim_end_matches('/')) {
+        let attr_name = attribute[1].to_ascii_lowercase();
+        let value = attribute.get(2).map(|v| v.as_str().trim_matches(|c| c == '"' || c == '\''));
+        if let Some(reason) = unsafe_attribute(&attr_name, value, blocked) {
+            removed.push(format!("{} on <{}> ({})", attr_name, name, reason));
+            continue;
+        }
+        rebuilt.push(' ');
+        rebuilt.push_str(&attribute[0]);
+    }
+    if self_closing {
+        rebuilt.push_str(" /");
+    }
+    rebuilt.push('>');
+    rebuilt
+}
+
+fn unsafe_attribute(name: &str, value: Option<&str>, blocked: &[String]) -> Option<&'static str> {
+    if name.starts_with("on") {
+        return Some("event handler");
+    }
+    let value = value?;
+    if name == "style" {
+        return EXTERNAL_CSS.is_match(value).then_some("external resource in style");
+    }
+    if !URL_ATTRIBUTES.contains(&name) {
+        return None;
+    }
+    let url = normalize_url(value);
+    if SCRIPT_SCHEMES.iter().any(|scheme| url.starts_with(scheme)) {
+        return Some("script URL");
+    }
+    if url.starts_with("file:") && !is_allowed_file(url.split(['?', '#']).next().unwrap_or(&url), blocked) {
+        return Some("blocked file type");
+    }
+    let external = url.starts_with("http:") || url.starts_with("https:") || url.starts_with("//") || url.starts_with("ftp:");
+    if external && !NAVIGATION_ATTRIBUTES.contains(&name) {
+        return Some("external resource");
+    }
+    None
+}
+
+/// Lowercase `value` and undo the tricks used to hide a scheme from naive
+/// checks: numeric entities, `&colon;`, and embedded whitespace or controls.
+fn normalize_url(value: &str) -> String {
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(i) = rest.find('&') {
+        decoded.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let end = rest.find(';').filter(|&end| end <= 10);
+        let entity = end.map(|end| &rest[1..end]);
+        let character = entity.and_then(|entity| match entity.to_ascii_lowercase().as_str() {
+            "colon" => Some(':'),
+            "tab" | "newline" => Some(' '),
+            e if e.starts_with("#x") => u32::from_str_radix(&e[2..], 16).ok().and_then(char::from_u32),
+            e if e.starts_with('#') => e[1..].parse().ok().and_then(char::from_u32),
+            _ => None,
+        });
+        match (character, end) {
+            (Some(c), Some(end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(markup: &str) -> ClipboardContent {
+        ClipboardContent { text: "text".to_string(), html: Some(markup.to_string()), rtf: None }
+    }
+
+    /// The HTML `markup` becomes under the default policy, and what changed.
+    fn cleaned(markup: &str) -> (String, Vec<String>) {
+        let mut content = html(markup);
+        let changes = apply(&FormatSecurityPolicy::default(), &mut content);
+        (content.html.unwrap(), changes)
+    }
+
+    #[test]
+    fn safe_markup_is_kept_as_it_is() {
+        let markup = r#"<p class="note"><b>bold</b> <a href="https://example.com">link</a> <img src="logo.png"></p><!-- <script> -->"#;
+        assert_eq!(cleaned(markup), (markup.to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn scripts_are_removed_with_their_contents() {
+        let (clean, changes) = cleaned("<p>hi</p><SCRIPT>alert('<p>')</script><p>there</p>");
+        assert_eq!(clean, "<p>hi</p><p>there</p>");
+        assert_eq!(changes, ["html: removed <script>"]);
+    }
+
+    #[test]
+    fn event_handlers_are_removed() {
+        let (clean, changes) = cleaned(r#"<img src="logo.png" onerror="alert(1)">"#);
+        assert_eq!(clean, r#"<img src="logo.png">"#);
+        assert_eq!(changes, ["html: removed onerror on <img> (event handler)"]);
+    }
+
+    #[test]
+    fn hidden_script_urls_are_removed() {
+        for url in ["javascript:alert(1)", "jav&#x61;script&colon;alert(1)", " java\tscript:alert(1)", "data:text/html,<b>"] {
+            let (clean, changes) = cleaned(&format!(r#"<a href="{}">x</a>"#, url));
+            assert_eq!(clean, "<a>x</a>", "{}", url);
+            assert_eq!(changes, ["html: removed href on <a> (script URL)"]);
+        }
+    }
+
+    #[test]
+    fn external_resources_are_removed_but_links_kept() {
+        let (clean, _) = cleaned(r#"<img src="https://tracker.example/p.gif"><a href="https://example.com">x</a>"#);
+        assert_eq!(clean, r#"<img><a href="https://example.com">x</a>"#);
+        let (clean, changes) = cleaned(r#"<style>body { background: url(http://x) }</style><p style="color: red">a</p>"#);
+        assert_eq!(clean, r#"<p style="color: red">a</p>"#);
+        assert_eq!(changes, ["html: removed <style>"]);
+    }
+
+    #[test]
+    fn links_to_blocked_file_types_are_removed() {
+        let (clean, changes) = cleaned(r#"<a href="file:///tmp/setup.EXE?x=1">run</a><a href="file:///tmp/notes.txt">read</a>"#);
+        assert_eq!(clean, r#"<a>run</a><a href="file:///tmp/notes.txt">read</a>"#);
+        assert_eq!(changes, ["html: removed href on <a> (blocked file type)"]);
+    }
+
+    #[test]
+    fn an_unterminated_tag_drops_the_rest() {
+        let (clean, changes) = cleaned(r#"<p>ok</p><img src="x onerror=alert(1)>"#);
+        assert_eq!(clean, "<p>ok</p>");
+        assert_eq!(changes, ["html: removed unterminated tag"]);
+    }
+
+    #[test]
+    fn rtf_embedding_objects_is_dropped() {
+        let policy = FormatSecurityPolicy::default();
+        let mut content = ClipboardContent { text: "text".to_string(), html: None, rtf: Some(r"{\rtf1 {\object\objemb {\objdata 0102}}}".to_string()) };
+        assert_eq!(apply(&policy, &mut content), ["rtf: dropped, it embeds objects"]);
+        assert_eq!(content.rtf, None);
+
+        let plain = r"{\rtf1 \b bold\b0}".to_string();
+        let mut content = ClipboardContent { rtf: Some(plain.clone()), ..content };
+        assert!(apply(&policy, &mut content).is_empty());
+        assert_eq!(content.rtf, Some(plain));
+    }
+
+    #[test]
+    fn nothing_changes_with_the_filters_off() {
+        let policy = FormatSecurityPolicy { sanitize_html: false, drop_rtf_objects: false, ..FormatSecurityPolicy::default() };
+        let mut content = ClipboardContent { rtf: Some(r"{\object}".to_string()), ..html("<script>alert(1)</script>") };
+        let before = content.clone();
+        assert!(apply(&policy, &mut content).is_empty());
+        assert_eq!(content, before);
+    }
+
+    #[test]
+    fn files_are_checked_by_extension_in_any_case() {
+        let blocked = vec!["exe".to_string(), ".sh".to_string()];
+        assert!(!is_allowed_file("setup.EXE", &blocked));
+        assert!(!is_allowed_file("install.sh", &blocked));
+        assert!(is_allowed_file("notes.txt", &blocked));
+        assert!(is_allowed_file("exe", &blocked));
+        assert!(is_allowed_file("setup.exe", &[]));
+    }
+}
@@ -0,0 +1,122 @@
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue};
+use tokio_tungstenite::tungstenite::Message;
+
+/// Handshake header listing the frame encodings the sender reads.
+const FRAMING_HEADER: &str = "x-unimesh-framing";
+const MSGPACK: &str = "msgpack";
+
+/// How messages are written to a peer. Both sides must offer MessagePack
+/// to use it; older builds only read JSON text frames.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    /// JSON in text frames
+    #[default]
+    Json,
+    /// The same messages as MessagePack in binary frames
+    MessagePack,
+}
+
+/// Offer MessagePack framing in a handshake request or response.
+pub fn advertise(headers: &mut HeaderMap) {
+    headers.insert(FRAMING_HEADER, HeaderValue::from_static(MSGPACK));
+}
+
+/// Framing to use with a peer, given whether we offer MessagePack and the
+/// peer's handshake headers.
+pub fn negotiate(enabled: bool, headers: &HeaderMap) -> Framing {
+    let offered = headers
+        .get(FRAMING_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',').any(|encoding| encoding.trim() == MSGPACK));
+    if enabled && offered {
+        Framing::MessagePack
+    } else {
+        Framing::Json
+    }
+}
+
+impl Framing {
+    /// Re-encode an outgoing text frame for the wire. Frames that fail to
+    /// convert go out as they are, which every peer reads.
+    pub fn encode(self, message: Message) -> Message {
+        let Message::Text(ref text) = message else {
+            return message;
+        };
+        if self == Framing::Json {
+            return message;
+        }
+        let packed = serde_json::from_str::<serde_json::Value>(text.as_str())
+            .map_err(anyhow::Error::from)
+            .and_then(|value| Ok(rmp_serde::to_vec_named(&value)?));
+        match packed {
+            Ok(bytes) => Message::Binary(bytes.into()),
+            Err(e) => {
+                tracing::debug!("Sending a frame as JSON: {}", e);
+                message
+            }
+        }
+    }
+}
+
+/// The JSON text of a binary frame from a peer, for the same handling as
+/// a text frame.
+///
+/// # Errors
+/// When the frame is not a MessagePack document
+pub fn decode(bytes: &[u8]) -> anyhow::Result<String> {
+    let value: serde_json::Value = rmp_serde::from_slice(bytes)?;
+    Ok(value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ClipboardContent, ClipboardMessage};
+    use crate::utils::crypto;
+
+    const KEY: &str = "c2hhcmVkLWtleS1mb3ItdGVzdHMtb25seS0zMmIhIQ==";
+
+    fn sealed() -> String {
+        let content = ClipboardContent { text: "hello".to_string(), html: Some("<b>hello</b>".to_string()), rtf: None };
+        let mut message = ClipboardMessage::clipboard_update(content).unwrap();
+        message.key_salt = Some("c2FsdA==".to_string());
+        message.seal(KEY).unwrap();
+        serde_json::to_string(&message).unwrap()
+    }
+
+    fn unpack(message: Message) -> String {
+        let Message::Binary(bytes) = message else {
+            panic!("expected a binary frame, got {:?}", message);
+        };
+        decode(&bytes).unwrap()
+    }
+
+    #[test]
+    fn messagepack_frames_carry_the_same_message_as_json() {
+        let json = sealed();
+        let unpacked = unpack(Framing::MessagePack.encode(Message::Text(json.clone().into())));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&unpacked).unwrap(),
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()
+        );
+        let message: ClipboardMessage = serde_json::from_str(&unpacked).unwrap();
+        assert!(crypto::verify_signature(KEY, &message.signing_payload(), message.signature.as_deref().unwrap()));
+    }
+
+    #[test]
+    fn json_framing_leaves_text_frames_alone() {
+        let json = sealed();
+        assert_eq!(Framing::Json.encode(Message::Text(json.clone().into())), Message::Text(json.into()));
+    }
+
+    #[test]
+    fn frames_that_are_not_json_go_out_as_text() {
+        let frame = Message::Text("not json".into());
+        assert_eq!(Framing::MessagePack.encode(frame.clone()), frame);
+    }
+
+    #[test]
+    fn binary_frames_that_are_not_messagepack_are_refused() {
+        assert!(decode(&[0xc1]).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use tokio::sync::RwLock;
+use crate::utils::{net, paths};
+use super::audit::{self, AuditEntry};
+use super::conflicts::ConflictGate;
+use super::events::{self, GUEST_EXPIRED_EVENT};
+use super::size_policy::OversizeGate;
+use super::trust::{TrustEntry, TrustStore, TRUST_STORE_KEY};
+use super::websocket::WebSocketServer;
+use super::workspaces::DesktopGate;
+use super::shutdown::Shutdown;
+
+/// How long a guest stays paired when no duration is given.
+pub const DEFAULT_GUEST_HOURS: u32 = 8;
+/// Longest guest pairing; anything longer should be a regular one.
+pub const MAX_GUEST_HOURS: u32 = 72;
+/// How often guest pairings are checked for expiry.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Payload of the `guest-expired` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct GuestExpired {
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+    pub expired_at: DateTime<Utc>,
+    /// Held clips from the guest that were dropped
+    pub purged: usize,
+}
+
+/// Everything an expired guest is removed from.
+pub struct GuestSweep {
+    pub app: Option<AppHandle>,
+    pub trust: Arc<RwLock<TrustStore>>,
+    pub websocket: Arc<WebSocketServer>,
+    pub oversize: Arc<OversizeGate>,
+    pub conflicts: Arc<ConflictGate>,
+    pub desktops: Arc<DesktopGate>,
+}
+
+impl GuestSweep {
+    /// Unpair guests whose time is up, until `shutdown`. Runs once right
+    /// away for guests that expired while sync was stopped.
+    pub fn spawn(self, shutdown: Shutdown) {
+        shutdown.clone().spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        if let Err(e) = self.sweep().await {
+                            tracing::warn!("Failed to unpair expired guests: {}", e);
+                        }
+                    }
+                    _ = shutdown.cancelled() => break,
+                }
+            }
+        });
+    }
+
+    async fn sweep(&self) -> Result<()> {
+        let expired = self.trust.write().await.take_expired(Utc::now());
+        if expired.is_empty() {
+            return Ok(());
+        }
+        self.save().await?;
+        for entry in expired {
+            self.unpair(entry).await;
+        }
+        Ok(())
+    }
+
+    async fn unpair(&self, entry: TrustEntry) {
+        let mut purged = 0;
+        if let Some(ip) = net::parse_ip(&entry.address) {
+            self.websocket.close_sessions_to(ip).await;
+            purged += self.oversize.purge_from(ip).await;
+            purged += self.conflicts.purge_from(ip);
+            purged += usize::from(self.desktops.purge_from(ip));
+        }
+        tracing::info!("Guest pairing with {} ({}:{}) expired", entry.name, entry.address, entry.port);
+        audit::record(AuditEntry::new("guest_expired", Some(entry.name.clone()), vec![format!("dropped {} held clips", purged)]));
+        events::emit(self.app.as_ref(), GUEST_EXPIRED_EVENT, GuestExpired {
+            name: entry.name,
+            address: entry.address,
+            port: entry.port,
+            expired_at: entry.expires_at.unwrap_or_else(Utc::now),
+            purged,
+        });
+    }
+
+    async fn save(&self) -> Result<()> {
+        if let Some(ref app) = self.app {
+            let store = app.store(paths::settings_store())?;
+            store.set(TRUST_STORE_KEY, serde_json::to_value(&*self.trust.read().await)?);
+            store.save()?;
+        }
+        Ok(())
+    }
+}
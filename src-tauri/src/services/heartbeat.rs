@@ -0,0 +1,209 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use tokio::time::Instant;
+use crate::models::Config;
+
+/// Answered heartbeats in a row before the interval is doubled, for a link
+/// with no recent drops.
+const STABLE_BEATS: u32 = 6;
+/// How long a dropped connection counts against a device's link.
+const DROP_MEMORY: Duration = Duration::from_secs(10 * 60);
+
+/// Link history per device address, kept across reconnects.
+pub(crate) type LinkHistory = Arc<RwLock<HashMap<IpAddr, LinkRecord>>>;
+
+#[derive(Debug, Default)]
+pub(crate) struct LinkRecord {
+    /// When recent connections to the device were lost
+    drops: VecDeque<Instant>,
+    /// Interval the last cleanly closed connection settled on
+    settled: Option<Duration>,
+}
+
+impl LinkRecord {
+    fn recent_drops(&mut self) -> u32 {
+        while self.drops.front().is_some_and(|at| at.elapsed() > DROP_MEMORY) {
+            self.drops.pop_front();
+        }
+        self.drops.len() as u32
+    }
+}
+
+/// Heartbeat cadence of one connection. It backs off towards
+/// `heartbeat_max_secs` while the peer keeps answering and falls back
+/// towards `heartbeat_min_secs` when it goes quiet. Links that dropped
+/// recently start fast and take longer to back off.
+#[derive(Debug, Clone)]
+pub(crate) struct Cadence {
+    interval: Duration,
+    min: Duration,
+    max: Duration,
+    stable_beats: u32,
+    answered: u32,
+}
+
+impl Cadence {
+    /// Starting cadence for a connection from `ip`.
+    pub async fn for_peer(history: &LinkHistory, ip: IpAddr, config: &Config) -> Self {
+        let min = Duration::from_secs(config.heartbeat_min_secs.max(1) as u64);
+        let max = Duration::from_secs(config.heartbeat_max_secs as u64).max(min);
+
+        let mut history = history.write().await;
+        let record = history.entry(ip).or_default();
+        let drops = record.recent_drops();
+        let interval = match record.settled {
+            Some(settled) if drops == 0 => settled.clamp(min, max),
+            _ => min,
+        };
+        Self {
+            interval,
+            min,
+            max,
+            stable_beats: STABLE_BEATS * (1 + drops),
+            answered: 0,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Update the cadence after a heartbeat period in which the peer was
+    /// or was not heard from. Returns whether the interval changed.
+    pub fn observe(&mut self, heard: bool) -> bool {
+        let previous = self.interval;
+        if heard {
+            self.answered += 1;
+            if self.answered >= self.stable_beats {
+                self.interval = (self.interval * 2).min(self.max);
+                self.answered = 0;
+            }
+        } else {
+            self.interval = (self.interval / 2).max(self.min);
+            self.answered = 0;
+        }
+        self.interval != previous
+    }
+}
+
+/// Remember how a connection from `ip` ended, for the next one.
+pub(crate) async fn record_end(history: &LinkHistory, ip: IpAddr, cadence: &Cadence, dropped: bool) {
+    let mut history = history.write().await;
+    let record = history.entry(ip).or_default();
+    if dropped {
+        record.drops.push_back(Instant::now());
+        record.settled = None;
+    } else {
+        record.settled = Some(cadence.interval());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Duration = Duration::from_secs(5);
+    const MAX: Duration = Duration::from_secs(40);
+
+    fn config() -> Config {
+        Config { heartbeat_min_secs: 5, heartbeat_max_secs: 40, ..Config::default() }
+    }
+
+    fn ip() -> IpAddr {
+        "192.168.1.20".parse().unwrap()
+    }
+
+    async fn cadence(history: &LinkHistory) -> Cadence {
+        Cadence::for_peer(history, ip(), &config()).await
+    }
+
+    /// Heartbeats answered before the interval first changes.
+    fn beats_to_back_off(cadence: &mut Cadence) -> u32 {
+        (1..=100).find(|_| cadence.observe(true)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn new_links_start_fast_and_back_off_while_answered() {
+        let mut cadence = cadence(&LinkHistory::default()).await;
+        assert_eq!(cadence.interval(), MIN);
+        assert_eq!(beats_to_back_off(&mut cadence), STABLE_BEATS);
+        assert_eq!(cadence.interval(), MIN * 2);
+        for _ in 0..STABLE_BEATS * 10 {
+            cadence.observe(true);
+        }
+        assert_eq!(cadence.interval(), MAX);
+        assert!(!cadence.observe(true));
+    }
+
+    #[tokio::test]
+    async fn silence_halves_the_interval_down_to_the_minimum() {
+        let mut cadence = cadence(&LinkHistory::default()).await;
+        for _ in 0..STABLE_BEATS * 3 {
+            cadence.observe(true);
+        }
+        assert_eq!(cadence.interval(), MIN * 8);
+        assert!(cadence.observe(false));
+        assert_eq!(cadence.interval(), MIN * 4);
+        cadence.observe(false);
+        cadence.observe(false);
+        assert!(!cadence.observe(false));
+        assert_eq!(cadence.interval(), MIN);
+    }
+
+    #[tokio::test]
+    async fn silence_restarts_the_count_of_answered_beats() {
+        let mut cadence = cadence(&LinkHistory::default()).await;
+        for _ in 0..STABLE_BEATS - 1 {
+            cadence.observe(true);
+        }
+        cadence.observe(false);
+        assert_eq!(beats_to_back_off(&mut cadence), STABLE_BEATS);
+    }
+
+    #[tokio::test]
+    async fn cleanly_closed_links_resume_where_they_settled() {
+        let history = LinkHistory::default();
+        let mut first = cadence(&history).await;
+        for _ in 0..STABLE_BEATS * 2 {
+            first.observe(true);
+        }
+        record_end(&history, ip(), &first, false).await;
+        assert_eq!(cadence(&history).await.interval(), MIN * 4);
+        // Another address has no history of its own
+        let other = Cadence::for_peer(&history, "192.168.1.21".parse().unwrap(), &config()).await;
+        assert_eq!(other.interval(), MIN);
+    }
+
+    #[tokio::test]
+    async fn dropped_links_start_fast_and_back_off_slower_for_a_while() {
+        let history = LinkHistory::default();
+        let mut first = cadence(&history).await;
+        for _ in 0..STABLE_BEATS * 2 {
+            first.observe(true);
+        }
+        record_end(&history, ip(), &first, true).await;
+
+        let mut after_drop = cadence(&history).await;
+        assert_eq!(after_drop.interval(), MIN);
+        assert_eq!(beats_to_back_off(&mut after_drop), STABLE_BEATS * 2);
+
+        // Once the drop is older than DROP_MEMORY it no longer counts
+        let long_ago = Instant::now().checked_sub(DROP_MEMORY + Duration::from_secs(1)).expect("clock runs back far enough");
+        *history.write().await.get_mut(&ip()).unwrap().drops.front_mut().unwrap() = long_ago;
+        assert_eq!(beats_to_back_off(&mut cadence(&history).await), STABLE_BEATS);
+    }
+
+    #[tokio::test]
+    async fn a_maximum_below_the_minimum_is_raised_to_it() {
+        let config = Config { heartbeat_min_secs: 0, heartbeat_max_secs: 0, ..Config::default() };
+        let mut cadence = Cadence::for_peer(&LinkHistory::default(), ip(), &config).await;
+        assert_eq!(cadence.interval(), Duration::from_secs(1));
+        for _ in 0..STABLE_BEATS {
+            cadence.observe(true);
+        }
+        assert_eq!(cadence.interval(), Duration::from_secs(1));
+    }
+}
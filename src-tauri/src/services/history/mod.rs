@@ -0,0 +1,329 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use uuid::Uuid;
+use crate::models::{ClipReceipt, HistoryEntry, HistoryOrigin};
+use super::history_crypto::HistoryCipher;
+use super::key_derivation::KeySalt;
+
+mod retention;
+mod sealing;
+mod search;
+#[cfg(test)]
+mod tests;
+
+pub const HISTORY_DB_FILE: &str = "history.db";
+/// Largest page the history commands will return.
+pub const MAX_PAGE_SIZE: u32 = 200;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        content TEXT NOT NULL,
+        origin TEXT NOT NULL,
+        device TEXT,
+        created_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS idx_history_created_at ON history(created_at);
+    CREATE TABLE IF NOT EXISTS receipts (
+        message_id TEXT NOT NULL,
+        device TEXT NOT NULL,
+        applied_at TEXT NOT NULL,
+        PRIMARY KEY (message_id, device)
+    );
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+";
+
+/// Columns added after the first release, with their types.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("message_id", "TEXT"),
+    ("sent_to", "INTEGER"),
+    ("pinned", "INTEGER NOT NULL DEFAULT 0"),
+    ("tags", "TEXT"),
+    ("encrypted", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+/// Bring a database created by an older version up to the current schema.
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let existing: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('history')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (column, kind) in ADDED_COLUMNS {
+        if !existing.iter().any(|name| name == column) {
+            conn.execute_batch(&format!("ALTER TABLE history ADD COLUMN {} {}", column, kind))?;
+        }
+    }
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_history_message_id ON history(message_id)")
+}
+
+const ENTRY_COLUMNS: &str = "id, content, origin, device, created_at, sent_to,
+    (SELECT COUNT(*) FROM receipts WHERE receipts.message_id = history.message_id),
+    pinned, tags, encrypted";
+
+/// Persistent record of local and remote clipboard changes.
+///
+/// SQLite calls are blocking, so every operation runs on the blocking pool.
+/// Content is sealed at rest whenever a key is available.
+pub struct HistoryStore {
+    conn: Arc<Mutex<Connection>>,
+    /// Changed only while `conn` is locked, see [`with_sealed`](Self::with_sealed)
+    cipher: Arc<RwLock<Option<Arc<HistoryCipher>>>>,
+}
+
+fn origin_to_sql(origin: HistoryOrigin) -> &'static str {
+    match origin {
+        HistoryOrigin::Local => "local",
+        HistoryOrigin::Remote => "remote",
+    }
+}
+
+fn origin_from_sql(value: &str) -> HistoryOrigin {
+    match value {
+        "remote" => HistoryOrigin::Remote,
+        _ => HistoryOrigin::Local,
+    }
+}
+
+/// Tags are stored as one comma-separated column, so they are trimmed,
+/// lowercased, deduplicated, and may not contain commas.
+pub fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().replace(',', " ").to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
+
+fn tags_from_sql(value: Option<String>) -> Vec<String> {
+    value
+        .map(|tags| tags.split(',').filter(|tag| !tag.is_empty()).map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn entry_from_row(row: &rusqlite::Row<'_>, cipher: Option<&HistoryCipher>) -> rusqlite::Result<HistoryEntry> {
+    let origin: String = row.get(2)?;
+    let content: String = row.get(1)?;
+    let content = match cipher {
+        Some(cipher) if row.get::<_, bool>(9)? => cipher
+            .open(&content)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into()))?,
+        _ => content,
+    };
+    Ok(HistoryEntry {
+        id: row.get(0)?,
+        content,
+        origin: origin_from_sql(&origin),
+        device: row.get(3)?,
+        created_at: row.get::<_, DateTime<Utc>>(4)?,
+        sent_to: row.get(5)?,
+        applied_on: row.get(6)?,
+        pinned: row.get(7)?,
+        tags: tags_from_sql(row.get(8)?),
+    })
+}
+
+impl HistoryStore {
+    /// Open (creating if needed) the history database at `path`, and
+    /// encrypt any entries still stored as plaintext.
+    ///
+    /// # Arguments
+    /// * `security_key` - Used to derive the history key when the OS
+    ///   keychain is unavailable
+    /// * `salt` - This installation's salt, for that derivation
+    ///
+    /// # Errors
+    /// Besides database errors, when the history is encrypted with a key
+    /// that cannot be found
+    pub fn open(path: &Path, security_key: Option<&str>, salt: &KeySalt) -> Result<Self> {
+        Self::open_with(path, security_key, salt, true)
+    }
+
+    /// [`open`](Self::open), looking in the OS keychain only when
+    /// `use_keychain` is set.
+    fn open_with(path: &Path, security_key: Option<&str>, salt: &KeySalt, use_keychain: bool) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        migrate(&conn)?;
+        let cipher = sealing::resolve_cipher(&conn, security_key, salt, use_keychain)?;
+        if let Some(ref cipher) = cipher {
+            let sealed = sealing::seal_plaintext(&conn, cipher)?;
+            if sealed > 0 {
+                tracing::info!("Encrypted {} existing history entries", sealed);
+            }
+        }
+        tracing::info!("Clipboard history database opened at {}", path.display());
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            cipher: Arc::new(RwLock::new(cipher.map(Arc::new))),
+        })
+    }
+
+    async fn with_conn<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            f(&conn)
+        })
+        .await?
+        .map_err(Into::into)
+    }
+
+    /// [`with_conn`](Self::with_conn), also handing `f` the key history is
+    /// sealed with. The key is read with the connection locked, so a
+    /// change of key cannot come between them.
+    async fn with_sealed<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection, Option<&HistoryCipher>) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let cipher = self.cipher.clone();
+        self.with_conn(move |conn| {
+            let cipher = cipher.read().unwrap_or_else(|e| e.into_inner()).clone();
+            f(conn, cipher.as_deref())
+        })
+        .await
+    }
+
+    /// Record a clipboard change.
+    ///
+    /// # Returns
+    /// The id of the new entry
+    pub async fn record(&self, content: String, origin: HistoryOrigin, device: Option<String>) -> Result<i64> {
+        self.with_sealed(move |conn, cipher| {
+            let (content, encrypted) = match cipher {
+                Some(cipher) => (
+                    cipher.seal(&content).map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?,
+                    true,
+                ),
+                None => (content, false),
+            };
+            conn.execute(
+                "INSERT INTO history (content, origin, device, created_at, encrypted) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![content, origin_to_sql(origin), device, Utc::now(), encrypted],
+            )?;
+            Ok(conn.last_insert_rowid())
+        })
+        .await
+    }
+
+    /// Note that a local entry went out as message `message_id` to
+    /// `sent_to` peers, so receipts for it can be matched.
+    pub async fn mark_sent(&self, id: i64, message_id: Uuid, sent_to: u32) -> Result<()> {
+        self.with_conn(move |conn| {
+            conn.execute(
+                "UPDATE history SET message_id = ?1, sent_to = ?2 WHERE id = ?3",
+                params![message_id.to_string(), sent_to, id],
+            )
+            .map(|_| ())
+        })
+        .await
+    }
+
+    /// Store a peer's receipt if it is for a clip sent from here.
+    ///
+    /// # Returns
+    /// Whether a matching entry exists
+    pub async fn record_receipt(&self, receipt: &ClipReceipt) -> Result<bool> {
+        let receipt = receipt.clone();
+        self.with_conn(move |conn| {
+            let message_id = receipt.message_id.to_string();
+            let known: bool = conn.query_row(
+                "SELECT EXISTS (SELECT 1 FROM history WHERE message_id = ?1)",
+                params![message_id],
+                |row| row.get(0),
+            )?;
+            if known {
+                conn.execute(
+                    "INSERT OR IGNORE INTO receipts (message_id, device, applied_at) VALUES (?1, ?2, ?3)",
+                    params![message_id, receipt.device, receipt.applied_at],
+                )?;
+            }
+            Ok(known)
+        })
+        .await
+    }
+
+    /// Number of entries of one origin recorded since `since`.
+    pub async fn count_since(&self, since: DateTime<Utc>, origin: HistoryOrigin) -> Result<u32> {
+        self.with_conn(move |conn| {
+            conn.query_row(
+                "SELECT COUNT(*) FROM history WHERE created_at >= ?1 AND origin = ?2",
+                params![since, origin_to_sql(origin)],
+                |row| row.get(0),
+            )
+        })
+        .await
+    }
+
+    /// One entry by id.
+    pub async fn get(&self, id: i64) -> Result<Option<HistoryEntry>> {
+        self.with_sealed(move |conn, cipher| {
+            conn.query_row(
+                &format!("SELECT {} FROM history WHERE id = ?1", ENTRY_COLUMNS),
+                params![id],
+                |row| entry_from_row(row, cipher),
+            )
+            .optional()
+        })
+        .await
+    }
+
+    /// Delete one entry. Returns whether it existed.
+    pub async fn delete(&self, id: i64) -> Result<bool> {
+        self.with_conn(move |conn| {
+            conn.execute("DELETE FROM history WHERE id = ?1", params![id])
+                .map(|removed| removed > 0)
+        })
+        .await
+    }
+
+    /// Every pinned entry, newest first.
+    pub async fn pinned(&self) -> Result<Vec<HistoryEntry>> {
+        self.with_sealed(move |conn, cipher| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM history WHERE pinned = 1 ORDER BY id DESC",
+                ENTRY_COLUMNS
+            ))?;
+            let entries = stmt.query_map([], |row| entry_from_row(row, cipher))?.collect::<rusqlite::Result<Vec<_>>>();
+            entries
+        })
+        .await
+    }
+
+    /// Pin or unpin an entry; pinned entries are never pruned. Returns
+    /// whether it existed.
+    pub async fn set_pinned(&self, id: i64, pinned: bool) -> Result<bool> {
+        self.with_conn(move |conn| {
+            conn.execute("UPDATE history SET pinned = ?1 WHERE id = ?2", params![pinned, id])
+                .map(|updated| updated > 0)
+        })
+        .await
+    }
+
+    /// Replace the tags on an entry. Returns whether it existed.
+    pub async fn set_tags(&self, id: i64, tags: &[String]) -> Result<bool> {
+        let tags = normalize_tags(tags);
+        let tags = (!tags.is_empty()).then(|| tags.join(","));
+        self.with_conn(move |conn| {
+            conn.execute("UPDATE history SET tags = ?1 WHERE id = ?2", params![tags, id])
+                .map(|updated| updated > 0)
+        })
+        .await
+    }
+}
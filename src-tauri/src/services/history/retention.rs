@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashSet};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use super::{tags_from_sql, HistoryStore};
+
+impl HistoryStore {
+    /// Delete every entry recorded from any of the given device names.
+    ///
+    /// # Returns
+    /// Number of entries removed
+    pub async fn delete_by_device(&self, devices: &[&str]) -> Result<usize> {
+        let devices: Vec<String> = devices.iter().map(|device| device.to_string()).collect();
+        self.with_conn(move |conn| {
+            let mut removed = 0;
+            for device in &devices {
+                removed += conn.execute("DELETE FROM history WHERE device = ?1", params![device])?;
+            }
+            Ok(removed)
+        })
+        .await
+    }
+
+    /// Enforce retention limits. Pinned entries are never removed, and
+    /// entries carrying a tag from `tag_retention` follow that tag's limit
+    /// instead of `max_age` and `max_entries`.
+    ///
+    /// # Arguments
+    /// * `max_age` - Entries older than this are removed
+    /// * `max_entries` - Only the newest `max_entries` entries are kept
+    /// * `tag_retention` - Days to keep entries with each tag (0 keeps them
+    ///   forever); with several such tags the longest wins
+    ///
+    /// # Returns
+    /// Number of entries removed
+    pub async fn prune(
+        &self,
+        max_age: Option<chrono::Duration>,
+        max_entries: Option<u32>,
+        tag_retention: BTreeMap<String, u32>,
+    ) -> Result<usize> {
+        let tag_retention: BTreeMap<String, u32> = tag_retention
+            .into_iter()
+            .map(|(tag, days)| (tag.trim().to_lowercase(), days))
+            .collect();
+        self.with_conn(move |conn| {
+            let now = Utc::now();
+            let mut removed = 0;
+
+            // Entries governed by a tag override, and those of them past it
+            let mut governed = HashSet::new();
+            let mut expired = Vec::new();
+            let mut stmt = conn.prepare("SELECT id, created_at, tags FROM history WHERE pinned = 0 AND tags IS NOT NULL")?;
+            let tagged = stmt
+                .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, DateTime<Utc>>(1)?, tags_from_sql(row.get(2)?))))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for (id, created_at, tags) in tagged {
+                let limits: Vec<u32> = tags.iter().filter_map(|tag| tag_retention.get(tag).copied()).collect();
+                if limits.is_empty() {
+                    continue;
+                }
+                governed.insert(id);
+                if !limits.contains(&0) {
+                    let days = limits.into_iter().max().unwrap_or_default();
+                    if created_at < now - chrono::Duration::days(days as i64) {
+                        expired.push(id);
+                    }
+                }
+            }
+            for id in expired {
+                removed += conn.execute("DELETE FROM history WHERE id = ?1", params![id])?;
+            }
+
+            conn.execute_batch(
+                "CREATE TEMP TABLE IF NOT EXISTS retention_exempt (id INTEGER PRIMARY KEY);
+                 DELETE FROM retention_exempt;",
+            )?;
+            for id in &governed {
+                conn.execute("INSERT INTO retention_exempt (id) VALUES (?1)", params![id])?;
+            }
+            const ELIGIBLE: &str = "pinned = 0 AND id NOT IN (SELECT id FROM retention_exempt)";
+            if let Some(max_age) = max_age {
+                removed += conn.execute(
+                    &format!("DELETE FROM history WHERE {} AND created_at < ?1", ELIGIBLE),
+                    params![now - max_age],
+                )?;
+            }
+            if let Some(max_entries) = max_entries {
+                removed += conn.execute(
+                    &format!(
+                        "DELETE FROM history WHERE {0} AND id NOT IN
+                         (SELECT id FROM history WHERE {0} ORDER BY id DESC LIMIT ?1)",
+                        ELIGIBLE
+                    ),
+                    params![max_entries],
+                )?;
+            }
+            conn.execute(
+                "DELETE FROM receipts WHERE message_id NOT IN
+                 (SELECT message_id FROM history WHERE message_id IS NOT NULL)",
+                [],
+            )?;
+            Ok(removed)
+        })
+        .await
+    }
+
+    /// Rebuild the database file to release space freed by deletions.
+    ///
+    /// # Returns
+    /// Bytes reclaimed
+    pub async fn vacuum(&self) -> Result<u64> {
+        self.with_conn(|conn| {
+            let size = |conn: &Connection| -> rusqlite::Result<u64> {
+                let pages: u64 = conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+                let page_size: u64 = conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+                Ok(pages * page_size)
+            };
+            let before = size(conn)?;
+            conn.execute_batch("VACUUM")?;
+            Ok(before.saturating_sub(size(conn)?))
+        })
+        .await
+    }
+
+    /// Delete every entry. Returns how many were removed.
+    pub async fn clear(&self) -> Result<usize> {
+        self.with_conn(|conn| {
+            conn.execute("DELETE FROM receipts", [])?;
+            conn.execute("DELETE FROM history", [])
+        })
+        .await
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension};
+use crate::services::history_crypto::{self, HistoryCipher, KEY_CHECK};
+use crate::services::key_derivation::KeySalt;
+use super::HistoryStore;
+
+/// `meta` key holding the sealed `KEY_CHECK` once history is encrypted.
+const KEY_CHECK_META: &str = "key_check";
+
+/// Record which key history is sealed with, or that it is not sealed.
+fn store_key_check(conn: &Connection, cipher: Option<&HistoryCipher>) -> Result<()> {
+    match cipher {
+        Some(cipher) => conn.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+            params![KEY_CHECK_META, cipher.seal(KEY_CHECK)?],
+        )?,
+        None => conn.execute("DELETE FROM meta WHERE key = ?1", params![KEY_CHECK_META])?,
+    };
+    Ok(())
+}
+
+/// Find the key history is sealed with, or pick one for a database that
+/// is not encrypted yet: the OS keychain first, then the security key.
+/// History sealed under the unsalted key of earlier builds is sealed
+/// again under the salted one.
+///
+/// # Errors
+/// When the database is encrypted and none of the available keys opens it
+pub(super) fn resolve_cipher(
+    conn: &Connection,
+    security_key: Option<&str>,
+    salt: &KeySalt,
+    use_keychain: bool,
+) -> Result<Option<HistoryCipher>> {
+    let check: Option<String> = conn
+        .query_row("SELECT value FROM meta WHERE key = ?1", params![KEY_CHECK_META], |row| row.get(0))
+        .optional()?;
+    let keychain = if use_keychain {
+        history_crypto::keychain_key().unwrap_or_else(|e| {
+            tracing::warn!("OS keychain is unavailable: {}", e);
+            None
+        })
+    } else {
+        None
+    };
+    let derived = security_key.map(|key| history_crypto::derived_key(key, salt)).transpose()?;
+
+    if let Some(check) = check {
+        if let Some(cipher) = [keychain, derived.clone()].into_iter().flatten().find(|cipher| cipher.opens(&check)) {
+            return Ok(Some(cipher));
+        }
+        if let (Some(key), Some(derived)) = (security_key, derived) {
+            let legacy = history_crypto::legacy_derived_key(key);
+            if legacy.opens(&check) {
+                let resealed = reseal(conn, Some(&legacy), Some(&derived))?;
+                tracing::info!("Encrypted {} history entries again with a salted key", resealed);
+                return Ok(Some(derived));
+            }
+        }
+        anyhow::bail!("History is encrypted with a key that is no longer available");
+    }
+
+    let cipher = match keychain {
+        Some(cipher) => Some(cipher),
+        None if use_keychain => match history_crypto::create_keychain_key() {
+            Ok(cipher) => Some(cipher),
+            Err(e) => {
+                tracing::warn!("Cannot store a history key in the OS keychain: {}", e);
+                derived
+            }
+        },
+        None => derived,
+    };
+    match cipher {
+        Some(ref cipher) => store_key_check(conn, Some(cipher))?,
+        None => tracing::warn!("No keychain or security key, so clipboard history is stored unencrypted"),
+    }
+    Ok(cipher)
+}
+
+/// Seal every entry still stored as plaintext, e.g. from before history
+/// was encrypted.
+pub(super) fn seal_plaintext(conn: &Connection, cipher: &HistoryCipher) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let plain = tx
+        .prepare("SELECT id, content FROM history WHERE encrypted = 0")?
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, content) in &plain {
+        tx.execute(
+            "UPDATE history SET content = ?1, encrypted = 1 WHERE id = ?2",
+            params![cipher.seal(content)?, id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(plain.len())
+}
+
+/// Open every entry with `from` and seal it with `to`, or store it as
+/// plaintext without one, in one transaction.
+///
+/// # Returns
+/// The number of entries rewritten
+///
+/// # Errors
+/// When an entry does not open with `from`; nothing is changed then
+pub(super) fn reseal(conn: &Connection, from: Option<&HistoryCipher>, to: Option<&HistoryCipher>) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let rows = tx
+        .prepare("SELECT id, content, encrypted FROM history")?
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, bool>(2)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    for (id, content, encrypted) in &rows {
+        let plaintext = match (encrypted, from) {
+            (true, Some(from)) => from.open(content)?,
+            (true, None) => anyhow::bail!("History entry {} is encrypted but no key was given to open it", id),
+            (false, _) => content.clone(),
+        };
+        let (content, encrypted) = match to {
+            Some(to) => (to.seal(&plaintext)?, true),
+            None => (plaintext, false),
+        };
+        tx.execute(
+            "UPDATE history SET content = ?1, encrypted = ?2 WHERE id = ?3",
+            params![content, encrypted, id],
+        )?;
+    }
+    store_key_check(&tx, to)?;
+    tx.commit()?;
+    Ok(rows.len())
+}
+
+impl HistoryStore {
+    /// Seal history again after the security key changed, when it has no
+    /// keychain key and so follows the security key. With the key removed
+    /// it is stored as plaintext, as a new history would be.
+    ///
+    /// # Errors
+    /// When the history cannot be sealed again; it stays under the old key
+    pub async fn follow_security_key(&self, security_key: Option<String>, salt: KeySalt) -> Result<()> {
+        let conn = self.conn.clone();
+        let cipher = self.cipher.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = conn.lock().unwrap_or_else(|e| e.into_inner());
+            let mut cipher = cipher.write().unwrap_or_else(|e| e.into_inner());
+            if cipher.as_ref().is_some_and(|cipher| !cipher.follows_security_key()) {
+                return Ok(());
+            }
+            let next = security_key.map(|key| history_crypto::derived_key(&key, &salt)).transpose()?;
+            let resealed = reseal(&conn, cipher.as_deref(), next.as_ref())?;
+            tracing::info!("Rewrote {} history entries for the changed security key", resealed);
+            *cipher = next.map(Arc::new);
+            Ok(())
+        })
+        .await?
+    }
+}
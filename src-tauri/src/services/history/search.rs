@@ -0,0 +1,113 @@
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use crate::models::HistoryPage;
+use crate::services::history_crypto::HistoryCipher;
+use super::{entry_from_row, HistoryStore, ENTRY_COLUMNS, MAX_PAGE_SIZE};
+
+/// Entries a search over sealed history opens at a time.
+const SEALED_SEARCH_BATCH: u32 = 500;
+
+impl HistoryStore {
+    /// List entries newest first.
+    ///
+    /// # Arguments
+    /// * `limit` - Page size, capped at `MAX_PAGE_SIZE`
+    /// * `offset` - Number of entries to skip
+    pub async fn list(&self, limit: u32, offset: u32) -> Result<HistoryPage> {
+        let limit = limit.min(MAX_PAGE_SIZE);
+        self.with_sealed(move |conn, cipher| {
+            let total: u64 = conn.query_row("SELECT COUNT(*) FROM history", [], |row| row.get(0))?;
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM history ORDER BY id DESC LIMIT ?1 OFFSET ?2",
+                ENTRY_COLUMNS
+            ))?;
+            let entries = stmt
+                .query_map(params![limit, offset], |row| entry_from_row(row, cipher))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(HistoryPage { entries, total })
+        })
+        .await
+    }
+
+    /// Find entries containing `query`, newest first. Matching ignores
+    /// case for ASCII letters, as SQLite's `LIKE` does.
+    ///
+    /// # Arguments
+    /// * `query` - Text to look for; empty matches every entry
+    /// * `device` - Only entries received from this device
+    /// * `limit` - Page size, capped at `MAX_PAGE_SIZE`
+    /// * `offset` - Number of matches to skip
+    pub async fn search(&self, query: String, device: Option<String>, limit: u32, offset: u32) -> Result<HistoryPage> {
+        let limit = limit.min(MAX_PAGE_SIZE);
+        self.with_sealed(move |conn, cipher| match cipher {
+            Some(cipher) => search_sealed(conn, cipher, &query, device, limit, offset),
+            None => search_plain(conn, &query, device, limit, offset),
+        })
+        .await
+    }
+}
+
+fn search_plain(conn: &Connection, query: &str, device: Option<String>, limit: u32, offset: u32) -> rusqlite::Result<HistoryPage> {
+    let pattern = format!(
+        "%{}%",
+        query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    );
+    const FILTER: &str = "content LIKE ?1 ESCAPE '\\' AND (?2 IS NULL OR device = ?2)";
+    let total: u64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM history WHERE {}", FILTER),
+        params![pattern, device],
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM history WHERE {} ORDER BY id DESC LIMIT ?3 OFFSET ?4",
+        ENTRY_COLUMNS, FILTER
+    ))?;
+    let entries = stmt
+        .query_map(params![pattern, device, limit, offset], |row| entry_from_row(row, None))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(HistoryPage { entries, total })
+}
+
+/// Search over sealed content, which SQLite cannot look into: every
+/// entry from `device` is opened and matched here instead, a batch at a
+/// time, keeping only the requested page.
+fn search_sealed(
+    conn: &Connection,
+    cipher: &HistoryCipher,
+    query: &str,
+    device: Option<String>,
+    limit: u32,
+    offset: u32,
+) -> rusqlite::Result<HistoryPage> {
+    let query = query.to_ascii_lowercase();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM history WHERE (?1 IS NULL OR device = ?1) AND id < ?2 ORDER BY id DESC LIMIT ?3",
+        ENTRY_COLUMNS
+    ))?;
+    let mut entries = Vec::new();
+    let mut total = 0u64;
+    let mut before = i64::MAX;
+    loop {
+        let batch = stmt
+            .query_map(params![device, before, SEALED_SEARCH_BATCH], |row| entry_from_row(row, Some(cipher)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        before = last.id;
+        let full = batch.len() as u32 == SEALED_SEARCH_BATCH;
+        for entry in batch {
+            if !entry.content.to_ascii_lowercase().contains(&query) {
+                continue;
+            }
+            if total >= u64::from(offset) && entries.len() < limit as usize {
+                entries.push(entry);
+            }
+            total += 1;
+        }
+        if !full {
+            break;
+        }
+    }
+    Ok(HistoryPage { entries, total })
+}
@@ -0,0 +1,238 @@
+use std::path::PathBuf;
+use super::*;
+use crate::services::history_crypto;
+use crate::models::HistoryPage;
+
+const SALT: KeySalt = [7; 16];
+
+/// A database file removed when the test ends.
+struct TempDb(PathBuf);
+
+impl TempDb {
+    fn new() -> Self {
+        Self(std::env::temp_dir().join(format!("unimesh-history-{}.db", Uuid::new_v4())))
+    }
+
+    fn open(&self, security_key: Option<&str>) -> Result<HistoryStore> {
+        HistoryStore::open_with(&self.0, security_key, &SALT, false)
+    }
+
+    /// `(content, encrypted)` of every row as stored.
+    fn raw(&self) -> Vec<(String, bool)> {
+        let conn = Connection::open(&self.0).unwrap();
+        let mut stmt = conn.prepare("SELECT content, encrypted FROM history ORDER BY id").unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.collect::<rusqlite::Result<_>>().unwrap()
+    }
+}
+
+impl Drop for TempDb {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+async fn contents(store: &HistoryStore) -> Vec<String> {
+    store.list(MAX_PAGE_SIZE, 0).await.unwrap().entries.into_iter().map(|entry| entry.content).collect()
+}
+
+#[tokio::test]
+async fn plaintext_history_is_sealed_once_a_key_is_available() {
+    let db = TempDb::new();
+    let store = db.open(None).unwrap();
+    store.record("first".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    store.record("second".to_string(), HistoryOrigin::Remote, Some("laptop".to_string())).await.unwrap();
+    drop(store);
+    assert_eq!(db.raw(), vec![("first".to_string(), false), ("second".to_string(), false)]);
+
+    let store = db.open(Some("passphrase")).unwrap();
+    assert!(db.raw().iter().all(|(content, encrypted)| *encrypted && !content.contains("first") && !content.contains("second")));
+    assert_eq!(contents(&store).await, ["second", "first"]);
+}
+
+#[tokio::test]
+async fn new_entries_are_sealed() {
+    let db = TempDb::new();
+    let store = db.open(Some("passphrase")).unwrap();
+    let id = store.record("secret".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    let (content, encrypted) = db.raw().remove(0);
+    assert!(encrypted);
+    assert_ne!(content, "secret");
+    assert_eq!(store.get(id).await.unwrap().unwrap().content, "secret");
+}
+
+#[tokio::test]
+async fn opening_with_another_key_fails() {
+    let db = TempDb::new();
+    let store = db.open(Some("passphrase")).unwrap();
+    store.record("secret".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    drop(store);
+    assert!(db.open(Some("another passphrase")).is_err());
+    assert!(db.open(None).is_err());
+    // The failed attempts leave the history as it was
+    assert_eq!(contents(&db.open(Some("passphrase")).unwrap()).await, ["secret"]);
+}
+
+#[tokio::test]
+async fn history_under_the_unsalted_key_is_sealed_again() {
+    let db = TempDb::new();
+    let store = db.open(None).unwrap();
+    store.record("old".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    drop(store);
+    let legacy = history_crypto::legacy_derived_key("passphrase");
+    sealing::reseal(&Connection::open(&db.0).unwrap(), None, Some(&legacy)).unwrap();
+    let sealed_before = db.raw();
+
+    let store = db.open(Some("passphrase")).unwrap();
+    assert_eq!(contents(&store).await, ["old"]);
+    let (content, encrypted) = db.raw().remove(0);
+    assert!(encrypted);
+    assert_ne!(content, sealed_before[0].0);
+    assert!(legacy.open(&content).is_err());
+}
+
+#[tokio::test]
+async fn history_follows_a_changed_security_key() {
+    let db = TempDb::new();
+    let store = db.open(Some("old passphrase")).unwrap();
+    store.record("kept".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    store.follow_security_key(Some("new passphrase".to_string()), SALT).await.unwrap();
+    store.record("added".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    assert_eq!(contents(&store).await, ["added", "kept"]);
+    drop(store);
+
+    assert!(db.open(Some("old passphrase")).is_err());
+    assert_eq!(contents(&db.open(Some("new passphrase")).unwrap()).await, ["added", "kept"]);
+}
+
+#[tokio::test]
+async fn removing_the_security_key_stores_history_as_plaintext() {
+    let db = TempDb::new();
+    let store = db.open(Some("passphrase")).unwrap();
+    store.record("kept".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    store.follow_security_key(None, SALT).await.unwrap();
+    drop(store);
+
+    assert_eq!(db.raw(), vec![("kept".to_string(), false)]);
+    assert_eq!(contents(&db.open(None).unwrap()).await, ["kept"]);
+}
+
+#[tokio::test]
+async fn a_failed_change_of_key_leaves_history_readable() {
+    let db = TempDb::new();
+    let store = db.open(Some("passphrase")).unwrap();
+    store.record("kept".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    // A row no key opens makes the rewrite fail part way
+    Connection::open(&db.0).unwrap()
+        .execute("INSERT INTO history (content, origin, created_at, encrypted) VALUES ('garbage', 'local', ?1, 1)", params![Utc::now()])
+        .unwrap();
+    assert!(store.follow_security_key(Some("new passphrase".to_string()), SALT).await.is_err());
+    drop(store);
+
+    assert!(db.open(Some("new passphrase")).is_err());
+    let store = db.open(Some("passphrase")).unwrap();
+    assert_eq!(store.get(1).await.unwrap().unwrap().content, "kept");
+}
+
+#[tokio::test]
+async fn sealed_search_looks_past_the_first_batch() {
+    let db = TempDb::new();
+    let store = db.open(Some("passphrase")).unwrap();
+    for i in 0..1200 {
+        let content = if i % 100 == 0 { format!("Needle {}", i) } else { format!("hay {}", i) };
+        store.record(content, HistoryOrigin::Local, None).await.unwrap();
+    }
+
+    let page = store.search("needle".to_string(), None, 5, 0).await.unwrap();
+    assert_eq!(page.total, 12);
+    let found: Vec<_> = page.entries.iter().map(|entry| entry.content.as_str()).collect();
+    assert_eq!(found, ["Needle 1100", "Needle 1000", "Needle 900", "Needle 800", "Needle 700"]);
+
+    let last = store.search("needle".to_string(), None, 5, 10).await.unwrap();
+    let found: Vec<_> = last.entries.iter().map(|entry| entry.content.as_str()).collect();
+    assert_eq!(found, ["Needle 100", "Needle 0"]);
+    assert_eq!(last.total, 12);
+}
+
+#[tokio::test]
+async fn entries_are_listed_newest_first_with_their_origin() {
+    let db = TempDb::new();
+    let store = db.open(None).unwrap();
+    let local = store.record("copied here".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    let remote = store.record("copied there".to_string(), HistoryOrigin::Remote, Some("laptop".to_string())).await.unwrap();
+
+    let page = store.list(10, 0).await.unwrap();
+    assert_eq!(page.total, 2);
+    let ids: Vec<i64> = page.entries.iter().map(|entry| entry.id).collect();
+    assert_eq!(ids, [remote, local]);
+    assert_eq!((page.entries[0].origin, page.entries[0].device.as_deref()), (HistoryOrigin::Remote, Some("laptop")));
+    assert_eq!((page.entries[1].origin, page.entries[1].device.as_deref()), (HistoryOrigin::Local, None));
+}
+
+#[tokio::test]
+async fn pages_are_capped_and_offset() {
+    let db = TempDb::new();
+    let store = db.open(None).unwrap();
+    for i in 0..MAX_PAGE_SIZE + 5 {
+        store.record(format!("clip {}", i), HistoryOrigin::Local, None).await.unwrap();
+    }
+    let page = store.list(MAX_PAGE_SIZE * 2, 0).await.unwrap();
+    assert_eq!((page.entries.len() as u32, page.total), (MAX_PAGE_SIZE, u64::from(MAX_PAGE_SIZE + 5)));
+
+    let last = store.list(10, MAX_PAGE_SIZE).await.unwrap();
+    let found: Vec<_> = last.entries.iter().map(|entry| entry.content.as_str()).collect();
+    assert_eq!(found, ["clip 4", "clip 3", "clip 2", "clip 1", "clip 0"]);
+    assert!(store.list(10, MAX_PAGE_SIZE + 5).await.unwrap().entries.is_empty());
+}
+
+#[tokio::test]
+async fn deleted_entries_are_gone() {
+    let db = TempDb::new();
+    let store = db.open(None).unwrap();
+    let kept = store.record("kept".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    let deleted = store.record("deleted".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    assert!(store.delete(deleted).await.unwrap());
+    assert!(!store.delete(deleted).await.unwrap());
+    assert!(store.get(deleted).await.unwrap().is_none());
+    assert_eq!(store.get(kept).await.unwrap().unwrap().content, "kept");
+    assert_eq!(contents(&store).await, ["kept"]);
+}
+
+#[tokio::test]
+async fn clearing_removes_every_entry() {
+    let db = TempDb::new();
+    let store = db.open(Some("passphrase")).unwrap();
+    for text in ["one", "two"] {
+        store.record(text.to_string(), HistoryOrigin::Local, None).await.unwrap();
+    }
+    assert_eq!(store.clear().await.unwrap(), 2);
+    assert_eq!(store.list(10, 0).await.unwrap().total, 0);
+    assert_eq!(store.clear().await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn entries_outlive_the_store() {
+    let db = TempDb::new();
+    db.open(None).unwrap().record("persisted".to_string(), HistoryOrigin::Local, None).await.unwrap();
+    assert_eq!(contents(&db.open(None).unwrap()).await, ["persisted"]);
+}
+
+#[tokio::test]
+async fn plain_search_matches_wildcards_literally() {
+    let db = TempDb::new();
+    let store = db.open(None).unwrap();
+    for text in ["100% sure", "100 percent", "snake_case", "snakeXcase"] {
+        store.record(text.to_string(), HistoryOrigin::Remote, Some("laptop".to_string())).await.unwrap();
+    }
+    let found = |page: HistoryPage| page.entries.into_iter().map(|entry| entry.content).collect::<Vec<_>>();
+    assert_eq!(found(store.search("0%".to_string(), None, 10, 0).await.unwrap()), ["100% sure"]);
+    assert_eq!(found(store.search("E_C".to_string(), None, 10, 0).await.unwrap()), ["snake_case"]);
+    assert!(found(store.search("case".to_string(), Some("desktop".to_string()), 10, 0).await.unwrap()).is_empty());
+}
+
+#[test]
+fn a_file_that_is_not_a_database_fails_to_open() {
+    let db = TempDb::new();
+    std::fs::write(&db.0, "not a database, just some text that is long enough").unwrap();
+    assert!(db.open(None).is_err());
+}
@@ -0,0 +1,97 @@
+use base64::{Engine as _, engine::general_purpose};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use crate::utils::crypto;
+use super::key_derivation::KeySalt;
+
+const KEYCHAIN_SERVICE: &str = "uni-mesh-clip";
+const KEYCHAIN_ACCOUNT: &str = "history-key";
+/// Domain separator so the history key never equals the transport key,
+/// which is derived from the same passphrase and salt.
+const DERIVED_KEY_CONTEXT: &[u8] = b"unimesh-clip/history-at-rest/v2";
+/// Context of the unsalted key earlier builds derived.
+const LEGACY_KEY_CONTEXT: &[u8] = b"unimesh-clip/history-at-rest/v1";
+/// Known plaintext stored sealed, to tell whether a key opens the database.
+pub const KEY_CHECK: &str = "unimesh-clip history";
+
+/// Seals history content at rest with AES-256-GCM.
+#[derive(Clone)]
+pub struct HistoryCipher {
+    key: String,
+    /// Derived from the security key rather than kept in the keychain
+    derived: bool,
+}
+
+impl HistoryCipher {
+    pub fn seal(&self, plaintext: &str) -> Result<String> {
+        crypto::encrypt_content(&self.key, plaintext)
+    }
+
+    pub fn open(&self, sealed: &str) -> Result<String> {
+        crypto::decrypt_content(&self.key, sealed)
+    }
+
+    /// Whether this key comes from the security key, and so has to change
+    /// with it.
+    pub fn follows_security_key(&self) -> bool {
+        self.derived
+    }
+
+    /// Whether this key opens `check`, a sealed [`KEY_CHECK`].
+    pub fn opens(&self, check: &str) -> bool {
+        self.open(check).is_ok_and(|text| text == KEY_CHECK)
+    }
+}
+
+fn keychain_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)
+}
+
+/// The history key saved in the OS keychain, if there is one.
+///
+/// # Errors
+/// When the keychain cannot be reached
+pub fn keychain_key() -> Result<Option<HistoryCipher>> {
+    match keychain_entry()?.get_password() {
+        Ok(key) => Ok(Some(HistoryCipher { key, derived: false })),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Generate a history key and save it in the OS keychain.
+///
+/// # Errors
+/// When the keychain cannot be reached or refuses the write
+pub fn create_keychain_key() -> Result<HistoryCipher> {
+    let key = general_purpose::STANDARD.encode(random_key());
+    keychain_entry()?.set_password(&key)?;
+    tracing::info!("Created a history encryption key in the OS keychain");
+    Ok(HistoryCipher { key, derived: false })
+}
+
+fn random_key() -> [u8; 32] {
+    use aes_gcm::aead::{rand_core::RngCore, OsRng};
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// A history key derived from the shared security key with Argon2id and
+/// this installation's salt, for systems without a usable keychain.
+///
+/// # Errors
+/// When Argon2 cannot derive the key
+pub fn derived_key(security_key: &str, salt: &KeySalt) -> Result<HistoryCipher> {
+    let salt = [DERIVED_KEY_CONTEXT, salt.as_slice()].concat();
+    Ok(HistoryCipher { key: crypto::derive_key(security_key, &salt)?, derived: true })
+}
+
+/// The unsalted key earlier builds derived from the security key, only
+/// tried so their history can be sealed again under [`derived_key`].
+pub fn legacy_derived_key(security_key: &str) -> HistoryCipher {
+    let mut hasher = Sha256::new();
+    hasher.update(LEGACY_KEY_CONTEXT);
+    hasher.update(security_key.as_bytes());
+    HistoryCipher { key: general_purpose::STANDARD.encode(hasher.finalize()), derived: true }
+}
@@ -0,0 +1,102 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use serde::Serialize;
+use tauri::AppHandle;
+use tokio::time::Duration;
+use crate::models::{ConnectionDirection, PeerEndpoint};
+use crate::utils::net::{self, LocalInterface};
+use super::events::{self, NETWORK_INTERFACES_CHANGED_EVENT};
+use super::mdns::MdnsService;
+use super::websocket::WebSocketServer;
+
+use super::shutdown::Shutdown;
+/// How often the interface list is compared against the last one seen.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Payload of the `network-interfaces-changed` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterfacesChangedEvent {
+    pub interfaces: Vec<LocalInterface>,
+    pub lost: Vec<IpAddr>,
+    pub gained: Vec<IpAddr>,
+}
+
+/// Watch the machine's interfaces until `shutdown` fires. When an address
+/// goes away, connections using it are closed and moved to an interface
+/// that still reaches the peer, rather than waiting for heartbeats to fail.
+pub fn spawn(
+    app: Option<AppHandle>,
+    ws: Arc<WebSocketServer>,
+    mdns: Arc<MdnsService>,
+    shutdown: Shutdown,
+) {
+    shutdown.clone().spawn(async move {
+        let mut known = net::interfaces();
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = shutdown.cancelled() => break,
+            }
+            let current = net::interfaces();
+            let lost: Vec<IpAddr> = known.iter()
+                .map(|interface| interface.ip)
+                .filter(|ip| !current.iter().any(|interface| interface.ip == *ip))
+                .collect();
+            let gained: Vec<IpAddr> = current.iter()
+                .map(|interface| interface.ip)
+                .filter(|ip| !known.iter().any(|interface| interface.ip == *ip))
+                .collect();
+            if lost.is_empty() && gained.is_empty() {
+                continue;
+            }
+            tracing::info!("Network interfaces changed: lost {:?}, gained {:?}", lost, gained);
+            if !lost.is_empty() {
+                migrate(&ws, &mdns, &lost, &current).await;
+            }
+            if !gained.is_empty() {
+                mdns.health().network_changed();
+                // Peers that were out of reach may be reachable now
+                ws.dial_peers();
+            }
+            events::emit(app.as_ref(), NETWORK_INTERFACES_CHANGED_EVENT, InterfacesChangedEvent {
+                interfaces: current.clone(),
+                lost,
+                gained,
+            });
+            known = current;
+        }
+    });
+}
+
+/// Close the connections on lost addresses and reconnect each peer over
+/// whatever interface still reaches it. We redial peers we had dialed
+/// ourselves; for peers that had dialed us we dial their discovered address.
+async fn migrate(ws: &WebSocketServer, mdns: &MdnsService, lost: &[IpAddr], remaining: &[LocalInterface]) {
+    let closed = ws.close_sessions_via(lost).await;
+    if closed.is_empty() {
+        return;
+    }
+    let devices = mdns.get_discovered_devices().await;
+    for (addr, direction) in closed {
+        if direction == ConnectionDirection::Outbound {
+            continue;
+        }
+        let Some(device) = devices.iter().find(|device| {
+            device.addresses.iter().chain([&device.address]).any(|a| net::parse_ip(a) == Some(addr.ip()))
+        }) else {
+            tracing::debug!("No discovered device for {}, leaving it to reconnect", addr);
+            continue;
+        };
+        let host = device.addresses.iter()
+            .find(|a| net::parse_ip(a).is_some_and(|ip| remaining.iter().any(|interface| interface.is_on_link(ip))))
+            .unwrap_or(&device.address)
+            .clone();
+        tracing::info!("Moving {} from {} to {}", device.display_name, addr, host);
+        ws.migrate_to(PeerEndpoint {
+            host,
+            port: device.port,
+            fingerprint: device.tls_fingerprint.clone(),
+        }).await;
+    }
+    ws.dial_peers();
+}
@@ -0,0 +1,341 @@
+//! Wire keys derived from the typed passphrase with Argon2id. Each
+//! installation keeps its own random salt and derives the key it sends
+//! with from it. The salt travels in the handshake, where the other side
+//! pins it for the connection, and on each message. Each hop protects what
+//! it sends with its own key, so relayed clips are opened and protected
+//! again rather than carrying a salt the next peer never saw.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use base64::{engine::general_purpose, Engine as _};
+use tokio::sync::OnceCell;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue, StatusCode};
+use uuid::Uuid;
+use crate::models::{ClipboardMessage, Config};
+use crate::utils::crypto;
+use super::connection::ConnectionContext;
+use super::device_names;
+use super::key_rotation;
+
+/// Settings store key of this installation's salt.
+pub const KEY_SALT_STORE_KEY: &str = "key_salt";
+/// Handshake header with the sender's salt, base64.
+const KEY_SALT_HEADER: &str = "x-unimesh-key-salt";
+
+pub type KeySalt = [u8; 16];
+
+/// Keys derived so far, by passphrase and salt; each is filled once.
+type DerivedKeys = HashMap<(String, KeySalt), Arc<OnceCell<String>>>;
+
+/// A fresh salt for a new installation.
+pub fn new_salt() -> KeySalt {
+    *Uuid::new_v4().as_bytes()
+}
+
+/// Add our salt to a handshake request or response.
+pub fn advertise(headers: &mut HeaderMap, salt: &KeySalt) {
+    if let Ok(value) = HeaderValue::from_str(&general_purpose::STANDARD.encode(salt)) {
+        headers.insert(KEY_SALT_HEADER, value);
+    }
+}
+
+/// The other side's salt; `None` for builds that use the passphrase as is.
+pub fn offered(headers: &HeaderMap) -> Option<KeySalt> {
+    let value = headers.get(KEY_SALT_HEADER)?.to_str().ok()?;
+    decode(value.trim())
+}
+
+fn decode(salt: &str) -> Option<KeySalt> {
+    general_purpose::STANDARD.decode(salt).ok()?.try_into().ok()
+}
+
+/// Whether a peer that offers no salt may connect. Such a peer is sent
+/// messages keyed with the passphrase itself, which anyone who connects
+/// could brute-force from, so it takes `legacy_key_peers` when a key is set.
+pub fn admits_legacy(config: &Config) -> bool {
+    config.effective_security_key().is_none() || config.legacy_key_peers
+}
+
+/// Answer an incoming handshake: refuse peers without a salt unless
+/// `legacy` allows them.
+// `ErrorResponse` is the rejection type tungstenite's handshake callback expects
+#[allow(clippy::result_large_err)]
+pub fn check_handshake(offered: Option<KeySalt>, legacy: bool, response: Response) -> Result<Response, ErrorResponse> {
+    if offered.is_some() || legacy {
+        return Ok(response);
+    }
+    let mut refusal = ErrorResponse::new(Some("This device needs a newer version to sync with a security key".to_string()));
+    *refusal.status_mut() = StatusCode::UPGRADE_REQUIRED;
+    Err(refusal)
+}
+
+/// This installation's salt, the salt each connected peer offered, and
+/// the keys derived for them.
+pub struct KeyDerivation {
+    salt: KeySalt,
+    /// Salts pinned from handshakes; peers admitted without one are absent
+    peers: RwLock<HashMap<Uuid, KeySalt>>,
+    /// Keys by passphrase and salt, derived once each
+    derived: Mutex<DerivedKeys>,
+}
+
+impl Default for KeyDerivation {
+    /// Derivation with a salt for this run only, for hosts that keep none.
+    fn default() -> Self {
+        Self::new(new_salt())
+    }
+}
+
+impl KeyDerivation {
+    pub fn new(salt: KeySalt) -> Self {
+        Self { salt, peers: RwLock::default(), derived: Mutex::default() }
+    }
+
+    pub fn salt(&self) -> &KeySalt {
+        &self.salt
+    }
+
+    /// The key `passphrase` derives to with `salt`. Argon2 is slow on
+    /// purpose, so it runs on the blocking pool, once per pair; callers
+    /// only pass our salt or one pinned from a handshake.
+    async fn derive(&self, passphrase: &str, salt: KeySalt) -> String {
+        let cell = self.derived.lock().unwrap_or_else(|e| e.into_inner())
+            .entry((passphrase.to_string(), salt))
+            .or_default()
+            .clone();
+        let passphrase = passphrase.to_string();
+        cell.get_or_init(|| async move {
+            let derived = tokio::task::spawn_blocking(move || crypto::derive_key(&passphrase, &salt)).await;
+            match derived {
+                Ok(Ok(key)) => key,
+                // A key nobody holds, so messages fail their check
+                Ok(Err(e)) => {
+                    tracing::error!("{}", e);
+                    general_purpose::STANDARD.encode(new_salt())
+                }
+                Err(e) => {
+                    tracing::error!("Key derivation stopped: {}", e);
+                    general_purpose::STANDARD.encode(new_salt())
+                }
+            }
+        }).await.clone()
+    }
+
+    /// Key `passphrase` gives a message from `peer_id`: derived with the
+    /// salt pinned from its handshake, or the passphrase itself for a peer
+    /// admitted without one.
+    ///
+    /// # Errors
+    /// When the message names a salt the peer did not offer, or none when
+    /// the peer offered one
+    pub async fn key_for(&self, peer_id: Uuid, passphrase: &str, message: &ClipboardMessage) -> Result<String, &'static str> {
+        let pinned = self.peers.read().unwrap_or_else(|e| e.into_inner()).get(&peer_id).copied();
+        match (message.key_salt.as_deref(), pinned) {
+            (Some(salt), Some(pinned)) if decode(salt) == Some(pinned) => Ok(self.derive(passphrase, pinned).await),
+            (Some(_), _) => Err("key salt not offered in the handshake"),
+            (None, Some(_)) => Err("missing key salt"),
+            (None, None) => Ok(passphrase.to_string()),
+        }
+    }
+
+    /// Key `passphrase` gives one of our own messages: derived with our
+    /// salt, or the passphrase itself once rewritten for a legacy peer.
+    pub async fn sending_key(&self, passphrase: &str, message: &ClipboardMessage) -> String {
+        match message.key_salt {
+            Some(_) => self.derive(passphrase, self.salt).await,
+            None => passphrase.to_string(),
+        }
+    }
+
+    /// Protect a message of ours under `passphrase`: with our derived key,
+    /// or with the passphrase itself for a peer that predates derivation.
+    /// It is stamped with our device id first, so the id is signed too.
+    pub async fn protect(&self, message: &mut ClipboardMessage, passphrase: Option<&str>, derived: bool) -> anyhow::Result<()> {
+        message.sender_id = device_names::local_device_id();
+        let Some(passphrase) = passphrase else {
+            return message.protect(None);
+        };
+        if derived {
+            let key = self.derive(passphrase, self.salt).await;
+            message.key_salt = Some(general_purpose::STANDARD.encode(self.salt));
+            message.protect(Some(&key))
+        } else {
+            message.key_salt = None;
+            message.protect(Some(passphrase))
+        }
+    }
+
+    /// Pin the salt `peer_id` offered for the life of its connection.
+    pub fn open(&self, peer_id: Uuid, salt: KeySalt) {
+        self.peers.write().unwrap_or_else(|e| e.into_inner()).insert(peer_id, salt);
+    }
+
+    /// Unpin a closed connection's salt, and drop keys no connection uses.
+    pub fn close(&self, peer_id: Uuid) {
+        let mut peers = self.peers.write().unwrap_or_else(|e| e.into_inner());
+        peers.remove(&peer_id);
+        self.derived.lock().unwrap_or_else(|e| e.into_inner())
+            .retain(|(_, salt), _| *salt == self.salt || peers.values().any(|pinned| pinned == salt));
+    }
+
+    pub fn takes_derived(&self, peer_id: Uuid) -> bool {
+        self.peers.read().unwrap_or_else(|e| e.into_inner()).contains_key(&peer_id)
+    }
+
+    /// Whether a message of ours is under our derived key, which
+    /// `peer_id`, admitted without a salt, cannot read.
+    pub fn rewrites_for(&self, peer_id: Uuid, message: &ClipboardMessage) -> bool {
+        message.key_salt.is_some() && !self.takes_derived(peer_id)
+    }
+
+    /// Protect a message of ours, changed for `peer_id`, for that peer with
+    /// one signature: opened with our derived key and protected with the
+    /// passphrase itself when the peer was admitted without a salt, else
+    /// signed again with the key it was protected with. Messages that went
+    /// out unprotected stay so.
+    ///
+    /// # Errors
+    /// When the message does not open with our key
+    pub async fn for_peer(&self, peer_id: Uuid, message: &mut ClipboardMessage, passphrase: &str) -> anyhow::Result<()> {
+        if message.signature.is_none() {
+            return Ok(());
+        }
+        let key = self.sending_key(passphrase, message).await;
+        if self.rewrites_for(peer_id, message) {
+            return reprotect(message, &key, passphrase, None);
+        }
+        message.sign(&key);
+        Ok(())
+    }
+}
+
+/// Open a verified message with `from` and protect it again with `to`,
+/// naming `salt` as the new key's.
+fn reprotect(message: &mut ClipboardMessage, from: &str, to: &str, salt: Option<String>) -> anyhow::Result<()> {
+    let (sealed, signed) = (message.encrypted, message.signature.is_some());
+    message.open(from)?;
+    message.key_salt = salt;
+    if sealed {
+        message.seal(to)?;
+    } else if signed {
+        message.sign(to);
+    }
+    Ok(())
+}
+
+/// A peer's message as we pass it on: protected with our own key rather
+/// than the sender's, whose salt the next peer never saw. `None` when it
+/// cannot be opened, and so is not relayed.
+pub(crate) async fn relay(ctx: &ConnectionContext, origin: Option<Uuid>, text: &str) -> Option<String> {
+    let (Some(origin), Some(passphrase)) = (origin, ctx.config.read().await.effective_security_key().map(str::to_string)) else {
+        return Some(text.to_string());
+    };
+    let Ok(mut message) = serde_json::from_str::<ClipboardMessage>(text) else {
+        return Some(text.to_string());
+    };
+    let own = general_purpose::STANDARD.encode(ctx.security.keys.salt);
+    if message.key_salt.as_deref() == Some(own.as_str()) {
+        return Some(text.to_string());
+    }
+    let from = key_rotation::verifying_key(&ctx.security, origin, Some(passphrase.clone()), &message).await.ok()??;
+    let to = ctx.security.keys.derive(&passphrase, ctx.security.keys.salt).await;
+    if let Err(e) = reprotect(&mut message, &from, &to, Some(own)) {
+        tracing::debug!("Not relaying message {}: {}", message.id, e);
+        return None;
+    }
+    serde_json::to_string(&message).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ClipboardContent, MessageType};
+    use unimesh_proto::SIGNING_FORMAT_V2;
+
+    const PASSPHRASE: &str = "shared passphrase for tests";
+    const OUR_SALT: KeySalt = [1; 16];
+    const PEER_SALT: KeySalt = [2; 16];
+
+    /// A clip of ours, sealed under our derived key as broadcasts are.
+    async fn our_clip(keys: &KeyDerivation) -> ClipboardMessage {
+        let mut message = ClipboardMessage::clipboard_update(ClipboardContent::plain("hello".to_string())).unwrap();
+        keys.protect(&mut message, Some(PASSPHRASE), true).await.unwrap();
+        message
+    }
+
+    fn verifies(key: &str, message: &ClipboardMessage) -> bool {
+        crypto::verify_signature(key, &message.signing_payload(), message.signature.as_deref().unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn salted_peers_get_our_clips_signed_with_our_derived_key() {
+        let keys = KeyDerivation::new(OUR_SALT);
+        let peer = Uuid::new_v4();
+        keys.open(peer, PEER_SALT);
+        let mut message = our_clip(&keys).await;
+        message.session = Some(Uuid::new_v4());
+        assert!(!keys.rewrites_for(peer, &message));
+
+        keys.for_peer(peer, &mut message, PASSPHRASE).await.unwrap();
+        assert_eq!(message.key_salt, Some(general_purpose::STANDARD.encode(OUR_SALT)));
+        assert!(message.signing_payload().contains(SIGNING_FORMAT_V2));
+        assert!(verifies(&keys.derive(PASSPHRASE, OUR_SALT).await, &message));
+        assert!(!verifies(PASSPHRASE, &message));
+    }
+
+    #[tokio::test]
+    async fn legacy_peers_get_our_clips_under_the_passphrase_in_format_1() {
+        let keys = KeyDerivation::new(OUR_SALT);
+        let peer = Uuid::new_v4();
+        let mut message = our_clip(&keys).await;
+        assert!(keys.rewrites_for(peer, &message));
+
+        keys.for_peer(peer, &mut message, PASSPHRASE).await.unwrap();
+        assert_eq!(message.key_salt, None);
+        assert!(!message.signing_payload().contains(SIGNING_FORMAT_V2));
+        assert!(verifies(PASSPHRASE, &message));
+        message.open(PASSPHRASE).unwrap();
+        assert_eq!(message.content.as_deref(), Some("hello"));
+    }
+
+    #[tokio::test]
+    async fn clips_under_another_salt_are_not_rewritten() {
+        let keys = KeyDerivation::new(OUR_SALT);
+        let mut message = our_clip(&KeyDerivation::new(PEER_SALT)).await;
+        let before = message.clone();
+        assert!(keys.for_peer(Uuid::new_v4(), &mut message, PASSPHRASE).await.is_err());
+        assert_eq!(message.content, before.content);
+    }
+
+    #[tokio::test]
+    async fn a_salt_the_peer_did_not_offer_is_refused() {
+        let keys = KeyDerivation::new(OUR_SALT);
+        let peer = Uuid::new_v4();
+        keys.open(peer, PEER_SALT);
+        let mut message = ClipboardMessage::new(MessageType::Heartbeat, None);
+
+        message.key_salt = Some(general_purpose::STANDARD.encode([3u8; 16]));
+        assert_eq!(keys.key_for(peer, PASSPHRASE, &message).await, Err("key salt not offered in the handshake"));
+        message.key_salt = None;
+        assert_eq!(keys.key_for(peer, PASSPHRASE, &message).await, Err("missing key salt"));
+        message.key_salt = Some(general_purpose::STANDARD.encode(PEER_SALT));
+        assert_eq!(keys.key_for(peer, PASSPHRASE, &message).await, Ok(keys.derive(PASSPHRASE, PEER_SALT).await));
+        // A peer admitted without a salt keys with the passphrase itself
+        message.key_salt = None;
+        assert_eq!(keys.key_for(Uuid::new_v4(), PASSPHRASE, &message).await, Ok(PASSPHRASE.to_string()));
+    }
+
+    #[tokio::test]
+    async fn our_messages_are_signed_with_our_device_id() {
+        let device_id = Uuid::new_v4();
+        device_names::set_local_device_id(device_id);
+        let mut message = ClipboardMessage::new(MessageType::Heartbeat, None);
+        KeyDerivation::new([1; 16]).protect(&mut message, Some("passphrase"), true).await.unwrap();
+        assert_eq!(message.sender_id, Some(device_id));
+        // Covered by the signature, so it cannot be swapped in transit
+        let signed = message.clone();
+        message.sender_id = Some(Uuid::new_v4());
+        assert_ne!(message.signing_payload(), signed.signing_payload());
+    }
+}
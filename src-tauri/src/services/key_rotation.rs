@@ -0,0 +1,254 @@
+//! Changing the security key without cutting off peers that still hold
+//! the old one: the old key is kept for a grace window, and peers are told
+//! about the change so they can ask their user for the new key.
+
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::models::{ClipOrigin, ClipboardMessage, ContentKind, KeyRotationNotice, MessageType, Protection};
+use crate::utils::crypto;
+use super::connection::{ConnectionContext, SecurityContext};
+use super::events::{self, KEY_ROTATED_EVENT};
+
+pub const RETIRED_KEY_STORE_KEY: &str = "retired_security_key";
+
+/// The key in use before the last change, and until when it is accepted.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetiredKey {
+    key: String,
+    rotated_at: DateTime<Utc>,
+    grace_until: DateTime<Utc>,
+}
+
+/// The previous security key, kept while its grace window lasts.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct KeyRing {
+    retired: Option<RetiredKey>,
+}
+
+pub type SharedKeyRing = Arc<RwLock<KeyRing>>;
+
+impl KeyRing {
+    /// Keep `key` for `grace_hours` after a change away from it. A grace of
+    /// zero forgets it at once.
+    pub fn retire(&mut self, key: &str, grace_hours: u32) -> Option<&RetiredKey> {
+        let now = Utc::now();
+        self.retired = (grace_hours > 0).then(|| RetiredKey {
+            key: key.to_string(),
+            rotated_at: now,
+            grace_until: now + Duration::hours(i64::from(grace_hours)),
+        });
+        self.retired.as_ref()
+    }
+
+    /// Drop the previous key, e.g. when there is no key any more.
+    pub fn clear(&mut self) {
+        self.retired = None;
+    }
+
+    /// Drop the previous key once its grace window is over. Returns
+    /// whether there was one to drop.
+    pub fn expire(&mut self) -> bool {
+        let expired = self.retired.as_ref().is_some_and(|retired| retired.grace_until <= Utc::now());
+        if expired {
+            self.retired = None;
+        }
+        expired
+    }
+
+    /// The previous key, while its grace window lasts.
+    fn in_grace(&self) -> Option<&RetiredKey> {
+        self.retired.as_ref().filter(|retired| retired.grace_until > Utc::now())
+    }
+}
+
+/// Key to check `message` from `peer_id` against: the one the current
+/// passphrase gives, or the previous passphrase's when that is what signed
+/// it and its grace window lasts. Anything else gets the current key and
+/// fails the check as before; `None` when there is no key.
+///
+/// # Errors
+/// When the message's key salt is not the one the peer offered
+pub(crate) async fn verifying_key(security: &SecurityContext, peer_id: Uuid, current: Option<String>, message: &ClipboardMessage) -> Result<Option<String>, &'static str> {
+    let Some(current) = current else {
+        return Ok(None);
+    };
+    let current = security.keys.key_for(peer_id, &current, message).await?;
+    let Some(signature) = message.signature.as_deref() else {
+        return Ok(Some(current));
+    };
+    let payload = message.signing_payload();
+    if crypto::verify_signature(&current, &payload, signature) {
+        return Ok(Some(current));
+    }
+    let retired = {
+        let mut ring = security.key_ring.write().await;
+        if ring.expire() {
+            tracing::info!("Grace window of the previous security key is over; no longer accepting it");
+        }
+        ring.in_grace().map(|retired| retired.key.clone())
+    };
+    let Some(retired) = retired else {
+        return Ok(Some(current));
+    };
+    let retired = security.keys.key_for(peer_id, &retired, message).await?;
+    if crypto::verify_signature(&retired, &payload, signature) {
+        tracing::debug!("Message {} is signed with the previous security key", message.id);
+        return Ok(Some(retired));
+    }
+    Ok(Some(current))
+}
+
+/// Tell `peer_id` the key changed, signed with the previous key so a peer
+/// still holding it can read the notice. Nothing is sent outside the grace
+/// window.
+pub(crate) async fn announce_to(ctx: &ConnectionContext, peer_id: Uuid) {
+    let Some(retired) = ctx.security.key_ring.read().await.in_grace().cloned() else {
+        return;
+    };
+    let notice = KeyRotationNotice { rotated_at: retired.rotated_at, grace_until: retired.grace_until };
+    let Ok(content) = serde_json::to_string(&notice) else {
+        return;
+    };
+    let message = ClipboardMessage::new(MessageType::KeyRotation, Some(content)).with_protection(Protection::Signed);
+    if let Err(e) = ctx.send_to_peer_with_key(peer_id, message, Some(&retired.key)).await {
+        tracing::debug!("Failed to send key rotation notice to {}: {}", peer_id, e);
+    }
+}
+
+/// Tell every connected peer the key changed.
+pub(crate) async fn announce(ctx: &ConnectionContext) {
+    let peer_ids: Vec<Uuid> = ctx.peers.read().await.keys().copied().collect();
+    for peer_id in peer_ids {
+        announce_to(ctx, peer_id).await;
+    }
+}
+
+/// Payload of the `key-rotated` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyRotatedEvent {
+    pub device: String,
+    pub address: String,
+    pub rotated_at: DateTime<Utc>,
+    pub grace_until: DateTime<Utc>,
+}
+
+/// A peer changed its key: ask the user to enter the new one here before
+/// the peer stops accepting ours. Notices signed with our previous key come
+/// from a change we already made, and unsigned ones could come from anyone.
+pub(crate) async fn handle(ctx: &ConnectionContext, peer_id: Uuid, message: &ClipboardMessage, content: Option<&str>) {
+    let Some(notice) = content.and_then(|content| serde_json::from_str::<KeyRotationNotice>(content).ok()) else {
+        tracing::warn!("Malformed key rotation notice from {}", peer_id);
+        return;
+    };
+    let Some(current) = ctx.config.read().await.effective_security_key().map(str::to_string) else {
+        tracing::warn!("Ignoring unsigned key rotation notice from {}", peer_id);
+        return;
+    };
+    let Ok(current) = ctx.security.keys.key_for(peer_id, &current, message).await else {
+        tracing::warn!("Ignoring key rotation notice from {} under a key salt it did not offer", peer_id);
+        return;
+    };
+    let signed_with_current = message.signature.as_deref()
+        .is_some_and(|signature| crypto::verify_signature(&current, &message.signing_payload(), signature));
+    if !signed_with_current {
+        tracing::debug!("Key rotation notice from {} is for a key we already replaced", peer_id);
+        return;
+    }
+    let Some(address) = ctx.peers.read().await.get(&peer_id).map(|(addr, _)| *addr) else {
+        return;
+    };
+    let origin = ClipOrigin { peer_id, address, device: None, message_id: None, sent_at: None, received_at: None, kind: ContentKind::Text };
+    let device = ctx.directory.name_for(&origin);
+    tracing::warn!("{} changed its security key; it accepts the old one until {}", device, notice.grace_until);
+    events::emit(ctx.app.as_ref(), KEY_ROTATED_EVENT, KeyRotatedEvent {
+        device,
+        address: address.ip().to_string(),
+        rotated_at: notice.rotated_at,
+        grace_until: notice.grace_until,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::broadcast;
+    use super::*;
+    use crate::services::key_derivation::KeyDerivation;
+
+    const OLD: &str = "old passphrase";
+    const NEW: &str = "new passphrase";
+
+    fn security() -> SecurityContext {
+        SecurityContext {
+            trust: Arc::default(),
+            api_tokens: Arc::default(),
+            api_revoked: broadcast::channel(1).0,
+            sessions: Arc::default(),
+            key_ring: Arc::default(),
+            keys: Arc::new(KeyDerivation::new([4; 16])),
+        }
+    }
+
+    /// A message from a peer admitted without a salt, signed with `key`.
+    fn signed_with(key: &str) -> ClipboardMessage {
+        let mut message = ClipboardMessage::new(MessageType::Heartbeat, None);
+        message.sign(key);
+        message
+    }
+
+    async fn key_for(security: &SecurityContext, message: &ClipboardMessage) -> Option<String> {
+        verifying_key(security, Uuid::new_v4(), Some(NEW.to_string()), message).await.unwrap()
+    }
+
+    /// Move the end of the grace window to `hours` from now.
+    async fn grace_ends_in(security: &SecurityContext, hours: i64) {
+        let mut ring = security.key_ring.write().await;
+        ring.retired.as_mut().unwrap().grace_until = Utc::now() + Duration::hours(hours);
+    }
+
+    #[tokio::test]
+    async fn the_previous_key_is_accepted_inside_the_grace_window() {
+        let security = security();
+        security.key_ring.write().await.retire(OLD, 24);
+        assert_eq!(key_for(&security, &signed_with(OLD)).await.as_deref(), Some(OLD));
+        assert_eq!(key_for(&security, &signed_with(NEW)).await.as_deref(), Some(NEW));
+    }
+
+    #[tokio::test]
+    async fn the_previous_key_is_refused_and_forgotten_after_the_window() {
+        let security = security();
+        security.key_ring.write().await.retire(OLD, 24);
+        grace_ends_in(&security, -1).await;
+        // The current key, which the message then fails
+        assert_eq!(key_for(&security, &signed_with(OLD)).await.as_deref(), Some(NEW));
+        assert!(security.key_ring.read().await.retired.is_none());
+    }
+
+    #[tokio::test]
+    async fn no_grace_forgets_the_previous_key_at_once() {
+        let security = security();
+        assert!(security.key_ring.write().await.retire(OLD, 0).is_none());
+        assert_eq!(key_for(&security, &signed_with(OLD)).await.as_deref(), Some(NEW));
+    }
+
+    #[tokio::test]
+    async fn no_key_means_nothing_to_check_against() {
+        let security = security();
+        security.key_ring.write().await.retire(OLD, 24);
+        assert_eq!(verifying_key(&security, Uuid::new_v4(), None, &signed_with(OLD)).await, Ok(None));
+    }
+
+    #[test]
+    fn an_expired_key_is_cleared_from_the_stored_ring() {
+        let mut ring = KeyRing::default();
+        ring.retire(OLD, 24);
+        assert!(!ring.expire());
+        ring.retired.as_mut().unwrap().grace_until = Utc::now() - Duration::seconds(1);
+        assert!(ring.expire());
+        let stored = serde_json::to_value(&ring).unwrap();
+        assert_eq!(stored, serde_json::json!({ "retired": null }));
+        assert!(!stored.to_string().contains(OLD));
+    }
+}
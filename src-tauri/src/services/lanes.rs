@@ -0,0 +1,150 @@
+use futures_util::{Sink, SinkExt};
+use serde::Deserialize;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+use crate::models::MessageType;
+use super::connection::ConnectionContext;
+use super::framing::Framing;
+use super::pacing::Pacer;
+use super::shutdown::Shutdown;
+
+/// Data-lane messages a connection buffers before it stops reading.
+pub const DATA_QUEUE_DEPTH: usize = 32;
+
+/// Logical stream a message travels on. Both lanes share one connection and
+/// are told apart by the message `type` tag; each side serves control before
+/// data, so pairing, heartbeats, acks, and device info never wait behind a
+/// large clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    Control,
+    Data,
+}
+
+#[derive(Deserialize)]
+struct Envelope {
+    #[serde(rename = "type")]
+    msg_type: MessageType,
+}
+
+impl Lane {
+    pub fn of(msg_type: &MessageType) -> Self {
+        match msg_type {
+            MessageType::ClipboardUpdate | MessageType::ClipboardDelta | MessageType::LogResponse | MessageType::Unknown => Lane::Data,
+            MessageType::Heartbeat
+            | MessageType::DeviceInfo
+            | MessageType::LogRequest
+            | MessageType::SyncState
+            | MessageType::DeviceRemoved
+            | MessageType::Receipt
+            | MessageType::DeltaAck
+            | MessageType::MuteRequest
+            | MessageType::Hello
+            | MessageType::KeyRotation => Lane::Control,
+        }
+    }
+
+    /// Lane of a serialized message, read from its tag without decoding the
+    /// payload. Anything unrecognised rides the data lane.
+    pub fn of_text(text: &str) -> Self {
+        serde_json::from_str::<Envelope>(text).map_or(Lane::Data, |envelope| Lane::of(&envelope.msg_type))
+    }
+}
+
+/// Outgoing queues of one peer connection, one per lane.
+#[derive(Debug, Clone)]
+pub(crate) struct PeerLink {
+    control: UnboundedSender<Message>,
+    data: UnboundedSender<Message>,
+}
+
+impl PeerLink {
+    /// A link plus the control and data receivers its writer drains.
+    pub fn new() -> (Self, UnboundedReceiver<Message>, UnboundedReceiver<Message>) {
+        let (control, control_rx) = mpsc::unbounded_channel();
+        let (data, data_rx) = mpsc::unbounded_channel();
+        (Self { control, data }, control_rx, data_rx)
+    }
+
+    /// Queue a frame on `lane`. Fails once the connection is closing.
+    pub fn send(&self, lane: Lane, message: Message) -> Result<(), Message> {
+        let queue = match lane {
+            Lane::Control => &self.control,
+            Lane::Data => &self.data,
+        };
+        queue.send(message).map_err(|e| e.0)
+    }
+}
+
+/// Write queued frames to the peer: control first, then data, then chunk
+/// frames of large messages, so small clips never wait behind a stream.
+/// Data and chunk frames are paced to the peer's bandwidth cap; control
+/// frames never are. Control and data frames are written in `framing`;
+/// chunk frames stay JSON text, as they are recognised by their prefix.
+/// The writer stops with `shutdown`, after close frames were sent.
+pub(crate) fn spawn_writer<S>(
+    mut sink: S,
+    mut control_rx: UnboundedReceiver<Message>,
+    mut data_rx: UnboundedReceiver<Message>,
+    mut bulk_rx: UnboundedReceiver<Message>,
+    mut pacer: Pacer,
+    framing: Framing,
+    shutdown: &Shutdown,
+) where
+    S: Sink<Message> + Unpin + Send + 'static,
+{
+    let stop = shutdown.clone();
+    shutdown.spawn(async move {
+        loop {
+            let (msg, paced) = tokio::select! {
+                biased;
+                _ = stop.cancelled() => break,
+                Some(msg) = control_rx.recv() => (framing.encode(msg), false),
+                Some(msg) = data_rx.recv() => (framing.encode(msg), true),
+                Some(msg) = bulk_rx.recv() => (msg, true),
+                else => break,
+            };
+            if paced {
+                pacer.pace(msg.len()).await;
+            }
+            if sink.send(msg).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Handle a peer's data-lane messages in order on their own task, so the
+/// reader stays free for control messages while a clip is being applied.
+/// The task ends when the returned sender is dropped or the server stops.
+pub(crate) fn spawn_data_worker(ctx: ConnectionContext, peer_id: Uuid) -> mpsc::Sender<String> {
+    let (tx, mut rx) = mpsc::channel::<String>(DATA_QUEUE_DEPTH);
+    let shutdown = ctx.shutdown.clone();
+    shutdown.clone().spawn(async move {
+        loop {
+            let text = tokio::select! {
+                text = rx.recv() => text,
+                _ = shutdown.cancelled() => break,
+            };
+            let Some(text) = text else {
+                break;
+            };
+            ctx.handle_text(peer_id, &text).await;
+        }
+    });
+    tx
+}
+
+/// Route one complete message from a peer to its lane.
+pub(crate) async fn dispatch(ctx: &ConnectionContext, peer_id: Uuid, data: &mpsc::Sender<String>, text: &str) {
+    match Lane::of_text(text) {
+        Lane::Control => ctx.handle_text(peer_id, text).await,
+        Lane::Data => {
+            // Waiting here only when the peer outpaces us, which pushes back on its sends
+            if data.send(text.to_string()).await.is_err() {
+                tracing::debug!("Data worker for {} has stopped", peer_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+use std::net::SocketAddr;
+use anyhow::{bail, Result};
+use whatlang::{Lang, Script};
+use crate::models::{ClipboardMessage, LanguageRoute, MessageType};
+use super::connection::ConnectionContext;
+
+/// Language guesses below this confidence are dropped and only the
+/// script is used, which is reliable even for short text.
+const MIN_CONFIDENCE: f64 = 0.8;
+/// Leading characters examined; more rarely changes the answer.
+const SAMPLE_CHARS: usize = 2048;
+/// Matches Chinese, Japanese, and Korean text.
+const CJK: &str = "cjk";
+
+/// The probable script and, when confident, language of a clip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detected {
+    pub script: Script,
+    pub lang: Option<Lang>,
+}
+
+/// Guess the script and language of `text`.
+pub fn detect(text: &str) -> Option<Detected> {
+    let sample = match text.char_indices().nth(SAMPLE_CHARS) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    };
+    let info = whatlang::detect(sample)?;
+    Some(Detected {
+        script: info.script(),
+        lang: (info.confidence() >= MIN_CONFIDENCE).then(|| info.lang()),
+    })
+}
+
+fn is_cjk(script: Script) -> bool {
+    matches!(script, Script::Mandarin | Script::Hiragana | Script::Katakana | Script::Hangul)
+}
+
+/// Whether a route's `language` names anything the detector can report.
+fn is_known(language: &str) -> bool {
+    language.eq_ignore_ascii_case(CJK)
+        || Lang::all().iter().any(|lang| lang.code().eq_ignore_ascii_case(language) || lang.eng_name().eq_ignore_ascii_case(language))
+        || Script::all().iter().any(|script| script.name().eq_ignore_ascii_case(language))
+}
+
+fn matches(language: &str, detected: Detected) -> bool {
+    if language.eq_ignore_ascii_case(CJK) {
+        return is_cjk(detected.script);
+    }
+    if detected.script.name().eq_ignore_ascii_case(language) {
+        return true;
+    }
+    detected.lang.is_some_and(|lang| lang.code().eq_ignore_ascii_case(language) || lang.eng_name().eq_ignore_ascii_case(language))
+}
+
+/// Check that every route names a known language or script.
+///
+/// # Errors
+/// Names the first route the detector could never match
+pub fn validate(routes: &[LanguageRoute]) -> Result<()> {
+    for route in routes {
+        if !is_known(route.language.trim()) {
+            bail!("Unknown language or script in language route: {:?}", route.language);
+        }
+    }
+    Ok(())
+}
+
+/// The language of a local clip about to be broadcast, when routes are
+/// configured and the clip is text.
+pub fn detect_message(routes: &[LanguageRoute], message: &ClipboardMessage) -> Option<Detected> {
+    if routes.is_empty() || !matches!(message.msg_type, MessageType::ClipboardUpdate) {
+        return None;
+    }
+    detect(message.content.as_deref()?)
+}
+
+/// Whether a local clip in `language` may go to the peer at `address`:
+/// the first route matching the language decides, and clips no route
+/// matches go everywhere.
+pub(crate) async fn allows(ctx: &ConnectionContext, language: Option<Detected>, address: SocketAddr) -> bool {
+    let Some(detected) = language else {
+        return true;
+    };
+    let config = ctx.config.read().await;
+    let Some(route) = config.language_routes.iter().find(|route| matches(route.language.trim(), detected)) else {
+        return true;
+    };
+    let ip = address.ip().to_string();
+    let name = ctx.directory.name_of(address.ip());
+    route.devices.iter().map(|device| device.trim()).any(|device| {
+        device == ip || name.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(device))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use tokio::sync::RwLock;
+    use crate::models::{ClipboardContent, Config};
+    use crate::services::websocket::WebSocketServer;
+    use super::*;
+
+    fn route(language: &str, devices: &[&str]) -> LanguageRoute {
+        LanguageRoute { language: language.to_string(), devices: devices.iter().map(|device| device.to_string()).collect() }
+    }
+
+    fn context(routes: Vec<LanguageRoute>) -> ConnectionContext {
+        let config = Config { language_routes: routes, ..Config::default() };
+        WebSocketServer::new(0, Arc::new(RwLock::new(config)), Arc::default(), Arc::default(), Arc::default(), Arc::default()).context()
+    }
+
+    const JAPANESE: &str = "今日はとても良い天気ですね。散歩に行きましょう。";
+    const RUSSIAN: &str = "Сегодня очень хорошая погода, давайте пойдём гулять в парк.";
+
+    #[test]
+    fn scripts_and_confident_languages_are_detected() {
+        assert!(is_cjk(detect(JAPANESE).unwrap().script));
+        assert_eq!(detect(RUSSIAN).unwrap().script, Script::Cyrillic);
+        let english = detect("The quick brown fox jumps over the lazy dog while the farmer watches from the porch.").unwrap();
+        assert_eq!((english.script, english.lang), (Script::Latin, Some(Lang::Eng)));
+        assert_eq!(detect(""), None);
+    }
+
+    #[test]
+    fn routes_match_by_code_name_script_or_cjk() {
+        let detected = Detected { script: Script::Cyrillic, lang: Some(Lang::Rus) };
+        for language in ["rus", "Russian", "cyrillic"] {
+            assert!(matches(language, detected), "{}", language);
+        }
+        assert!(!matches("cjk", detected));
+        assert!(!matches("ukr", detected));
+        assert!(matches("CJK", Detected { script: Script::Hangul, lang: None }));
+        // Without a confident language only the script can match
+        assert!(!matches("rus", Detected { lang: None, ..detected }));
+    }
+
+    #[test]
+    fn routes_must_name_something_the_detector_reports() {
+        assert!(validate(&[route(" jpn ", &[]), route("Cyrillic", &[]), route("cjk", &[])]).is_ok());
+        let error = validate(&[route("klingon", &[])]).unwrap_err().to_string();
+        assert!(error.contains("klingon"), "{}", error);
+    }
+
+    #[test]
+    fn only_local_text_clips_are_detected_and_only_with_routes() {
+        let routes = [route("cjk", &[])];
+        let clip = ClipboardMessage::clipboard_update(ClipboardContent::plain(JAPANESE.to_string())).unwrap();
+        assert!(detect_message(&routes, &clip).is_some());
+        assert_eq!(detect_message(&[], &clip), None);
+        let ack = ClipboardMessage::new(MessageType::DeltaAck, Some(JAPANESE.to_string()));
+        assert_eq!(detect_message(&routes, &ack), None);
+    }
+
+    #[tokio::test]
+    async fn the_first_matching_route_picks_the_devices() {
+        let ctx = context(vec![route("cjk", &["laptop", "10.0.0.9"]), route("Cyrillic", &[]), route("cyrillic", &["desktop"])]);
+        ctx.directory.record("10.0.0.5".parse().unwrap(), "Laptop".to_string());
+        let (laptop, listed, other) = ("10.0.0.5:7878".parse().unwrap(), "10.0.0.9:7878".parse().unwrap(), "10.0.0.7:7878".parse().unwrap());
+
+        let japanese = detect(JAPANESE);
+        assert!(allows(&ctx, japanese, laptop).await);
+        assert!(allows(&ctx, japanese, listed).await);
+        assert!(!allows(&ctx, japanese, other).await);
+        // The first Cyrillic route keeps those clips here
+        assert!(!allows(&ctx, detect(RUSSIAN), other).await);
+    }
+
+    #[tokio::test]
+    async fn clips_no_route_matches_go_everywhere() {
+        let ctx = context(vec![route("cjk", &[])]);
+        let address = "10.0.0.7:7878".parse().unwrap();
+        assert!(allows(&ctx, detect(RUSSIAN), address).await);
+        assert!(allows(&ctx, None, address).await);
+    }
+}
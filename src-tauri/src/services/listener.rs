@@ -0,0 +1,47 @@
+use std::net::{Ipv6Addr, SocketAddr};
+use anyhow::Result;
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::TcpListener;
+use crate::utils::port_probe::BindError;
+
+/// Listen on `[::]:port` for both IPv6 and IPv4 clients. The socket is
+/// made dual-stack explicitly, since Windows defaults to IPv6 only.
+fn bind_dual_stack(port: u16) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_only_v6(false)?;
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Bind the sync port on all interfaces, falling back to IPv4 only where
+/// IPv6 is unavailable.
+///
+/// # Returns
+/// The listener and the address it listens on, for logging
+///
+/// # Errors
+/// A `BindError` naming what holds the port, when it cannot be bound
+pub async fn bind(port: u16) -> Result<(TcpListener, String)> {
+    match bind_dual_stack(port) {
+        Ok(listener) => Ok((listener, format!("[::]:{}", port))),
+        Err(e) if matches!(e.kind(), std::io::ErrorKind::AddrInUse | std::io::ErrorKind::PermissionDenied) => {
+            tracing::error!("Failed to listen on [::]:{}: {}", port, e);
+            Err(BindError::probe(port, &e).into())
+        }
+        Err(e) => {
+            tracing::info!("IPv6 unavailable ({}), listening on IPv4 only", e);
+            let addr = format!("0.0.0.0:{}", port);
+            match TcpListener::bind(&addr).await {
+                Ok(listener) => Ok((listener, addr)),
+                Err(e) => {
+                    tracing::error!("Failed to listen on {}: {}", addr, e);
+                    Err(BindError::probe(port, &e).into())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+use crate::models::DeviceLiveness;
+
+/// How long a disconnected device stays fresh after its last sign of life.
+pub const STALE_AFTER: Duration = Duration::from_secs(30);
+/// How long a disconnected device stays listed after its last sign of life.
+pub const OFFLINE_AFTER: Duration = Duration::from_secs(60);
+
+/// Everything we know about one address being alive.
+#[derive(Debug, Default, Clone, Copy)]
+struct Signals {
+    /// Last mDNS announcement; cleared when the device withdraws it
+    advertised: Option<Instant>,
+    /// Open sessions. Each session drops itself after missed heartbeats,
+    /// so an open one means the device is answering.
+    connections: u32,
+    /// Last heartbeat answered, or when the last session closed
+    heard: Option<Instant>,
+}
+
+impl Signals {
+    fn last_seen(&self) -> Option<Instant> {
+        self.advertised.max(self.heard)
+    }
+
+    fn state(&self) -> DeviceLiveness {
+        if self.connections > 0 {
+            return DeviceLiveness::Connected;
+        }
+        match self.last_seen().map(|at| at.elapsed()) {
+            Some(age) if age < STALE_AFTER => DeviceLiveness::Online,
+            Some(age) if age < OFFLINE_AFTER => DeviceLiveness::Stale,
+            _ => DeviceLiveness::Offline,
+        }
+    }
+}
+
+/// Liveness of LAN devices by address, combining mDNS announcements,
+/// connection state, and heartbeats so discovery and sessions agree on
+/// when a device is gone.
+#[derive(Debug, Default)]
+pub struct LivenessRegistry {
+    signals: RwLock<HashMap<IpAddr, Signals>>,
+}
+
+impl LivenessRegistry {
+    fn update(&self, ips: &[IpAddr], change: impl Fn(&mut Signals)) {
+        let mut signals = self.signals.write().unwrap_or_else(|e| e.into_inner());
+        for ip in ips {
+            change(signals.entry(*ip).or_default());
+        }
+    }
+
+    /// The device announced itself on these addresses.
+    pub fn advertised(&self, ips: &[IpAddr]) {
+        self.update(ips, |signals| signals.advertised = Some(Instant::now()));
+    }
+
+    /// The device said goodbye over mDNS.
+    pub fn withdrawn(&self, ips: &[IpAddr]) {
+        self.update(ips, |signals| signals.advertised = None);
+    }
+
+    pub fn connected(&self, ip: IpAddr) {
+        self.update(&[ip], |signals| {
+            signals.connections += 1;
+            signals.heard = Some(Instant::now());
+        });
+    }
+
+    pub fn disconnected(&self, ip: IpAddr) {
+        self.update(&[ip], |signals| {
+            signals.connections = signals.connections.saturating_sub(1);
+            signals.heard = Some(Instant::now());
+        });
+    }
+
+    /// A connected device answered a heartbeat.
+    pub fn heard(&self, ip: IpAddr) {
+        self.update(&[ip], |signals| signals.heard = Some(Instant::now()));
+    }
+
+    /// When the device on `ip` last announced itself over mDNS.
+    pub fn last_advertised(&self, ip: IpAddr) -> Option<Instant> {
+        self.signals.read().unwrap_or_else(|e| e.into_inner()).get(&ip)?.advertised
+    }
+
+    /// State of a device reachable on any of `ips`, with how long ago it
+    /// last showed a sign of life.
+    pub fn state(&self, ips: &[IpAddr]) -> (DeviceLiveness, Option<Duration>) {
+        let signals = self.signals.read().unwrap_or_else(|e| e.into_inner());
+        ips.iter()
+            .filter_map(|ip| signals.get(ip))
+            .map(|signals| (signals.state(), signals.last_seen().map(|at| at.elapsed())))
+            .min_by_key(|(state, age)| (*state, *age))
+            .unwrap_or((DeviceLiveness::Offline, None))
+    }
+
+    /// Drop addresses nothing has been heard from in a while.
+    pub fn prune(&self) {
+        self.signals
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|_, signals| signals.state() != DeviceLiveness::Offline);
+    }
+}
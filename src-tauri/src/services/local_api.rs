@@ -0,0 +1,182 @@
+use std::net::SocketAddr;
+use anyhow::Result;
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use uuid::Uuid;
+use crate::models::{ApiNotice, ApiRequest, ApiScope, ClipOrigin, ClipboardMessage, ContentKind, HistoryOrigin, MessageType};
+use super::clipboard_sync;
+use super::connection::{ApplyOutcome, ConnectionContext};
+use super::device_names;
+
+/// A connection authenticated with a local API token.
+#[derive(Debug, Clone, Copy)]
+pub struct ApiClient {
+    pub token_id: Uuid,
+    pub scope: ApiScope,
+}
+
+/// Token presented during the handshake, from an `Authorization: Bearer`
+/// header or, for clients that cannot set headers, a `token` query parameter.
+pub fn presented_token(request: &Request) -> Option<String> {
+    let from_header = request
+        .headers()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string());
+
+    from_header.or_else(|| {
+        request.uri().query()?.split('&').find_map(|pair| {
+            pair.strip_prefix("token=").map(str::to_string)
+        })
+    })
+}
+
+pub fn unauthorized() -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("Invalid or revoked API token".to_string()));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+}
+
+/// Serve a local API client: stream clipboard events to it, answer
+/// `status_summary` requests and then push summary changes, and, with the
+/// `read_write` scope, accept clipboard content it pushes.
+pub async fn serve<S>(
+    ws_stream: WebSocketStream<S>,
+    addr: SocketAddr,
+    client: ApiClient,
+    ctx: ConnectionContext,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let conn_id = Uuid::new_v4();
+    tracing::info!("Local API client {} connected from {} ({:?})", conn_id, addr, client.scope);
+
+    let (mut sender, mut receiver) = ws_stream.split();
+    let mut events = ctx.api_tx.subscribe();
+    let mut revoked = ctx.security.api_revoked.subscribe();
+    let mut status = ctx.telemetry.status.subscribe();
+    let mut wants_status = false;
+
+    loop {
+        tokio::select! {
+            msg = receiver.next() => {
+                match msg {
+                    Some(Ok(Message::Text(text))) => {
+                        if let Ok(ApiRequest::StatusSummary) = serde_json::from_str::<ApiRequest>(&text) {
+                            wants_status = true;
+                            let notice = ApiNotice::StatusSummary(status.borrow_and_update().clone());
+                            if send_notice(&mut sender, &notice).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
+                        if !client.scope.can_push() {
+                            tracing::warn!("Ignoring push from read-only API client {}", conn_id);
+                            continue;
+                        }
+                        if !ctx.security.api_tokens.read().await.is_active(client.token_id) {
+                            break;
+                        }
+                        handle_push(&ctx, conn_id, addr, &text).await;
+                    }
+                    Some(Ok(Message::Close(_))) | None => break,
+                    Some(Err(e)) => {
+                        tracing::warn!("Local API connection error for {}: {}", conn_id, e);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            event = events.recv() => {
+                if let Ok((skip, json)) = event {
+                    if skip != Some(conn_id) && sender.send(Message::Text(json.into())).await.is_err() {
+                        break;
+                    }
+                }
+            }
+            changed = status.changed(), if wants_status => {
+                if changed.is_err() {
+                    break;
+                }
+                let notice = ApiNotice::StatusSummary(status.borrow_and_update().clone());
+                if send_notice(&mut sender, &notice).await.is_err() {
+                    break;
+                }
+            }
+            token_id = revoked.recv() => {
+                if token_id.is_ok_and(|id| id == client.token_id) {
+                    tracing::info!("Closing local API client {}: token revoked", conn_id);
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+            }
+        }
+    }
+
+    tracing::info!("Local API client {} disconnected", conn_id);
+    Ok(())
+}
+
+async fn send_notice<W>(sender: &mut W, notice: &ApiNotice) -> Result<()>
+where
+    W: futures_util::Sink<Message> + Unpin,
+    W::Error: std::error::Error + Send + Sync + 'static,
+{
+    sender.send(Message::Text(serde_json::to_string(notice)?.into())).await?;
+    Ok(())
+}
+
+/// Apply content pushed by an API client locally and send it to peers,
+/// through the same sync mode, filter, and size gates as local copies.
+/// API clients are authenticated by token, so pushes are plaintext.
+async fn handle_push(ctx: &ConnectionContext, conn_id: Uuid, addr: SocketAddr, text: &str) {
+    let message = match serde_json::from_str::<ClipboardMessage>(text) {
+        Ok(message) if matches!(message.msg_type, MessageType::ClipboardUpdate) => message,
+        Ok(message) => {
+            tracing::warn!("Ignoring {:?} message from API client {}", message.msg_type, conn_id);
+            return;
+        }
+        Err(e) => {
+            tracing::warn!("Malformed push from API client {}: {}", conn_id, e);
+            return;
+        }
+    };
+    let Some(text) = message.content else {
+        return;
+    };
+    if *ctx.policy.paused.read().await {
+        tracing::debug!("Sync paused, ignoring push from API client {}", conn_id);
+        return;
+    }
+
+    let content = ConnectionContext::clipboard_content(text, message.formats.as_deref());
+    let origin = ClipOrigin { peer_id: conn_id, address: addr, device: None, message_id: Some(message.id), sent_at: Some(message.timestamp), received_at: Some(chrono::Utc::now()), kind: message.descriptor.as_ref().map_or(ContentKind::Text, |descriptor| descriptor.kind.into()) };
+    let ApplyOutcome::Applied(content) = ctx.apply_clipboard_update(&origin, &content).await else {
+        return;
+    };
+    ctx.publish_api_event(Some(conn_id), content.clone(), HistoryOrigin::Local, None);
+
+    let config = ctx.config.read().await.clone();
+    if !config.sync_mode.sends() {
+        tracing::debug!("Receive-only mode, not broadcasting push from API client {}", conn_id);
+        return;
+    }
+    let Some(content) = clipboard_sync::gate_outgoing(&config, &ctx.policy.oversize, content).await else {
+        return;
+    };
+    match ClipboardMessage::clipboard_update(content) {
+        Ok(mut update) => {
+            update.device = Some(device_names::local_device_info());
+            if let Err(e) = ctx.broadcast(update).await {
+                tracing::error!("Failed to broadcast API push: {}", e);
+            }
+        }
+        Err(e) => tracing::error!("Failed to encode API push: {}", e),
+    }
+}
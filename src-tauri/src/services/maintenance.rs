@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use anyhow::Result;
+use tauri::AppHandle;
+use tokio::sync::RwLock;
+use tokio::time::Duration;
+use crate::models::{Config, MaintenanceReport};
+use super::blob_store::{BlobStore, Eviction};
+use super::events;
+use super::history::HistoryStore;
+use super::shutdown::Shutdown;
+
+pub const MAINTENANCE_EVENT: &str = "maintenance-completed";
+/// How often the background task prunes and compacts the history.
+pub const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Apply retention limits, vacuum the history database and evict blobs
+/// beyond the size cap.
+pub async fn run(history: &HistoryStore, blobs: Option<&BlobStore>, config: &Config) -> Result<MaintenanceReport> {
+    let max_age = (config.history_retention_days > 0)
+        .then(|| chrono::Duration::days(config.history_retention_days as i64));
+    let max_entries = (config.history_max_entries > 0).then_some(config.history_max_entries);
+
+    let entries_removed = history.prune(max_age, max_entries, config.history_tag_retention.clone()).await?;
+    let vacuumed = history.vacuum().await?;
+    let eviction = match blobs {
+        Some(blobs) if config.blob_store_max_mb > 0 => {
+            let blobs = blobs.clone();
+            let max_bytes = u64::from(config.blob_store_max_mb) * 1024 * 1024;
+            tokio::task::spawn_blocking(move || blobs.evict(max_bytes)).await??
+        }
+        _ => Eviction::default(),
+    };
+    let bytes_reclaimed = vacuumed + eviction.bytes_reclaimed;
+
+    tracing::info!(
+        "History maintenance removed {} entries and {} blobs and reclaimed {} bytes",
+        entries_removed, eviction.blobs_removed, bytes_reclaimed
+    );
+    Ok(MaintenanceReport {
+        entries_removed,
+        blobs_evicted: eviction.blobs_removed,
+        bytes_reclaimed,
+        finished_at: chrono::Utc::now(),
+    })
+}
+
+/// Run maintenance and announce the result to the frontend.
+pub async fn run_and_report(
+    app: Option<&AppHandle>,
+    history: &HistoryStore,
+    blobs: Option<&BlobStore>,
+    config: &Config,
+) -> Result<MaintenanceReport> {
+    let report = run(history, blobs, config).await?;
+    events::emit(app, MAINTENANCE_EVENT, report.clone());
+    Ok(report)
+}
+
+/// Spawn the periodic maintenance task. The first run happens right away.
+/// A run under way when `shutdown` fires finishes first.
+pub fn spawn(app: Option<AppHandle>, history: Arc<HistoryStore>, blobs: Option<BlobStore>, config: Arc<RwLock<Config>>, shutdown: &Shutdown) {
+    let stop = shutdown.clone();
+    shutdown.spawn(async move {
+        let mut interval = tokio::time::interval(MAINTENANCE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = stop.cancelled() => break,
+            }
+            let config = config.read().await.clone();
+            if let Err(e) = run_and_report(app.as_ref(), &history, blobs.as_ref(), &config).await {
+                tracing::error!("History maintenance failed: {}", e);
+            }
+        }
+    });
+}
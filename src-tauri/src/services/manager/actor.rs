@@ -0,0 +1,187 @@
+use anyhow::Result;
+use futures_util::future::BoxFuture;
+use tokio::sync::{mpsc, oneshot};
+use crate::models::Config;
+use super::ServiceManager;
+
+/// Commands waiting for the manager before senders are made to wait.
+const MAILBOX_CAPACITY: usize = 64;
+
+/// Work run with exclusive access to the manager.
+type Job = Box<dyn for<'a> FnOnce(&'a mut ServiceManager) -> BoxFuture<'a, ()> + Send>;
+
+/// A message in the manager's mailbox. Lifecycle changes have their own
+/// commands; everything else is a query.
+enum Command {
+    Start(oneshot::Sender<Result<()>>),
+    Stop(oneshot::Sender<Result<()>>),
+    Shutdown(oneshot::Sender<Result<()>>),
+    UpdateConfig(Box<Config>, oneshot::Sender<Result<()>>),
+    SetPaused(bool, oneshot::Sender<Result<()>>),
+    Query(Job),
+}
+
+fn job<F>(f: F) -> Job
+where
+    F: for<'a> FnOnce(&'a mut ServiceManager) -> BoxFuture<'a, ()> + Send + 'static,
+{
+    Box::new(f)
+}
+
+/// Handle to the task that owns the `ServiceManager`. Commands are handled
+/// one at a time in the order they arrive, so a restart can never
+/// interleave with another caller, and nobody holds a lock across awaits.
+#[derive(Clone)]
+pub struct ManagerHandle {
+    mailbox: mpsc::Sender<Command>,
+}
+
+impl ManagerHandle {
+    /// Move `manager` into its own task and return the handle to it.
+    pub fn spawn(manager: ServiceManager) -> Self {
+        let (mailbox, commands) = mpsc::channel(MAILBOX_CAPACITY);
+        tokio::spawn(run(manager, commands));
+        Self { mailbox }
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        self.request(Command::Start).await
+    }
+
+    pub async fn stop(&self) -> Result<()> {
+        self.request(Command::Stop).await
+    }
+
+    /// Stop services for app exit, leaving the saved sync state alone.
+    /// The mailbox closes after this, so later commands fail.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.request(Command::Shutdown).await
+    }
+
+    pub async fn update_config(&self, config: Config) -> Result<()> {
+        self.request(|reply| Command::UpdateConfig(Box::new(config), reply)).await
+    }
+
+    pub async fn set_paused(&self, paused: bool) -> Result<()> {
+        self.request(|reply| Command::SetPaused(paused, reply)).await
+    }
+
+    /// Run `f` on the manager and return what it produced.
+    pub async fn query<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: for<'a> FnOnce(&'a mut ServiceManager) -> BoxFuture<'a, T> + Send + 'static,
+    {
+        let (reply, response) = oneshot::channel();
+        self.send(Command::Query(job(move |manager| Box::pin(async move {
+            let _ = reply.send(f(manager).await);
+        })))).await?;
+        response.await.map_err(|_| stopped())
+    }
+
+    /// `query` for work that can fail itself.
+    pub async fn execute<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: for<'a> FnOnce(&'a mut ServiceManager) -> BoxFuture<'a, Result<T>> + Send + 'static,
+    {
+        self.query(f).await?
+    }
+
+    async fn request(&self, command: impl FnOnce(oneshot::Sender<Result<()>>) -> Command) -> Result<()> {
+        let (reply, response) = oneshot::channel();
+        self.send(command(reply)).await?;
+        response.await.map_err(|_| stopped())?
+    }
+
+    async fn send(&self, command: Command) -> Result<()> {
+        self.mailbox.send(command).await.map_err(|_| stopped())
+    }
+}
+
+fn stopped() -> anyhow::Error {
+    anyhow::anyhow!("Service manager has stopped")
+}
+
+async fn run(mut manager: ServiceManager, mut commands: mpsc::Receiver<Command>) {
+    while let Some(command) = commands.recv().await {
+        match command {
+            Command::Start(reply) => {
+                let _ = reply.send(manager.start().await);
+            }
+            Command::Stop(reply) => {
+                let _ = reply.send(manager.stop().await);
+            }
+            Command::Shutdown(reply) => {
+                let _ = reply.send(manager.shutdown().await);
+                break;
+            }
+            Command::UpdateConfig(config, reply) => {
+                let _ = reply.send(manager.update_config(*config).await);
+            }
+            Command::SetPaused(paused, reply) => {
+                let _ = reply.send(manager.set_paused(paused).await);
+            }
+            Command::Query(job) => job(&mut manager).await,
+        }
+    }
+    tracing::debug!("Service manager mailbox closed");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use futures_util::future::join_all;
+    use super::*;
+
+    fn handle() -> ManagerHandle {
+        ManagerHandle::spawn(ServiceManager::new())
+    }
+
+    #[tokio::test]
+    async fn commands_run_one_at_a_time_in_arrival_order() {
+        let handle = handle();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let queries = (0..5u64).map(|i| {
+            let order = order.clone();
+            handle.query(move |_| Box::pin(async move {
+                // Earlier commands take longer, so overlapping would reorder them
+                tokio::time::sleep(Duration::from_millis(10 * (5 - i))).await;
+                order.lock().unwrap().push(i);
+            }))
+        });
+        join_all(queries).await;
+        assert_eq!(*order.lock().unwrap(), [0, 1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn query_returns_what_the_job_produced() {
+        let handle = handle();
+        let running = handle.query(|manager| Box::pin(async move { manager.websocket.is_some() })).await.unwrap();
+        assert!(!running);
+    }
+
+    #[tokio::test]
+    async fn execute_passes_on_the_job_result() {
+        let handle = handle();
+        assert_eq!(handle.execute(|_| Box::pin(async { Ok(7) })).await.unwrap(), 7);
+        let failed = handle.execute::<(), _>(|_| Box::pin(async { Err(anyhow::anyhow!("job failed")) })).await;
+        assert_eq!(failed.unwrap_err().to_string(), "job failed");
+        // A failing job leaves the manager serving
+        assert!(handle.query(|_| Box::pin(async {})).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn mailbox_closes_on_shutdown() {
+        let handle = handle();
+        let queued = handle.clone();
+        let (shutdown, late) = tokio::join!(handle.shutdown(), async move {
+            tokio::task::yield_now().await;
+            queued.query(|_| Box::pin(async {})).await
+        });
+        shutdown.unwrap();
+        assert!(late.is_err());
+        assert_eq!(handle.start().await.unwrap_err().to_string(), "Service manager has stopped");
+    }
+}
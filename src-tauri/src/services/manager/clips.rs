@@ -0,0 +1,72 @@
+use anyhow::Result;
+use uuid::Uuid;
+use super::ServiceManager;
+
+impl ServiceManager {
+    /// Read the clipboard once and send it to peers, changed or not.
+    ///
+    /// # Errors
+    /// When sync is not running or paused, or the clipboard holds no text
+    pub async fn push_clipboard_now(&self) -> Result<()> {
+        let (Some(ws), Some(clipboard)) = (self.websocket.as_ref(), self.clipboard.as_ref()) else {
+            return Err(anyhow::anyhow!("Sync is not running"));
+        };
+        if ws.is_paused().await {
+            return Err(anyhow::anyhow!("Sync is paused"));
+        }
+        if !self.config.read().await.sync_mode.sends() {
+            return Err(anyhow::anyhow!("This device is receive-only"));
+        }
+        let content = clipboard.read_now().await?;
+        if content.text.is_empty() {
+            return Err(anyhow::anyhow!("Clipboard is empty"));
+        }
+        self.clipboard_sync(clipboard.clone()).share_local(ws, content).await;
+        Ok(())
+    }
+
+    /// Send or apply an oversized clip the user was asked about, or drop it.
+    ///
+    /// # Returns
+    /// Whether a held clip with this id existed
+    pub async fn resolve_oversize_content(&self, id: uuid::Uuid, accept: bool) -> Result<bool> {
+        let Some(held) = self.oversize.take(id).await else {
+            return Ok(false);
+        };
+        if !accept {
+            tracing::info!("Discarded oversized clip {}", id);
+            return Ok(true);
+        }
+        let (Some(ws), Some(clipboard)) = (self.websocket.as_ref(), self.clipboard.as_ref()) else {
+            return Err(anyhow::anyhow!("Sync is not running"));
+        };
+        match held.origin {
+            Some(origin) => ws.apply_held_clip(&origin, held.content).await,
+            None => self.clipboard_sync(clipboard.clone()).send_local(ws, held.content).await,
+        }
+        Ok(true)
+    }
+
+    /// Settle a clip held by the `never_overwrite` conflict policy: write
+    /// it to the clipboard when `accept` is true, drop it otherwise.
+    /// Returns whether a held clip with this id was found.
+    pub async fn resolve_clipboard_conflict(&self, id: Uuid, accept: bool) -> Result<bool> {
+        let Some(held) = self.conflicts.take(id) else {
+            return Ok(false);
+        };
+        if !accept {
+            tracing::info!("Kept local clip over conflicting clip {}", id);
+            return Ok(true);
+        }
+        if !self.config.read().await.sync_mode.receives() {
+            return Err(anyhow::anyhow!("This device is send-only"));
+        }
+        let (Some(ws), Some(clipboard)) = (self.websocket.as_ref(), self.clipboard.as_ref()) else {
+            return Err(anyhow::anyhow!("Sync is not running"));
+        };
+        self.clipboard_sync(clipboard.clone())
+            .apply_now(held.content, held.origin, &ws.apply_report())
+            .await;
+        Ok(true)
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use crate::models::{DeviceTombstone, DiscoveredDevice, DiscoveryStatus};
+use crate::services::tls;
+use crate::utils::net;
+use crate::services::guests::{DEFAULT_GUEST_HOURS, MAX_GUEST_HOURS};
+use crate::services::tombstones::TombstoneTargets;
+use super::ServiceManager;
+
+impl ServiceManager {
+    pub async fn get_discovered_devices(&self) -> Vec<DiscoveredDevice> {
+        let mut devices = if let Some(ref mdns) = self.mdns {
+            mdns.get_discovered_devices().await
+        } else {
+            vec![]
+        };
+        
+        let paused_addresses = match self.websocket {
+            Some(ref ws) => ws.get_paused_peer_addresses().await,
+            None => vec![],
+        };
+        
+        if let Err(e) = self.reconcile_identities(&devices).await {
+            tracing::warn!("Failed to save migrated trust decisions: {}", e);
+        }
+        
+        let mismatches = match self.websocket {
+            Some(ref ws) => ws.version_mismatches().await,
+            None => Default::default(),
+        };
+        let mutes = self.websocket.as_ref().map(|ws| ws.mutes());
+        
+        let trust = self.trust.read().await;
+        for device in devices.iter_mut() {
+            if let Some(mismatch) = net::parse_ip(&device.address).and_then(|ip| mismatches.get(&ip)) {
+                device.version_mismatch = Some(mismatch.clone());
+            }
+            device.trusted = trust.is_trusted(&device.address, device.port, device.tls_fingerprint.as_deref());
+            device.guest_until = trust.guest_until(&device.address, device.port).filter(|_| device.trusted);
+            device.paused = paused_addresses.iter().any(|addr| device.addresses.iter().chain([&device.address]).any(|a| net::parse_ip(a) == Some(addr.ip())));
+            if let Some(ref mutes) = mutes {
+                let ips: Vec<_> = device.addresses.iter().chain([&device.address]).filter_map(|a| net::parse_ip(a)).collect();
+                device.muted_until = ips.iter().filter_map(|ip| mutes.honored_until(*ip)).max();
+                device.mute_requested_until = ips.iter().filter_map(|ip| mutes.requested_until(*ip)).max();
+            }
+        }
+        devices
+    }
+
+    /// Ask the device at `address` to stop sending us content for
+    /// `minutes`, or to resume when `None`.
+    pub async fn request_device_mute(&self, address: String, minutes: Option<u32>) -> Result<()> {
+        let ws = self.websocket.as_ref().ok_or_else(|| anyhow::anyhow!("Sync is not running"))?;
+        let ip = net::parse_ip(&address).ok_or_else(|| anyhow::anyhow!("Not an IP address: {}", address))?;
+        let until = minutes
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| chrono::Utc::now() + chrono::Duration::minutes(minutes as i64));
+        ws.request_mute(ip, until).await
+    }
+
+    pub fn get_discovery_status(&self) -> DiscoveryStatus {
+        self.mdns.as_ref().map(|mdns| mdns.status()).unwrap_or_default()
+    }
+
+    /// Carry trust decisions keyed by a legacy instance's `address:port`
+    /// over to the stable identity its device now announces.
+    async fn reconcile_identities(&self, devices: &[DiscoveredDevice]) -> Result<()> {
+        let mut changed = false;
+        {
+            let mut trust = self.trust.write().await;
+            for device in devices {
+                changed |= trust.reconcile_identity(device);
+            }
+        }
+        if changed {
+            self.save_trust().await?;
+        }
+        Ok(())
+    }
+
+    /// Record a trust decision for the device at `address:port`. Trusting a
+    /// device that serves TLS pins its certificate once it proves it holds it.
+    pub async fn set_device_trusted(&self, address: String, port: u16, trusted: bool) -> Result<()> {
+        self.record_trust(address, port, trusted, None).await
+    }
+
+    /// Trust the device at `address:port` for `hours` (8 by default), after
+    /// which it is unpaired and anything held from it is dropped.
+    pub async fn pair_guest(&self, address: String, port: u16, hours: Option<u32>) -> Result<()> {
+        let hours = hours.unwrap_or(DEFAULT_GUEST_HOURS);
+        if hours == 0 || hours > MAX_GUEST_HOURS {
+            anyhow::bail!("Guest pairings last 1 to {} hours", MAX_GUEST_HOURS);
+        }
+        let expires_at = chrono::Utc::now() + chrono::Duration::hours(hours as i64);
+        self.record_trust(address, port, true, Some(expires_at)).await
+    }
+
+    async fn record_trust(&self, address: String, port: u16, trusted: bool, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Result<()> {
+        let device = self.get_discovered_devices().await
+            .into_iter()
+            .find(|device| device.address == address && device.port == port);
+        let name = device.as_ref().map_or_else(|| format!("{}:{}", address, port), |device| device.display_name.clone());
+        let fingerprint = device.and_then(|device| device.tls_fingerprint);
+        
+        if trusted {
+            if let Some(ref fingerprint) = fingerprint {
+                let addr = net::parse_socket_addr(&address, port)
+                    .ok_or_else(|| anyhow::anyhow!("Not an IP address: {}", address))?;
+                tls::verify_pinned(addr, fingerprint).await?;
+            }
+            self.tombstones.write().await.clear(&address, port);
+        }
+        let mut trust = self.trust.write().await;
+        match expires_at {
+            Some(expires_at) => trust.set_guest(name, address, port, fingerprint, expires_at),
+            None => trust.set_trusted(name, address, port, trusted, fingerprint),
+        }
+        drop(trust);
+        self.save_trust().await
+    }
+
+    /// Remove every trace of a device: its trust decision, discovery entry,
+    /// and history contributions. Trusted peers are sent a tombstone so
+    /// they drop it too.
+    pub async fn forget_device(&self, address: String, port: u16) -> Result<()> {
+        let name = self.trust.read().await.name_of(&address, port)
+            .unwrap_or_else(|| format!("{}:{}", address, port));
+        let tombstone = DeviceTombstone { name, address, port, removed_at: chrono::Utc::now() };
+
+        self.tombstones.write().await.record(tombstone.clone());
+        self.tombstone_targets().apply(&tombstone).await?;
+        if let Some(ref ws) = self.websocket {
+            ws.send_tombstone(&tombstone).await?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn tombstone_targets(&self) -> TombstoneTargets {
+        TombstoneTargets {
+            app: self.app_handle.clone(),
+            trust: self.trust.clone(),
+            tombstones: self.tombstones.clone(),
+            mdns: self.mdns.clone(),
+            history: self.history.clone(),
+        }
+    }
+}
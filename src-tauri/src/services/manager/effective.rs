@@ -0,0 +1,47 @@
+use anyhow::Result;
+use crate::models::EffectiveConfig;
+use crate::services::diagnostics::DiagnosticsProbe;
+use crate::services::mdns::MdnsService;
+use crate::utils::paths;
+use super::ServiceManager;
+
+impl ServiceManager {
+    /// The configuration actually in effect: stored settings over defaults,
+    /// plus what the running services chose. The security key is left out
+    /// so the result is safe to paste into a bug report.
+    pub async fn get_effective_config(&self) -> EffectiveConfig {
+        let mut config = self.config.read().await.clone();
+        let security_key_set = config.effective_security_key().is_some();
+        config.security_key = None;
+
+        let ws = self.websocket.as_ref();
+        EffectiveConfig {
+            config,
+            security_key_set,
+            running: self.is_running().await,
+            paused: self.is_paused().await,
+            bound_port: ws.map(|ws| ws.port()),
+            advertised_address: self.mdns.as_ref().and_then(|_| MdnsService::get_local_ip()).map(|ip| ip.to_string()),
+            tls_active: ws.is_some_and(|ws| ws.is_tls()),
+            clipboard_monitoring: self.clipboard.is_some(),
+            history_available: self.history.is_some(),
+            portable: paths::is_portable(),
+            data_dir: self.data_dir.as_ref().map(|dir| dir.display().to_string()),
+            clipboard_backend: "arboard".to_string(),
+            discovery_backend: "mdns-sd".to_string(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+        }
+    }
+
+    /// What a diagnostic capture reads from the services, so the capture
+    /// can run without holding the manager.
+    pub async fn diagnostics_probe(&self) -> Result<DiagnosticsProbe> {
+        let data_dir = self.data_dir.clone().ok_or_else(|| anyhow::anyhow!("No data directory to write diagnostics to"))?;
+        Ok(DiagnosticsProbe {
+            websocket: self.websocket.clone(),
+            config: self.get_effective_config().await,
+            data_dir,
+        })
+    }
+}
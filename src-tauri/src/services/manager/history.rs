@@ -0,0 +1,78 @@
+use std::sync::Arc;
+use anyhow::Result;
+use crate::models::{ClipboardContent, HistoryEntry, HistoryPage, MaintenanceReport};
+use crate::services::maintenance;
+use crate::services::history::HistoryStore;
+use super::ServiceManager;
+
+impl ServiceManager {
+    pub(super) fn history(&self) -> Result<&Arc<HistoryStore>> {
+        self.history.as_ref().ok_or_else(|| anyhow::anyhow!("Clipboard history is unavailable"))
+    }
+
+    pub async fn get_history(&self, limit: u32, offset: u32) -> Result<HistoryPage> {
+        self.history()?.list(limit, offset).await
+    }
+
+    pub async fn search_history(&self, query: String, device: Option<String>, limit: u32, offset: u32) -> Result<HistoryPage> {
+        self.history()?.search(query, device, limit, offset).await
+    }
+
+    pub async fn get_history_entry(&self, id: i64) -> Result<Option<HistoryEntry>> {
+        self.history()?.get(id).await
+    }
+
+    pub async fn delete_history_entry(&self, id: i64) -> Result<bool> {
+        self.history()?.delete(id).await
+    }
+
+    pub async fn set_history_tags(&self, id: i64, tags: Vec<String>) -> Result<bool> {
+        self.history()?.set_tags(id, &tags).await
+    }
+
+    pub async fn set_history_pinned(&self, id: i64, pinned: bool) -> Result<bool> {
+        self.history()?.set_pinned(id, pinned).await
+    }
+
+    pub async fn get_pinned_entries(&self) -> Result<Vec<HistoryEntry>> {
+        self.history()?.pinned().await
+    }
+
+    pub async fn clear_history(&self) -> Result<usize> {
+        self.history()?.clear().await
+    }
+
+    /// Put a history entry back on the clipboard and, when `broadcast` is
+    /// set, send it to peers as a new local clip.
+    ///
+    /// # Returns
+    /// Whether an entry with that id existed
+    pub async fn restore_history_entry(&self, id: i64, broadcast: bool) -> Result<bool> {
+        let Some(clipboard) = self.clipboard.as_ref() else {
+            return Err(anyhow::anyhow!("Sync is not running"));
+        };
+        let Some(entry) = self.history()?.get(id).await? else {
+            return Ok(false);
+        };
+        let content = ClipboardContent::plain(entry.content);
+        clipboard.set_clipboard(content.clone()).await?;
+        if broadcast {
+            let Some(ws) = self.websocket.as_ref() else {
+                return Err(anyhow::anyhow!("Sync is not running"));
+            };
+            if ws.is_paused().await {
+                return Err(anyhow::anyhow!("Sync is paused"));
+            }
+            if !self.config.read().await.sync_mode.sends() {
+                return Err(anyhow::anyhow!("This device is receive-only"));
+            }
+            self.clipboard_sync(clipboard.clone()).share_local(ws, content).await;
+        }
+        Ok(true)
+    }
+
+    pub async fn run_maintenance_now(&self) -> Result<MaintenanceReport> {
+        let config = self.config.read().await.clone();
+        maintenance::run_and_report(self.app_handle.as_ref(), self.history()?, self.blobs.as_ref(), &config).await
+    }
+}
@@ -0,0 +1,50 @@
+use anyhow::Result;
+use tauri_plugin_store::StoreExt;
+use crate::models::Config;
+use crate::services::key_rotation::RETIRED_KEY_STORE_KEY;
+use crate::utils::paths;
+use super::ServiceManager;
+
+impl ServiceManager {
+    async fn save_key_ring(&self) -> Result<()> {
+        if let Some(ref app) = self.app_handle {
+            let store = app.store(paths::settings_store())?;
+            let ring = self.key_ring.read().await;
+            store.set(RETIRED_KEY_STORE_KEY, serde_json::to_value(&*ring)?);
+            store.save()?;
+        }
+        Ok(())
+    }
+
+    /// After a change of security key, keep accepting the old one for the
+    /// configured grace and tell connected peers, so they can prompt for
+    /// the new key. Setting a first key or removing it is not a rotation.
+    pub(super) async fn rotate_key(&self, old: &Config, new: &Config) -> Result<()> {
+        let (previous, current) = (old.effective_security_key(), new.effective_security_key());
+        if previous == current {
+            return Ok(());
+        }
+        if let Some(ref history) = self.history {
+            if let Err(e) = history.follow_security_key(current.map(str::to_string), *self.keys.salt()).await {
+                tracing::error!("Failed to encrypt history for the new security key: {}", e);
+            }
+        }
+        match (previous, current) {
+            (Some(previous), Some(_)) => {
+                let retired = self.key_ring.write().await.retire(previous, new.key_rotation_grace_hours).is_some();
+                self.save_key_ring().await?;
+                if retired {
+                    tracing::info!("Security key changed; accepting the old one for {} hours", new.key_rotation_grace_hours);
+                    if let Some(ref ws) = self.websocket {
+                        ws.announce_key_rotation().await;
+                    }
+                }
+            }
+            _ => {
+                self.key_ring.write().await.clear();
+                self.save_key_ring().await?;
+            }
+        }
+        Ok(())
+    }
+}
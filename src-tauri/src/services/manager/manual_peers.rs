@@ -0,0 +1,43 @@
+use std::net::IpAddr;
+use anyhow::{bail, Result};
+use crate::models::ManualPeer;
+use super::ServiceManager;
+
+impl ServiceManager {
+    /// Save a device to dial directly and connect to it if sync is running.
+    /// Adding a known address again updates its fingerprint.
+    pub async fn add_manual_peer(&self, address: IpAddr, port: u16, fingerprint: Option<String>) -> Result<()> {
+        if port == 0 {
+            bail!("Port must not be 0");
+        }
+        let fingerprint = fingerprint.map(|f| f.trim().to_lowercase()).filter(|f| !f.is_empty());
+        {
+            let mut config = self.config.write().await;
+            config.manual_peers.retain(|peer| peer.address != address || peer.port != port);
+            config.manual_peers.push(ManualPeer { address, port, fingerprint });
+        }
+        self.save_config().await?;
+        tracing::info!("Added manual peer {}:{}", address, port);
+
+        if let Some(ref ws) = self.websocket {
+            ws.dial_peers();
+        }
+        Ok(())
+    }
+
+    /// Stop dialing a device. An open connection to it stays up until it
+    /// closes. Returns whether the peer was in the list.
+    pub async fn remove_manual_peer(&self, address: IpAddr, port: u16) -> Result<bool> {
+        let removed = {
+            let mut config = self.config.write().await;
+            let before = config.manual_peers.len();
+            config.manual_peers.retain(|peer| peer.address != address || peer.port != port);
+            config.manual_peers.len() != before
+        };
+        if removed {
+            self.save_config().await?;
+            tracing::info!("Removed manual peer {}:{}", address, port);
+        }
+        Ok(removed)
+    }
+}
@@ -0,0 +1,37 @@
+use crate::models::{ConnectedPeer, PeerMetrics, SyncMetrics};
+use super::ServiceManager;
+
+impl ServiceManager {
+    /// Peers with an open session; empty when sync is not running.
+    pub async fn get_connected_peers(&self) -> Vec<ConnectedPeer> {
+        match self.websocket {
+            Some(ref ws) => ws.get_connected_peers().await,
+            None => Vec::new(),
+        }
+    }
+
+    /// Per-peer counters; empty when sync is not running.
+    pub async fn get_peer_metrics(&self) -> Vec<PeerMetrics> {
+        match self.websocket {
+            Some(ref ws) => ws.get_peer_metrics().await,
+            None => Vec::new(),
+        }
+    }
+
+    /// Zero the sync counters, e.g. to count clips from the start of a
+    /// meeting. Nothing to do when sync is not running.
+    pub async fn reset_sync_metrics(&self) {
+        if let Some(ref ws) = self.websocket {
+            ws.reset_metrics().await;
+            tracing::info!("Sync metrics reset");
+        }
+    }
+
+    pub async fn get_sync_metrics(&self) -> Option<SyncMetrics> {
+        if let Some(ref ws) = self.websocket {
+            Some(ws.get_sync_metrics().await)
+        } else {
+            None
+        }
+    }
+}
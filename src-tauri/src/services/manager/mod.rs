@@ -0,0 +1,467 @@
+mod actor;
+mod clips;
+mod devices;
+mod effective;
+mod history;
+mod keys;
+mod manual_peers;
+mod metrics;
+mod presentation;
+mod profiles;
+mod reconfigure;
+mod store;
+mod transfer;
+mod tokens;
+
+pub use actor::ManagerHandle;
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use anyhow::Result;
+use crate::models::{ComponentHealth, Config, StatusSummary};
+use super::{websocket::WebSocketServer, mdns::MdnsService, clipboard::ClipboardMonitor, maintenance};
+use super::applied_log::{AppliedLog, APPLIED_LOG_FILE};
+use super::clipboard_sync::ClipboardSync;
+use super::conflicts::ConflictGate;
+use super::workspaces::DesktopGate;
+use super::content_filter;
+use super::language_routes;
+use super::interfaces;
+use super::liveness::LivenessRegistry;
+use super::previews::{MessagePreview, PreviewCache};
+use super::receipts;
+use super::size_policy::{OversizeGate, OVERSIZE_EVENT};
+use super::trust::TrustStore;
+use super::api_tokens::ApiTokenStore;
+use super::history::{HistoryStore, HISTORY_DB_FILE};
+use super::blob_store::{BlobStore, BLOBS_DIR};
+use super::tls::{self, TlsIdentity};
+use super::device_names::DeviceDirectory;
+use super::tombstones::TombstoneStore;
+use super::events::{self, SyncStatusEvent, STATUS_SUMMARY_EVENT, SYNC_STATUS_EVENT};
+use super::status_summary;
+use super::prometheus;
+use super::guests::GuestSweep;
+use super::folder_watch;
+use super::shutdown::{Shutdown, DRAIN_TIMEOUT};
+use super::key_rotation::SharedKeyRing;
+use super::key_derivation::{KeyDerivation, KeySalt};
+use crate::utils::{crash_report, paths, safe_mode};
+use tauri::{AppHandle, Emitter};
+
+pub struct ServiceManager {
+    config: Arc<RwLock<Config>>,
+    trust: Arc<RwLock<TrustStore>>,
+    api_tokens: Arc<RwLock<ApiTokenStore>>,
+    oversize: Arc<OversizeGate>,
+    tombstones: Arc<RwLock<TombstoneStore>>,
+    /// The previous security key, accepted for a while after a change
+    key_ring: SharedKeyRing,
+    /// This installation's salt for deriving wire keys, and the keys so far
+    keys: Arc<KeyDerivation>,
+    directory: Arc<DeviceDirectory>,
+    previews: Arc<PreviewCache>,
+    conflicts: Arc<ConflictGate>,
+    desktops: Arc<DesktopGate>,
+    applied: Arc<AppliedLog>,
+    websocket: Option<Arc<WebSocketServer>>,
+    mdns: Option<Arc<MdnsService>>,
+    clipboard: Option<Arc<ClipboardMonitor>>,
+    history: Option<Arc<HistoryStore>>,
+    blobs: Option<BlobStore>,
+    is_running: Arc<RwLock<bool>>,
+    /// Tasks that outlive sync runs, such as history maintenance
+    tasks: Shutdown,
+    app_handle: Option<AppHandle>,
+    /// Where history, certificates, and applied clips are kept; without
+    /// one they live in memory or are skipped
+    data_dir: Option<PathBuf>,
+}
+
+impl ServiceManager {
+    pub fn new() -> Self {
+        Self {
+            config: Arc::new(RwLock::new(Config::default())),
+            trust: Arc::new(RwLock::new(TrustStore::default())),
+            api_tokens: Arc::new(RwLock::new(ApiTokenStore::default())),
+            oversize: Arc::new(OversizeGate::default()),
+            tombstones: Arc::new(RwLock::new(TombstoneStore::default())),
+            key_ring: Arc::default(),
+            keys: Arc::default(),
+            directory: Arc::new(DeviceDirectory::default()),
+            previews: Arc::new(PreviewCache::default()),
+            conflicts: Arc::new(ConflictGate::default()),
+            desktops: Arc::new(DesktopGate::default()),
+            applied: Arc::default(),
+            websocket: None,
+            mdns: None,
+            clipboard: None,
+            history: None,
+            blobs: None,
+            is_running: Arc::new(RwLock::new(false)),
+            tasks: Shutdown::new(),
+            app_handle: None,
+            data_dir: None,
+        }
+    }
+
+    pub async fn set_app_handle(&mut self, handle: AppHandle) {
+        let app = handle.clone();
+        self.oversize.set_notifier(move |event| {
+            if let Err(e) = app.emit(OVERSIZE_EVENT, &event) {
+                tracing::warn!("Failed to emit oversize event: {}", e);
+            }
+        }).await;
+        match paths::data_dir(&handle) {
+            Ok(dir) => self.set_data_dir(dir),
+            Err(e) => tracing::warn!("Applied clips will not be remembered across restarts: {}", e),
+        }
+        self.app_handle = Some(handle);
+    }
+
+    /// Keep state under `dir`, starting with the applied clips log.
+    pub fn set_data_dir(&mut self, dir: PathBuf) {
+        self.applied = Arc::new(AppliedLog::open(dir.join(APPLIED_LOG_FILE)));
+        self.data_dir = Some(dir);
+    }
+
+    /// Derive wire keys with `salt`, this installation's own.
+    pub fn set_key_salt(&mut self, salt: KeySalt) {
+        self.keys = Arc::new(KeyDerivation::new(salt));
+    }
+
+    /// Open the clipboard history database and blob store in the data
+    /// directory and start their periodic maintenance.
+    pub fn init_history(&mut self) -> Result<()> {
+        if let Some(ref dir) = self.data_dir {
+            let path = dir.join(HISTORY_DB_FILE);
+            let security_key = self.config.try_read().ok().and_then(|config| config.effective_security_key().map(str::to_string));
+            let history = Arc::new(HistoryStore::open(&path, security_key.as_deref(), self.keys.salt())?);
+            let blobs = BlobStore::new(dir.join(BLOBS_DIR));
+            maintenance::spawn(self.app_handle.clone(), history.clone(), Some(blobs.clone()), self.config.clone(), &self.tasks);
+            self.history = Some(history);
+            self.blobs = Some(blobs);
+        }
+        Ok(())
+    }
+
+    /// This device's certificate when TLS is enabled, created on first use.
+    fn tls_identity(&self, config: &Config) -> Result<Option<TlsIdentity>> {
+        match self.data_dir {
+            Some(ref dir) if config.tls_enabled => {
+                Ok(Some(TlsIdentity::load_or_create(&dir.join(tls::TLS_DIR))?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    pub async fn start(&mut self) -> Result<()> {
+        tracing::info!("Starting services...");
+        
+        // Check if already running
+        {
+            let running_lock = self.is_running.read().await;
+            if *running_lock {
+                tracing::warn!("Services are already running, skipping start");
+                return Ok(());
+            }
+        }
+        if safe_mode::is_active() {
+            anyhow::bail!("Running in safe mode after repeated crashes; leave safe mode to start sync");
+        }
+
+        // Get config outside of critical section
+        let config = self.config.read().await.clone();
+        tracing::info!("Starting with config: websocket_port={}, mdns_service_name={}", 
+                      config.websocket_port, config.mdns_service_name);
+        
+        // Start WebSocket server
+        tracing::info!("Starting WebSocket server on port {}", config.websocket_port);
+        let tls = self.tls_identity(&config)?;
+        // Discovery and sessions decide together when a device is gone
+        let liveness = Arc::new(LivenessRegistry::default());
+        let mut ws = WebSocketServer::new(
+            config.websocket_port,
+            self.config.clone(),
+            self.trust.clone(),
+            self.api_tokens.clone(),
+            self.oversize.clone(),
+            self.directory.clone(),
+        )
+        .with_liveness(liveness.clone())
+        .with_applied_log(self.applied.clone())
+        .with_key_ring(self.key_ring.clone())
+        .with_key_derivation(self.keys.clone())
+        .with_app_handle(self.app_handle.clone());
+        if let Some(ref identity) = tls {
+            ws = ws.with_tls(identity.acceptor()?);
+        }
+        let ws = Arc::new(ws);
+        match ws.start().await {
+            Ok(()) => {
+                self.websocket = Some(ws.clone());
+                tracing::info!("WebSocket server started successfully");
+                // Dialed whether or not discovery works on this network
+                if !config.static_peers.is_empty() {
+                    tracing::info!("Connecting to {} static peers", config.static_peers.len());
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to start WebSocket server: {}", e);
+                return Err(e);
+            }
+        }
+        
+        // Start mDNS service
+        tracing::info!("Starting mDNS service...");
+        let mut mdns = MdnsService::new(
+            config.mdns_service_name.clone(),
+            config.websocket_port,
+        )
+        .with_directory(self.directory.clone())
+        .with_same_host_filter(config.filter_same_host)
+        .with_liveness(liveness)
+        .with_shutdown(ws.shutdown_signal())
+        .with_app_handle(self.app_handle.clone());
+        if let Some(identity) = tls {
+            mdns = mdns.with_tls_fingerprint(identity.fingerprint);
+        }
+        let mdns = Arc::new(mdns);
+        
+        // Failures are retried or reported in the background; sync with
+        // manual peers works either way
+        mdns.supervise().await;
+        
+        // Add some sample devices for demonstration
+        if cfg!(debug_assertions) {
+            tracing::info!("Debug mode - no sample devices added");
+        }
+        
+        self.mdns = Some(mdns.clone());
+        interfaces::spawn(self.app_handle.clone(), ws.clone(), mdns.clone(), ws.shutdown_signal());
+        self.tombstone_targets().spawn_listener(ws.subscribe_tombstones(), ws.shutdown_signal());
+        GuestSweep {
+            app: self.app_handle.clone(),
+            trust: self.trust.clone(),
+            websocket: ws.clone(),
+            oversize: self.oversize.clone(),
+            conflicts: self.conflicts.clone(),
+            desktops: self.desktops.clone(),
+        }.spawn(ws.shutdown_signal());
+        if let Some(ref history) = self.history {
+            receipts::spawn_listener(history.clone(), ws.subscribe_receipts(), ws.shutdown_signal());
+        }
+        status_summary::spawn(self.app_handle.clone(), ws.clone(), self.history.clone());
+        if let Some(port) = config.metrics_port {
+            // Sync works without it, so a taken port is only reported
+            if let Err(e) = prometheus::spawn(ws.clone(), self.config.clone(), port, ws.shutdown_signal()).await {
+                tracing::error!("Failed to serve metrics on port {}: {}", port, e);
+            }
+        }
+        
+        // Start clipboard monitor with proper error handling
+        tracing::info!("Initializing clipboard monitor...");
+        match ClipboardMonitor::new().await {
+            Ok(monitor) => {
+                monitor.set_skip_concealed(self.config.read().await.skip_concealed_content).await;
+                let clipboard = Arc::new(monitor);
+                let sync = self.clipboard_sync(clipboard.clone());
+                match sync.attach(&ws).await {
+                    Ok(_) => {
+                        folder_watch::spawn(&config.watched_folders, sync, ws.clone(), ws.shutdown_signal());
+                        self.clipboard = Some(clipboard);
+                        tracing::info!("Clipboard monitoring started successfully");
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to start clipboard monitoring: {}", e);
+                        // Continue without clipboard monitoring - don't fail the whole startup
+                    }
+                }
+            }
+            Err(e) => {
+                tracing::error!("Failed to initialize clipboard monitor: {}. Clipboard sync will be disabled.", e);
+                tracing::warn!("This is often due to missing clipboard permissions. The application will continue to work for device discovery and manual sync.");
+                // Continue without clipboard monitoring - the app can still function for network sync
+            }
+        }
+        
+        // All services started successfully - now mark as running and update config
+        *self.is_running.write().await = true;
+        
+        // Update config to reflect running state
+        {
+            let mut config_write = self.config.write().await;
+            config_write.sync_enabled = true;
+        }
+        self.save_config().await?;
+        self.emit_status(true, false);
+        
+        tracing::info!("All services started successfully");
+        Ok(())
+    }
+
+    fn clipboard_sync(&self, clipboard: Arc<ClipboardMonitor>) -> ClipboardSync {
+        ClipboardSync {
+            clipboard,
+            config: self.config.clone(),
+            history: self.history.clone(),
+            app: self.app_handle.clone(),
+            oversize: self.oversize.clone(),
+            directory: self.directory.clone(),
+            previews: self.previews.clone(),
+            conflicts: self.conflicts.clone(),
+            desktops: self.desktops.clone(),
+        }
+    }
+
+    pub async fn stop(&mut self) -> Result<()> {
+        self.stop_services().await;
+        
+        // Update config to reflect stopped state
+        {
+            let mut config = self.config.write().await;
+            config.sync_enabled = false;
+        }
+        self.save_config().await?;
+        self.emit_status(false, false);
+        
+        tracing::info!("All services stopped");
+        Ok(())
+    }
+
+    /// Stop services as the app exits: peers get a close frame and the
+    /// mDNS record is withdrawn. Sync stays enabled, so it starts again
+    /// with the app.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        if *self.is_running.read().await {
+            self.stop_services().await;
+            self.emit_status(false, false);
+        }
+        self.tasks.drain(DRAIN_TIMEOUT).await;
+        tracing::info!("Services shut down for exit");
+        Ok(())
+    }
+
+    async fn stop_services(&mut self) {
+        tracing::info!("Stopping services...");
+        
+        // Mark as not running first to prevent new operations
+        *self.is_running.write().await = false;
+        
+        // Stop mDNS discovery explicitly
+        if let Some(ref mdns) = self.mdns {
+            if let Err(e) = mdns.shutdown().await {
+                tracing::error!("Failed to stop mDNS discovery: {}", e);
+            }
+        }
+        
+        // Stop WebSocket server explicitly
+        if let Some(ref ws) = self.websocket {
+            if let Err(e) = ws.stop().await {
+                tracing::error!("Failed to stop WebSocket server: {}", e);
+            }
+        }
+        
+        // Their background tasks stopped with the server
+        self.websocket = None;
+        self.mdns = None;
+        self.clipboard = None;
+    }
+
+    pub async fn update_config(&mut self, new_config: Config) -> Result<()> {
+        content_filter::validate(&new_config.content_filters)?;
+        language_routes::validate(&new_config.language_routes)?;
+        let old = std::mem::replace(&mut *self.config.write().await, new_config.clone());
+        let scope = reconfigure::scope(&old, &new_config);
+        
+        // Save config to store
+        self.save_config().await?;
+        self.rotate_key(&old, &new_config).await?;
+        
+        self.apply_live(&old, &new_config, scope).await;
+        if *self.is_running.read().await {
+            match scope {
+                reconfigure::ApplyScope::Restart => {
+                    self.stop().await?;
+                    self.start().await?;
+                }
+                reconfigure::ApplyScope::Port => self.move_port(new_config.websocket_port).await?,
+                _ => {}
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Preview of a recently sent or received clip, if still cached.
+    pub fn get_message_preview(&self, message_id: &Uuid) -> Option<MessagePreview> {
+        self.previews.get(message_id)
+    }
+
+    pub async fn get_config(&self) -> Config {
+        self.config.read().await.clone()
+    }
+    
+    /// Tasks that live as long as the manager, stopped on exit.
+    pub fn background_tasks(&self) -> Shutdown {
+        self.tasks.clone()
+    }
+
+    pub async fn is_running(&self) -> bool {
+        *self.is_running.read().await
+    }
+
+    /// Pause or resume sync without stopping services. While paused the
+    /// clipboard is not captured or written; peers are notified.
+    pub async fn set_paused(&self, paused: bool) -> Result<()> {
+        match self.websocket {
+            Some(ref ws) => {
+                ws.set_paused(paused).await;
+                if let Some(ref clipboard) = self.clipboard {
+                    clipboard.set_capture_paused(paused).await;
+                }
+                self.emit_status(true, paused);
+                tracing::info!("Sync {}", if paused { "paused" } else { "resumed" });
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("Sync is not running")),
+        }
+    }
+
+    fn emit_status(&self, running: bool, paused: bool) {
+        crash_report::record_health(ComponentHealth {
+            running,
+            paused,
+            websocket: self.websocket.is_some(),
+            mdns: self.mdns.is_some(),
+            clipboard_monitoring: self.clipboard.is_some(),
+            history: self.history.is_some(),
+        });
+        events::emit(self.app_handle.as_ref(), SYNC_STATUS_EVENT, SyncStatusEvent { running, paused });
+        if !running {
+            events::emit(self.app_handle.as_ref(), STATUS_SUMMARY_EVENT, StatusSummary::default());
+        }
+    }
+
+    pub async fn get_status_summary(&self) -> StatusSummary {
+        status_summary::collect(self.websocket.as_deref(), self.history.as_deref()).await
+    }
+
+    pub async fn is_paused(&self) -> bool {
+        match self.websocket {
+            Some(ref ws) => ws.is_paused().await,
+            None => false,
+        }
+    }
+
+    /// Send a log request to a peer. The answer may take up to
+    /// `LOG_REQUEST_TIMEOUT`, so the returned future holds its own handle to
+    /// the server and is awaited outside the manager's mailbox.
+    pub fn request_peer_logs(&self, peer_id: Uuid, lines: usize) -> Result<impl Future<Output = Result<Vec<String>>> + Send + 'static> {
+        let ws = self.websocket.clone().ok_or_else(|| anyhow::anyhow!("Sync is not running"))?;
+        Ok(async move { ws.request_peer_logs(peer_id, lines).await })
+    }
+}
@@ -0,0 +1,107 @@
+use std::net::IpAddr;
+use anyhow::Result;
+use crate::models::{PeerEndpoint, PresentationStatus};
+use crate::services::presentation::{self, Presentation};
+use crate::services::websocket::WebSocketServer;
+use super::ServiceManager;
+
+impl ServiceManager {
+    fn running_server(&self) -> Result<&WebSocketServer> {
+        self.websocket.as_deref().ok_or_else(|| anyhow::anyhow!("Sync is not running"))
+    }
+
+    /// Start broadcasting this clipboard to viewers that join with the
+    /// returned code. Viewers cannot send anything back.
+    pub async fn start_presentation(&self) -> Result<PresentationStatus> {
+        let ctx = self.running_server()?.context();
+        {
+            let mut state = ctx.policy.presentation.write().await;
+            match *state {
+                Presentation::Viewing { .. } => anyhow::bail!("Leave the presentation you are viewing first"),
+                Presentation::Presenting { .. } => return Ok(state.status()),
+                Presentation::Idle => {}
+            }
+            *state = Presentation::Presenting {
+                code: presentation::new_code(),
+                started_at: chrono::Utc::now(),
+                viewers: Default::default(),
+            };
+        }
+        tracing::info!("Presentation started");
+        presentation::emit_status(&ctx).await;
+        let status = ctx.policy.presentation.read().await.status();
+        Ok(status)
+    }
+
+    /// End the presentation and disconnect every viewer.
+    pub async fn stop_presentation(&self) -> Result<()> {
+        let ctx = self.running_server()?.context();
+        let viewers = {
+            let mut state = ctx.policy.presentation.write().await;
+            let Presentation::Presenting { ref viewers, .. } = *state else {
+                anyhow::bail!("Not presenting");
+            };
+            let viewers = viewers.clone();
+            *state = Presentation::Idle;
+            viewers
+        };
+        presentation::close_sessions(&ctx, |addr| viewers.contains(&addr)).await;
+        tracing::info!("Presentation ended, {} viewers disconnected", viewers.len());
+        presentation::emit_status(&ctx).await;
+        Ok(())
+    }
+
+    /// Follow the presenter at `address:port` as a read-only viewer.
+    pub async fn join_presentation(&self, address: IpAddr, port: u16, code: &str, fingerprint: Option<String>) -> Result<PresentationStatus> {
+        let ws = self.running_server()?;
+        let ctx = ws.context();
+        let code = presentation::normalize_code(code);
+        if code.is_empty() {
+            anyhow::bail!("Enter the session code shown on the presenter");
+        }
+        {
+            let mut state = ctx.policy.presentation.write().await;
+            if !matches!(*state, Presentation::Idle) {
+                anyhow::bail!("Already in a presentation");
+            }
+            *state = Presentation::Viewing {
+                presenter: PeerEndpoint { host: address.to_string(), port, fingerprint },
+                code,
+                started_at: chrono::Utc::now(),
+            };
+        }
+        // An existing connection was opened without the code; replace it
+        presentation::close_sessions(&ctx, |addr| addr.ip() == address).await;
+        ws.dial_peers();
+        tracing::info!("Joining presentation at {}:{}", address, port);
+        presentation::emit_status(&ctx).await;
+        let status = ctx.policy.presentation.read().await.status();
+        Ok(status)
+    }
+
+    /// Stop following the presenter.
+    pub async fn leave_presentation(&self) -> Result<()> {
+        let ctx = self.running_server()?.context();
+        let presenter = {
+            let mut state = ctx.policy.presentation.write().await;
+            let Presentation::Viewing { ref presenter, .. } = *state else {
+                anyhow::bail!("Not viewing a presentation");
+            };
+            let presenter = presenter.clone();
+            *state = Presentation::Idle;
+            presenter
+        };
+        let presenter_ip = presenter.host.parse::<IpAddr>().ok();
+        presentation::close_sessions(&ctx, |addr| Some(addr.ip()) == presenter_ip && addr.port() == presenter.port).await;
+        tracing::info!("Left presentation at {}:{}", presenter.host, presenter.port);
+        presentation::emit_status(&ctx).await;
+        Ok(())
+    }
+
+    pub async fn get_presentation_status(&self) -> PresentationStatus {
+        match self.websocket {
+            Some(ref ws) => ws.context().policy.presentation.read().await.status(),
+            None => Presentation::Idle.status(),
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::BTreeMap;
+use anyhow::{bail, Result};
+use tauri_plugin_store::StoreExt;
+use crate::models::Config;
+use crate::utils::paths;
+use super::ServiceManager;
+
+/// Settings store key of the saved config profiles, by name.
+pub const PROFILES_KEY: &str = "config_profiles";
+
+impl ServiceManager {
+    fn load_profiles(&self) -> Result<BTreeMap<String, Config>> {
+        let Some(ref app) = self.app_handle else {
+            return Ok(BTreeMap::new());
+        };
+        let store = app.store(paths::settings_store())?;
+        Ok(store
+            .get(PROFILES_KEY)
+            .and_then(|value| serde_json::from_value(value).ok())
+            .unwrap_or_default())
+    }
+
+    /// Names of the saved profiles, sorted.
+    pub fn list_profiles(&self) -> Result<Vec<String>> {
+        Ok(self.load_profiles()?.into_keys().collect())
+    }
+
+    /// Save the current config as profile `name`, replacing one with the
+    /// same name.
+    pub async fn save_profile(&self, name: String) -> Result<()> {
+        let name = name.trim().to_string();
+        if name.is_empty() {
+            bail!("Profile name must not be empty");
+        }
+        let Some(ref app) = self.app_handle else {
+            bail!("Profiles are unavailable");
+        };
+        let mut profiles = self.load_profiles()?;
+        profiles.insert(name.clone(), self.config.read().await.clone());
+        let store = app.store(paths::settings_store())?;
+        store.set(PROFILES_KEY, serde_json::to_value(&profiles)?);
+        store.save()?;
+        tracing::info!("Saved config profile {}", name);
+        Ok(())
+    }
+
+    /// Make profile `name` the active config, restarting services as a
+    /// config change would.
+    ///
+    /// # Returns
+    /// The config now in effect
+    pub async fn activate_profile(&mut self, name: String) -> Result<Config> {
+        let Some(config) = self.load_profiles()?.remove(name.trim()) else {
+            bail!("No profile named {}", name);
+        };
+        self.update_config(config.clone()).await?;
+        tracing::info!("Activated config profile {}", name);
+        Ok(config)
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::Duration;
+use anyhow::Result;
+use crate::models::Config;
+use crate::services::device_names;
+use super::ServiceManager;
+
+/// How long the old port keeps accepting after a port change, for peers
+/// that connect before they see the new announcement.
+pub const PORT_HANDOFF_GRACE: Duration = Duration::from_secs(60);
+
+/// How much of the running services a config change has to disturb.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApplyScope {
+    /// Services read the new values as they go; nothing is restarted
+    Live,
+    /// Only mDNS re-announces and rescans; peer connections stay up
+    Discovery,
+    /// The listener moves to a new port and re-announces; peer connections
+    /// stay up
+    Port,
+    /// The listener or clipboard monitor is rebuilt, so every service restarts
+    Restart,
+}
+
+/// Sort a config change by the least disruptive way to apply it. Fields
+/// not named here are read live by the services that use them.
+pub fn scope(old: &Config, new: &Config) -> ApplyScope {
+    if old.mdns_service_name != new.mdns_service_name
+        || old.tls_enabled != new.tls_enabled
+        || old.monitor_clipboard != new.monitor_clipboard
+        || old.metrics_port != new.metrics_port
+        || old.watched_folders != new.watched_folders
+    {
+        ApplyScope::Restart
+    } else if old.websocket_port != new.websocket_port {
+        ApplyScope::Port
+    } else if old.device_name != new.device_name || old.filter_same_host != new.filter_same_host {
+        ApplyScope::Discovery
+    } else {
+        ApplyScope::Live
+    }
+}
+
+impl ServiceManager {
+    /// Push a changed config into the running services without restarting
+    /// them. Restart-scope changes are left to the caller.
+    pub(super) async fn apply_live(&self, old: &Config, new: &Config, scope: ApplyScope) {
+        if let Some(ref clipboard) = self.clipboard {
+            clipboard.set_skip_concealed(new.skip_concealed_content).await;
+        }
+        device_names::set_local_device_name(&new.device_name);
+        if scope == ApplyScope::Restart {
+            return;
+        }
+
+        // A port move re-announces on its own once the new port is bound
+        if let Some(ref mdns) = self.mdns {
+            match scope {
+                ApplyScope::Discovery => {
+                    mdns.set_same_host_filter(new.filter_same_host);
+                    mdns.refresh();
+                }
+                ApplyScope::Port => mdns.set_same_host_filter(new.filter_same_host),
+                _ => {}
+            }
+        }
+        if old.static_peers != new.static_peers {
+            if let Some(ref ws) = self.websocket {
+                ws.dial_peers();
+            }
+        }
+        tracing::info!("Applied config change without restarting services ({:?})", scope);
+    }
+
+    /// Move the sync listener to `port` and announce it, keeping the old
+    /// port open for `PORT_HANDOFF_GRACE` so no peer is dropped.
+    ///
+    /// # Errors
+    /// When `port` cannot be bound; sync carries on at the old port
+    pub(super) async fn move_port(&self, port: u16) -> Result<()> {
+        let Some(ref ws) = self.websocket else {
+            return Ok(());
+        };
+        ws.move_to_port(port, PORT_HANDOFF_GRACE).await?;
+        if let Some(ref mdns) = self.mdns {
+            mdns.set_port(port);
+            mdns.refresh();
+        }
+        Ok(())
+    }
+}
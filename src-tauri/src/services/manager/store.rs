@@ -0,0 +1,115 @@
+use anyhow::Result;
+use uuid::Uuid;
+use crate::models::Config;
+use crate::services::api_tokens::{ApiTokenStore, API_TOKENS_KEY};
+use crate::services::device_names;
+use crate::services::key_derivation::{self, KeySalt, KEY_SALT_STORE_KEY};
+use crate::services::key_rotation::{KeyRing, RETIRED_KEY_STORE_KEY};
+use crate::services::tombstones::{TombstoneStore, TOMBSTONES_KEY};
+use crate::services::trust::{TrustStore, TRUST_STORE_KEY};
+use crate::utils::paths;
+use tauri_plugin_store::StoreExt;
+use super::ServiceManager;
+
+impl ServiceManager {
+    pub async fn load_config(&mut self) -> Result<()> {
+        if let Some(ref app) = self.app_handle {
+            let store = app.store(paths::settings_store())?;
+            
+            // Load config from store
+            if let Some(stored_config) = store.get("config") {
+                if let Ok(config) = serde_json::from_value::<Config>(stored_config) {
+                    *self.config.write().await = config;
+                }
+            }
+            device_names::set_local_device_name(&self.config.read().await.device_name);
+            
+            // Stable device id, created on first run
+            let device_id = store.get(device_names::DEVICE_ID_KEY)
+                .and_then(|value| serde_json::from_value::<Uuid>(value).ok());
+            let device_id = match device_id {
+                Some(id) => id,
+                None => {
+                    let id = Uuid::new_v4();
+                    store.set(device_names::DEVICE_ID_KEY, serde_json::to_value(id)?);
+                    store.save()?;
+                    tracing::info!("Created device id {}", id);
+                    id
+                }
+            };
+            device_names::set_local_device_id(device_id);
+            
+            // Salt for deriving wire keys, also created on first run
+            let key_salt = store.get(KEY_SALT_STORE_KEY)
+                .and_then(|value| serde_json::from_value::<KeySalt>(value).ok());
+            let key_salt = match key_salt {
+                Some(salt) => salt,
+                None => {
+                    let salt = key_derivation::new_salt();
+                    store.set(KEY_SALT_STORE_KEY, serde_json::to_value(salt)?);
+                    store.save()?;
+                    salt
+                }
+            };
+            self.set_key_salt(key_salt);
+            
+            // Load trust decisions
+            if let Some(stored_trust) = store.get(TRUST_STORE_KEY) {
+                match serde_json::from_value::<TrustStore>(stored_trust) {
+                    Ok(trust) => *self.trust.write().await = trust,
+                    Err(e) => tracing::warn!("Ignoring unreadable trust store: {}", e),
+                }
+            }
+            
+            // Load local API tokens
+            if let Some(stored_tokens) = store.get(API_TOKENS_KEY) {
+                match serde_json::from_value::<ApiTokenStore>(stored_tokens) {
+                    Ok(tokens) => *self.api_tokens.write().await = tokens,
+                    Err(e) => tracing::warn!("Ignoring unreadable API token store: {}", e),
+                }
+            }
+            
+            if let Some(stored_tombstones) = store.get(TOMBSTONES_KEY) {
+                match serde_json::from_value::<TombstoneStore>(stored_tombstones) {
+                    Ok(tombstones) => *self.tombstones.write().await = tombstones,
+                    Err(e) => tracing::warn!("Ignoring unreadable device tombstones: {}", e),
+                }
+            }
+            
+            if let Some(stored_ring) = store.get(RETIRED_KEY_STORE_KEY) {
+                match serde_json::from_value::<KeyRing>(stored_ring) {
+                    Ok(mut ring) => {
+                        // The grace window may have run out while the app was closed
+                        if ring.expire() {
+                            store.set(RETIRED_KEY_STORE_KEY, serde_json::to_value(&ring)?);
+                            store.save()?;
+                        }
+                        *self.key_ring.write().await = ring;
+                    }
+                    Err(e) => tracing::warn!("Ignoring unreadable previous security key: {}", e),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn save_trust(&self) -> Result<()> {
+        if let Some(ref app) = self.app_handle {
+            let store = app.store(paths::settings_store())?;
+            let trust = self.trust.read().await;
+            store.set(TRUST_STORE_KEY, serde_json::to_value(&*trust)?);
+            store.save()?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn save_config(&self) -> Result<()> {
+        if let Some(ref app) = self.app_handle {
+            let store = app.store(paths::settings_store())?;
+            let config = self.config.read().await;
+            store.set("config", serde_json::to_value(&*config)?);
+            store.save()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use anyhow::Result;
+use tauri_plugin_store::StoreExt;
+use crate::utils::paths;
+use crate::models::{ApiScope, ApiTokenInfo, CreatedApiToken};
+use crate::services::api_tokens::API_TOKENS_KEY;
+use super::ServiceManager;
+
+impl ServiceManager {
+    async fn save_api_tokens(&self) -> Result<()> {
+        if let Some(ref app) = self.app_handle {
+            let store = app.store(paths::settings_store())?;
+            let tokens = self.api_tokens.read().await;
+            store.set(API_TOKENS_KEY, serde_json::to_value(&*tokens)?);
+            store.save()?;
+        }
+        Ok(())
+    }
+
+    /// Mint a local API token; the secret is only returned here.
+    pub async fn create_api_token(&self, scope: ApiScope) -> Result<CreatedApiToken> {
+        let created = self.api_tokens.write().await.create(scope);
+        self.save_api_tokens().await?;
+        tracing::info!("Created {:?} API token {}", scope, created.info.id);
+        Ok(created)
+    }
+
+    /// Revoke a token and close connections using it. Returns whether it existed.
+    pub async fn revoke_api_token(&self, id: uuid::Uuid) -> Result<bool> {
+        let existed = self.api_tokens.write().await.revoke(id);
+        if existed {
+            self.save_api_tokens().await?;
+            if let Some(ref ws) = self.websocket {
+                ws.disconnect_api_token(id);
+            }
+            tracing::info!("Revoked API token {}", id);
+        }
+        Ok(existed)
+    }
+
+    pub async fn list_api_tokens(&self) -> Vec<ApiTokenInfo> {
+        self.api_tokens.read().await.list()
+    }
+}
@@ -0,0 +1,103 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::models::Config;
+use crate::services::{content_filter, language_routes};
+use super::ServiceManager;
+
+/// Marks a JSON document as an exported config.
+const EXPORT_FORMAT: &str = "uni-mesh-clip-config";
+/// Layout of the export envelope; bumped on incompatible changes.
+const EXPORT_VERSION: u32 = 1;
+
+/// What `export_config` writes and `import_config` reads.
+#[derive(Debug, Serialize, Deserialize)]
+struct ConfigExport {
+    format: String,
+    version: u32,
+    app_version: String,
+    exported_at: DateTime<Utc>,
+    /// The security key was left out; importing keeps the local one
+    secrets_redacted: bool,
+    config: serde_json::Value,
+}
+
+/// Check an imported config against the current schema: every field must
+/// be known and well typed, and values must make sense together.
+fn validate(value: serde_json::Value) -> Result<Config> {
+    let known = serde_json::to_value(Config::default())?;
+    let (Some(fields), Some(known)) = (value.as_object(), known.as_object()) else {
+        bail!("Config must be a JSON object");
+    };
+    let unknown: Vec<&str> = fields.keys().filter(|key| !known.contains_key(*key)).map(String::as_str).collect();
+    if !unknown.is_empty() {
+        bail!("Unknown config fields: {}", unknown.join(", "));
+    }
+
+    let config: Config = serde_json::from_value(value).context("Invalid config")?;
+    if config.websocket_port == 0 {
+        bail!("websocket_port must not be 0");
+    }
+    if config.mdns_service_name.trim().is_empty() {
+        bail!("mdns_service_name must not be empty");
+    }
+    if config.heartbeat_min_secs > config.heartbeat_max_secs {
+        bail!("heartbeat_min_secs must not exceed heartbeat_max_secs");
+    }
+    content_filter::validate(&config.content_filters)?;
+    language_routes::validate(&config.language_routes)?;
+    Ok(config)
+}
+
+impl ServiceManager {
+    /// The current config as a JSON document for `import_config`.
+    ///
+    /// # Arguments
+    /// * `redact_secrets` - Leave out the security key
+    pub async fn export_config(&self, redact_secrets: bool) -> Result<String> {
+        let mut config = self.config.read().await.clone();
+        if redact_secrets {
+            config.security_key = None;
+        }
+        let export = ConfigExport {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: Utc::now(),
+            secrets_redacted: redact_secrets,
+            config: serde_json::to_value(config)?,
+        };
+        Ok(serde_json::to_string_pretty(&export)?)
+    }
+
+    /// Replace the config with one from `export_config`, restarting
+    /// services as a config change would. This device keeps its own name,
+    /// and its security key when the export left it out.
+    ///
+    /// # Returns
+    /// The config now in effect
+    ///
+    /// # Errors
+    /// When the document is not a config export, comes from a newer
+    /// format, or the config fails validation
+    pub async fn import_config(&mut self, json: String) -> Result<Config> {
+        let export: ConfigExport = serde_json::from_str(&json).context("Not a config export")?;
+        if export.format != EXPORT_FORMAT {
+            bail!("Not a config export");
+        }
+        if export.version > EXPORT_VERSION {
+            bail!("Config was exported by a newer version ({}); update this app first", export.app_version);
+        }
+        let mut config = validate(export.config)?;
+
+        let current = self.config.read().await.clone();
+        config.device_name = current.device_name;
+        config.sync_enabled = current.sync_enabled;
+        if export.secrets_redacted {
+            config.security_key = current.security_key;
+        }
+        self.update_config(config.clone()).await?;
+        tracing::info!("Imported config exported by version {} at {}", export.app_version, export.exported_at);
+        Ok(config)
+    }
+}
@@ -0,0 +1,460 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use tokio::sync::RwLock;
+use std::time::Duration;
+use crate::models::{DeviceLiveness, DiscoveredDevice, DiscoveryStatus};
+use super::device_names::{friendly_name, DeviceDirectory};
+use super::liveness::LivenessRegistry;
+use super::mdns_health::{self, DiscoveryHealth};
+use super::shutdown::{Shutdown, StoppableTask, DRAIN_TIMEOUT};
+use super::versions::PeerVersion;
+use super::events::{self, DeviceLostEvent, DEVICE_DISCOVERED_EVENT, DEVICE_LOST_EVENT};
+use crate::utils::net;
+use mdns_sd::{ServiceDaemon, ServiceInfo, ServiceEvent};
+use tauri::AppHandle;
+use std::net::IpAddr;
+use uuid::Uuid;
+
+mod publish;
+
+const SERVICE_TYPE: &str = "_unimesh._tcp.local.";
+const DISCOVERY_INTERVAL: Duration = Duration::from_secs(5); // Check every 5 seconds
+
+// TXT properties describing this device
+const TXT_APP_VERSION: &str = "version";
+const TXT_PROTOCOL: &str = "proto";
+const TXT_MIN_PROTOCOL: &str = "min_proto";
+const TXT_NAME: &str = "name";
+const TXT_DEVICE_ID: &str = "device_id";
+const TXT_FINGERPRINT: &str = "fp";
+
+/// Metadata a device announced in its TXT record, listed under its best
+/// address with the rest kept as alternatives.
+fn device_from_txt(info: &ServiceInfo, display_name: String) -> Option<DiscoveredDevice> {
+    let mut ips: Vec<IpAddr> = info.get_addresses().iter().copied().collect();
+    ips.sort_by_key(|ip| net::rank(*ip));
+    let addr = *ips.first()?;
+    let app_version = info.get_property_val_str(TXT_APP_VERSION).map(str::to_string);
+    let protocol_version = info.get_property_val_str(TXT_PROTOCOL).and_then(|v| v.parse().ok());
+    let version_mismatch = protocol_version.and_then(|protocol| {
+        PeerVersion {
+            app_version: app_version.clone(),
+            protocol,
+            min_protocol: info.get_property_val_str(TXT_MIN_PROTOCOL).and_then(|v| v.parse().ok()).unwrap_or(protocol),
+        }
+        .compare(addr)
+    });
+    Some(DiscoveredDevice {
+        name: info.get_fullname().to_string(),
+        address: net::format_address(addr),
+        addresses: ips.into_iter().map(net::format_address).collect(),
+        port: info.get_port(),
+        last_seen: chrono::Utc::now(),
+        trusted: false,
+        paused: false,
+        tls_fingerprint: info.get_property_val_str(TXT_FINGERPRINT).map(str::to_string),
+        device_id: info.get_property_val_str(TXT_DEVICE_ID).and_then(|id| id.parse().ok()),
+        display_name,
+        app_version,
+        protocol_version,
+        version_mismatch,
+        liveness: DeviceLiveness::Online,
+        muted_until: None,
+        mute_requested_until: None,
+        guest_until: None,
+    })
+}
+
+/// Every address a discovered device announced.
+fn device_ips(device: &DiscoveredDevice) -> Vec<IpAddr> {
+    device.addresses.iter().chain([&device.address]).filter_map(|a| net::parse_ip(a)).collect()
+}
+
+pub struct MdnsService {
+    service_name: String,
+    port: AtomicU16,
+    discovered_devices: Arc<RwLock<HashMap<String, DiscoveredDevice>>>,
+    discovery: RwLock<Option<StoppableTask>>,
+    supervisor: RwLock<Option<StoppableTask>>,
+    /// Stops discovery along with the rest of sync
+    shutdown: Shutdown,
+    health: Arc<DiscoveryHealth>,
+    mdns_daemon: Arc<RwLock<Option<ServiceDaemon>>>,
+    /// Full name of our registered service, to withdraw it on stop
+    published: RwLock<Option<String>>,
+    local_service_id: String, // Random id to uniquely identify our service
+    tls_fingerprint: Option<String>,
+    /// Ignore services on one of our addresses and our port
+    filter_same_host: Arc<AtomicBool>,
+    directory: Option<Arc<DeviceDirectory>>,
+    liveness: Arc<LivenessRegistry>,
+    app: Option<AppHandle>,
+}
+
+/// Tell the UI a device went away.
+fn emit_lost(app: Option<&AppHandle>, device: &DiscoveredDevice) {
+    events::emit(app, DEVICE_LOST_EVENT, DeviceLostEvent {
+        name: device.name.clone(),
+        address: device.address.clone(),
+        port: device.port,
+    });
+}
+
+impl MdnsService {
+    pub fn new(service_name: String, port: u16) -> Self {
+        Self { 
+            service_name, 
+            port: AtomicU16::new(port),
+            discovered_devices: Arc::new(RwLock::new(HashMap::new())),
+            discovery: RwLock::new(None),
+            supervisor: RwLock::new(None),
+            shutdown: Shutdown::new(),
+            health: Arc::default(),
+            mdns_daemon: Arc::new(RwLock::new(None)),
+            published: RwLock::new(None),
+            local_service_id: Uuid::new_v4().simple().to_string()[..8].to_string(),
+            tls_fingerprint: None,
+            filter_same_host: Arc::new(AtomicBool::new(true)),
+            directory: None,
+            liveness: Arc::default(),
+            app: None,
+        }
+    }
+
+    /// Stop discovery when `shutdown` fires, with the rest of sync.
+    pub fn with_shutdown(mut self, shutdown: Shutdown) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// Emit `device-discovered` and `device-lost` events to the UI.
+    pub fn with_app_handle(mut self, app: Option<AppHandle>) -> Self {
+        self.app = app;
+        self
+    }
+
+    /// Record the friendly names of discovered devices in `directory`.
+    pub fn with_directory(mut self, directory: Arc<DeviceDirectory>) -> Self {
+        self.directory = Some(directory);
+        self
+    }
+
+    /// Share device liveness with the sync server, so a device is listed
+    /// while either discovery or a session can vouch for it.
+    pub fn with_liveness(mut self, liveness: Arc<LivenessRegistry>) -> Self {
+        self.liveness = liveness;
+        self
+    }
+
+    /// Advertise that peers should connect with TLS and pin this certificate.
+    pub fn with_tls_fingerprint(mut self, fingerprint: String) -> Self {
+        self.tls_fingerprint = Some(fingerprint);
+        self
+    }
+
+    /// Whether a service on one of our addresses and our port is taken to
+    /// be ourselves. Turn off to sync instances sharing this host's
+    /// addresses, e.g. under different users or in bridged VMs.
+    pub fn with_same_host_filter(self, enabled: bool) -> Self {
+        self.set_same_host_filter(enabled);
+        self
+    }
+
+    pub fn set_same_host_filter(&self, enabled: bool) {
+        self.filter_same_host.store(enabled, Ordering::Relaxed);
+    }
+
+    fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
+
+    /// Announce `port` as the sync port from the next `refresh` on.
+    pub fn set_port(&self, port: u16) {
+        self.port.store(port, Ordering::Relaxed);
+    }
+
+    /// Re-announce under the current device name and browse again, e.g.
+    /// after a config change. Peer connections are left alone.
+    pub fn refresh(&self) {
+        self.health.daemon_lost();
+    }
+
+    /// The address peers most likely reach this device on
+    pub fn get_local_ip() -> Option<IpAddr> {
+        net::local_addresses().first().map(|(ip, _)| *ip)
+    }
+
+    pub fn health(&self) -> Arc<DiscoveryHealth> {
+        self.health.clone()
+    }
+
+    pub fn status(&self) -> DiscoveryStatus {
+        self.health.status()
+    }
+
+    /// Run discovery and publishing in the background, recovering from
+    /// failures (see `mdns_health`), until `shutdown`.
+    pub async fn supervise(self: &Arc<Self>) {
+        let (mdns, app) = (self.clone(), self.app.clone());
+        let task = StoppableTask::spawn(&self.shutdown, |shutdown| mdns_health::supervise(mdns, app, shutdown));
+        if let Some(old) = self.supervisor.write().await.replace(task) {
+            old.stop(DRAIN_TIMEOUT, "mDNS supervisor").await;
+        }
+    }
+
+    /// Stop the supervisor, discovery, and the daemon.
+    pub async fn shutdown(&self) -> Result<()> {
+        if let Some(supervisor) = self.supervisor.write().await.take() {
+            supervisor.stop(DRAIN_TIMEOUT, "mDNS supervisor").await;
+        }
+        self.stop_discovery().await
+    }
+
+    pub async fn start_discovery(&self) -> Result<()> {
+        // Stop existing discovery if running
+        self.stop_discovery().await?;
+        
+        // Create mDNS daemon
+        let mdns_daemon = ServiceDaemon::new().map_err(|e| {
+            anyhow::anyhow!("Failed to create mDNS daemon: {}", e)
+        })?;
+        
+        *self.mdns_daemon.write().await = Some(mdns_daemon.clone());
+        
+        let devices = self.discovered_devices.clone();
+        let service_type = SERVICE_TYPE.to_string();
+        let local_service_id = self.local_service_id.clone();
+        let local_port = self.port();
+        let filter_same_host = self.filter_same_host.clone();
+        let directory = self.directory.clone();
+        let liveness = self.liveness.clone();
+        let app = self.app.clone();
+        let health = self.health.clone();
+        
+        let task = StoppableTask::spawn(&self.shutdown, |shutdown| async move {
+            tracing::info!("Starting mDNS discovery for service: {}", service_type);
+            
+            // Browse for services
+            let receiver = mdns_daemon.browse(&service_type).map_err(|e| {
+                tracing::error!("Failed to start mDNS browse: {}", e);
+                e
+            });
+            
+            if receiver.is_err() {
+                health.daemon_lost();
+                return;
+            }
+            
+            let receiver = receiver.unwrap();
+            
+            loop {
+                tokio::select! {
+                    event = tokio::task::spawn_blocking({
+                        let receiver = receiver.clone();
+                        move || receiver.recv()
+                    }) => {
+                        match event {
+                            Ok(Ok(event)) => {
+                                match event {
+                                    ServiceEvent::ServiceResolved(info) => {
+                                        tracing::info!("Discovered service: {} at {}:{}", 
+                                                      info.get_fullname(), 
+                                                      info.get_addresses().iter().next().unwrap_or(&IpAddr::from([0,0,0,0])), 
+                                                      info.get_port());
+                                        
+                                        // Simple self-filtering: check if this service contains our UUID
+                                        if info.get_fullname().contains(&local_service_id) {
+                                            tracing::debug!("Ignoring our own service: {}", info.get_fullname());
+                                            continue;
+                                        }
+                                        
+                                        // Check if this service is on the same port as ours (additional safety,
+                                        // off when several instances share this host on purpose)
+                                        if filter_same_host.load(Ordering::Relaxed) && info.get_port() == local_port {
+                                            // Check if any IP matches one of ours
+                                            let ours = net::local_addresses()
+                                                .into_iter()
+                                                .find(|(ip, _)| info.get_addresses().contains(ip));
+                                            if let Some((local_ip, _)) = ours {
+                                                tracing::debug!("Ignoring service on same IP:port as ours: {}:{}", local_ip, local_port);
+                                                continue;
+                                            }
+                                        }
+                                        
+                                        // The name the device announces, else its hostname
+                                        let mut display_name = info.get_property_val_str(TXT_NAME)
+                                            .map(str::to_string)
+                                            .unwrap_or_else(|| friendly_name(info.get_hostname()));
+                                        if display_name.is_empty() {
+                                            display_name = info.get_fullname().to_string();
+                                        }
+                                        liveness.advertised(&info.get_addresses().iter().copied().collect::<Vec<_>>());
+                                        if let Some(ref directory) = directory {
+                                            for addr in info.get_addresses() {
+                                                directory.record(*addr, display_name.clone());
+                                            }
+                                        }
+                                        
+                                        // Convert to DiscoveredDevice
+                                        if let Some(device) = device_from_txt(&info, display_name) {
+                                            
+                                            let mut devices_write = devices.write().await;
+                                            let key = format!("{}:{}", device.address, device.port);
+                                            
+                                            // Same device under a new instance name or address
+                                            if let Some(id) = device.device_id {
+                                                devices_write.retain(|old_key, old| {
+                                                    let moved = old.device_id == Some(id) && *old_key != key;
+                                                    if moved {
+                                                        tracing::info!("Device {} moved from {} to {}", id, old_key, key);
+                                                    }
+                                                    !moved
+                                                });
+                                            }
+                                            
+                                            if let Some(existing_device) = devices_write.get_mut(&key) {
+                                                existing_device.last_seen = chrono::Utc::now();
+                                                // An announcement without a fingerprint does not
+                                                // downgrade a device that advertised one
+                                                if device.tls_fingerprint.is_some() {
+                                                    existing_device.tls_fingerprint = device.tls_fingerprint;
+                                                }
+                                                existing_device.device_id = device.device_id;
+                                                existing_device.name = device.name;
+                                                existing_device.display_name = device.display_name;
+                                                existing_device.addresses = device.addresses;
+                                                existing_device.app_version = device.app_version;
+                                                existing_device.protocol_version = device.protocol_version;
+                                                existing_device.version_mismatch = device.version_mismatch;
+                                                tracing::debug!("Updated existing device: {}", key);
+                                            } else {
+                                                events::emit(app.as_ref(), DEVICE_DISCOVERED_EVENT, &device);
+                                                devices_write.insert(key.clone(), device);
+                                                tracing::info!("Added new device: {}", key);
+                                            }
+                                        }
+                                    }
+                                    ServiceEvent::ServiceRemoved(typ, fullname) => {
+                                        tracing::info!("Service removed: {} ({})", fullname, typ);
+                                        
+                                        // Don't remove our own service
+                                        if fullname.contains(&local_service_id) {
+                                            tracing::debug!("Ignoring removal of our own service: {}", fullname);
+                                            continue;
+                                        }
+                                        
+                                        // Remove from discovered devices, unless a session
+                                        // shows the device is still there
+                                        let mut devices_write = devices.write().await;
+                                        devices_write.retain(|_, device| {
+                                            if device.name != fullname {
+                                                return true;
+                                            }
+                                            let ips = device_ips(device);
+                                            liveness.withdrawn(&ips);
+                                            let should_keep = liveness.state(&ips).0 == DeviceLiveness::Connected;
+                                            if !should_keep {
+                                                tracing::info!("Removed device: {}", device.name);
+                                                emit_lost(app.as_ref(), device);
+                                            }
+                                            should_keep
+                                        });
+                                    }
+                                    ServiceEvent::SearchStarted(service_type) => {
+                                        tracing::info!("mDNS search started for: {}", service_type);
+                                    }
+                                    ServiceEvent::SearchStopped(service_type) => {
+                                        tracing::info!("mDNS search stopped for: {}", service_type);
+                                    }
+                                    _ => {
+                                        tracing::debug!("Received other mDNS event: {:?}", event);
+                                    }
+                                }
+                            }
+                            // The daemon dropped its end of the channel
+                            Ok(Err(e)) => {
+                                tracing::warn!("mDNS discovery stopped: {}", e);
+                                health.daemon_lost();
+                                break;
+                            }
+                            Err(e) => {
+                                tracing::error!("mDNS task error: {}", e);
+                                health.daemon_lost();
+                                break;
+                            }
+                        }
+                    }
+                    _ = shutdown.cancelled() => break,
+                    _ = tokio::time::sleep(DISCOVERY_INTERVAL) => {
+                        // Clean up devices neither discovery nor a session has seen lately
+                        let mut devices_write = devices.write().await;
+                        let initial_count = devices_write.len();
+                        
+                        devices_write.retain(|_key, device| {
+                            let (state, age) = liveness.state(&device_ips(device));
+                            let should_keep = state != DeviceLiveness::Offline;
+                            if !should_keep {
+                                tracing::info!("Removing stale device: {} (last seen {:?} ago)", 
+                                             device.name, age);
+                                emit_lost(app.as_ref(), device);
+                            }
+                            should_keep
+                        });
+                        liveness.prune();
+                        
+                        let final_count = devices_write.len();
+                        if initial_count != final_count {
+                            tracing::info!("Cleaned up {} stale devices, {} remaining", 
+                                         initial_count - final_count, final_count);
+                        }
+                    }
+                }
+            }
+        });
+        
+        *self.discovery.write().await = Some(task);
+        Ok(())
+    }
+
+    pub async fn stop_discovery(&self) -> Result<()> {
+        if let Some(task) = self.discovery.write().await.take() {
+            task.stop(DRAIN_TIMEOUT, "mDNS discovery").await;
+            tracing::info!("Stopped mDNS discovery");
+        }
+        
+        // Stop the mDNS daemon, first telling the network we are gone
+        let mut daemon_guard = self.mdns_daemon.write().await;
+        if let Some(daemon) = daemon_guard.take() {
+            if let Some(fullname) = self.published.write().await.take() {
+                publish::unpublish(&daemon, fullname).await;
+            }
+            daemon.shutdown().map_err(|e| {
+                anyhow::anyhow!("Failed to shutdown mDNS daemon: {}", e)
+            })?;
+        }
+        
+        Ok(())
+    }
+
+    /// Drop a device from the discovered list until it announces itself again.
+    pub async fn forget_device(&self, address: &str, port: u16) {
+        self.discovered_devices.write().await.remove(&format!("{}:{}", address, port));
+    }
+
+    pub async fn get_discovered_devices(&self) -> Vec<DiscoveredDevice> {
+        self.discovered_devices.read().await
+            .values()
+            .map(|device| {
+                let mut device = device.clone();
+                let (state, age) = self.liveness.state(&device_ips(&device));
+                device.liveness = state;
+                if let Some(age) = age.and_then(|age| chrono::Duration::from_std(age).ok()) {
+                    device.last_seen = chrono::Utc::now() - age;
+                }
+                device
+            })
+            .collect()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,130 @@
+use anyhow::Result;
+use std::time::Duration;
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+use crate::services::{device_names, versions};
+use crate::utils::net;
+use super::{MdnsService, SERVICE_TYPE, TXT_APP_VERSION, TXT_DEVICE_ID, TXT_FINGERPRINT, TXT_MIN_PROTOCOL, TXT_NAME, TXT_PROTOCOL};
+
+const MAX_INSTANCE_LEN: usize = 63; // DNS label limit, in bytes
+/// How long stopping waits for the goodbye of our service to be sent.
+const UNREGISTER_WAIT: Duration = Duration::from_secs(1);
+
+/// `<device name> (<id>)`, shortening the name to fit a DNS label.
+fn instance_name(device_name: &str, service_id: &str) -> String {
+    let suffix = format!(" ({})", service_id);
+    let mut name = device_name.trim().to_string();
+    while name.len() + suffix.len() > MAX_INSTANCE_LEN {
+        name.pop();
+    }
+    format!("{}{}", name.trim_end(), suffix)
+}
+
+/// Withdraw a registered service, waiting briefly for the goodbye packets
+/// to go out.
+pub(super) async fn unpublish(daemon: &ServiceDaemon, fullname: String) {
+    match daemon.unregister(&fullname) {
+        Ok(status) => {
+            let _ = tokio::task::spawn_blocking(move || status.recv_timeout(UNREGISTER_WAIT)).await;
+            tracing::info!("Withdrew mDNS service {}", fullname);
+        }
+        Err(e) => tracing::warn!("Failed to withdraw mDNS service {}: {}", fullname, e),
+    }
+}
+
+impl MdnsService {
+    pub async fn publish_service(&self) -> Result<()> {
+        // Every address, IPv6 included, so IPv6-only networks can reach us
+        let local_ips = net::local_addresses()
+            .into_iter()
+            .map(|(ip, _)| ip.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        if local_ips.is_empty() {
+            anyhow::bail!("No suitable local IP address found");
+        }
+        
+        let port = self.port();
+        tracing::info!("Publishing mDNS service: {} on {} port {}", 
+                      self.service_name, local_ips, port);
+        
+        // Get or create mDNS daemon
+        let daemon = {
+            let mut daemon_guard = self.mdns_daemon.write().await;
+            if daemon_guard.is_none() {
+                let new_daemon = ServiceDaemon::new().map_err(|e| {
+                    anyhow::anyhow!("Failed to create mDNS daemon for publishing: {}", e)
+                })?;
+                *daemon_guard = Some(new_daemon.clone());
+                new_daemon
+            } else {
+                daemon_guard.as_ref().unwrap().clone()
+            }
+        };
+        
+        // Readable instance name; the id keeps it unique when two devices
+        // share a name and lets discovery recognise our own service
+        let instance_name = instance_name(&device_names::local_device_name(), &self.local_service_id);
+        
+        // Get hostname for service registration
+        let hostname = hostname::get()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "localhost".to_string());
+        
+        let clean_hostname = if hostname.ends_with(".local") {
+            hostname.trim_end_matches(".local").to_string()
+        } else {
+            hostname
+        };
+        
+        // Identity and versions, so peers can list this device properly
+        // before connecting
+        let (name, protocol, min_protocol) = (
+            device_names::local_device_name(),
+            versions::PROTOCOL_VERSION.to_string(),
+            versions::MIN_PROTOCOL_VERSION.to_string(),
+        );
+        let mut properties: Vec<(&str, &str)> = vec![
+            (TXT_APP_VERSION, env!("CARGO_PKG_VERSION")),
+            (TXT_PROTOCOL, &protocol),
+            (TXT_MIN_PROTOCOL, &min_protocol),
+            (TXT_NAME, &name),
+            ("service_id", &self.local_service_id),
+        ];
+        let device_id = device_names::local_device_id().map(|id| id.to_string());
+        if let Some(ref device_id) = device_id {
+            properties.push((TXT_DEVICE_ID, device_id));
+        }
+        if let Some(ref fingerprint) = self.tls_fingerprint {
+            properties.push(("tls", "1"));
+            properties.push((TXT_FINGERPRINT, fingerprint));
+        }
+        
+        tracing::info!("Creating mDNS service: {} -> {}.local.:{}", 
+                      instance_name, clean_hostname, port);
+        
+        let service_info = ServiceInfo::new(
+            SERVICE_TYPE,
+            &instance_name,
+            &format!("{}.local.", clean_hostname),
+            local_ips.as_str(),
+            port,
+            &properties[..],
+        ).map_err(|e| {
+            anyhow::anyhow!("Failed to create service info: {}", e)
+        })?
+        // Follow address changes, e.g. when an IPv6 prefix is renewed
+        .enable_addr_auto();
+        
+        // Register the service
+        let fullname = service_info.get_fullname().to_string();
+        daemon.register(service_info).map_err(|e| {
+            anyhow::anyhow!("Failed to register mDNS service: {}", e)
+        })?;
+        *self.published.write().await = Some(fullname);
+        
+        tracing::info!("mDNS service published successfully: {} at {} port {}", 
+                      instance_name, local_ips, port);
+        
+        Ok(())
+    }
+}
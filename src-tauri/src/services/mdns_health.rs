@@ -0,0 +1,166 @@
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use socket2::{Domain, Protocol, Socket, Type};
+use tauri::AppHandle;
+use tokio::sync::Notify;
+use crate::models::{DiscoveryState, DiscoveryStatus};
+use crate::utils::net;
+use super::events::{self, DISCOVERY_STATUS_EVENT};
+use super::mdns::MdnsService;
+use super::reconnect::Backoff;
+use super::shutdown::Shutdown;
+
+const MDNS_V4: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_V6: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 0xfb);
+/// How often a network without multicast is looked at again, besides
+/// whenever the interfaces change.
+const UNAVAILABLE_RECHECK: Duration = Duration::from_secs(5 * 60);
+
+/// Why discovery could not run, and so how to recover.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MdnsFailure {
+    /// Socket busy, an interface coming or going, the daemon exiting:
+    /// retry with backoff.
+    Transient(String),
+    /// No interface can join the mDNS group. Retrying will not help until
+    /// the network changes.
+    MulticastUnavailable(String),
+}
+
+/// Sort an mDNS error by whether retrying is likely to help.
+pub fn classify(message: &str) -> MdnsFailure {
+    let lower = message.to_lowercase();
+    let no_multicast = ["join multicast group", "set multicast_if", "no such device", "cannot assign requested address"]
+        .iter()
+        .any(|hint| lower.contains(hint));
+    if no_multicast {
+        MdnsFailure::MulticastUnavailable(message.to_string())
+    } else {
+        MdnsFailure::Transient(message.to_string())
+    }
+}
+
+/// Check that at least one interface can join the mDNS multicast group.
+///
+/// # Errors
+/// A description of why none can, e.g. no network at all or only
+/// point-to-point VPN adapters
+pub fn probe_multicast() -> Result<(), String> {
+    let interfaces = net::interfaces();
+    if interfaces.is_empty() {
+        return Err("No network interface is up".to_string());
+    }
+    let mut last_error = None;
+    for interface in interfaces {
+        let joined = match interface.ip {
+            std::net::IpAddr::V4(ip) => Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)).and_then(|socket| {
+                socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)).into())?;
+                socket.join_multicast_v4(&MDNS_V4, &ip)
+            }),
+            std::net::IpAddr::V6(_) => Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP)).and_then(|socket| {
+                socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)).into())?;
+                socket.join_multicast_v6(&MDNS_V6, interface.index.unwrap_or(0))
+            }),
+        };
+        match joined {
+            Ok(()) => return Ok(()),
+            Err(e) => last_error = Some(format!("{} ({}): {}", interface.name, interface.ip, e)),
+        }
+    }
+    Err(format!("No interface can join the mDNS group; last error on {}", last_error.unwrap_or_default()))
+}
+
+/// Discovery's current state, shared between the mDNS service, its
+/// supervisor, and the interface watcher.
+#[derive(Default)]
+pub struct DiscoveryHealth {
+    status: RwLock<DiscoveryStatus>,
+    /// The daemon or its browse channel stopped
+    daemon_lost: Notify,
+    network_changed: Notify,
+}
+
+impl DiscoveryHealth {
+    pub fn status(&self) -> DiscoveryStatus {
+        self.status.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn set(&self, app: Option<&AppHandle>, status: DiscoveryStatus) {
+        *self.status.write().unwrap_or_else(|e| e.into_inner()) = status.clone();
+        events::emit(app, DISCOVERY_STATUS_EVENT, status);
+    }
+
+    /// The running daemon went away; the supervisor brings it back.
+    pub fn daemon_lost(&self) {
+        self.daemon_lost.notify_one();
+    }
+
+    /// Interfaces changed, so a network without multicast may have one now.
+    pub fn network_changed(&self) {
+        self.network_changed.notify_one();
+    }
+}
+
+/// Keep discovery and publishing running: transient failures are retried
+/// with backoff, while a network without multicast is reported as such and
+/// only looked at again when the interfaces change. Runs until
+/// `shutdown`, letting an attempt under way finish first.
+pub async fn supervise(mdns: Arc<MdnsService>, app: Option<AppHandle>, shutdown: Shutdown) {
+    let health = mdns.health();
+    let mut backoff = Backoff::default();
+    let mut attempts = 0;
+    loop {
+        let result = match probe_multicast() {
+            Ok(()) => bring_up(&mdns).await,
+            Err(reason) => Err(MdnsFailure::MulticastUnavailable(reason)),
+        };
+        let wait = match result {
+            Ok(()) => {
+                tracing::info!("mDNS discovery is running");
+                backoff = Backoff::default();
+                attempts = 0;
+                health.set(app.as_ref(), DiscoveryStatus { state: DiscoveryState::Active, ..Default::default() });
+                tokio::select! {
+                    _ = health.daemon_lost.notified() => {}
+                    _ = shutdown.cancelled() => return,
+                }
+                tracing::warn!("mDNS daemon stopped, restarting discovery");
+                continue;
+            }
+            Err(MdnsFailure::Transient(reason)) => {
+                attempts += 1;
+                let delay = backoff.failed(None);
+                tracing::warn!("mDNS failed (attempt {}), retrying in {:?}: {}", attempts, delay, reason);
+                health.set(app.as_ref(), DiscoveryStatus {
+                    state: DiscoveryState::Retrying,
+                    reason: Some(reason),
+                    attempts,
+                    next_attempt_at: chrono::Duration::from_std(delay).ok().map(|delay| chrono::Utc::now() + delay),
+                });
+                delay
+            }
+            Err(MdnsFailure::MulticastUnavailable(reason)) => {
+                tracing::error!("Multicast is unavailable on this network: {}", reason);
+                health.set(app.as_ref(), DiscoveryStatus {
+                    state: DiscoveryState::MulticastUnavailable,
+                    reason: Some(reason),
+                    ..Default::default()
+                });
+                UNAVAILABLE_RECHECK
+            }
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = health.network_changed.notified() => {
+                backoff = Backoff::default();
+            }
+            _ = shutdown.cancelled() => return,
+        }
+    }
+}
+
+async fn bring_up(mdns: &MdnsService) -> Result<(), MdnsFailure> {
+    mdns.start_discovery().await.map_err(|e| classify(&e.to_string()))?;
+    mdns.publish_service().await.map_err(|e| classify(&e.to_string()))
+}
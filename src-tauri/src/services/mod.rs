@@ -1,4 +1,61 @@
 pub mod websocket;
+pub mod connection;
 pub mod mdns;
 pub mod clipboard;
-pub mod manager;
\ No newline at end of file
+pub mod manager;
+pub mod remote_logs;
+pub mod cues;
+pub mod trust;
+pub mod history;
+pub mod access_policy;
+pub mod maintenance;
+pub mod api_tokens;
+pub mod local_api;
+pub use unimesh_proto::chunking;
+pub mod size_policy;
+pub mod clipboard_sync;
+pub mod power;
+pub mod tombstones;
+pub mod tls;
+pub mod device_names;
+pub mod notifications;
+pub mod events;
+pub mod lanes;
+pub mod peer_metrics;
+pub mod receipts;
+pub mod peer_session;
+pub mod heartbeat;
+pub mod dialer;
+pub mod versions;
+pub mod previews;
+pub mod liveness;
+pub mod reconnect;
+pub mod conflicts;
+pub mod interfaces;
+pub mod diagnostics;
+pub mod audit;
+pub mod format_filter;
+pub mod presentation;
+pub mod mdns_health;
+pub mod content_filter;
+pub mod delta_sync;
+pub mod remote_mute;
+pub mod history_crypto;
+pub mod status_summary;
+pub mod pacing;
+pub mod language_routes;
+pub mod applied_log;
+pub mod workspaces;
+pub mod sessions;
+pub mod protocol_trace;
+pub mod listener;
+pub mod announcements;
+pub mod prometheus;
+pub mod guests;
+pub mod framing;
+pub mod folder_watch;
+pub mod shutdown;
+pub mod peer_capabilities;
+pub mod key_rotation;
+pub mod key_derivation;
+pub mod blob_store;
\ No newline at end of file
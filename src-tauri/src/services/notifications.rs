@@ -0,0 +1,34 @@
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+use crate::models::DeviceInfo;
+
+/// Longest preview shown in a notification, in characters.
+const PREVIEW_CHARS: usize = 80;
+
+/// First line of `text`, shortened to fit a notification body.
+fn preview(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or_default();
+    let mut preview: String = line.chars().take(PREVIEW_CHARS).collect();
+    if line.chars().count() > PREVIEW_CHARS || text.trim().lines().nth(1).is_some() {
+        preview.push('…');
+    }
+    preview
+}
+
+/// Show an OS notification for a clip applied from `device`, naming its
+/// platform when the sender announced one.
+pub fn notify_remote_clip(app: &AppHandle, device: &str, sender: Option<&DeviceInfo>, text: &str) {
+    let title = match sender {
+        Some(info) => format!("Clipboard from {} ({})", device, info.platform),
+        None => format!("Clipboard from {}", device),
+    };
+    let result = app
+        .notification()
+        .builder()
+        .title(title)
+        .body(preview(text))
+        .show();
+    if let Err(e) = result {
+        tracing::warn!("Failed to show clipboard notification: {}", e);
+    }
+}
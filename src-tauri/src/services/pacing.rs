@@ -0,0 +1,67 @@
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+use crate::models::Config;
+
+/// Token bucket that holds at most one second of traffic.
+struct TokenBucket {
+    /// Bytes per second
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self { rate, tokens: rate, last: Instant::now() }
+    }
+
+    /// Spend `bytes`, waiting until the bucket has refilled enough to
+    /// cover them. A frame larger than the bucket goes out at once and is
+    /// paid off by waiting afterwards.
+    async fn take(&mut self, bytes: usize) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * self.rate).min(self.rate);
+        self.last = now;
+        self.tokens -= bytes as f64;
+        if self.tokens < 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(-self.tokens / self.rate)).await;
+            self.tokens = 0.0;
+            self.last = Instant::now();
+        }
+    }
+}
+
+/// Paces frames to one peer at the rate set in `Config::bandwidth_caps`.
+/// The cap is read on every frame, so changes apply to open connections.
+pub(crate) struct Pacer {
+    config: Arc<RwLock<Config>>,
+    ip: IpAddr,
+    bucket: Option<TokenBucket>,
+}
+
+impl Pacer {
+    pub fn new(config: Arc<RwLock<Config>>, ip: IpAddr) -> Self {
+        Self { config, ip, bucket: None }
+    }
+
+    /// Wait until `bytes` more may be sent without exceeding the cap.
+    pub async fn pace(&mut self, bytes: usize) {
+        let rate = self.config.read().await
+            .bandwidth_caps
+            .get(&self.ip.to_string())
+            .copied()
+            .filter(|kib| *kib > 0)
+            .map(|kib| f64::from(kib) * 1024.0);
+        let Some(rate) = rate else {
+            self.bucket = None;
+            return;
+        };
+        let bucket = self.bucket.get_or_insert_with(|| TokenBucket::new(rate));
+        if bucket.rate != rate {
+            *bucket = TokenBucket::new(rate);
+        }
+        bucket.take(bytes).await;
+    }
+}
@@ -0,0 +1,92 @@
+//! What each connected peer declared it can take in its `Hello`, and
+//! tailoring clips to it before they go out.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+use crate::models::{ClipboardContent, ClipboardMessage, ContentDescriptor, Hello, MessageType, PeerCapabilities};
+use crate::utils::hashing;
+use super::connection::ConnectionContext;
+
+/// Clip formats this build reads.
+pub const FORMATS: &[&str] = &["text", "html", "rtf"];
+/// Compression schemes this build reads; none so far.
+pub const COMPRESSION: &[&str] = &[];
+
+/// The capabilities in `hello`, or `None` when it declares none, as
+/// builds from before capabilities do.
+pub fn from_hello(hello: &Hello) -> Option<PeerCapabilities> {
+    if hello.formats.is_empty() {
+        return None;
+    }
+    Some(PeerCapabilities {
+        device_id: hello.device_id,
+        name: hello.name.clone(),
+        formats: hello.formats.clone(),
+        max_message_bytes: hello.max_message_bytes.filter(|max| *max > 0),
+        compression: hello.compression.clone(),
+    })
+}
+
+/// Declared capabilities by connection. Peers without an entry are sent
+/// everything.
+#[derive(Default)]
+pub struct CapabilityRegistry {
+    peers: RwLock<HashMap<Uuid, PeerCapabilities>>,
+}
+
+impl CapabilityRegistry {
+    pub fn record(&self, peer_id: Uuid, capabilities: PeerCapabilities) {
+        self.peers.write().unwrap_or_else(|e| e.into_inner()).insert(peer_id, capabilities);
+    }
+
+    pub fn forget(&self, peer_id: Uuid) {
+        self.peers.write().unwrap_or_else(|e| e.into_inner()).remove(&peer_id);
+    }
+
+    pub fn get(&self, peer_id: Uuid) -> Option<PeerCapabilities> {
+        self.peers.read().unwrap_or_else(|e| e.into_inner()).get(&peer_id).cloned()
+    }
+}
+
+/// Our side of the `Hello`: who we are and what we take.
+pub(crate) async fn fill_hello(ctx: &ConnectionContext, hello: &mut Hello) {
+    let max = ctx.config.read().await.max_content_bytes;
+    hello.device_id = super::device_names::local_device_id();
+    hello.name = Some(super::device_names::local_device_name());
+    hello.formats = FORMATS.iter().map(|format| format.to_string()).collect();
+    hello.max_message_bytes = (max > 0).then_some(max);
+    hello.compression = COMPRESSION.iter().map(|scheme| scheme.to_string()).collect();
+}
+
+/// Whether a peer with `capabilities` takes `message`: clips larger than
+/// it declared are not sent. Anything but a clip is.
+pub fn fits(capabilities: &PeerCapabilities, message: &ClipboardMessage) -> bool {
+    match (&message.msg_type, &message.descriptor, capabilities.max_message_bytes) {
+        (MessageType::ClipboardUpdate, Some(descriptor), Some(max)) => descriptor.size <= max,
+        _ => true,
+    }
+}
+
+/// Drop the rich formats of a clip that a peer with `capabilities` does
+/// not read, leaving its text. Sealed clips cannot be trimmed and go
+/// whole; the peer falls back to their text. Returns whether the message
+/// changed and so must be signed again.
+pub fn tailor(capabilities: &PeerCapabilities, message: &mut ClipboardMessage) -> bool {
+    if message.msg_type != MessageType::ClipboardUpdate || message.encrypted || message.formats.is_none() {
+        return false;
+    }
+    let Some(ref descriptor) = message.descriptor else {
+        return false;
+    };
+    if descriptor.formats.iter().all(|format| capabilities.formats.contains(format)) {
+        return false;
+    }
+    let plain = ClipboardContent::plain(message.content.clone().unwrap_or_default());
+    message.formats = None;
+    let content_hash = hashing::hash_content(&plain);
+    message.descriptor = Some(ContentDescriptor::of(&plain, &content_hash));
+    message.content_hash = Some(content_hash);
+    tracing::debug!("Sending the text of clip {} only", message.id);
+    true
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::models::{ClipOrigin, ContentKind, PeerMetrics};
+use super::connection::{ConnectionContext, TelemetryContext};
+
+/// Counters of live peer connections, keyed by connection id.
+pub(crate) type PeerMetricsMap = Arc<RwLock<HashMap<Uuid, PeerMetrics>>>;
+
+async fn update(telemetry: &TelemetryContext, peer_id: Uuid, apply: impl FnOnce(&mut PeerMetrics)) {
+    if let Some(metrics) = telemetry.peer_metrics.write().await.get_mut(&peer_id) {
+        apply(metrics);
+        metrics.last_activity = chrono::Utc::now();
+    }
+}
+
+pub(crate) async fn record_received(telemetry: &TelemetryContext, peer_id: Uuid, bytes: usize) {
+    update(telemetry, peer_id, |metrics| {
+        metrics.messages_received += 1;
+        metrics.bytes_received += bytes as u64;
+    }).await;
+}
+
+pub(crate) async fn record_sent(telemetry: &TelemetryContext, peer_id: Uuid, bytes: usize) {
+    update(telemetry, peer_id, |metrics| {
+        metrics.messages_sent += 1;
+        metrics.bytes_sent += bytes as u64;
+    }).await;
+}
+
+pub(crate) async fn record_heartbeat(telemetry: &TelemetryContext, peer_id: Uuid) {
+    update(telemetry, peer_id, |metrics| metrics.last_heartbeat = Some(chrono::Utc::now())).await;
+}
+
+pub(crate) async fn record_heartbeat_interval(telemetry: &TelemetryContext, peer_id: Uuid, interval: Duration) {
+    if let Some(metrics) = telemetry.peer_metrics.write().await.get_mut(&peer_id) {
+        metrics.heartbeat_interval_secs = interval.as_secs();
+    }
+}
+
+/// Count a rejected message against the peer and the global totals.
+pub(crate) async fn record_rejected(telemetry: &TelemetryContext, peer_id: Uuid) {
+    telemetry.sync_metrics.write().await.messages_rejected += 1;
+    update(telemetry, peer_id, |metrics| metrics.failures += 1).await;
+}
+
+/// Zero the message, byte, and failure counters of every connected peer.
+pub(crate) async fn reset(telemetry: &TelemetryContext) {
+    for metrics in telemetry.peer_metrics.write().await.values_mut() {
+        metrics.messages_sent = 0;
+        metrics.messages_received = 0;
+        metrics.bytes_sent = 0;
+        metrics.bytes_received = 0;
+        metrics.failures = 0;
+    }
+}
+
+/// Snapshot of every connected peer, with device names resolved now.
+pub(crate) async fn snapshot(ctx: &ConnectionContext) -> Vec<PeerMetrics> {
+    let mut peers: Vec<PeerMetrics> = ctx.telemetry.peer_metrics.read().await.values().cloned().collect();
+    for metrics in &mut peers {
+        let origin = ClipOrigin { peer_id: metrics.peer_id, address: metrics.address, device: None, message_id: None, sent_at: None, received_at: None, kind: ContentKind::Text };
+        metrics.device = Some(ctx.directory.name_for(&origin));
+        metrics.capabilities = ctx.policy.capabilities.get(metrics.peer_id);
+    }
+    peers.sort_by_key(|metrics| metrics.connected_at);
+    peers
+}
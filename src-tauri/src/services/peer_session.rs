@@ -0,0 +1,289 @@
+use std::net::SocketAddr;
+use anyhow::Result;
+use futures_util::StreamExt;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+use tokio_tungstenite::WebSocketStream;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+use crate::models::{ClipboardMessage, ConnectionDirection, MessageType, PeerMetrics, Protection};
+use super::chunking::{self, ChunkFrame, Reassembler};
+use super::framing::{self, Framing};
+use super::connection::ConnectionContext;
+use super::heartbeat::{self, Cadence};
+use super::key_derivation::KeySalt;
+use super::key_rotation;
+use super::lanes::{self, Lane, PeerLink};
+use super::language_routes;
+use super::pacing::Pacer;
+use super::peer_metrics;
+use super::presentation;
+use super::protocol_trace;
+use super::size_policy;
+use super::versions;
+use crate::utils::net;
+
+/// Heartbeat intervals a peer may stay silent before it is considered gone.
+pub const MISSED_HEARTBEATS: u32 = 3;
+
+/// Send every peer a close frame and wait up to `grace` for the sessions
+/// to end, so peers drop the connection now rather than at their next
+/// missed heartbeat.
+pub(crate) async fn close_all(ctx: &ConnectionContext, grace: std::time::Duration) {
+    for (_, link) in ctx.peers.read().await.values() {
+        let _ = link.send(Lane::Control, Message::Close(None));
+    }
+    let deadline = Instant::now() + grace;
+    while !ctx.peers.read().await.is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+}
+
+/// Send one heartbeat. Older peers ignore `Heartbeat` messages but still
+/// answer the ping, so they are not dropped for staying quiet.
+async fn send_heartbeat(ctx: &ConnectionContext, peer_id: Uuid, link: &PeerLink) {
+    let message = ClipboardMessage::new(MessageType::Heartbeat, None).with_protection(Protection::Signed);
+    if let Err(e) = ctx.send_to_peer(peer_id, message).await {
+        tracing::debug!("Failed to send heartbeat to {}: {}", peer_id, e);
+    }
+    let _ = link.send(Lane::Control, Message::Ping(Default::default()));
+}
+
+/// Largest raw message worth accepting under the configured size policy.
+async fn wire_limit(ctx: &ConnectionContext) -> Option<usize> {
+    size_policy::wire_limit(&*ctx.config.read().await).map(|limit| limit as usize)
+}
+
+/// How a session reached its peer.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Route {
+    pub direction: ConnectionDirection,
+    /// Our end of the connection, which tells the interface in use
+    pub local: Option<SocketAddr>,
+    /// The peer offered delta sync in its handshake
+    pub delta: bool,
+    /// Session agreed in the handshake, unless the peer predates sessions
+    pub session: Option<Uuid>,
+    /// The peer takes `Hello` messages
+    pub hello: bool,
+    /// Encoding both sides agreed on for frames
+    pub framing: Framing,
+    /// The peer's salt, unless it predates derived keys
+    pub key_salt: Option<KeySalt>,
+}
+
+/// Run a peer connection until it closes: register the peer, pump its
+/// lanes, and forward broadcasts to it. It also ends when `peer_close`
+/// names it, e.g. because its interface went away.
+pub(crate) async fn run<S>(ws_stream: WebSocketStream<S>, addr: SocketAddr, route: Route, ctx: ConnectionContext) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let peer_id = Uuid::new_v4();
+    tracing::info!("New {:?} WebSocket connection with {} with id {}", route.direction, addr, peer_id);
+
+    let (ws_sender, mut ws_receiver) = ws_stream.split();
+    let (link, control_rx, data_rx) = PeerLink::new();
+
+    // Add peer to the map
+    ctx.peers.write().await.insert(peer_id, (addr, link.clone()));
+    if route.delta {
+        ctx.policy.delta.connected(peer_id);
+    }
+    if let Some(session) = route.session {
+        ctx.security.sessions.open(peer_id, session);
+    }
+    if let Some(salt) = route.key_salt {
+        ctx.security.keys.open(peer_id, salt);
+    }
+    let mut metrics = PeerMetrics::new(peer_id, addr, route.direction);
+    metrics.local_address = route.local;
+    metrics.interface = route.local.and_then(|local| net::interface_name(local.ip()));
+    ctx.telemetry.peer_metrics.write().await.insert(peer_id, metrics);
+    
+    // Update connected peers count
+    {
+        let mut metrics = ctx.telemetry.sync_metrics.write().await;
+        metrics.connected_peers = ctx.peers.read().await.len() as u32;
+    }
+    ctx.telemetry.liveness.connected(addr.ip());
+    ctx.emit_peer_event(true, peer_id, addr).await;
+
+    // Chunk frames of large messages go through a separate queue that
+    // yields to everything else, so small updates never wait behind them
+    let (bulk_tx, bulk_rx) = tokio::sync::mpsc::unbounded_channel::<Message>();
+    lanes::spawn_writer(ws_sender, control_rx, data_rx, bulk_rx, Pacer::new(ctx.config.clone(), addr.ip()), route.framing, &ctx.shutdown);
+    let data_worker = lanes::spawn_data_worker(ctx.clone(), peer_id);
+
+    if route.hello {
+        versions::send_hello(&ctx, peer_id).await;
+    }
+    // Peers that connect during a key change's grace window hear of it too
+    key_rotation::announce_to(&ctx, peer_id).await;
+
+    // Let the new peer know right away if we are paused
+    if *ctx.policy.paused.read().await {
+        if let Err(e) = ctx.send_sync_state(peer_id, true).await {
+            tracing::warn!("Failed to send sync state to {}: {}", peer_id, e);
+        }
+    }
+
+    // Subscribe to broadcast messages
+    let mut rx = ctx.tx.subscribe();
+    let mut close = ctx.peer_close.subscribe();
+
+    let mut reassembler = Reassembler::default();
+
+    let mut cadence = Cadence::for_peer(&ctx.telemetry.link_history, addr.ip(), &*ctx.config.read().await).await;
+    peer_metrics::record_heartbeat_interval(&ctx.telemetry, peer_id, cadence.interval()).await;
+    let mut last_beat = Instant::now();
+    let mut last_inbound = last_beat;
+    let mut dropped = false;
+
+    // Handle incoming messages
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(last_beat + cadence.interval()) => {
+                // Any frame counts, so a half-open TCP session is noticed
+                // even when the peer has nothing to say
+                if last_inbound.elapsed() > cadence.interval() * MISSED_HEARTBEATS {
+                    tracing::warn!("Peer {} missed {} heartbeats, dropping the connection", peer_id, MISSED_HEARTBEATS);
+                    dropped = true;
+                    break;
+                }
+                let heard = last_inbound > last_beat;
+                if heard {
+                    ctx.telemetry.liveness.heard(addr.ip());
+                }
+                if cadence.observe(heard) {
+                    tracing::debug!("Heartbeat interval for {} is now {:?}", peer_id, cadence.interval());
+                    peer_metrics::record_heartbeat_interval(&ctx.telemetry, peer_id, cadence.interval()).await;
+                }
+                last_beat = Instant::now();
+                send_heartbeat(&ctx, peer_id, &link).await;
+            }
+            msg = ws_receiver.next() => {
+                if matches!(msg, Some(Ok(_))) {
+                    last_inbound = Instant::now();
+                }
+                match msg {
+                    Some(Ok(Message::Text(text))) if ChunkFrame::looks_like_chunk(&text) => {
+                        let max_bytes = wire_limit(&ctx).await.unwrap_or(chunking::MAX_REASSEMBLY_BYTES);
+                        let complete = serde_json::from_str::<ChunkFrame>(&text)
+                            .map_err(|e| e.to_string())
+                            .and_then(|frame| reassembler.accept(frame, max_bytes));
+                        match complete {
+                            Ok(Some(message)) => lanes::dispatch(&ctx, peer_id, &data_worker, &message).await,
+                            Ok(None) => {}
+                            Err(e) => {
+                                tracing::warn!("Discarding chunked message from {}: {}", peer_id, e);
+                                peer_metrics::record_rejected(&ctx.telemetry, peer_id).await;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Text(text))) => {
+                        if wire_limit(&ctx).await.is_some_and(|limit| text.len() > limit) {
+                            tracing::info!("Dropping {} byte frame from {}: over the size limit", text.len(), peer_id);
+                            peer_metrics::record_rejected(&ctx.telemetry, peer_id).await;
+                            continue;
+                        }
+                        lanes::dispatch(&ctx, peer_id, &data_worker, &text).await;
+                    }
+                    Some(Ok(Message::Binary(bytes))) => {
+                        if wire_limit(&ctx).await.is_some_and(|limit| bytes.len() > limit) {
+                            tracing::info!("Dropping {} byte frame from {}: over the size limit", bytes.len(), peer_id);
+                            peer_metrics::record_rejected(&ctx.telemetry, peer_id).await;
+                            continue;
+                        }
+                        match framing::decode(&bytes) {
+                            Ok(text) => lanes::dispatch(&ctx, peer_id, &data_worker, &text).await,
+                            Err(e) => {
+                                tracing::warn!("Discarding binary frame from {}: {}", peer_id, e);
+                                peer_metrics::record_rejected(&ctx.telemetry, peer_id).await;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) => {
+                        tracing::info!("WebSocket connection closed for {}", peer_id);
+                        break;
+                    }
+                    None => {
+                        tracing::info!("WebSocket connection for {} ended without a close frame", peer_id);
+                        dropped = true;
+                        break;
+                    }
+                    Some(Err(e)) => {
+                        tracing::error!("WebSocket error for {}: {}", peer_id, e);
+                        dropped = true;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            Ok(closing) = close.recv() => {
+                if closing == peer_id {
+                    tracing::info!("Closing connection to {} on request", peer_id);
+                    let _ = link.send(Lane::Control, Message::Close(None));
+                    break;
+                }
+            }
+            broadcast_msg = rx.recv() => {
+                // Each connection task forwards to its own peer only;
+                // don't echo back to sender
+                if let Ok(outbound) = broadcast_msg {
+                    if outbound.origin == Some(peer_id) {
+                        continue;
+                    }
+                    // Pause and trust only hold back clipboard data
+                    if outbound.lane == Lane::Control {
+                        let Some(json) = ctx.frame_for(peer_id, &outbound).await else {
+                            continue;
+                        };
+                        protocol_trace::sent(Some(addr), outbound.id, outbound.msg_type, json.len());
+                        peer_metrics::record_sent(&ctx.telemetry, peer_id, json.len()).await;
+                        let _ = link.send(Lane::Control, Message::Text(json.into()));
+                    } else if ctx.should_send_to(peer_id).await && language_routes::allows(&ctx, outbound.language, addr).await {
+                        let Some(json) = ctx.frame_for(peer_id, &outbound).await else {
+                            continue;
+                        };
+                        protocol_trace::sent(Some(addr), outbound.id, outbound.msg_type, json.len());
+                        peer_metrics::record_sent(&ctx.telemetry, peer_id, json.len()).await;
+                        match chunking::split(outbound.id, &json) {
+                            Some(frames) => {
+                                for frame in frames {
+                                    let _ = bulk_tx.send(Message::Text(frame.into()));
+                                }
+                            }
+                            None => {
+                                let _ = link.send(Lane::Data, Message::Text(json.into()));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    heartbeat::record_end(&ctx.telemetry.link_history, addr.ip(), &cadence, dropped).await;
+    protocol_trace::closed(route.direction, addr, if dropped { "dropped" } else { "closed" });
+    ctx.telemetry.liveness.disconnected(addr.ip());
+    presentation::viewer_left(&ctx, addr).await;
+
+    // Remove peer from map on disconnect
+    ctx.peers.write().await.remove(&peer_id);
+    ctx.policy.delta.disconnected(peer_id);
+    ctx.security.sessions.close(peer_id);
+    ctx.policy.capabilities.forget(peer_id);
+    ctx.security.keys.close(peer_id);
+    ctx.policy.paused_peers.write().await.remove(&peer_id);
+    ctx.telemetry.peer_metrics.write().await.remove(&peer_id);
+    
+    // Update connected peers count
+    {
+        let mut metrics = ctx.telemetry.sync_metrics.write().await;
+        metrics.connected_peers = ctx.peers.read().await.len() as u32;
+    }
+    ctx.emit_peer_event(false, peer_id, addr).await;
+    
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use std::time::SystemTime;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tokio::time::Duration;
+use crate::models::ResumePolicy;
+use super::manager::{ManagerHandle, ServiceManager};
+use super::shutdown::Shutdown;
+
+pub const RESUME_EVENT: &str = "system-resumed";
+/// How often the watcher compares the wall clock against the monotonic clock.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// Wall-clock time beyond the poll interval that counts as a sleep. Large
+/// enough that NTP adjustments and a busy runtime do not trigger it.
+const SLEEP_THRESHOLD: Duration = Duration::from_secs(60);
+
+/// Payload of the `system-resumed` event.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResumeEvent {
+    pub policy: ResumePolicy,
+    /// Roughly how long the machine was asleep
+    pub slept_secs: u64,
+    /// Whether sync is paused after applying the policy
+    pub paused: bool,
+    /// Set in `ask` mode: the UI should offer to resume
+    pub awaiting_decision: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Watch for system sleep and apply the post-resume policy on wake.
+///
+/// Suspend is detected without platform hooks: the monotonic clock tokio
+/// sleeps on stops while the machine is asleep, the wall clock does not.
+/// The watcher stops with `shutdown`.
+pub fn spawn(app: AppHandle, manager: ManagerHandle, shutdown: &Shutdown) {
+    let stop = shutdown.clone();
+    shutdown.spawn(async move {
+        let mut last = SystemTime::now();
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = stop.cancelled() => break,
+            }
+            let now = SystemTime::now();
+            let elapsed = now.duration_since(last).unwrap_or_default();
+            last = now;
+            if elapsed > POLL_INTERVAL + SLEEP_THRESHOLD {
+                let app = app.clone();
+                let slept = elapsed - POLL_INTERVAL;
+                if let Err(e) = manager.query(move |manager| Box::pin(async move { on_resume(&app, manager, slept).await })).await {
+                    tracing::warn!("Could not apply the resume policy: {}", e);
+                }
+            }
+        }
+    });
+}
+
+async fn on_resume(app: &AppHandle, manager: &ServiceManager, slept: Duration) {
+    tracing::info!("System resumed after about {}s", slept.as_secs());
+    if !manager.is_running().await {
+        return;
+    }
+
+    let policy = manager.get_config().await.resume_policy;
+    let was_paused = manager.is_paused().await;
+    if policy != ResumePolicy::Auto && !was_paused {
+        if let Err(e) = manager.set_paused(true).await {
+            tracing::warn!("Failed to pause sync after resume: {}", e);
+        }
+    }
+
+    // A pause the user chose before sleeping is left alone in every mode
+    let event = ResumeEvent {
+        policy,
+        slept_secs: slept.as_secs(),
+        paused: manager.is_paused().await,
+        awaiting_decision: policy == ResumePolicy::Ask && !was_paused,
+        timestamp: Utc::now(),
+    };
+    if let Err(e) = app.emit(RESUME_EVENT, &event) {
+        tracing::warn!("Failed to emit resume event: {}", e);
+    }
+}
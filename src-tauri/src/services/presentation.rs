@@ -0,0 +1,177 @@
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue, StatusCode};
+use uuid::Uuid;
+use crate::models::{PeerEndpoint, PresentationRole, PresentationStatus};
+use super::connection::ConnectionContext;
+use super::events::{self, PRESENTATION_CHANGED_EVENT};
+
+/// Handshake header a viewer presents its session code in.
+const SESSION_CODE_HEADER: &str = "x-unimesh-session-code";
+/// Session codes avoid characters that are easy to misread aloud.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const CODE_LEN: usize = 6;
+
+/// This device's part in a presentation, if any.
+#[derive(Debug, Clone, Default)]
+pub enum Presentation {
+    #[default]
+    Idle,
+    /// Broadcasting to viewers that joined with `code`
+    Presenting {
+        code: String,
+        started_at: DateTime<Utc>,
+        viewers: HashSet<SocketAddr>,
+    },
+    /// Following a presenter; nothing is sent back to it
+    Viewing {
+        presenter: PeerEndpoint,
+        code: String,
+        started_at: DateTime<Utc>,
+    },
+}
+
+pub(crate) type SharedPresentation = Arc<RwLock<Presentation>>;
+
+/// A fresh code for viewers to join with.
+pub fn new_code() -> String {
+    (0..CODE_LEN)
+        .map(|_| CODE_ALPHABET[fastrand::usize(..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+/// Normalise a code as typed by a user.
+pub fn normalize_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+impl Presentation {
+    pub fn status(&self) -> PresentationStatus {
+        match self {
+            Presentation::Idle => PresentationStatus {
+                role: PresentationRole::Idle,
+                code: None,
+                presenter: None,
+                viewers: Vec::new(),
+                started_at: None,
+            },
+            Presentation::Presenting { code, started_at, viewers } => PresentationStatus {
+                role: PresentationRole::Presenting,
+                code: Some(code.clone()),
+                presenter: None,
+                viewers: viewers.iter().map(|addr| addr.to_string()).collect(),
+                started_at: Some(*started_at),
+            },
+            Presentation::Viewing { presenter, code, started_at } => PresentationStatus {
+                role: PresentationRole::Viewing,
+                code: Some(code.clone()),
+                presenter: Some(format!("{}:{}", presenter.host, presenter.port)),
+                viewers: Vec::new(),
+                started_at: Some(*started_at),
+            },
+        }
+    }
+
+    /// Code incoming viewers must present, while presenting.
+    pub fn expected_code(&self) -> Option<String> {
+        match self {
+            Presentation::Presenting { code, .. } => Some(code.clone()),
+            _ => None,
+        }
+    }
+
+    /// Code to present when dialing `addr`, if it is the presenter we view.
+    pub fn code_for(&self, addr: SocketAddr) -> Option<String> {
+        match self {
+            Presentation::Viewing { code, .. } if self.is_presenter(addr) => Some(code.clone()),
+            _ => None,
+        }
+    }
+
+    /// Presenter to dial while viewing.
+    pub fn presenter(&self) -> Option<&PeerEndpoint> {
+        match self {
+            Presentation::Viewing { presenter, .. } => Some(presenter),
+            _ => None,
+        }
+    }
+
+    pub fn is_viewer(&self, addr: SocketAddr) -> bool {
+        matches!(self, Presentation::Presenting { viewers, .. } if viewers.contains(&addr))
+    }
+
+    /// Whether `addr` is the presenter this device is watching.
+    pub fn is_presenter(&self, addr: SocketAddr) -> bool {
+        matches!(self, Presentation::Viewing { presenter, .. }
+            if presenter.port == addr.port() && crate::utils::net::parse_ip(&presenter.host) == Some(addr.ip()))
+    }
+}
+
+/// Add the session code to a viewer's handshake request.
+pub fn present_code(headers: &mut HeaderMap, code: &str) {
+    if let Ok(value) = HeaderValue::from_str(code) {
+        headers.insert(SESSION_CODE_HEADER, value);
+    }
+}
+
+/// The code a connecting viewer presented, if any.
+pub fn presented_code(headers: &HeaderMap) -> Option<String> {
+    headers.get(SESSION_CODE_HEADER)?.to_str().ok().map(normalize_code)
+}
+
+/// Check a viewer's code during the handshake. Connections without a code
+/// are ordinary peers and pass through.
+// `ErrorResponse` is the rejection type tungstenite's handshake callback expects
+#[allow(clippy::result_large_err)]
+pub fn check_handshake(presented: Option<&str>, expected: Option<&str>, response: Response) -> Result<Response, ErrorResponse> {
+    match presented {
+        Some(code) if Some(code) != expected => {
+            let mut refusal = ErrorResponse::new(Some("Invalid or expired session code".to_string()));
+            *refusal.status_mut() = StatusCode::FORBIDDEN;
+            Err(refusal)
+        }
+        _ => Ok(response),
+    }
+}
+
+/// Record a viewer that joined with a valid code.
+pub(crate) async fn admit(ctx: &ConnectionContext, addr: SocketAddr) {
+    if let Presentation::Presenting { ref mut viewers, .. } = *ctx.policy.presentation.write().await {
+        tracing::info!("Viewer {} joined the presentation", addr);
+        viewers.insert(addr);
+    }
+    emit_status(ctx).await;
+}
+
+/// Forget a viewer whose session ended.
+pub(crate) async fn viewer_left(ctx: &ConnectionContext, addr: SocketAddr) {
+    let removed = match *ctx.policy.presentation.write().await {
+        Presentation::Presenting { ref mut viewers, .. } => viewers.remove(&addr),
+        _ => false,
+    };
+    if removed {
+        tracing::info!("Viewer {} left the presentation", addr);
+        emit_status(ctx).await;
+    }
+}
+
+/// Close every session matching `matches`, e.g. all viewers on teardown.
+pub(crate) async fn close_sessions(ctx: &ConnectionContext, matches: impl Fn(SocketAddr) -> bool) {
+    let closing: Vec<Uuid> = ctx.peers.read().await
+        .iter()
+        .filter(|(_, (addr, _))| matches(*addr))
+        .map(|(id, _)| *id)
+        .collect();
+    for peer_id in closing {
+        let _ = ctx.peer_close.send(peer_id);
+    }
+}
+
+pub(crate) async fn emit_status(ctx: &ConnectionContext) {
+    let status = ctx.policy.presentation.read().await.status();
+    events::emit(ctx.app.as_ref(), PRESENTATION_CHANGED_EVENT, status);
+}
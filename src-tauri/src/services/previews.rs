@@ -0,0 +1,81 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use uuid::Uuid;
+use crate::models::{ClipboardContent, ContentKind};
+use crate::utils::hashing;
+use super::events::SyncDirection;
+use super::size_policy;
+
+/// Previews kept; older ones are dropped first.
+const CAPACITY: usize = 200;
+/// Longest first line kept, in characters.
+const FIRST_LINE_CHARS: usize = 120;
+
+/// What the UI needs to show a clip without fetching its content.
+#[derive(Debug, Clone, Serialize)]
+pub struct MessagePreview {
+    pub message_id: Uuid,
+    pub direction: SyncDirection,
+    pub kind: ContentKind,
+    /// Formats the clip carries besides plain text, e.g. `html`
+    pub rich_formats: Vec<String>,
+    pub bytes: u64,
+    pub first_line: String,
+    /// Whether the text goes on past `first_line`
+    pub truncated: bool,
+    pub content_hash: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl MessagePreview {
+    fn new(message_id: Uuid, direction: SyncDirection, content: &ClipboardContent) -> Self {
+        let text = content.text.trim();
+        let line = text.lines().next().unwrap_or_default();
+        let first_line: String = line.chars().take(FIRST_LINE_CHARS).collect();
+        let rich_formats = [("html", &content.html), ("rtf", &content.rtf)]
+            .into_iter()
+            .filter(|(_, value)| value.is_some())
+            .map(|(name, _)| name.to_string())
+            .collect();
+        Self {
+            message_id,
+            direction,
+            kind: ContentKind::Text,
+            rich_formats,
+            bytes: size_policy::content_size(content),
+            truncated: first_line.len() < text.len(),
+            first_line,
+            content_hash: hashing::hash_content(content),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Recent clip previews by message id, so activity feeds can show what
+/// was sent or received without the content crossing IPC again.
+#[derive(Debug, Default)]
+pub struct PreviewCache {
+    entries: Mutex<(HashMap<Uuid, MessagePreview>, VecDeque<Uuid>)>,
+}
+
+impl PreviewCache {
+    pub fn record(&self, message_id: Uuid, direction: SyncDirection, content: &ClipboardContent) {
+        let preview = MessagePreview::new(message_id, direction, content);
+        let mut guard = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (previews, order) = &mut *guard;
+        if previews.insert(message_id, preview).is_none() {
+            order.push_back(message_id);
+        }
+        while order.len() > CAPACITY {
+            if let Some(oldest) = order.pop_front() {
+                previews.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn get(&self, message_id: &Uuid) -> Option<MessagePreview> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).0.get(message_id).cloned()
+    }
+}
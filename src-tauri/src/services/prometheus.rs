@@ -0,0 +1,119 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use crate::models::{Config, PeerMetrics, SyncMetrics};
+use crate::utils::net;
+use super::access_policy::{AccessPolicy, Endpoint};
+use super::listener;
+use super::websocket::WebSocketServer;
+use super::shutdown::Shutdown;
+
+/// Largest request head read before giving up on a client.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+/// How long a client gets to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Quote a label value as the text format wants it.
+fn label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}\n{} {}", name, help, name, kind, name, value);
+}
+
+fn peer_metric(out: &mut String, name: &str, help: &str, peers: &[PeerMetrics], value: fn(&PeerMetrics) -> u64) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter", name, help, name);
+    for peer in peers {
+        let device = peer.device.as_deref().unwrap_or_default();
+        let _ = writeln!(out, "{}{{peer=\"{}\",device=\"{}\"}} {}", name, peer.address, label(device), value(peer));
+    }
+}
+
+/// `metrics` and `peers` in the Prometheus text format.
+fn render(metrics: &SyncMetrics, peers: &[PeerMetrics]) -> String {
+    let mut out = String::new();
+    metric(&mut out, "unimesh_messages_sent_total", "counter", "Messages sent to peers.", metrics.messages_sent);
+    metric(&mut out, "unimesh_messages_received_total", "counter", "Messages received from peers.", metrics.messages_received);
+    metric(&mut out, "unimesh_messages_failed_total", "counter", "Messages that could not be sent.", metrics.messages_failed);
+    metric(&mut out, "unimesh_messages_rejected_total", "counter", "Received messages that were refused.", metrics.messages_rejected);
+    metric(&mut out, "unimesh_clipboard_updates_applied_total", "counter", "Peer clips written to the clipboard.", metrics.clipboard_updates_applied);
+    metric(&mut out, "unimesh_clipboard_updates_failed_total", "counter", "Peer clips that could not be written.", metrics.clipboard_updates_failed);
+    metric(&mut out, "unimesh_bytes_sent_total", "counter", "Message bytes sent to peers.", metrics.bytes_sent);
+    metric(&mut out, "unimesh_bytes_received_total", "counter", "Message bytes received from peers.", metrics.bytes_received);
+    metric(&mut out, "unimesh_connected_peers", "gauge", "Peers with an open session.", u64::from(metrics.connected_peers));
+    metric(&mut out, "unimesh_uptime_seconds", "gauge", "Seconds since the sync server started.", metrics.uptime_secs);
+    peer_metric(&mut out, "unimesh_peer_bytes_sent_total", "Message bytes sent to each connected peer.", peers, |peer| peer.bytes_sent);
+    peer_metric(&mut out, "unimesh_peer_bytes_received_total", "Message bytes received from each connected peer.", peers, |peer| peer.bytes_received);
+    out
+}
+
+/// Read the request line: method and path.
+async fn request_line(stream: &mut TcpStream) -> Option<(String, String)> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_BYTES {
+        let read = stream.read(&mut buf).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut parts = head.lines().next()?.split_whitespace();
+    Some((parts.next()?.to_string(), parts.next()?.to_string()))
+}
+
+async fn respond(ws: &WebSocketServer, config: &RwLock<Config>, mut stream: TcpStream) {
+    let Ok(source) = stream.peer_addr().map(net::canonical) else {
+        return;
+    };
+    let allowed = AccessPolicy::from_config(&*config.read().await).check(Endpoint::Metrics, source.ip(), None);
+    let request = tokio::time::timeout(REQUEST_TIMEOUT, request_line(&mut stream)).await.ok().flatten();
+    let (status, body) = match (allowed, request) {
+        (Err(reason), _) => {
+            tracing::debug!("Refusing metrics request from {}: {}", source, reason);
+            ("403 Forbidden", String::new())
+        }
+        (Ok(()), Some((method, path))) if path.split('?').next() == Some("/metrics") => match method.as_str() {
+            "GET" => ("200 OK", render(&ws.get_sync_metrics().await, &ws.get_peer_metrics().await)),
+            _ => ("405 Method Not Allowed", String::new()),
+        },
+        (Ok(()), Some(_)) => ("404 Not Found", String::new()),
+        (Ok(()), None) => ("400 Bad Request", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Serve `/metrics` on `port` until `shutdown`. Reachable from loopback
+/// and `api_allowed_sources`, like the local API.
+///
+/// # Errors
+/// When `port` cannot be bound
+pub async fn spawn(ws: Arc<WebSocketServer>, config: Arc<RwLock<Config>>, port: u16, shutdown: Shutdown) -> anyhow::Result<()> {
+    let (listener, addr) = listener::bind(port).await?;
+    tracing::info!("Serving Prometheus metrics on {}/metrics", addr);
+    shutdown.clone().spawn(async move {
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        let (ws, config) = (ws.clone(), config.clone());
+                        shutdown.spawn(async move { respond(&ws, &config, stream).await });
+                    }
+                    Err(e) => tracing::warn!("Failed to accept metrics connection: {}", e),
+                },
+                _ = shutdown.cancelled() => break,
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Mutex, OnceLock};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::{ClipboardMessage, ConnectionDirection, MessageType};
+
+/// Protocol events kept in memory.
+pub const CAPACITY: usize = 500;
+
+static TRACE: OnceLock<Mutex<VecDeque<TraceEvent>>> = OnceLock::new();
+
+fn trace() -> &'static Mutex<VecDeque<TraceEvent>> {
+    TRACE.get_or_init(|| Mutex::new(VecDeque::with_capacity(CAPACITY)))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TraceKind {
+    Handshake,
+    Received,
+    Sent,
+    Closed,
+}
+
+/// One thing exchanged with a peer: its structure, never its content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TraceEvent {
+    pub timestamp: DateTime<Utc>,
+    pub kind: TraceKind,
+    /// Which side opened the connection, for handshakes and closes
+    pub direction: Option<ConnectionDirection>,
+    /// Peer address, when the connection got far enough to have one
+    pub peer: Option<SocketAddr>,
+    pub message_id: Option<Uuid>,
+    pub message_type: Option<MessageType>,
+    /// Bytes on the wire
+    pub bytes: Option<usize>,
+    pub encrypted: bool,
+    /// What became of it, e.g. `accepted`, `duplicate`, or why it was refused
+    pub verdict: String,
+}
+
+impl TraceEvent {
+    fn new(kind: TraceKind, peer: Option<SocketAddr>, verdict: &str) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            kind,
+            direction: None,
+            peer,
+            message_id: None,
+            message_type: None,
+            bytes: None,
+            encrypted: false,
+            verdict: verdict.to_string(),
+        }
+    }
+}
+
+/// Which events `recent` returns. Unset fields match everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraceFilter {
+    /// Peer address or IP, e.g. `192.168.1.20` or `192.168.1.20:8765`
+    pub peer: Option<String>,
+    pub kind: Option<TraceKind>,
+    pub message_type: Option<MessageType>,
+    /// Only events whose verdict is not `accepted` or `sent`
+    pub problems_only: bool,
+}
+
+impl TraceFilter {
+    fn matches(&self, event: &TraceEvent) -> bool {
+        let peer = self.peer.as_deref().map(str::trim).filter(|peer| !peer.is_empty());
+        peer.is_none_or(|peer| {
+            event.peer.is_some_and(|addr| addr.to_string() == peer || addr.ip().to_string() == peer)
+        }) && self.kind.is_none_or(|kind| kind == event.kind)
+            && self.message_type.as_ref().is_none_or(|wanted| event.message_type.as_ref() == Some(wanted))
+            && (!self.problems_only || !matches!(event.verdict.as_str(), "accepted" | "sent"))
+    }
+}
+
+fn push(event: TraceEvent) {
+    let mut events = trace().lock().unwrap_or_else(|e| e.into_inner());
+    if events.len() == CAPACITY {
+        events.pop_front();
+    }
+    events.push_back(event);
+}
+
+/// Record a handshake with `peer` and how it ended.
+pub fn handshake(direction: ConnectionDirection, peer: SocketAddr, verdict: &str) {
+    let mut event = TraceEvent::new(TraceKind::Handshake, Some(peer), verdict);
+    event.direction = Some(direction);
+    push(event);
+}
+
+/// Record that the session with `peer` ended.
+pub fn closed(direction: ConnectionDirection, peer: SocketAddr, verdict: &str) {
+    let mut event = TraceEvent::new(TraceKind::Closed, Some(peer), verdict);
+    event.direction = Some(direction);
+    push(event);
+}
+
+/// Record a message received from `peer` and what was done with it.
+/// Frames that did not parse have no message.
+pub fn received(peer: Option<SocketAddr>, message: Option<&ClipboardMessage>, bytes: usize, verdict: &str) {
+    let mut event = TraceEvent::new(TraceKind::Received, peer, verdict);
+    if let Some(message) = message {
+        event.message_id = Some(message.id);
+        event.message_type = Some(message.msg_type.clone());
+        event.encrypted = message.encrypted;
+    }
+    event.bytes = Some(bytes);
+    push(event);
+}
+
+/// Record a message sent to `peer`.
+pub fn sent(peer: Option<SocketAddr>, message_id: Uuid, message_type: Option<MessageType>, bytes: usize) {
+    let mut event = TraceEvent::new(TraceKind::Sent, peer, "sent");
+    event.message_id = Some(message_id);
+    event.message_type = message_type;
+    event.bytes = Some(bytes);
+    push(event);
+}
+
+/// The last `limit` events passing `filter`, oldest first.
+pub fn recent(limit: usize, filter: &TraceFilter) -> Vec<TraceEvent> {
+    let events = trace().lock().unwrap_or_else(|e| e.into_inner());
+    let mut matching: Vec<TraceEvent> = events.iter().rev().filter(|event| filter.matches(event)).take(limit).cloned().collect();
+    matching.reverse();
+    matching
+}
@@ -0,0 +1,136 @@
+use std::sync::Arc;
+use anyhow::Result;
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+use crate::models::{ClipOrigin, ClipReceipt, ClipboardMessage, MessageType, SyncMetrics};
+use super::connection::ConnectionContext;
+use super::device_names;
+use super::history::HistoryStore;
+use super::shutdown::Shutdown;
+
+/// Receipts received from peers, for the history listener.
+pub(crate) type ReceiptTx = broadcast::Sender<ClipReceipt>;
+/// Peer clips written to the clipboard, for the server to acknowledge.
+pub(crate) type AppliedTx = broadcast::Sender<ClipOrigin>;
+
+/// Where the clipboard side reports on the peer clips it writes: the sync
+/// counters, and the server, which acknowledges each clip once written.
+#[derive(Clone)]
+pub struct ApplyReport {
+    pub metrics: Arc<RwLock<SyncMetrics>>,
+    applied: AppliedTx,
+}
+
+impl ApplyReport {
+    pub(crate) fn new(metrics: Arc<RwLock<SyncMetrics>>, applied: AppliedTx) -> Self {
+        Self { metrics, applied }
+    }
+
+    /// Count a clip that reached the clipboard and have it acknowledged.
+    pub async fn applied(&self, origin: &ClipOrigin) {
+        self.metrics.write().await.clipboard_updates_applied += 1;
+        // No receivers just means sync stopped meanwhile
+        let _ = self.applied.send(origin.clone());
+    }
+}
+
+/// Tell the mesh that this device applied clip `message_id`.
+pub(crate) async fn acknowledge(ctx: &ConnectionContext, message_id: Uuid) {
+    let receipt = ClipReceipt {
+        message_id,
+        device: device_names::local_device_name(),
+        applied_at: chrono::Utc::now(),
+    };
+    if let Err(e) = send(ctx, &receipt, Uuid::new_v4(), None).await {
+        tracing::warn!("Failed to send receipt for {}: {}", message_id, e);
+    }
+}
+
+/// Send a receipt to every connected peer clips may flow to, except `skip`.
+/// Reusing `id` when relaying lets peers drop copies they have already seen,
+/// so a receipt reaches the origin even when the clip was relayed to us.
+async fn send(ctx: &ConnectionContext, receipt: &ClipReceipt, id: Uuid, skip: Option<Uuid>) -> Result<()> {
+    let mut message = ClipboardMessage::new(MessageType::Receipt, Some(serde_json::to_string(receipt)?));
+    message.id = id;
+    ctx.message_cache.write().await.add_message(id);
+
+    let peer_ids: Vec<Uuid> = ctx.peers.read().await.keys().copied().filter(|peer_id| Some(*peer_id) != skip).collect();
+    for peer_id in peer_ids {
+        if !ctx.is_peer_allowed(peer_id).await {
+            continue;
+        }
+        if let Err(e) = ctx.send_to_peer(peer_id, message.clone()).await {
+            tracing::warn!("Failed to send receipt to {}: {}", peer_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Mark peer clips applied, and acknowledge them, as they reach the
+/// clipboard, until sync stops.
+pub(crate) fn spawn_acknowledger(ctx: ConnectionContext, mut applied: broadcast::Receiver<ClipOrigin>, shutdown: Shutdown) {
+    shutdown.clone().spawn(async move {
+        loop {
+            let origin = tokio::select! {
+                origin = applied.recv() => origin,
+                _ = shutdown.cancelled() => break,
+            };
+            match origin {
+                Ok(origin) => {
+                    let Some(message_id) = origin.message_id else {
+                        continue;
+                    };
+                    ctx.applied.clone().record(message_id, origin.sent_at).await;
+                    if ctx.config.read().await.send_receipts {
+                        acknowledge(&ctx, message_id).await;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Missed {} applied clips to acknowledge", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// Record a peer's receipt locally, then pass it on to the other peers.
+pub(crate) async fn handle(ctx: &ConnectionContext, peer_id: Uuid, id: Uuid, content: Option<&str>) {
+    if !ctx.is_peer_allowed(peer_id).await {
+        tracing::debug!("Ignoring receipt from untrusted peer {}", peer_id);
+        return;
+    }
+    let Some(receipt) = content.and_then(|c| serde_json::from_str::<ClipReceipt>(c).ok()) else {
+        tracing::warn!("Malformed receipt from {}", peer_id);
+        return;
+    };
+
+    let _ = ctx.receipt_tx.send(receipt.clone());
+    if let Err(e) = send(ctx, &receipt, id, Some(peer_id)).await {
+        tracing::warn!("Failed to relay receipt: {}", e);
+    }
+}
+
+/// Store receipts for clips sent from this device, until `shutdown`.
+/// Receipts for clips sent elsewhere match no history entry and are ignored.
+pub fn spawn_listener(history: Arc<HistoryStore>, mut receipts: broadcast::Receiver<ClipReceipt>, shutdown: Shutdown) {
+    shutdown.clone().spawn(async move {
+        loop {
+            let receipt = tokio::select! {
+                receipt = receipts.recv() => receipt,
+                _ = shutdown.cancelled() => break,
+            };
+            match receipt {
+                Ok(receipt) => {
+                    if let Err(e) = history.record_receipt(&receipt).await {
+                        tracing::warn!("Failed to record receipt: {}", e);
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("Missed {} receipts", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
@@ -0,0 +1,70 @@
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+use super::liveness::LivenessRegistry;
+
+/// Delay before the first retry, and before redialing a dropped session.
+pub const BASE_DELAY: Duration = Duration::from_secs(2);
+/// Longest delay between retries.
+const MAX_DELAY: Duration = Duration::from_secs(300);
+/// Failed attempts in a row before an endpoint is left alone until
+/// discovery sees it again or the peer list changes.
+pub const MAX_ATTEMPTS: u32 = 10;
+
+/// Retry schedule for one outbound endpoint: jittered exponential backoff
+/// after failures, reset by a successful connection or a fresh mDNS
+/// announcement.
+#[derive(Debug, Clone, Default)]
+pub struct Backoff {
+    failures: u32,
+    retry_at: Option<Instant>,
+    failed_at: Option<Instant>,
+    /// Address the endpoint last resolved to, for matching announcements
+    address: Option<IpAddr>,
+}
+
+impl Backoff {
+    pub fn is_due(&self) -> bool {
+        self.failures < MAX_ATTEMPTS && self.retry_at.is_none_or(|at| at <= Instant::now())
+    }
+
+    pub fn gave_up(&self) -> bool {
+        self.failures >= MAX_ATTEMPTS
+    }
+
+    /// Schedule the next attempt and return how long it is away.
+    pub fn failed(&mut self, address: Option<IpAddr>) -> Duration {
+        self.failures += 1;
+        self.address = address.or(self.address);
+        let exponential = BASE_DELAY.saturating_mul(1 << (self.failures - 1).min(16)).min(MAX_DELAY);
+        // Between half and all of the delay, so peers that lost each other
+        // at the same moment do not retry in lockstep
+        let delay = exponential.mul_f64(0.5 + fastrand::f64() / 2.0);
+        let now = Instant::now();
+        self.failed_at = Some(now);
+        self.retry_at = Some(now + delay);
+        delay
+    }
+
+    /// The endpoint was reached; forget past failures and look at it
+    /// again after `check_again`.
+    pub fn connected(&mut self, address: IpAddr, check_again: Duration) {
+        *self = Self {
+            address: Some(address),
+            retry_at: Some(Instant::now() + check_again),
+            ..Self::default()
+        };
+    }
+
+    /// Start over if the device announced itself since the last failure.
+    pub fn reset_if_seen(&mut self, liveness: &LivenessRegistry) -> bool {
+        let (Some(address), Some(failed_at)) = (self.address, self.failed_at) else {
+            return false;
+        };
+        let seen = liveness.last_advertised(address).is_some_and(|at| at > failed_at.into_std());
+        if seen {
+            *self = Self { address: Some(address), ..Self::default() };
+        }
+        seen
+    }
+}
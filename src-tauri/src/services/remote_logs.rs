@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{oneshot, RwLock};
+use tokio::time::Duration;
+use uuid::Uuid;
+use anyhow::Result;
+use crate::models::{ClipboardMessage, LogRequest, LogResponse, MessageType};
+use crate::utils::log_buffer;
+use super::connection::ConnectionContext;
+
+/// Upper bound on lines a peer may request in one go.
+pub const MAX_REMOTE_LOG_LINES: usize = 500;
+/// How long to wait for a peer to answer a log request.
+pub const LOG_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Outstanding log requests keyed by request message id.
+pub type PendingLogRequests = Arc<RwLock<HashMap<Uuid, oneshot::Sender<LogResponse>>>>;
+
+/// Build the answer to a peer's log request.
+///
+/// # Arguments
+/// * `request_id` - Id of the `LogRequest` message being answered
+/// * `request` - The decoded request payload
+/// * `allowed` - Whether the local user has consented to sharing logs
+///
+/// # Returns
+/// A response carrying sanitized log lines, or a denial without any lines
+pub fn build_response(request_id: Uuid, request: &LogRequest, allowed: bool) -> LogResponse {
+    if !allowed {
+        return LogResponse {
+            request_id,
+            granted: false,
+            lines: Vec::new(),
+        };
+    }
+
+    let lines = log_buffer::recent_lines(request.lines.min(MAX_REMOTE_LOG_LINES))
+        .iter()
+        .map(|line| log_buffer::sanitize_line(line))
+        .collect();
+    LogResponse {
+        request_id,
+        granted: true,
+        lines,
+    }
+}
+
+/// Send a log request to `peer_id` and wait for its answer. Backs
+/// `WebSocketServer::request_peer_logs`.
+pub(crate) async fn request(ctx: &ConnectionContext, peer_id: Uuid, lines: usize) -> Result<Vec<String>> {
+    let request = LogRequest {
+        lines: lines.min(MAX_REMOTE_LOG_LINES),
+    };
+    let message = ClipboardMessage::new(
+        MessageType::LogRequest,
+        Some(serde_json::to_string(&request)?),
+    );
+    let request_id = message.id;
+
+    let (waiter, response) = tokio::sync::oneshot::channel();
+    ctx.pending_log_requests.write().await.insert(request_id, waiter);
+    ctx.message_cache.write().await.add_message(request_id);
+
+    if let Err(e) = ctx.send_to_peer(peer_id, message).await {
+        ctx.pending_log_requests.write().await.remove(&request_id);
+        return Err(e);
+    }
+
+    let result = tokio::time::timeout(LOG_REQUEST_TIMEOUT, response).await;
+    ctx.pending_log_requests.write().await.remove(&request_id);
+    match result {
+        Ok(Ok(response)) if response.granted => Ok(response.lines),
+        Ok(Ok(_)) => Err(anyhow::anyhow!("Peer {} has not enabled remote log sharing", peer_id)),
+        Ok(Err(_)) | Err(_) => Err(anyhow::anyhow!("Peer {} did not answer the log request", peer_id)),
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::models::{ClipboardMessage, MessageType, MuteRequest, Protection};
+use super::connection::ConnectionContext;
+
+/// Longest mute a peer may ask for; longer requests are cut to this.
+const MAX_MUTE: chrono::Duration = chrono::Duration::hours(24);
+
+/// Mutes requested over the wire, by peer IP so they outlast reconnects.
+#[derive(Default)]
+pub struct MuteRegistry {
+    /// Peers that asked us not to send them content, until when
+    honored: Mutex<HashMap<IpAddr, DateTime<Utc>>>,
+    /// Peers we asked not to send us content, until when
+    requested: Mutex<HashMap<IpAddr, DateTime<Utc>>>,
+}
+
+fn until(mutes: &Mutex<HashMap<IpAddr, DateTime<Utc>>>, ip: IpAddr) -> Option<DateTime<Utc>> {
+    let mut mutes = mutes.lock().unwrap_or_else(|e| e.into_inner());
+    let now = Utc::now();
+    mutes.retain(|_, until| *until > now);
+    mutes.get(&ip).copied()
+}
+
+fn set(mutes: &Mutex<HashMap<IpAddr, DateTime<Utc>>>, ip: IpAddr, until: Option<DateTime<Utc>>) {
+    let mut mutes = mutes.lock().unwrap_or_else(|e| e.into_inner());
+    match until {
+        Some(until) => mutes.insert(ip, until),
+        None => mutes.remove(&ip),
+    };
+}
+
+impl MuteRegistry {
+    /// When we may send content to the peer at `ip` again, if it asked us to stop.
+    pub fn honored_until(&self, ip: IpAddr) -> Option<DateTime<Utc>> {
+        until(&self.honored, ip)
+    }
+
+    /// When the peer at `ip` may send us content again, if we asked it to stop.
+    pub fn requested_until(&self, ip: IpAddr) -> Option<DateTime<Utc>> {
+        until(&self.requested, ip)
+    }
+
+    pub fn is_muted(&self, ip: IpAddr) -> bool {
+        self.honored_until(ip).is_some()
+    }
+}
+
+/// Ask every connected peer at `ip` to stop sending us content until
+/// `until`, or to start again when `None`.
+///
+/// # Errors
+/// When no session to that address is open
+pub(crate) async fn request(ctx: &ConnectionContext, ip: IpAddr, until: Option<DateTime<Utc>>) -> Result<()> {
+    let peer_ids: Vec<Uuid> = ctx.peers.read().await
+        .iter()
+        .filter(|(_, (addr, _))| addr.ip() == ip)
+        .map(|(peer_id, _)| *peer_id)
+        .collect();
+    if peer_ids.is_empty() {
+        anyhow::bail!("Not connected to {}", ip);
+    }
+
+    let payload = serde_json::to_string(&MuteRequest { until })?;
+    let message = ClipboardMessage::new(MessageType::MuteRequest, Some(payload))
+        .with_protection(Protection::Signed);
+    for peer_id in peer_ids {
+        ctx.send_to_peer(peer_id, message.clone()).await?;
+    }
+    set(&ctx.policy.mutes.requested, ip, until);
+    tracing::info!("Asked {} to {}", ip, match until {
+        Some(until) => format!("stop sending until {}", until),
+        None => "resume sending".to_string(),
+    });
+    Ok(())
+}
+
+/// A peer asked us to stop, or resume, sending it content.
+pub(crate) async fn handle(ctx: &ConnectionContext, peer_id: Uuid, content: Option<&str>) {
+    let Some(request) = content.and_then(|c| serde_json::from_str::<MuteRequest>(c).ok()) else {
+        tracing::warn!("Malformed mute request from {}", peer_id);
+        return;
+    };
+    let Some(address) = ctx.peers.read().await.get(&peer_id).map(|(addr, _)| *addr) else {
+        return;
+    };
+    if !ctx.is_peer_allowed(peer_id).await {
+        tracing::warn!("Ignoring mute request from untrusted peer {}", address);
+        return;
+    }
+
+    let until = request.until
+        .filter(|until| *until > Utc::now())
+        .map(|until| until.min(Utc::now() + MAX_MUTE));
+    match until {
+        Some(until) => tracing::info!("{} asked not to be sent content until {}", address, until),
+        None => tracing::info!("{} asked to be sent content again", address),
+    }
+    set(&ctx.policy.mutes.honored, address.ip(), until);
+}
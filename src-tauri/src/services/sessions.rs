@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue};
+use uuid::Uuid;
+use crate::models::ClipboardMessage;
+use unimesh_proto::version::SESSION_NONCE_HEADER;
+
+/// Our random half of a session id, sent in the handshake.
+pub fn offer(headers: &mut HeaderMap) -> Uuid {
+    let nonce = Uuid::new_v4();
+    if let Ok(value) = HeaderValue::from_str(&nonce.to_string()) {
+        headers.insert(SESSION_NONCE_HEADER, value);
+    }
+    nonce
+}
+
+/// The other side's half of the session id; `None` for builds that
+/// predate sessions.
+pub fn offered(headers: &HeaderMap) -> Option<Uuid> {
+    headers.get(SESSION_NONCE_HEADER)?.to_str().ok()?.trim().parse().ok()
+}
+
+/// Session id of a connection, from both halves. Neither side alone picks
+/// it, so a peer cannot steer a new connection onto an old session.
+pub fn derive(client: Uuid, server: Uuid) -> Uuid {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(b"unimesh-session");
+    hasher.update(client.as_bytes());
+    hasher.update(server.as_bytes());
+    let mut id = [0u8; 16];
+    id.copy_from_slice(&hasher.finalize().as_bytes()[..16]);
+    Uuid::from_bytes(id)
+}
+
+/// Answer an incoming handshake: when the peer offered its half, add ours
+/// and return the session id.
+pub fn accept(request: &HeaderMap, response: &mut HeaderMap) -> Option<Uuid> {
+    let client = offered(request)?;
+    Some(derive(client, offer(response)))
+}
+
+/// Session ids by connection. Connections to peers without sessions have
+/// no entry.
+#[derive(Default)]
+pub struct SessionRegistry {
+    sessions: RwLock<HashMap<Uuid, Uuid>>,
+}
+
+impl SessionRegistry {
+    pub fn open(&self, peer_id: Uuid, session: Uuid) {
+        self.sessions.write().unwrap_or_else(|e| e.into_inner()).insert(peer_id, session);
+    }
+
+    pub fn close(&self, peer_id: Uuid) {
+        self.sessions.write().unwrap_or_else(|e| e.into_inner()).remove(&peer_id);
+    }
+
+    pub fn get(&self, peer_id: Uuid) -> Option<Uuid> {
+        self.sessions.read().unwrap_or_else(|e| e.into_inner()).get(&peer_id).copied()
+    }
+
+    /// Stamp a message about to go to `peer_id` with that connection's
+    /// session. Returns whether it has one, in which case the message must
+    /// be signed again.
+    pub fn bind(&self, peer_id: Uuid, message: &mut ClipboardMessage) -> bool {
+        message.session = self.get(peer_id);
+        message.session.is_some()
+    }
+
+    /// Whether a message from `peer_id` carries that connection's session.
+    /// A cheap check made before the signature, so messages replayed from
+    /// another connection are dropped without the HMAC.
+    pub fn admits(&self, peer_id: Uuid, message: &ClipboardMessage) -> bool {
+        self.get(peer_id).is_none_or(|session| message.session == Some(session))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MessageType;
+
+    fn message() -> ClipboardMessage {
+        ClipboardMessage::new(MessageType::Heartbeat, None)
+    }
+
+    #[test]
+    fn bound_messages_are_admitted_on_their_connection() {
+        let sessions = SessionRegistry::default();
+        let peer = Uuid::new_v4();
+        sessions.open(peer, Uuid::new_v4());
+        let mut message = message();
+        assert!(sessions.bind(peer, &mut message));
+        assert!(sessions.admits(peer, &message));
+    }
+
+    #[test]
+    fn messages_replayed_on_another_connection_are_refused() {
+        let sessions = SessionRegistry::default();
+        let (peer, other) = (Uuid::new_v4(), Uuid::new_v4());
+        sessions.open(peer, Uuid::new_v4());
+        sessions.open(other, Uuid::new_v4());
+        let mut message = message();
+        sessions.bind(peer, &mut message);
+        assert!(!sessions.admits(other, &message));
+    }
+
+    #[test]
+    fn messages_of_a_closed_session_are_refused_on_the_next() {
+        let sessions = SessionRegistry::default();
+        let peer = Uuid::new_v4();
+        sessions.open(peer, Uuid::new_v4());
+        let mut message = message();
+        sessions.bind(peer, &mut message);
+        sessions.close(peer);
+        sessions.open(peer, Uuid::new_v4());
+        assert!(!sessions.admits(peer, &message));
+    }
+
+    #[test]
+    fn unbound_messages_are_refused_on_a_session() {
+        let sessions = SessionRegistry::default();
+        let peer = Uuid::new_v4();
+        sessions.open(peer, Uuid::new_v4());
+        assert!(!sessions.admits(peer, &message()));
+    }
+
+    #[test]
+    fn peers_without_sessions_are_neither_bound_nor_checked() {
+        let sessions = SessionRegistry::default();
+        let peer = Uuid::new_v4();
+        let mut message = message();
+        assert!(!sessions.bind(peer, &mut message));
+        assert_eq!(message.session, None);
+        assert!(sessions.admits(peer, &message));
+    }
+
+    #[test]
+    fn session_ids_take_both_halves() {
+        let (client, server) = (Uuid::new_v4(), Uuid::new_v4());
+        assert_eq!(derive(client, server), derive(client, server));
+        assert_ne!(derive(client, server), derive(client, Uuid::new_v4()));
+        assert_ne!(derive(client, server), derive(server, client));
+    }
+}
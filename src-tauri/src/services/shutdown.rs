@@ -0,0 +1,93 @@
+//! Stopping background tasks cleanly. Tasks watch a shared cancellation
+//! token and finish what they are doing instead of being aborted mid-way,
+//! which could leave the mDNS daemon, the clipboard lock, or files half
+//! written. Waits are bounded so a stuck task cannot hold up a stop.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+use tokio_util::task::TaskTracker;
+
+/// Longest a stop waits for tasks to finish on their own.
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cancellation shared by a group of tasks, e.g. everything one sync run
+/// started, plus a record of those tasks to wait for.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    token: CancellationToken,
+    tasks: TaskTracker,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `task` until it returns; it should return soon after
+    /// `cancelled` fires. Draining waits for it.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.spawn(task);
+    }
+
+    /// Resolves once the group is told to stop.
+    pub fn cancelled(&self) -> WaitForCancellationFutureOwned {
+        self.token.clone().cancelled_owned()
+    }
+
+    /// A group that stops with this one but can also be stopped on its own.
+    /// Its tasks are still waited for when this one drains.
+    fn child(&self) -> Self {
+        Self { token: self.token.child_token(), tasks: self.tasks.clone() }
+    }
+
+    /// Tell every task to stop and wait up to `timeout` for them.
+    ///
+    /// # Returns
+    /// Whether every task finished in time; the rest are left to finish
+    /// in the background
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.token.cancel();
+        self.tasks.close();
+        if tokio::time::timeout(timeout, self.tasks.wait()).await.is_ok() {
+            return true;
+        }
+        tracing::warn!("{} background tasks did not stop within {:?}", self.tasks.len(), timeout);
+        false
+    }
+}
+
+/// A task that can be stopped and waited for by itself, such as discovery,
+/// which restarts while the rest of sync keeps running.
+pub struct StoppableTask {
+    shutdown: Shutdown,
+    handle: JoinHandle<()>,
+}
+
+impl StoppableTask {
+    /// Spawn the future `task` builds from a child of `parent`, so it also
+    /// stops with `parent`.
+    pub fn spawn<F>(parent: &Shutdown, task: impl FnOnce(Shutdown) -> F) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let shutdown = parent.child();
+        let handle = parent.tasks.spawn(task(shutdown.clone()));
+        Self { shutdown, handle }
+    }
+
+    /// Tell the task to stop and wait up to `timeout` for it, aborting it
+    /// only if it does not finish in time.
+    pub async fn stop(self, timeout: Duration, name: &str) {
+        self.shutdown.token.cancel();
+        let abort = self.handle.abort_handle();
+        if tokio::time::timeout(timeout, self.handle).await.is_err() {
+            tracing::warn!("{} did not stop within {:?}, aborting it", name, timeout);
+            abort.abort();
+        }
+    }
+}
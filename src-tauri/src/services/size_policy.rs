@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::models::{ClipOrigin, ClipboardContent, Config, ContentDescriptor, OversizePolicy};
+
+pub const OVERSIZE_EVENT: &str = "oversize-content";
+/// Room for JSON framing and base64/AES-GCM expansion on top of the
+/// content limit when checking raw frames.
+const WIRE_OVERHEAD: u64 = 64 * 1024;
+/// Most clips held for a decision at once; the oldest goes first.
+const MAX_PENDING: usize = 16;
+/// Most bytes held for a decision at once; the oldest go first.
+const MAX_PENDING_BYTES: u64 = 256 * 1024 * 1024;
+
+/// Which way an oversized clip was travelling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+/// Payload of the `oversize-content` event, asking the user to decide.
+#[derive(Debug, Clone, Serialize)]
+pub struct OversizeEvent {
+    pub id: Uuid,
+    pub direction: Direction,
+    pub bytes: u64,
+    pub limit: u64,
+    pub device: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// A clip held back until the user accepts or rejects it.
+pub struct PendingClip {
+    pub content: ClipboardContent,
+    /// Sender of an incoming clip; `None` for outgoing ones
+    pub origin: Option<ClipOrigin>,
+    pub held_at: DateTime<Utc>,
+}
+
+/// Outcome of checking a clip against the size limit.
+pub enum SizeDecision {
+    Allow(ClipboardContent),
+    Drop,
+    Prompt(ClipboardContent),
+}
+
+pub type OversizeNotifier = Box<dyn Fn(OversizeEvent) + Send + Sync>;
+
+/// Clips awaiting a decision, plus the hook that tells the UI about them.
+#[derive(Default)]
+pub struct OversizeGate {
+    pending: RwLock<HashMap<Uuid, PendingClip>>,
+    notifier: RwLock<Option<OversizeNotifier>>,
+}
+
+pub fn content_size(content: &ClipboardContent) -> u64 {
+    let rich = content.html.as_ref().map_or(0, String::len) + content.rtf.as_ref().map_or(0, String::len);
+    (content.text.len() + rich) as u64
+}
+
+/// Largest raw frame worth parsing when clips are capped at `max_content_bytes`.
+/// Frames beyond this cannot hold an acceptable clip in any policy but `Prompt`.
+pub fn wire_limit(config: &Config) -> Option<u64> {
+    let limited = config.max_content_bytes > 0 && config.oversize_policy == OversizePolicy::Skip;
+    limited.then(|| config.max_content_bytes * 2 + WIRE_OVERHEAD)
+}
+
+/// Whether a clip can be dropped on its descriptor alone, before its body
+/// is decrypted: only when oversized clips are skipped, not cut or held.
+pub fn rejects(config: &Config, descriptor: &ContentDescriptor) -> bool {
+    config.max_content_bytes > 0
+        && config.oversize_policy == OversizePolicy::Skip
+        && descriptor.size > config.max_content_bytes
+}
+
+/// Apply the configured size policy to a clip.
+pub fn check(config: &Config, content: ClipboardContent) -> SizeDecision {
+    let limit = config.max_content_bytes;
+    if limit == 0 || content_size(&content) <= limit {
+        return SizeDecision::Allow(content);
+    }
+    match config.oversize_policy {
+        OversizePolicy::Skip => SizeDecision::Drop,
+        OversizePolicy::Prompt => SizeDecision::Prompt(content),
+        OversizePolicy::Truncate => SizeDecision::Allow(truncate(content, limit as usize)),
+    }
+}
+
+/// Drop rich formats, then cut the text at a character boundary.
+fn truncate(content: ClipboardContent, limit: usize) -> ClipboardContent {
+    let mut text = content.text;
+    if text.len() > limit {
+        let mut end = limit;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    ClipboardContent::plain(text)
+}
+
+/// Drop the oldest held clips until the rest fit the caps.
+fn evict_oldest(pending: &mut HashMap<Uuid, PendingClip>) {
+    let mut bytes: u64 = pending.values().map(|clip| content_size(&clip.content)).sum();
+    while pending.len() > MAX_PENDING || (bytes > MAX_PENDING_BYTES && pending.len() > 1) {
+        let Some(oldest) = pending.iter().min_by_key(|(_, clip)| clip.held_at).map(|(id, _)| *id) else {
+            break;
+        };
+        if let Some(clip) = pending.remove(&oldest) {
+            bytes -= content_size(&clip.content);
+            tracing::info!("Dropping held clip {} of {} bytes: too many clips awaiting a decision", oldest, content_size(&clip.content));
+        }
+    }
+}
+
+impl OversizeGate {
+    pub async fn set_notifier<F>(&self, notifier: F)
+    where
+        F: Fn(OversizeEvent) + Send + Sync + 'static,
+    {
+        *self.notifier.write().await = Some(Box::new(notifier));
+    }
+
+    /// Hold a clip and ask the user about it. `device` names the sender of
+    /// an incoming clip. Past `MAX_PENDING` clips or `MAX_PENDING_BYTES`,
+    /// the oldest held clips are dropped undecided.
+    pub async fn hold(&self, direction: Direction, content: ClipboardContent, origin: Option<ClipOrigin>, device: Option<String>, limit: u64) {
+        let id = Uuid::new_v4();
+        let event = OversizeEvent {
+            id,
+            direction,
+            bytes: content_size(&content),
+            limit,
+            device,
+            timestamp: Utc::now(),
+        };
+        tracing::info!("Holding {:?} clip of {} bytes for confirmation", direction, event.bytes);
+        {
+            let mut pending = self.pending.write().await;
+            pending.insert(id, PendingClip { content, origin, held_at: event.timestamp });
+            evict_oldest(&mut pending);
+        }
+        if let Some(ref notify) = *self.notifier.read().await {
+            notify(event);
+        }
+    }
+
+    /// Take a held clip out of the gate.
+    pub async fn take(&self, id: Uuid) -> Option<PendingClip> {
+        self.pending.write().await.remove(&id)
+    }
+
+    /// Drop every incoming clip held from `ip`. Returns how many there were.
+    pub async fn purge_from(&self, ip: IpAddr) -> usize {
+        let mut pending = self.pending.write().await;
+        let before = pending.len();
+        pending.retain(|_, clip| clip.origin.as_ref().is_none_or(|origin| origin.address.ip() != ip));
+        before - pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMIT: u64 = 10;
+
+    fn config(policy: OversizePolicy) -> Config {
+        Config { max_content_bytes: LIMIT, oversize_policy: policy, ..Config::default() }
+    }
+
+    fn text(bytes: u64) -> ClipboardContent {
+        ClipboardContent::plain("x".repeat(bytes as usize))
+    }
+
+    fn allowed(decision: SizeDecision) -> Option<String> {
+        match decision {
+            SizeDecision::Allow(content) => Some(content.text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn clips_up_to_the_limit_pass_under_every_policy() {
+        for policy in [OversizePolicy::Skip, OversizePolicy::Truncate, OversizePolicy::Prompt] {
+            for bytes in [LIMIT - 1, LIMIT] {
+                assert_eq!(allowed(check(&config(policy), text(bytes))).map(|text| text.len() as u64), Some(bytes), "{:?}", policy);
+            }
+        }
+    }
+
+    #[test]
+    fn skip_drops_a_clip_one_byte_over() {
+        assert!(matches!(check(&config(OversizePolicy::Skip), text(LIMIT + 1)), SizeDecision::Drop));
+    }
+
+    #[test]
+    fn truncate_cuts_a_clip_one_byte_over_to_the_limit() {
+        assert_eq!(allowed(check(&config(OversizePolicy::Truncate), text(LIMIT + 1))), Some("x".repeat(LIMIT as usize)));
+    }
+
+    #[test]
+    fn truncate_drops_rich_formats_and_keeps_whole_characters() {
+        let content = ClipboardContent { text: "aaaaaaaaaé".to_string(), html: Some("<b>a</b>".to_string()), rtf: None };
+        let cut = match check(&config(OversizePolicy::Truncate), content) {
+            SizeDecision::Allow(content) => content,
+            _ => panic!("truncated clip was not allowed"),
+        };
+        // "é" straddles the limit, so the cut falls before it
+        assert_eq!(cut.text, "aaaaaaaaa");
+        assert_eq!(cut.html, None);
+    }
+
+    #[test]
+    fn prompt_holds_a_clip_one_byte_over_as_it_is() {
+        match check(&config(OversizePolicy::Prompt), text(LIMIT + 1)) {
+            SizeDecision::Prompt(content) => assert_eq!(content_size(&content), LIMIT + 1),
+            _ => panic!("oversized clip was not held for a prompt"),
+        }
+    }
+
+    #[test]
+    fn rich_formats_count_toward_the_limit() {
+        let content = ClipboardContent { text: "x".repeat(6), html: Some("y".repeat(5)), rtf: None };
+        assert_eq!(content_size(&content), 11);
+        assert!(matches!(check(&config(OversizePolicy::Skip), content), SizeDecision::Drop));
+    }
+
+    #[test]
+    fn no_limit_allows_anything() {
+        let config = Config { max_content_bytes: 0, ..config(OversizePolicy::Skip) };
+        assert!(allowed(check(&config, text(1_000_000))).is_some());
+        assert_eq!(wire_limit(&config), None);
+    }
+
+    #[test]
+    fn only_skip_rejects_on_the_descriptor() {
+        let descriptor = |bytes| ContentDescriptor::of(&text(bytes), "");
+        let skip = config(OversizePolicy::Skip);
+        assert!(!rejects(&skip, &descriptor(LIMIT)));
+        assert!(rejects(&skip, &descriptor(LIMIT + 1)));
+        for policy in [OversizePolicy::Truncate, OversizePolicy::Prompt] {
+            assert!(!rejects(&config(policy), &descriptor(LIMIT + 1)), "{:?}", policy);
+            assert_eq!(wire_limit(&config(policy)), None);
+        }
+        assert_eq!(wire_limit(&skip), Some(LIMIT * 2 + WIRE_OVERHEAD));
+    }
+
+    #[tokio::test]
+    async fn held_clips_are_announced_and_taken_once() {
+        let gate = OversizeGate::default();
+        let (events, mut announced) = tokio::sync::mpsc::unbounded_channel();
+        gate.set_notifier(move |event| {
+            let _ = events.send(event);
+        }).await;
+        gate.hold(Direction::Outgoing, text(LIMIT + 1), None, None, LIMIT).await;
+
+        let event = announced.try_recv().unwrap();
+        assert_eq!((event.bytes, event.limit, event.direction), (LIMIT + 1, LIMIT, Direction::Outgoing));
+        assert_eq!(gate.take(event.id).await.map(|clip| content_size(&clip.content)), Some(LIMIT + 1));
+        assert!(gate.take(event.id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn the_oldest_held_clips_go_past_the_cap() {
+        let gate = OversizeGate::default();
+        for _ in 0..MAX_PENDING + 2 {
+            gate.hold(Direction::Outgoing, text(LIMIT + 1), None, None, LIMIT).await;
+        }
+        assert_eq!(gate.pending.read().await.len(), MAX_PENDING);
+    }
+}
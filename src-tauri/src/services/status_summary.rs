@@ -0,0 +1,72 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Local, Utc};
+use tauri::AppHandle;
+use crate::models::{HistoryOrigin, StatusSummary};
+use super::events::{self, STATUS_SUMMARY_EVENT};
+use super::history::HistoryStore;
+use super::websocket::WebSocketServer;
+
+/// How often the summary is recomputed; listeners hear only of changes.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Start of the local day, for the daily counts.
+fn start_of_today() -> DateTime<Utc> {
+    Local::now()
+        .date_naive()
+        .and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(Local).earliest())
+        .map_or_else(Utc::now, |midnight| midnight.with_timezone(&Utc))
+}
+
+/// Current summary of a running server, or of stopped sync when `ws` is `None`.
+pub async fn collect(ws: Option<&WebSocketServer>, history: Option<&HistoryStore>) -> StatusSummary {
+    let Some(ws) = ws else {
+        return StatusSummary::default();
+    };
+    let metrics = ws.get_sync_metrics().await;
+    let (copied_today, received_today) = match history {
+        Some(history) => {
+            let since = start_of_today();
+            let copied = history.count_since(since, HistoryOrigin::Local).await;
+            let received = history.count_since(since, HistoryOrigin::Remote).await;
+            (copied.unwrap_or_default(), received.unwrap_or_default())
+        }
+        None => (0, 0),
+    };
+    StatusSummary {
+        running: true,
+        paused: ws.is_paused().await,
+        connected_peers: metrics.connected_peers,
+        last_sync_at: metrics.last_sync_time,
+        last_sync_secs_ago: metrics.last_sync_time.map(|at| (Utc::now() - at).num_seconds().max(0) as u64),
+        copied_today,
+        received_today,
+    }
+}
+
+/// Keep the server's summary current while it runs, telling the UI and
+/// subscribed local API clients whenever it changes. Time passing alone
+/// is not a change.
+pub fn spawn(app: Option<AppHandle>, ws: Arc<WebSocketServer>, history: Option<Arc<HistoryStore>>) {
+    let shutdown = ws.shutdown_signal();
+    shutdown.clone().spawn(async move {
+        let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            let summary = collect(Some(&ws), history.as_deref()).await;
+            let changed = ws.status_summary().send_if_modified(|current| {
+                let moved = StatusSummary { last_sync_secs_ago: current.last_sync_secs_ago, ..summary.clone() };
+                let changed = moved != *current;
+                *current = summary.clone();
+                changed
+            });
+            if changed {
+                events::emit(app.as_ref(), STATUS_SUMMARY_EVENT, summary);
+            }
+        }
+    });
+}
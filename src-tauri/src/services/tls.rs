@@ -0,0 +1,148 @@
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use tokio_rustls::rustls::crypto::{self, ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{ClientConfig, DigitallySignedStruct, Error as TlsError, ServerConfig, SignatureScheme};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// Directory under the app data dir holding this device's certificate.
+pub const TLS_DIR: &str = "tls";
+const CERT_FILE: &str = "cert.der";
+const KEY_FILE: &str = "key.der";
+/// Name the self-signed certificate is issued for. Peers are identified
+/// by fingerprint, so it is never checked.
+const CERT_SUBJECT: &str = "unimesh-clip.local";
+
+/// This device's persistent self-signed certificate.
+pub struct TlsIdentity {
+    cert: CertificateDer<'static>,
+    key: PrivatePkcs8KeyDer<'static>,
+    /// Hex SHA-256 of the certificate, advertised over mDNS
+    pub fingerprint: String,
+}
+
+/// Hex SHA-256 of a DER certificate.
+pub fn fingerprint(cert: &[u8]) -> String {
+    Sha256::digest(cert).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+impl TlsIdentity {
+    /// Load the certificate stored in `dir`, generating and saving one on
+    /// first use so the fingerprint peers pinned stays valid across restarts.
+    pub fn load_or_create(dir: &Path) -> Result<Self> {
+        let (cert_path, key_path) = (dir.join(CERT_FILE), dir.join(KEY_FILE));
+        let (cert, key) = if cert_path.exists() && key_path.exists() {
+            (std::fs::read(&cert_path)?, std::fs::read(&key_path)?)
+        } else {
+            tracing::info!("Generating TLS certificate in {}", dir.display());
+            let generated = rcgen::generate_simple_self_signed(vec![CERT_SUBJECT.to_string()])
+                .context("Failed to generate TLS certificate")?;
+            let (cert, key) = (generated.cert.der().to_vec(), generated.signing_key.serialize_der());
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(&cert_path, &cert)?;
+            std::fs::write(&key_path, &key)?;
+            (cert, key)
+        };
+
+        Ok(Self {
+            fingerprint: fingerprint(&cert),
+            cert: CertificateDer::from(cert),
+            key: PrivatePkcs8KeyDer::from(key),
+        })
+    }
+
+    pub fn acceptor(&self) -> Result<TlsAcceptor> {
+        let config = ServerConfig::builder_with_provider(provider())
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(vec![self.cert.clone()], PrivateKeyDer::Pkcs8(self.key.clone_key()))?;
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// Accepts exactly the certificate whose fingerprint was pinned, instead of
+/// checking a CA chain the self-signed certificates do not have.
+#[derive(Debug)]
+struct PinnedVerifier {
+    fingerprint: String,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, TlsError> {
+        if fingerprint(end_entity) == self.fingerprint {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TlsError::General("certificate does not match the pinned fingerprint".to_string()))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, TlsError> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// A client that only completes handshakes with the pinned certificate.
+pub fn pinned_connector(fingerprint: &str) -> Result<TlsConnector> {
+    let provider = provider();
+    let verifier = PinnedVerifier { fingerprint: fingerprint.to_lowercase(), provider: provider.clone() };
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(verifier))
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+/// Open a TLS session to `addr` that only completes if the device presents
+/// the pinned certificate.
+pub async fn connect_pinned(addr: SocketAddr, fingerprint: &str) -> Result<TlsStream<TcpStream>> {
+    let stream = TcpStream::connect(addr).await?;
+    let server_name = ServerName::try_from(CERT_SUBJECT)?;
+    pinned_connector(fingerprint)?
+        .connect(server_name, stream)
+        .await
+        .with_context(|| format!("{} did not present the advertised certificate", addr))
+}
+
+/// Check that the device at `addr` presents the certificate it advertised,
+/// before its fingerprint is pinned.
+pub async fn verify_pinned(addr: SocketAddr, fingerprint: &str) -> Result<()> {
+    connect_pinned(addr, fingerprint).await?;
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tauri_plugin_store::StoreExt;
+use crate::utils::paths;
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+use crate::models::{ClipboardMessage, DeviceTombstone, MessageType};
+use super::connection::ConnectionContext;
+use super::history::HistoryStore;
+use super::mdns::MdnsService;
+use super::shutdown::Shutdown;
+use super::trust::{TrustStore, TRUST_STORE_KEY};
+
+/// Key under which tombstones are persisted in the settings store.
+pub const TOMBSTONES_KEY: &str = "device_tombstones";
+
+/// Tombstones received from trusted peers, for the service manager to apply.
+pub(crate) type TombstoneTx = broadcast::Sender<DeviceTombstone>;
+
+/// Removed devices keyed by `address:port`, so repeated or stale
+/// tombstones are recognised.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TombstoneStore {
+    entries: HashMap<String, DeviceTombstone>,
+}
+
+impl TombstoneStore {
+    /// Keep a tombstone unless an equal or newer one is already known.
+    /// Returns whether it was new.
+    pub fn record(&mut self, tombstone: DeviceTombstone) -> bool {
+        let key = format!("{}:{}", tombstone.address, tombstone.port);
+        if self.entries.get(&key).is_some_and(|known| known.removed_at >= tombstone.removed_at) {
+            return false;
+        }
+        self.entries.insert(key, tombstone);
+        true
+    }
+
+    /// Forget the tombstone for a device that was paired again.
+    pub fn clear(&mut self, address: &str, port: u16) {
+        self.entries.remove(&format!("{}:{}", address, port));
+    }
+}
+
+/// Everything a tombstone removes a device from.
+#[derive(Clone)]
+pub struct TombstoneTargets {
+    pub app: Option<AppHandle>,
+    pub trust: Arc<RwLock<TrustStore>>,
+    pub tombstones: Arc<RwLock<TombstoneStore>>,
+    pub mdns: Option<Arc<MdnsService>>,
+    pub history: Option<Arc<HistoryStore>>,
+}
+
+impl TombstoneTargets {
+    /// Drop the device's trust decision, discovery entry, and history
+    /// contributions, then persist.
+    pub async fn apply(&self, tombstone: &DeviceTombstone) -> Result<()> {
+        self.trust.write().await.forget(&tombstone.address, tombstone.port);
+        if let Some(ref mdns) = self.mdns {
+            mdns.forget_device(&tombstone.address, tombstone.port).await;
+        }
+        if let Some(ref history) = self.history {
+            // History names remote devices by name, or by IP when unnamed
+            let removed = history.delete_by_device(&[&tombstone.name, &tombstone.address]).await?;
+            tracing::info!("Removed {} history entries from {}", removed, tombstone.name);
+        }
+        self.save().await
+    }
+
+    async fn save(&self) -> Result<()> {
+        if let Some(ref app) = self.app {
+            let store = app.store(paths::settings_store())?;
+            store.set(TRUST_STORE_KEY, serde_json::to_value(&*self.trust.read().await)?);
+            store.set(TOMBSTONES_KEY, serde_json::to_value(&*self.tombstones.read().await)?);
+            store.save()?;
+        }
+        Ok(())
+    }
+
+    /// Apply tombstones from peers as they arrive, until `shutdown`.
+    pub fn spawn_listener(self, mut rx: broadcast::Receiver<DeviceTombstone>, shutdown: Shutdown) {
+        shutdown.clone().spawn(async move {
+            loop {
+                let tombstone = tokio::select! {
+                    tombstone = rx.recv() => tombstone,
+                    _ = shutdown.cancelled() => break,
+                };
+                match tombstone {
+                    Ok(tombstone) => {
+                        if !self.tombstones.write().await.record(tombstone.clone()) {
+                            continue;
+                        }
+                        tracing::info!("Peer removed device {} ({}:{})", tombstone.name, tombstone.address, tombstone.port);
+                        if let Err(e) = self.apply(&tombstone).await {
+                            tracing::warn!("Failed to apply device tombstone: {}", e);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("Missed {} device tombstones", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+    }
+}
+
+/// Send a tombstone to every connected trusted peer except `skip` and the
+/// removed device itself. Reusing `id` when relaying lets peers drop copies
+/// they have already seen.
+pub(crate) async fn send(ctx: &ConnectionContext, tombstone: &DeviceTombstone, id: Uuid, skip: Option<Uuid>) -> Result<()> {
+    let mut message = ClipboardMessage::new(MessageType::DeviceRemoved, Some(serde_json::to_string(tombstone)?));
+    message.id = id;
+
+    let targets: Vec<Uuid> = {
+        let trust = ctx.security.trust.read().await;
+        ctx.peers.read().await
+            .iter()
+            .filter(|(peer_id, (addr, _))| Some(**peer_id) != skip && addr.ip().to_string() != tombstone.address)
+            .filter(|(_, (addr, _))| trust.is_trusted_ip(addr.ip()))
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    };
+    for peer_id in targets {
+        if let Err(e) = ctx.send_to_peer(peer_id, message.clone()).await {
+            tracing::warn!("Failed to send device tombstone to {}: {}", peer_id, e);
+        }
+    }
+    Ok(())
+}
+
+/// Accept a tombstone only when it is signed with the shared key and comes
+/// from a trusted peer, then pass it on to the other trusted peers.
+pub(crate) async fn handle(ctx: &ConnectionContext, peer_id: Uuid, id: Uuid, content: Option<&str>) {
+    let Some(tombstone) = content.and_then(|c| serde_json::from_str::<DeviceTombstone>(c).ok()) else {
+        tracing::warn!("Malformed device tombstone from {}", peer_id);
+        return;
+    };
+
+    let signed = ctx.config.read().await.effective_security_key().is_some();
+    let sender = ctx.peers.read().await.get(&peer_id).map(|(addr, _)| addr.ip());
+    let trusted = match sender {
+        Some(ip) => ctx.security.trust.read().await.is_trusted_ip(ip),
+        None => false,
+    };
+    if !signed || !trusted {
+        tracing::warn!("Ignoring device tombstone from untrusted or unpaired peer {}", peer_id);
+        return;
+    }
+
+    let _ = ctx.tombstone_tx.send(tombstone.clone());
+    if let Err(e) = send(ctx, &tombstone, id, Some(peer_id)).await {
+        tracing::warn!("Failed to relay device tombstone: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use tokio::sync::mpsc::UnboundedReceiver;
+    use tokio_tungstenite::tungstenite::Message;
+    use crate::models::Config;
+    use crate::services::lanes::PeerLink;
+    use crate::services::websocket::WebSocketServer;
+    use super::*;
+
+    const PORT: u16 = 7878;
+
+    fn tombstone(address: &str, removed_at: chrono::DateTime<Utc>) -> DeviceTombstone {
+        DeviceTombstone { name: "old laptop".to_string(), address: address.to_string(), port: PORT, removed_at }
+    }
+
+    #[test]
+    fn only_newer_tombstones_are_recorded() {
+        let mut store = TombstoneStore::default();
+        let now = Utc::now();
+        assert!(store.record(tombstone("192.168.1.20", now)));
+        assert!(!store.record(tombstone("192.168.1.20", now)));
+        assert!(!store.record(tombstone("192.168.1.20", now - Duration::seconds(1))));
+        assert!(store.record(tombstone("192.168.1.20", now + Duration::seconds(1))));
+        assert!(store.record(tombstone("192.168.1.21", now)));
+    }
+
+    #[test]
+    fn devices_paired_again_can_be_removed_again() {
+        let mut store = TombstoneStore::default();
+        let now = Utc::now();
+        store.record(tombstone("192.168.1.20", now));
+        store.clear("192.168.1.20", PORT);
+        assert!(store.record(tombstone("192.168.1.20", now)));
+    }
+
+    #[tokio::test]
+    async fn applying_a_tombstone_forgets_the_device() {
+        let trust = Arc::new(RwLock::new(TrustStore::default()));
+        trust.write().await.set_trusted("old laptop".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        let targets = TombstoneTargets { app: None, trust: trust.clone(), tombstones: Arc::default(), mdns: None, history: None };
+        targets.apply(&tombstone("192.168.1.20", Utc::now())).await.unwrap();
+        assert!(!trust.read().await.is_trusted("192.168.1.20", PORT, None));
+    }
+
+    fn context(security_key: Option<&str>) -> ConnectionContext {
+        let config = Config { security_key: security_key.map(str::to_string), ..Config::default() };
+        WebSocketServer::new(0, Arc::new(RwLock::new(config)), Arc::default(), Arc::default(), Arc::default(), Arc::default()).context()
+    }
+
+    /// Connect a peer at `ip`, trusted or not, returning its control lane.
+    async fn connect(ctx: &ConnectionContext, ip: &str, trusted: bool) -> (Uuid, UnboundedReceiver<Message>) {
+        let peer_id = Uuid::new_v4();
+        let (link, control, _) = PeerLink::new();
+        ctx.peers.write().await.insert(peer_id, (format!("{}:50000", ip).parse().unwrap(), link));
+        ctx.security.trust.write().await.set_trusted(ip.to_string(), ip.to_string(), PORT, trusted, None);
+        (peer_id, control)
+    }
+
+    fn payload(removed: &DeviceTombstone) -> String {
+        serde_json::to_string(removed).unwrap()
+    }
+
+    #[tokio::test]
+    async fn tombstones_from_trusted_peers_are_applied_and_relayed() {
+        let ctx = context(Some("passphrase"));
+        let mut applied = ctx.tombstone_tx.subscribe();
+        let (sender, mut sender_lane) = connect(&ctx, "192.168.1.10", true).await;
+        let (_, mut trusted_lane) = connect(&ctx, "192.168.1.11", true).await;
+        let (_, mut untrusted_lane) = connect(&ctx, "192.168.1.12", false).await;
+        let (_, mut removed_lane) = connect(&ctx, "192.168.1.20", true).await;
+        let removed = tombstone("192.168.1.20", Utc::now());
+        let id = Uuid::new_v4();
+
+        handle(&ctx, sender, id, Some(&payload(&removed))).await;
+        assert_eq!(applied.try_recv().unwrap().address, "192.168.1.20");
+        let Ok(Message::Text(relayed)) = trusted_lane.try_recv() else {
+            panic!("tombstone was not relayed to the trusted peer");
+        };
+        assert_eq!(serde_json::from_str::<ClipboardMessage>(&relayed).unwrap().id, id);
+        assert!(sender_lane.try_recv().is_err(), "tombstone went back to its sender");
+        assert!(untrusted_lane.try_recv().is_err(), "tombstone went to an untrusted peer");
+        assert!(removed_lane.try_recv().is_err(), "tombstone went to the removed device");
+    }
+
+    #[tokio::test]
+    async fn tombstones_from_untrusted_peers_are_ignored() {
+        let ctx = context(Some("passphrase"));
+        let mut applied = ctx.tombstone_tx.subscribe();
+        let (sender, _) = connect(&ctx, "192.168.1.10", false).await;
+        let (_, mut trusted_lane) = connect(&ctx, "192.168.1.11", true).await;
+        handle(&ctx, sender, Uuid::new_v4(), Some(&payload(&tombstone("192.168.1.20", Utc::now())))).await;
+        assert!(applied.try_recv().is_err());
+        assert!(trusted_lane.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn tombstones_are_ignored_without_a_security_key_or_when_malformed() {
+        let ctx = context(None);
+        let mut applied = ctx.tombstone_tx.subscribe();
+        let (sender, _) = connect(&ctx, "192.168.1.10", true).await;
+        handle(&ctx, sender, Uuid::new_v4(), Some(&payload(&tombstone("192.168.1.20", Utc::now())))).await;
+        assert!(applied.try_recv().is_err());
+
+        let ctx = context(Some("passphrase"));
+        let mut applied = ctx.tombstone_tx.subscribe();
+        let (sender, _) = connect(&ctx, "192.168.1.10", true).await;
+        handle(&ctx, sender, Uuid::new_v4(), Some("not a tombstone")).await;
+        handle(&ctx, sender, Uuid::new_v4(), None).await;
+        assert!(applied.try_recv().is_err());
+    }
+}
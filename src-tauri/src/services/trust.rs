@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::DiscoveredDevice;
+use crate::utils::net;
+
+/// Key under which trust decisions are persisted in the settings store.
+pub const TRUST_STORE_KEY: &str = "trusted_devices";
+
+/// A persisted trust decision about a device's server endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustEntry {
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+    pub trusted: bool,
+    pub decided_at: DateTime<Utc>,
+    /// TLS certificate fingerprint pinned when the device was trusted
+    #[serde(default)]
+    pub tls_fingerprint: Option<String>,
+    /// Stable identity the device announced; `None` for decisions made
+    /// before devices had one
+    #[serde(default)]
+    pub device_id: Option<Uuid>,
+    /// When a guest pairing ends; `None` for lasting decisions
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl TrustEntry {
+    /// Trusted, and not a guest whose time is up.
+    fn grants_trust(&self) -> bool {
+        self.trusted && self.expires_at.is_none_or(|expires_at| expires_at > Utc::now())
+    }
+}
+
+/// Trust decisions keyed by `address:port`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TrustStore {
+    entries: HashMap<String, TrustEntry>,
+}
+
+fn endpoint_key(address: &str, port: u16) -> String {
+    format!("{}:{}", address, port)
+}
+
+impl TrustStore {
+    /// A device stops counting as trusted when it advertises a certificate
+    /// other than the one pinned for it. An entry trusted without a pin
+    /// only matches a device advertising none; trusting it again pins the
+    /// certificate it advertises now.
+    pub fn is_trusted(&self, address: &str, port: u16, fingerprint: Option<&str>) -> bool {
+        self.entries
+            .get(&endpoint_key(address, port))
+            .is_some_and(|entry| entry.grants_trust() && entry.tls_fingerprint.as_deref() == fingerprint)
+    }
+
+    /// Certificate fingerprint pinned for a trusted device at `ip`, which
+    /// outranks whatever is advertised for it now.
+    pub fn pinned_fingerprint(&self, ip: IpAddr) -> Option<String> {
+        self.entries
+            .values()
+            .filter(|entry| entry.grants_trust() && net::parse_ip(&entry.address) == Some(ip))
+            .find_map(|entry| entry.tls_fingerprint.clone())
+    }
+
+    /// Inbound connections come from ephemeral ports, so they are matched
+    /// against trusted endpoints by IP alone.
+    pub fn is_trusted_ip(&self, ip: IpAddr) -> bool {
+        self.entries
+            .values()
+            .any(|entry| entry.grants_trust() && net::parse_ip(&entry.address) == Some(ip))
+    }
+
+    /// Record an explicit trust or distrust decision.
+    pub fn set_trusted(&mut self, name: String, address: String, port: u16, trusted: bool, tls_fingerprint: Option<String>) {
+        let entry = TrustEntry {
+            name,
+            address: address.clone(),
+            port,
+            trusted,
+            decided_at: Utc::now(),
+            tls_fingerprint,
+            device_id: None,
+            expires_at: None,
+        };
+        self.entries.insert(endpoint_key(&address, port), entry);
+    }
+
+    /// Trust a device as a guest until `expires_at`.
+    pub fn set_guest(&mut self, name: String, address: String, port: u16, tls_fingerprint: Option<String>, expires_at: DateTime<Utc>) {
+        self.set_trusted(name, address.clone(), port, true, tls_fingerprint);
+        if let Some(entry) = self.entries.get_mut(&endpoint_key(&address, port)) {
+            entry.expires_at = Some(expires_at);
+        }
+    }
+
+    /// Remove and return the guest pairings that ended by `now`.
+    pub fn take_expired(&mut self, now: DateTime<Utc>) -> Vec<TrustEntry> {
+        let expired: Vec<String> = self.entries
+            .iter()
+            .filter(|(_, entry)| entry.expires_at.is_some_and(|expires_at| expires_at <= now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired.iter().filter_map(|key| self.entries.remove(key)).collect()
+    }
+
+    /// Reconcile a discovered device that announces a stable identity with
+    /// decisions made about it earlier.
+    ///
+    /// An entry recorded for its `address:port` without an identity (made
+    /// when instance names were random per run) adopts the identity and the
+    /// current name. An entry already holding the identity under another
+    /// endpoint follows the device to its new address.
+    ///
+    /// # Returns
+    /// Whether any entry changed and the store should be saved
+    pub fn reconcile_identity(&mut self, device: &DiscoveredDevice) -> bool {
+        let Some(device_id) = device.device_id else {
+            return false;
+        };
+        let key = endpoint_key(&device.address, device.port);
+
+        let moved_from = self.entries
+            .iter()
+            .find(|(entry_key, entry)| entry.device_id == Some(device_id) && **entry_key != key)
+            .map(|(entry_key, _)| entry_key.clone());
+        if let Some(old_key) = moved_from {
+            if let Some(mut entry) = self.entries.remove(&old_key) {
+                tracing::info!("Trusted device {} moved from {} to {}", device_id, old_key, key);
+                entry.address = device.address.clone();
+                entry.port = device.port;
+                entry.name = device.name.clone();
+                self.entries.insert(key, entry);
+                return true;
+            }
+        }
+
+        match self.entries.get_mut(&key) {
+            Some(entry) if entry.device_id.is_none() => {
+                tracing::info!("Migrating trust decision for {} to device id {}", key, device_id);
+                entry.device_id = Some(device_id);
+                entry.name = device.name.clone();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Name recorded with the decision about a device, if any.
+    pub fn name_of(&self, address: &str, port: u16) -> Option<String> {
+        self.entries.get(&endpoint_key(address, port)).map(|entry| entry.name.clone())
+    }
+
+    /// When a guest pairing with a device ends; `None` for other devices.
+    pub fn guest_until(&self, address: &str, port: u16) -> Option<DateTime<Utc>> {
+        self.entries.get(&endpoint_key(address, port)).and_then(|entry| entry.expires_at)
+    }
+
+    /// Drop any decision about a device. Returns whether one existed.
+    pub fn forget(&mut self, address: &str, port: u16) -> bool {
+        self.entries.remove(&endpoint_key(address, port)).is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use super::*;
+
+    const PORT: u16 = 7878;
+
+    fn device(address: &str, device_id: Uuid) -> DiscoveredDevice {
+        serde_json::from_value(serde_json::json!({
+            "name": "laptop-now",
+            "address": address,
+            "port": PORT,
+            "last_seen": Utc::now(),
+            "trusted": false,
+            "device_id": device_id,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn trusted_devices_are_trusted_by_endpoint_and_ip() {
+        let mut store = TrustStore::default();
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        assert!(store.is_trusted("192.168.1.20", PORT, None));
+        assert!(!store.is_trusted("192.168.1.20", PORT + 1, None));
+        assert!(store.is_trusted_ip("192.168.1.20".parse().unwrap()));
+        assert!(!store.is_trusted_ip("192.168.1.21".parse().unwrap()));
+        assert_eq!(store.name_of("192.168.1.20", PORT).as_deref(), Some("laptop"));
+    }
+
+    #[test]
+    fn distrusted_devices_are_not_trusted() {
+        let mut store = TrustStore::default();
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, false, Some("ab".to_string()));
+        assert!(!store.is_trusted("192.168.1.20", PORT, Some("ab")));
+        assert!(!store.is_trusted_ip("192.168.1.20".parse().unwrap()));
+        assert_eq!(store.pinned_fingerprint("192.168.1.20".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn another_certificate_than_the_pinned_one_is_not_trusted() {
+        let mut store = TrustStore::default();
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, true, Some("ab".to_string()));
+        assert!(store.is_trusted("192.168.1.20", PORT, Some("ab")));
+        assert!(!store.is_trusted("192.168.1.20", PORT, Some("cd")));
+        assert!(!store.is_trusted("192.168.1.20", PORT, None));
+        assert_eq!(store.pinned_fingerprint("192.168.1.20".parse().unwrap()).as_deref(), Some("ab"));
+
+        // Trusted without a pin, a device advertising a certificate is not
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        assert!(!store.is_trusted("192.168.1.20", PORT, Some("ab")));
+    }
+
+    #[test]
+    fn guests_are_trusted_until_their_time_is_up() {
+        let mut store = TrustStore::default();
+        let until = Utc::now() + Duration::hours(1);
+        store.set_guest("phone".to_string(), "192.168.1.30".to_string(), PORT, None, until);
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        assert!(store.is_trusted("192.168.1.30", PORT, None));
+        assert_eq!(store.guest_until("192.168.1.30", PORT), Some(until));
+        assert_eq!(store.guest_until("192.168.1.20", PORT), None);
+
+        assert!(store.take_expired(until - Duration::seconds(1)).is_empty());
+        let expired = store.take_expired(until);
+        assert_eq!(expired.iter().map(|entry| entry.name.as_str()).collect::<Vec<_>>(), ["phone"]);
+        assert!(!store.is_trusted("192.168.1.30", PORT, None));
+        assert!(store.is_trusted("192.168.1.20", PORT, None));
+    }
+
+    #[test]
+    fn expired_guests_are_not_trusted_before_they_are_removed() {
+        let mut store = TrustStore::default();
+        store.set_guest("phone".to_string(), "192.168.1.30".to_string(), PORT, Some("ab".to_string()), Utc::now() - Duration::seconds(1));
+        assert!(!store.is_trusted("192.168.1.30", PORT, Some("ab")));
+        assert!(!store.is_trusted_ip("192.168.1.30".parse().unwrap()));
+        assert_eq!(store.pinned_fingerprint("192.168.1.30".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn decisions_without_an_identity_adopt_the_announced_one() {
+        let mut store = TrustStore::default();
+        store.set_trusted("laptop-old".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        let device_id = Uuid::new_v4();
+        assert!(store.reconcile_identity(&device("192.168.1.20", device_id)));
+        assert_eq!(store.name_of("192.168.1.20", PORT).as_deref(), Some("laptop-now"));
+        // Already adopted, so nothing changes again
+        assert!(!store.reconcile_identity(&device("192.168.1.20", device_id)));
+    }
+
+    #[test]
+    fn decisions_follow_a_device_to_its_new_address() {
+        let mut store = TrustStore::default();
+        let device_id = Uuid::new_v4();
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        store.reconcile_identity(&device("192.168.1.20", device_id));
+
+        assert!(store.reconcile_identity(&device("192.168.1.44", device_id)));
+        assert!(store.is_trusted("192.168.1.44", PORT, None));
+        assert!(!store.is_trusted("192.168.1.20", PORT, None));
+        // Another device at the old address gets no trust from it
+        assert!(!store.reconcile_identity(&device("192.168.1.20", Uuid::new_v4())));
+    }
+
+    #[test]
+    fn devices_without_an_identity_are_left_alone() {
+        let mut store = TrustStore::default();
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        let mut anonymous = device("192.168.1.20", Uuid::new_v4());
+        anonymous.device_id = None;
+        assert!(!store.reconcile_identity(&anonymous));
+    }
+
+    #[test]
+    fn forgotten_devices_are_no_longer_trusted() {
+        let mut store = TrustStore::default();
+        store.set_trusted("laptop".to_string(), "192.168.1.20".to_string(), PORT, true, None);
+        assert!(store.forget("192.168.1.20", PORT));
+        assert!(!store.forget("192.168.1.20", PORT));
+        assert!(!store.is_trusted("192.168.1.20", PORT, None));
+    }
+}
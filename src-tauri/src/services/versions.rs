@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Response};
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderValue, StatusCode};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::models::{ClipboardMessage, Hello, MessageType, MismatchSeverity, PeerVersionMismatch, Protection, UpgradeSide};
+use super::connection::ConnectionContext;
+use super::peer_capabilities;
+use super::events::{self, PEER_VERSION_MISMATCH_EVENT};
+
+pub use unimesh_proto::version::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use unimesh_proto::version::{ProtocolVersion, APP_VERSION_HEADER, MIN_PROTOCOL_HEADER, PROTOCOL_HEADER, PROTOCOL_MINOR_HEADER};
+
+/// First minor version that takes `Hello` messages.
+const HELLO_MINOR: u32 = 1;
+
+/// Known version mismatches by peer address, for marking devices in listings.
+pub(crate) type VersionMismatches = Arc<RwLock<HashMap<IpAddr, PeerVersionMismatch>>>;
+
+/// Versions a peer advertised in its handshake headers.
+#[derive(Debug, Clone)]
+pub struct PeerVersion {
+    pub app_version: Option<String>,
+    pub protocol: u32,
+    pub min_protocol: u32,
+}
+
+impl PeerVersion {
+    /// Read the version headers; `None` for builds that predate them.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let number = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u32>().ok();
+        let protocol = number(PROTOCOL_HEADER)?;
+        Some(Self {
+            app_version: headers.get(APP_VERSION_HEADER).and_then(|v| v.to_str().ok()).map(str::to_string),
+            protocol,
+            min_protocol: number(MIN_PROTOCOL_HEADER).unwrap_or(protocol),
+        })
+    }
+
+    /// How far this peer is from us, or `None` when it runs our protocol.
+    pub fn compare(&self, address: IpAddr) -> Option<PeerVersionMismatch> {
+        let (severity, upgrade) = if self.protocol < MIN_PROTOCOL_VERSION {
+            (MismatchSeverity::Incompatible, UpgradeSide::Peer)
+        } else if self.min_protocol > PROTOCOL_VERSION {
+            (MismatchSeverity::Incompatible, UpgradeSide::Local)
+        } else if self.protocol < PROTOCOL_VERSION {
+            (MismatchSeverity::Deprecated, UpgradeSide::Peer)
+        } else if self.protocol > PROTOCOL_VERSION {
+            (MismatchSeverity::Deprecated, UpgradeSide::Local)
+        } else {
+            return None;
+        };
+        Some(PeerVersionMismatch {
+            address: address.to_string(),
+            peer_app_version: self.app_version.clone(),
+            peer_protocol: self.protocol,
+            local_app_version: env!("CARGO_PKG_VERSION").to_string(),
+            local_protocol: PROTOCOL_VERSION,
+            severity,
+            upgrade,
+            detected_at: chrono::Utc::now(),
+        })
+    }
+}
+
+/// Add our version headers to a handshake request or response.
+pub fn advertise(headers: &mut HeaderMap) {
+    headers.insert(APP_VERSION_HEADER, HeaderValue::from_static(env!("CARGO_PKG_VERSION")));
+    headers.insert(PROTOCOL_HEADER, HeaderValue::from(PROTOCOL_VERSION));
+    headers.insert(MIN_PROTOCOL_HEADER, HeaderValue::from(MIN_PROTOCOL_VERSION));
+    headers.insert(PROTOCOL_MINOR_HEADER, HeaderValue::from(ProtocolVersion::CURRENT.minor));
+}
+
+/// Whether the other side of a handshake takes `Hello` messages. Older
+/// builds would drop them as unparseable.
+pub fn offers_hello(headers: &HeaderMap) -> bool {
+    headers.get(PROTOCOL_MINOR_HEADER)
+        .and_then(|value| value.to_str().ok()?.trim().parse::<u32>().ok())
+        .is_some_and(|minor| minor >= HELLO_MINOR)
+}
+
+/// Answer an incoming handshake: refuse peers we cannot talk to with a
+/// readable reason, and advertise our versions to the rest.
+// `ErrorResponse` is the rejection type tungstenite's handshake callback expects
+#[allow(clippy::result_large_err)]
+pub fn check_handshake(peer: Option<&PeerVersion>, source: IpAddr, mut response: Response) -> Result<Response, ErrorResponse> {
+    let mismatch = peer.and_then(|peer| peer.compare(source));
+    if let Some(ref mismatch) = mismatch.filter(|m| m.severity == MismatchSeverity::Incompatible) {
+        let mut refusal = ErrorResponse::new(Some(mismatch.describe()));
+        *refusal.status_mut() = StatusCode::UPGRADE_REQUIRED;
+        advertise(refusal.headers_mut());
+        return Err(refusal);
+    }
+    advertise(response.headers_mut());
+    Ok(response)
+}
+
+/// Remember how `addr` compares to us and tell the UI about a mismatch.
+/// Peers without version headers are left as they are.
+pub(crate) async fn record(ctx: &ConnectionContext, addr: SocketAddr, peer: Option<&PeerVersion>) {
+    let Some(peer) = peer else {
+        return;
+    };
+    match peer.compare(addr.ip()) {
+        Some(mismatch) => {
+            tracing::warn!("Version mismatch with {}: {}", addr, mismatch.describe());
+            ctx.telemetry.version_mismatches.write().await.insert(addr.ip(), mismatch.clone());
+            events::emit(ctx.app.as_ref(), PEER_VERSION_MISMATCH_EVENT, mismatch);
+        }
+        None => {
+            ctx.telemetry.version_mismatches.write().await.remove(&addr.ip());
+        }
+    }
+}
+
+/// Protocol a raw message was written in, read even when the rest of it
+/// does not parse.
+pub fn written_in(text: &str) -> Option<ProtocolVersion> {
+    #[derive(Deserialize)]
+    struct Versioned {
+        protocol_version: Option<ProtocolVersion>,
+    }
+    serde_json::from_str::<Versioned>(text).ok()?.protocol_version
+}
+
+/// Whether a message written in `version` comes from a protocol this build
+/// cannot read. The mismatch is reported once, like one found in a
+/// handshake, rather than leaving the peer's messages to fail silently.
+pub(crate) async fn refuses(ctx: &ConnectionContext, addr: Option<SocketAddr>, version: Option<ProtocolVersion>) -> bool {
+    let Some(version) = version.filter(|version| !version.is_supported()) else {
+        return false;
+    };
+    tracing::warn!("Dropping a message from {:?} in protocol {}, which this build cannot read", addr, version);
+    let Some(addr) = addr else {
+        return true;
+    };
+    let known = ctx.telemetry.version_mismatches.read().await.get(&addr.ip()).is_some_and(|known| known.peer_protocol == version.major);
+    if !known {
+        let peer = PeerVersion { app_version: None, protocol: version.major, min_protocol: version.major };
+        record(ctx, addr, Some(&peer)).await;
+    }
+    true
+}
+
+/// Tell a peer that takes `Hello` messages which versions we run and
+/// what we can take.
+pub(crate) async fn send_hello(ctx: &ConnectionContext, peer_id: Uuid) {
+    let mut hello = Hello {
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        protocol: ProtocolVersion::CURRENT,
+        min_protocol: MIN_PROTOCOL_VERSION,
+        device_id: None,
+        name: None,
+        formats: Vec::new(),
+        max_message_bytes: None,
+        compression: Vec::new(),
+    };
+    peer_capabilities::fill_hello(ctx, &mut hello).await;
+    let Ok(content) = serde_json::to_string(&hello) else {
+        return;
+    };
+    let message = ClipboardMessage::new(MessageType::Hello, Some(content)).with_protection(Protection::Signed);
+    if let Err(e) = ctx.send_to_peer(peer_id, message).await {
+        tracing::debug!("Failed to send hello to {}: {}", peer_id, e);
+    }
+}
+
+/// Record the versions and capabilities a peer confirmed in its `Hello`,
+/// closing the connection when the two cannot talk.
+pub(crate) async fn handle_hello(ctx: &ConnectionContext, peer_id: Uuid, content: Option<&str>) {
+    let Some(hello) = content.and_then(|content| serde_json::from_str::<Hello>(content).ok()) else {
+        tracing::warn!("Malformed hello from {}", peer_id);
+        return;
+    };
+    let Some(addr) = ctx.peers.read().await.get(&peer_id).map(|(addr, _)| *addr) else {
+        return;
+    };
+    tracing::info!("{} runs version {} on protocol {}", addr, hello.app_version, hello.protocol);
+    if let Some(capabilities) = peer_capabilities::from_hello(&hello) {
+        tracing::debug!("{} takes {:?}", addr, capabilities);
+        ctx.policy.capabilities.record(peer_id, capabilities);
+    }
+    let peer = PeerVersion {
+        app_version: Some(hello.app_version),
+        protocol: hello.protocol.major,
+        min_protocol: hello.min_protocol,
+    };
+    let incompatible = peer.compare(addr.ip()).is_some_and(|mismatch| mismatch.severity == MismatchSeverity::Incompatible);
+    record(ctx, addr, Some(&peer)).await;
+    if incompatible {
+        tracing::warn!("Closing the connection with {}: incompatible protocol", addr);
+        let _ = ctx.peer_close.send(peer_id);
+    }
+}
@@ -0,0 +1,95 @@
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::handshake::server::Request;
+use anyhow::Result;
+use std::net::SocketAddr;
+use crate::models::ConnectionDirection;
+use crate::services::access_policy::{AccessPolicy, Endpoint};
+use crate::services::connection::ConnectionContext;
+use crate::services::local_api::{self, ApiClient};
+use crate::services::{delta_sync, key_derivation, presentation, protocol_trace, sessions, versions};
+use crate::services::framing::{self, Framing};
+use crate::services::peer_session::{self, Route};
+use crate::services::versions::PeerVersion;
+use super::WebSocketServer;
+
+impl WebSocketServer {
+    /// Run one accepted connection until it closes.
+    pub(super) async fn serve_connection<S>(stream: S, addr: SocketAddr, local: Option<SocketAddr>, ctx: ConnectionContext)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        if let Err(e) = Self::handle_connection(stream, addr, local, ctx).await {
+            tracing::debug!("Connection from {} ended: {:#}", addr, e);
+        }
+    }
+
+    // `ErrorResponse` is the rejection type tungstenite's handshake callback expects
+    #[allow(clippy::result_large_err)]
+    async fn handle_connection<S>(
+        stream: S,
+        addr: SocketAddr,
+        local: Option<SocketAddr>,
+        ctx: ConnectionContext,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let policy = AccessPolicy::from_config(&*ctx.config.read().await);
+        let tokens = ctx.security.api_tokens.read().await.clone();
+        let session_code = ctx.policy.presentation.read().await.expected_code();
+        let mut api_client = None;
+        let mut peer_version = None;
+        let mut viewer = false;
+        let mut delta = false;
+        let mut session = None;
+        let mut hello = false;
+        let mut key_salt = None;
+        let legacy_keys = key_derivation::admits_legacy(&*ctx.config.read().await);
+        let binary_framing = ctx.config.read().await.binary_framing;
+        let mut framing = Framing::Json;
+        let handshake = accept_hdr_async(stream, |request: &Request, response| {
+            // A token marks the connection as a local API client rather than a peer
+            let Some(secret) = local_api::presented_token(request) else {
+                let response = policy.check_handshake(Endpoint::PeerSync, addr.ip(), request, response)?;
+                let presented = presentation::presented_code(request.headers());
+                let response = presentation::check_handshake(presented.as_deref(), session_code.as_deref(), response)?;
+                viewer = presented.is_some();
+                delta = delta_sync::offered(request.headers());
+                hello = versions::offers_hello(request.headers());
+                peer_version = PeerVersion::from_headers(request.headers());
+                let mut response = versions::check_handshake(peer_version.as_ref(), addr.ip(), response)?;
+                delta_sync::advertise(response.headers_mut());
+                framing = framing::negotiate(binary_framing, request.headers());
+                if framing == Framing::MessagePack {
+                    framing::advertise(response.headers_mut());
+                }
+                session = sessions::accept(request.headers(), response.headers_mut());
+                key_salt = key_derivation::offered(request.headers());
+                let mut response = key_derivation::check_handshake(key_salt, legacy_keys, response)?;
+                key_derivation::advertise(response.headers_mut(), ctx.security.keys.salt());
+                return Ok(response);
+            };
+            let response = policy.check_handshake(Endpoint::LocalApi, addr.ip(), request, response)?;
+            let (token_id, scope) = tokens.authenticate(&secret).ok_or_else(local_api::unauthorized)?;
+            api_client = Some(ApiClient { token_id, scope });
+            Ok(response)
+        }).await;
+        versions::record(&ctx, addr, peer_version.as_ref()).await;
+        let verdict = match handshake {
+            Ok(_) if api_client.is_some() => "accepted local API client".to_string(),
+            Ok(_) => "accepted".to_string(),
+            Err(ref e) => format!("refused: {}", e),
+        };
+        protocol_trace::handshake(ConnectionDirection::Inbound, addr, &verdict);
+        let ws_stream = handshake?;
+
+        if let Some(client) = api_client {
+            return local_api::serve(ws_stream, addr, client, ctx).await;
+        }
+        if viewer {
+            presentation::admit(&ctx, addr).await;
+        }
+        peer_session::run(ws_stream, addr, Route { direction: ConnectionDirection::Inbound, local, delta, session, hello, framing, key_salt }, ctx).await
+    }
+}
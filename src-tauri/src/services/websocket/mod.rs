@@ -0,0 +1,386 @@
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use tokio::sync::{Notify, RwLock, broadcast, oneshot, watch};
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+use anyhow::Result;
+use crate::utils::net;
+use std::time::Duration;
+use crate::models::{ClipOrigin, ClipboardContent, Config, MessageCache, PeerEndpoint, StatusSummary, SyncMetrics};
+use super::connection::{ApiTx, ApplyOutcome, ClipboardCallback, ConnectionContext, PeerMap, PolicyContext, SecurityContext, TelemetryContext, Tx};
+use super::remote_logs::{self, PendingLogRequests};
+use super::trust::TrustStore;
+use super::api_tokens::ApiTokenStore;
+use super::dialer;
+use super::listener;
+use super::applied_log::AppliedLog;
+use super::liveness::LivenessRegistry;
+use super::key_rotation::SharedKeyRing;
+use super::key_derivation::KeyDerivation;
+use super::peer_session;
+use super::presentation::SharedPresentation;
+use super::remote_mute::{self, MuteRegistry};
+use super::receipts::{self, AppliedTx, ReceiptTx};
+use super::size_policy::OversizeGate;
+use super::tombstones::TombstoneTx;
+use super::device_names::DeviceDirectory;
+use super::events::{self, METRICS_UPDATED_EVENT};
+use super::shutdown::{Shutdown, StoppableTask, DRAIN_TIMEOUT};
+use tauri::AppHandle;
+
+mod accept;
+mod peers;
+
+/// How often changed metrics are pushed to the UI.
+const METRICS_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long stopping waits for peers to answer our close frames.
+const CLOSE_GRACE: Duration = Duration::from_secs(2);
+
+pub struct WebSocketServer {
+    port: AtomicU16,
+    peers: PeerMap,
+    tx: Tx,
+    /// Stops every task this server and the services around it started
+    shutdown: Shutdown,
+    started: AtomicBool,
+    /// Accepts on the current port; a replaced one drains on its own
+    listener: RwLock<Option<StoppableTask>>,
+    message_cache: Arc<RwLock<MessageCache>>,
+    clipboard_callback: ClipboardCallback,
+    config: Arc<RwLock<Config>>,
+    pending_log_requests: PendingLogRequests,
+    api_tx: ApiTx,
+    peer_close: broadcast::Sender<Uuid>,
+    migrations: Arc<RwLock<Vec<PeerEndpoint>>>,
+    tombstone_tx: TombstoneTx,
+    receipt_tx: ReceiptTx,
+    applied_tx: AppliedTx,
+    applied: Arc<AppliedLog>,
+    security: SecurityContext,
+    policy: PolicyContext,
+    telemetry: TelemetryContext,
+    dial_wake: Arc<Notify>,
+    tls: Option<TlsAcceptor>,
+    directory: Arc<DeviceDirectory>,
+    app: Option<AppHandle>,
+}
+
+impl WebSocketServer {
+    /// Create a server on `port`. `config`, `trust`, `api_tokens`,
+    /// `oversize`, and `directory` are the live state shared with the
+    /// service manager, so changes apply immediately.
+    pub fn new(
+        port: u16,
+        config: Arc<RwLock<Config>>,
+        trust: Arc<RwLock<TrustStore>>,
+        api_tokens: Arc<RwLock<ApiTokenStore>>,
+        oversize: Arc<OversizeGate>,
+        directory: Arc<DeviceDirectory>,
+    ) -> Self {
+        let (tx, _) = broadcast::channel(100);
+        let (api_tx, _) = broadcast::channel(100);
+        let (api_revoked, _) = broadcast::channel(16);
+        let (peer_close, _) = broadcast::channel(16);
+        let (tombstone_tx, _) = broadcast::channel(16);
+        let (receipt_tx, _) = broadcast::channel(64);
+        let (applied_tx, _) = broadcast::channel(64);
+        Self {
+            port: AtomicU16::new(port),
+            peers: Arc::new(RwLock::new(HashMap::new())),
+            tx,
+            shutdown: Shutdown::new(),
+            started: AtomicBool::new(false),
+            listener: RwLock::new(None),
+            message_cache: Arc::new(RwLock::new(MessageCache::new())),
+            clipboard_callback: Arc::new(RwLock::new(None)),
+            config,
+            pending_log_requests: Arc::new(RwLock::new(HashMap::new())),
+            api_tx,
+            peer_close,
+            migrations: Arc::new(RwLock::new(Vec::new())),
+            tombstone_tx,
+            receipt_tx,
+            applied_tx,
+            applied: Arc::default(),
+            security: SecurityContext {
+                trust,
+                api_tokens,
+                api_revoked,
+                sessions: Arc::default(),
+                key_ring: Arc::default(),
+                keys: Arc::default(),
+            },
+            policy: PolicyContext {
+                paused: Arc::new(RwLock::new(false)),
+                paused_peers: Arc::new(RwLock::new(HashSet::new())),
+                presentation: SharedPresentation::default(),
+                delta: Arc::default(),
+                mutes: Arc::default(),
+                oversize,
+                capabilities: Arc::default(),
+            },
+            telemetry: TelemetryContext {
+                sync_metrics: Arc::new(RwLock::new(SyncMetrics::default())),
+                peer_metrics: Arc::new(RwLock::new(HashMap::new())),
+                status: Arc::new(watch::Sender::new(StatusSummary::default())),
+                link_history: Arc::new(RwLock::new(HashMap::new())),
+                liveness: Arc::default(),
+                version_mismatches: Arc::new(RwLock::new(HashMap::new())),
+            },
+            dial_wake: Arc::new(Notify::new()),
+            tls: None,
+            directory,
+            app: None,
+        }
+    }
+
+    /// Emit peer and metrics events to the UI.
+    pub fn with_app_handle(mut self, app: Option<AppHandle>) -> Self {
+        self.app = app;
+        self
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port.load(Ordering::Relaxed)
+    }
+
+    pub fn is_tls(&self) -> bool {
+        self.tls.is_some()
+    }
+
+    /// Report session and heartbeat state to the shared liveness registry.
+    pub fn with_liveness(mut self, liveness: Arc<LivenessRegistry>) -> Self {
+        self.telemetry.liveness = liveness;
+        self
+    }
+
+    /// Remember applied clips in `log`, which may outlive this server.
+    pub fn with_applied_log(mut self, log: Arc<AppliedLog>) -> Self {
+        self.applied = log;
+        self
+    }
+
+    /// Accept the previous security key from `ring` while its grace lasts.
+    pub fn with_key_ring(mut self, ring: SharedKeyRing) -> Self {
+        self.security.key_ring = ring;
+        self
+    }
+
+    /// Derive wire keys with this installation's salt in `keys`.
+    pub fn with_key_derivation(mut self, keys: Arc<KeyDerivation>) -> Self {
+        self.security.keys = keys;
+        self
+    }
+
+    /// Serve `wss://` with this acceptor instead of plain `ws://`.
+    pub fn with_tls(mut self, acceptor: TlsAcceptor) -> Self {
+        self.tls = Some(acceptor);
+        self
+    }
+
+    pub(crate) fn context(&self) -> ConnectionContext {
+        ConnectionContext {
+            peers: self.peers.clone(),
+            tx: self.tx.clone(),
+            message_cache: self.message_cache.clone(),
+            clipboard_callback: self.clipboard_callback.clone(),
+            config: self.config.clone(),
+            pending_log_requests: self.pending_log_requests.clone(),
+            api_tx: self.api_tx.clone(),
+            peer_close: self.peer_close.clone(),
+            migrations: self.migrations.clone(),
+            tombstone_tx: self.tombstone_tx.clone(),
+            receipt_tx: self.receipt_tx.clone(),
+            applied: self.applied.clone(),
+            directory: self.directory.clone(),
+            security: self.security.clone(),
+            policy: self.policy.clone(),
+            telemetry: self.telemetry.clone(),
+            app: self.app.clone(),
+            shutdown: self.shutdown.clone(),
+        }
+    }
+
+    pub async fn start(&self) -> Result<()> {
+        // Check if already running
+        if self.started.swap(true, Ordering::SeqCst) {
+            tracing::warn!("WebSocket server is already running");
+            return Ok(());
+        }
+
+        // Bind to all interfaces to allow cross-device connections
+        let (listener, addr) = match listener::bind(self.port()).await {
+            Ok(bound) => bound,
+            Err(e) => {
+                self.started.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+        self.telemetry.sync_metrics.write().await.started_at = Some(chrono::Utc::now());
+        tracing::info!(
+            "WebSocket server listening on {} (all interfaces, {})",
+            addr, if self.tls.is_some() { "TLS" } else { "plaintext" }
+        );
+        *self.listener.write().await = Some(self.serve(listener));
+
+        let ctx = self.context();
+        let shutdown = self.shutdown.clone();
+        let mut metrics_tick = tokio::time::interval(METRICS_EVENT_INTERVAL);
+        let mut last_metrics = None;
+
+        self.shutdown.spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = metrics_tick.tick(), if ctx.app.is_some() => {
+                        // Only changes are pushed, so an idle server stays quiet
+                        let metrics = ctx.telemetry.sync_metrics.read().await.clone();
+                        if last_metrics.as_ref() != Some(&metrics) {
+                            events::emit(ctx.app.as_ref(), METRICS_UPDATED_EVENT, &metrics);
+                            last_metrics = Some(metrics);
+                        }
+                    }
+                    _ = shutdown.cancelled() => {
+                        tracing::info!("WebSocket server shutting down");
+                        break;
+                    }
+                }
+            }
+        });
+
+        receipts::spawn_acknowledger(self.context(), self.applied_tx.subscribe(), self.shutdown.clone());
+        dialer::spawn(self.context(), self.dial_wake.clone(), self.shutdown.clone());
+        Ok(())
+    }
+
+    /// Accept connections on `listener` until it is replaced or the server
+    /// shuts down. Connections it accepted carry on either way.
+    fn serve(&self, listener: TcpListener) -> StoppableTask {
+        let ctx = self.context();
+        let tls = self.tls.clone();
+        let sessions = self.shutdown.clone();
+        StoppableTask::spawn(&self.shutdown, |shutdown| async move {
+            loop {
+                tokio::select! {
+                    result = listener.accept() => {
+                        match result {
+                            Ok((stream, addr)) => match (tls.as_ref(), net::canonical(addr), stream.local_addr().ok().map(net::canonical)) {
+                                (Some(acceptor), addr, local) => {
+                                    let (acceptor, ctx) = (acceptor.clone(), ctx.clone());
+                                    sessions.spawn(async move {
+                                        match acceptor.accept(stream).await {
+                                            Ok(stream) => Self::serve_connection(stream, addr, local, ctx).await,
+                                            Err(e) => tracing::warn!("TLS handshake with {} failed: {}", addr, e),
+                                        }
+                                    });
+                                }
+                                (None, addr, local) => {
+                                    sessions.spawn(Self::serve_connection(stream, addr, local, ctx.clone()));
+                                }
+                            },
+                            Err(e) => {
+                                tracing::error!("Failed to accept connection: {}", e);
+                            }
+                        }
+                    }
+                    _ = shutdown.cancelled() => break,
+                }
+            }
+        })
+    }
+
+    /// Listen on `port` instead, without dropping anyone: the new port is
+    /// bound first, sessions already up carry on, and the old port keeps
+    /// accepting for `grace` for peers that have not seen the new one yet.
+    ///
+    /// # Errors
+    /// When `port` cannot be bound; the old port is then left as it was
+    pub async fn move_to_port(&self, port: u16, grace: Duration) -> Result<()> {
+        let old_port = self.port();
+        if port == old_port || !self.started.load(Ordering::SeqCst) {
+            self.port.store(port, Ordering::Relaxed);
+            return Ok(());
+        }
+        let (listener, addr) = listener::bind(port).await?;
+        let previous = self.listener.write().await.replace(self.serve(listener));
+        self.port.store(port, Ordering::Relaxed);
+        tracing::info!("WebSocket server now listening on {}; port {} closes in {:?}", addr, old_port, grace);
+        if let Some(previous) = previous {
+            let shutdown = self.shutdown.clone();
+            self.shutdown.spawn(async move {
+                tokio::select! {
+                    _ = tokio::time::sleep(grace) => {}
+                    _ = shutdown.cancelled() => {}
+                }
+                previous.stop(DRAIN_TIMEOUT, "Old port listener").await;
+                tracing::info!("Stopped listening on old port {}", old_port);
+            });
+        }
+        Ok(())
+    }
+
+    pub async fn stop(&self) -> Result<()> {
+        tracing::info!("Stopping WebSocket server on port {}", self.port());
+        
+        // Say goodbye while the sessions are still up, so peers do not
+        // hold half-open sockets
+        peer_session::close_all(&self.context(), CLOSE_GRACE).await;
+        
+        // Stop accepting first, then let every task finish what it is doing
+        if let Some(listener) = self.listener.write().await.take() {
+            listener.stop(DRAIN_TIMEOUT, "WebSocket listener").await;
+        }
+        if self.started.swap(false, Ordering::SeqCst) && self.shutdown.drain(DRAIN_TIMEOUT).await {
+            tracing::info!("WebSocket server stopped");
+        }
+        
+        // Clear all peers
+        self.peers.write().await.clear();
+        self.telemetry.peer_metrics.write().await.clear();
+        
+        Ok(())
+    }
+
+    /// Connect to static and manual peers from the config now instead of at
+    /// the next redial.
+    pub fn dial_peers(&self) {
+        self.dial_wake.notify_one();
+    }
+
+    pub async fn set_clipboard_callback<F>(&self, callback: F)
+    where
+        F: Fn(ClipboardContent, ClipOrigin) -> oneshot::Receiver<ApplyOutcome> + Send + Sync + 'static,
+    {
+        *self.clipboard_callback.write().await = Some(Box::new(callback));
+    }
+
+    /// Ask a connected peer for its most recent log lines.
+    ///
+    /// # Arguments
+    /// * `peer_id` - Connection id of the peer to query
+    /// * `lines` - Number of lines wanted, capped at `MAX_REMOTE_LOG_LINES`
+    ///
+    /// # Errors
+    /// When the peer is not connected, does not answer in time, or has not
+    /// enabled remote log sharing
+    pub async fn request_peer_logs(&self, peer_id: Uuid, lines: usize) -> Result<Vec<String>> {
+        remote_logs::request(&self.context(), peer_id, lines).await
+    }
+
+    /// Ask the peer at `ip` to stop sending us content until `until`, or
+    /// to resume when `None`.
+    pub async fn request_mute(&self, ip: std::net::IpAddr, until: Option<chrono::DateTime<chrono::Utc>>) -> Result<()> {
+        remote_mute::request(&self.context(), ip, until).await
+    }
+
+    /// Latest status summary, for widgets and local API clients.
+    pub fn status_summary(&self) -> Arc<watch::Sender<StatusSummary>> {
+        self.telemetry.status.clone()
+    }
+
+    /// Mutes in both directions, for device listings.
+    pub fn mutes(&self) -> Arc<MuteRegistry> {
+        self.policy.mutes.clone()
+    }
+}
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use anyhow::Result;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+use crate::models::{ClipOrigin, ClipReceipt, ClipboardContent, ClipboardMessage, ConnectedPeer, ConnectionDirection, DeviceTombstone, HistoryOrigin, PeerEndpoint, PeerMetrics, PeerVersionMismatch, SyncMetrics};
+use crate::services::{key_rotation, peer_metrics, tombstones};
+use crate::services::connection::ApplyOutcome;
+use crate::services::receipts::ApplyReport;
+use crate::services::shutdown::Shutdown;
+use super::WebSocketServer;
+
+impl WebSocketServer {
+    /// Send a message to every peer, protected per the current config.
+    pub async fn broadcast_message(&self, message: ClipboardMessage) -> Result<()> {
+        self.context().broadcast(message).await
+    }
+
+    /// Tell local API clients about a change copied on this machine.
+    pub fn publish_local_change(&self, content: ClipboardContent) {
+        self.context().publish_api_event(None, content, HistoryOrigin::Local, None);
+    }
+
+    /// Apply a held incoming clip the user accepted.
+    pub async fn apply_held_clip(&self, origin: &ClipOrigin, content: ClipboardContent) {
+        let ctx = self.context();
+        if let ApplyOutcome::Applied(content) = ctx.apply_clipboard_update(origin, &content).await {
+            ctx.publish_api_event(None, content, HistoryOrigin::Remote, Some(ctx.directory.name_for(origin)));
+        }
+    }
+
+    /// Close the sessions whose local end is one of `lost`, because the
+    /// interface holding it went away. Returns the peers that were closed
+    /// and which side had dialed.
+    pub async fn close_sessions_via(&self, lost: &[IpAddr]) -> Vec<(SocketAddr, ConnectionDirection)> {
+        let closing: Vec<PeerMetrics> = self.telemetry.peer_metrics.read().await
+            .values()
+            .filter(|metrics| metrics.local_address.is_some_and(|local| lost.contains(&local.ip())))
+            .cloned()
+            .collect();
+        for metrics in &closing {
+            tracing::info!("Interface {} went away, closing connection to {}", metrics.interface.as_deref().unwrap_or("?"), metrics.address);
+            let _ = self.peer_close.send(metrics.peer_id);
+        }
+        closing.into_iter().map(|metrics| (metrics.address, metrics.direction)).collect()
+    }
+
+    /// Close every session with the device at `ip`.
+    pub async fn close_sessions_to(&self, ip: IpAddr) {
+        for metrics in self.telemetry.peer_metrics.read().await.values().filter(|metrics| metrics.address.ip() == ip) {
+            let _ = self.peer_close.send(metrics.peer_id);
+        }
+    }
+
+    /// Dial `endpoint` until connected once, e.g. to move a peer that had
+    /// connected to us onto an interface that is still up.
+    pub async fn migrate_to(&self, endpoint: PeerEndpoint) {
+        let mut migrations = self.migrations.write().await;
+        if !migrations.iter().any(|known| known.host == endpoint.host && known.port == endpoint.port) {
+            migrations.push(endpoint);
+        }
+        drop(migrations);
+        self.dial_wake.notify_one();
+    }
+
+    /// Close live local API connections that use a revoked token.
+    pub fn disconnect_api_token(&self, token_id: Uuid) {
+        let _ = self.security.api_revoked.send(token_id);
+    }
+
+    /// Tell trusted peers that a device was removed here.
+    pub async fn send_tombstone(&self, tombstone: &DeviceTombstone) -> Result<()> {
+        tombstones::send(&self.context(), tombstone, Uuid::new_v4(), None).await
+    }
+
+    /// Cancellation for tasks that live as long as the server; stopping it
+    /// waits for them.
+    pub fn shutdown_signal(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Tombstones received from trusted peers.
+    pub fn subscribe_tombstones(&self) -> broadcast::Receiver<DeviceTombstone> {
+        self.tombstone_tx.subscribe()
+    }
+
+    /// Receipts from peers that applied clips.
+    pub fn subscribe_receipts(&self) -> broadcast::Receiver<ClipReceipt> {
+        self.receipt_tx.subscribe()
+    }
+
+    /// Number of connected peers a clip sent now would go to.
+    pub async fn data_recipients(&self) -> u32 {
+        let ctx = self.context();
+        let peer_ids: Vec<Uuid> = self.peers.read().await.keys().copied().collect();
+        let mut count = 0;
+        for peer_id in peer_ids {
+            if ctx.should_send_to(peer_id).await {
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Tell every connected peer the security key changed.
+    pub async fn announce_key_rotation(&self) {
+        key_rotation::announce(&self.context()).await;
+    }
+
+    /// Pause or resume sync locally and tell every connected peer.
+    pub async fn set_paused(&self, paused: bool) {
+        *self.policy.paused.write().await = paused;
+
+        let ctx = self.context();
+        let peer_ids: Vec<Uuid> = self.peers.read().await.keys().copied().collect();
+        for peer_id in peer_ids {
+            if let Err(e) = ctx.send_sync_state(peer_id, paused).await {
+                tracing::warn!("Failed to send sync state to {}: {}", peer_id, e);
+            }
+        }
+    }
+
+    pub async fn is_paused(&self) -> bool {
+        *self.policy.paused.read().await
+    }
+
+    /// Addresses of connected peers that have paused sync.
+    pub async fn get_paused_peer_addresses(&self) -> Vec<SocketAddr> {
+        let paused_peers = self.policy.paused_peers.read().await;
+        self.peers.read().await
+            .iter()
+            .filter(|(id, _)| paused_peers.contains(id))
+            .map(|(_, (addr, _))| *addr)
+            .collect()
+    }
+
+    /// Peers with an open session, oldest connection first.
+    pub async fn get_connected_peers(&self) -> Vec<ConnectedPeer> {
+        let paused_peers = self.policy.paused_peers.read().await.clone();
+        peer_metrics::snapshot(&self.context()).await
+            .into_iter()
+            .map(|metrics| ConnectedPeer {
+                peer_id: metrics.peer_id,
+                address: metrics.address,
+                device: metrics.device,
+                direction: metrics.direction,
+                local_address: metrics.local_address,
+                interface: metrics.interface,
+                connected_since: metrics.connected_at,
+                last_activity: metrics.last_activity,
+                paused: paused_peers.contains(&metrics.peer_id),
+            })
+            .collect()
+    }
+
+    /// Counters of each connected peer, oldest connection first.
+    pub async fn get_peer_metrics(&self) -> Vec<PeerMetrics> {
+        peer_metrics::snapshot(&self.context()).await
+    }
+
+    /// Peers known to run a different app version, by address.
+    pub async fn version_mismatches(&self) -> HashMap<std::net::IpAddr, PeerVersionMismatch> {
+        self.telemetry.version_mismatches.read().await.clone()
+    }
+
+    /// Where the clipboard side reports peer clips it wrote, so they are
+    /// counted and acknowledged.
+    pub fn apply_report(&self) -> ApplyReport {
+        ApplyReport::new(self.telemetry.sync_metrics.clone(), self.applied_tx.clone())
+    }
+
+    /// Start the global and per-peer counters again from zero.
+    pub async fn reset_metrics(&self) {
+        self.telemetry.sync_metrics.write().await.reset_counters();
+        peer_metrics::reset(&self.telemetry).await;
+    }
+
+    pub async fn get_sync_metrics(&self) -> SyncMetrics {
+        let mut metrics = self.telemetry.sync_metrics.read().await.clone();
+        metrics.connected_peers = self.peers.read().await.len() as u32;
+        metrics.uptime_secs = metrics.started_at
+            .map_or(0, |started| (chrono::Utc::now() - started).num_seconds().max(0) as u64);
+        metrics
+    }
+}
@@ -0,0 +1,124 @@
+//! Holding peer clips while the user is away from their work desktops.
+//!
+//! Desktops are told apart by whatever identifier the platform exposes:
+//! the 1-based desktop number under X11 window managers and the desktop
+//! GUID on Windows. macOS and Wayland keep theirs private, so there every
+//! desktop counts as a work desktop.
+
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::models::{ClipOrigin, ClipboardContent};
+use super::receipts::ApplyReport;
+
+/// How often the active desktop is checked while a clip is held.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Identifier of the virtual desktop the user is on.
+#[cfg(target_os = "linux")]
+pub fn current_desktop() -> Option<String> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::xproto::{AtomEnum, ConnectionExt};
+
+    let (conn, screen) = x11rb::connect(None).ok()?;
+    let root = conn.setup().roots.get(screen)?.root;
+    let atom = conn.intern_atom(true, b"_NET_CURRENT_DESKTOP").ok()?.reply().ok()?.atom;
+    let reply = conn.get_property(false, root, atom, AtomEnum::CARDINAL, 0, 1).ok()?.reply().ok()?;
+    let index = reply.value32()?.next()?;
+    Some((index + 1).to_string())
+}
+
+/// Identifier of the virtual desktop the user is on.
+#[cfg(windows)]
+pub fn current_desktop() -> Option<String> {
+    use winreg::enums::HKEY_CURRENT_USER;
+    use winreg::RegKey;
+
+    let key = RegKey::predef(HKEY_CURRENT_USER)
+        .open_subkey(r"Software\Microsoft\Windows\CurrentVersion\Explorer\VirtualDesktops")
+        .ok()?;
+    let value = key.get_raw_value("CurrentVirtualDesktop").ok()?;
+    let bytes: [u8; 16] = value.bytes.try_into().ok()?;
+    // Stored as a Windows GUID, whose first three fields are little-endian
+    Some(uuid::Uuid::from_bytes_le(bytes).to_string())
+}
+
+/// Identifier of the virtual desktop the user is on.
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn current_desktop() -> Option<String> {
+    None
+}
+
+/// Whether `desktop` counts as a work desktop. With no work desktops
+/// configured, or when the active one cannot be told, every desktop does.
+pub fn is_work(work_desktops: &[String], desktop: Option<&str>) -> bool {
+    let Some(desktop) = desktop else {
+        return true;
+    };
+    work_desktops.is_empty() || work_desktops.iter().any(|work| work.trim().eq_ignore_ascii_case(desktop))
+}
+
+/// A peer clip that arrived while the user was away from work desktops.
+pub struct HeldClip {
+    pub content: ClipboardContent,
+    pub origin: ClipOrigin,
+    /// Desktop that was active when the clip arrived
+    pub arrived_on: Option<String>,
+    pub report: ApplyReport,
+}
+
+#[derive(Default)]
+struct Held {
+    clip: Option<HeldClip>,
+    waiting: bool,
+}
+
+/// The newest peer clip held until the user returns to a work desktop.
+/// Shared across restarts of the sync services.
+#[derive(Default)]
+pub struct DesktopGate {
+    held: Mutex<Held>,
+}
+
+impl DesktopGate {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Held> {
+        self.held.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Hold `clip`, replacing any older one, since only the newest would
+    /// end up on the clipboard anyway.
+    ///
+    /// # Returns
+    /// Whether the caller should start waiting for a work desktop; false
+    /// when a wait is already under way
+    pub fn hold(&self, clip: HeldClip) -> bool {
+        let mut held = self.lock();
+        if let Some(replaced) = held.clip.replace(clip) {
+            tracing::debug!("Dropping held clip {:?} for a newer one", replaced.origin.message_id);
+        }
+        !std::mem::replace(&mut held.waiting, true)
+    }
+
+    /// Take the held clip and end the wait.
+    pub fn release(&self) -> Option<HeldClip> {
+        let mut held = self.lock();
+        held.waiting = false;
+        held.clip.take()
+    }
+
+    /// End the wait but keep the clip, so the next clip held starts a new
+    /// wait that can still release it.
+    pub fn stop_waiting(&self) {
+        self.lock().waiting = false;
+    }
+
+    /// Drop the held clip if it came from `ip`. Returns whether it did.
+    pub fn purge_from(&self, ip: IpAddr) -> bool {
+        let mut held = self.lock();
+        let from_ip = held.clip.as_ref().is_some_and(|clip| clip.origin.address.ip() == ip);
+        if from_ip {
+            held.clip = None;
+        }
+        from_ip
+    }
+}
@@ -0,0 +1,174 @@
+use std::time::Duration;
+use anyhow::Result;
+use tauri::menu::{IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::tray::TrayIconBuilder;
+use tauri::{AppHandle, Manager, Wry};
+use crate::AppState;
+
+const TRAY_ID: &str = "main";
+/// How often the tray menu is refreshed from the service manager.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(3);
+/// Clipboard history entries offered in the tray menu.
+const RECENT_CLIPS: u32 = 5;
+/// Characters of a clip shown as its menu label.
+const PREVIEW_CHARS: usize = 40;
+const RECENT_PREFIX: &str = "recent:";
+
+/// What the tray shows; the menu is rebuilt only when this changes.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TrayStatus {
+    running: bool,
+    peers: u32,
+    recent: Vec<(i64, String)>,
+}
+
+/// Create the tray icon and keep its menu in sync with the services.
+pub fn init(app: &AppHandle) -> Result<()> {
+    let menu = build_menu(app, &TrayStatus::default())?;
+    let mut builder = TrayIconBuilder::with_id(TRAY_ID)
+        .menu(&menu)
+        .tooltip("UniMesh Clip")
+        .on_menu_event(|app, event| handle_menu_event(app, event.id.as_ref()));
+    if let Some(icon) = app.default_window_icon() {
+        builder = builder.icon(icon.clone());
+    }
+    builder.build(app)?;
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let mut shown = TrayStatus::default();
+        loop {
+            tokio::time::sleep(REFRESH_INTERVAL).await;
+            let status = read_status(&app).await;
+            if status != shown {
+                if let Err(e) = apply_status(&app, &status) {
+                    tracing::warn!("Failed to update tray: {}", e);
+                }
+                shown = status;
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn read_status(app: &AppHandle) -> TrayStatus {
+    let manager = app.state::<AppState>().service_manager.clone();
+    let status = manager.query(|manager| Box::pin(async move {
+        let recent = match manager.get_history(RECENT_CLIPS, 0).await {
+            Ok(page) => page.entries.into_iter().map(|entry| (entry.id, preview(&entry.content))).collect(),
+            Err(_) => Vec::new(),
+        };
+        TrayStatus {
+            running: manager.is_running().await,
+            peers: manager.get_sync_metrics().await.map_or(0, |metrics| metrics.connected_peers),
+            recent,
+        }
+    })).await;
+    status.unwrap_or_default()
+}
+
+/// First line of a clip, shortened for a menu label.
+fn preview(content: &str) -> String {
+    let line = content.lines().find(|line| !line.trim().is_empty()).unwrap_or("").trim();
+    if line.chars().count() > PREVIEW_CHARS {
+        format!("{}…", line.chars().take(PREVIEW_CHARS).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+fn build_menu(app: &AppHandle, status: &TrayStatus) -> Result<Menu<Wry>> {
+    let peers = match (status.running, status.peers) {
+        (false, _) => "Sync stopped".to_string(),
+        (true, 1) => "1 peer connected".to_string(),
+        (true, count) => format!("{} peers connected", count),
+    };
+    let peers = MenuItem::with_id(app, "peers", peers, false, None::<&str>)?;
+    let toggle_label = if status.running { "Stop Sync" } else { "Start Sync" };
+    let toggle = MenuItem::with_id(app, "toggle_sync", toggle_label, true, None::<&str>)?;
+
+    let recent_items = status.recent
+        .iter()
+        .map(|(id, label)| MenuItem::with_id(app, format!("{}{}", RECENT_PREFIX, id), label, true, None::<&str>))
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let recent_refs: Vec<&dyn IsMenuItem<Wry>> = recent_items.iter().map(|item| item as &dyn IsMenuItem<Wry>).collect();
+    let recent = Submenu::with_id_and_items(app, "recent", "Recent Clips", !recent_items.is_empty(), &recent_refs)?;
+
+    let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let separator = PredefinedMenuItem::separator(app)?;
+    let separator2 = PredefinedMenuItem::separator(app)?;
+
+    Ok(Menu::with_items(app, &[&peers, &toggle, &separator, &recent, &separator2, &show, &quit])?)
+}
+
+/// Bring the main window to the front, restoring it if hidden or minimized.
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
+
+fn apply_status(app: &AppHandle, status: &TrayStatus) -> Result<()> {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+    tray.set_menu(Some(build_menu(app, status)?))?;
+    let tooltip = if status.running {
+        format!("UniMesh Clip — {} connected", status.peers)
+    } else {
+        "UniMesh Clip — stopped".to_string()
+    };
+    tray.set_tooltip(Some(tooltip))?;
+    // Shown next to the icon on macOS; other platforms rely on the tooltip
+    let badge = (status.running && status.peers > 0).then(|| status.peers.to_string());
+    tray.set_title(badge)?;
+    Ok(())
+}
+
+fn handle_menu_event(app: &AppHandle, id: &str) {
+    match id {
+        "show" => show_main_window(app),
+        "quit" => app.exit(0),
+        "toggle_sync" => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move {
+                let manager = app.state::<AppState>().service_manager.clone();
+                let result = manager.execute(|manager| Box::pin(async move {
+                    if manager.is_running().await {
+                        manager.stop().await
+                    } else {
+                        manager.start().await
+                    }
+                })).await;
+                if let Err(e) = result {
+                    tracing::error!("Failed to toggle sync from tray: {}", e);
+                }
+            });
+        }
+        _ => {
+            if let Some(entry_id) = id.strip_prefix(RECENT_PREFIX).and_then(|id| id.parse::<i64>().ok()) {
+                let app = app.clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = copy_recent(&app, entry_id).await {
+                        tracing::warn!("Failed to copy history entry {}: {}", entry_id, e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// Put a recent clip back on the clipboard; the monitor syncs it as usual.
+async fn copy_recent(app: &AppHandle, entry_id: i64) -> Result<()> {
+    let manager = app.state::<AppState>().service_manager.clone();
+    let page = manager.execute(|manager| Box::pin(manager.get_history(RECENT_CLIPS, 0))).await?;
+    let entry = page.entries
+        .into_iter()
+        .find(|entry| entry.id == entry_id)
+        .ok_or_else(|| anyhow::anyhow!("History entry {} no longer exists", entry_id))?;
+    arboard::Clipboard::new()?.set_text(entry.content)?;
+    Ok(())
+}
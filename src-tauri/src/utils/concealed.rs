@@ -0,0 +1,38 @@
+//! Detect clipboard content that its source marked as sensitive, so password
+//! managers' copies are never broadcast.
+//!
+//! Only macOS and Windows publish such markers in a form we can read;
+//! elsewhere nothing is ever reported as concealed.
+
+/// Pasteboard type set by password managers under the nspasteboard.org convention.
+#[cfg(target_os = "macos")]
+const CONCEALED_TYPE: &str = "org.nspasteboard.ConcealedType";
+
+/// Registered formats whose mere presence asks monitors to leave the content alone.
+#[cfg(windows)]
+const EXCLUDE_FORMATS: &[&str] = &["ExcludeClipboardContentFromMonitorProcessing", "Clipboard Viewer Ignore"];
+
+/// Whether what is on the clipboard right now is marked as sensitive.
+#[cfg(target_os = "macos")]
+pub fn is_concealed() -> bool {
+    use objc2_app_kit::NSPasteboard;
+
+    let Some(types) = NSPasteboard::generalPasteboard().types() else {
+        return false;
+    };
+    types.iter().any(|kind| kind.to_string() == CONCEALED_TYPE)
+}
+
+/// Whether what is on the clipboard right now is marked as sensitive.
+#[cfg(windows)]
+pub fn is_concealed() -> bool {
+    EXCLUDE_FORMATS.iter().any(|name| {
+        clipboard_win::register_format(name).is_some_and(|format| clipboard_win::is_format_avail(format.get()))
+    })
+}
+
+/// Whether what is on the clipboard right now is marked as sensitive.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn is_concealed() -> bool {
+    false
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use anyhow::Result;
+use crate::models::{ComponentHealth, CrashReport};
+use super::log_buffer;
+
+/// File under the app data dir holding the most recent crash report.
+pub const CRASH_REPORT_FILE: &str = "last_crash.json";
+/// Warning and error lines from the log kept in a report.
+const RECENT_ERRORS: usize = 20;
+/// Log lines searched for warnings and errors.
+const SCANNED_LINES: usize = 500;
+
+static REPORT_DIR: OnceLock<PathBuf> = OnceLock::new();
+static HEALTH: Mutex<Option<ComponentHealth>> = Mutex::new(None);
+
+/// Write a report for every panic, then run the previous hook. Reports are
+/// only written once `set_report_dir` has been called.
+pub fn install() {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if let Some(dir) = REPORT_DIR.get() {
+            // Never panic inside the hook; a failed report is just lost
+            let _ = write(dir, &build(info));
+        }
+        previous(info);
+    }));
+}
+
+pub fn set_report_dir(dir: PathBuf) {
+    let _ = REPORT_DIR.set(dir);
+}
+
+/// The state of the services as last recorded.
+pub fn health() -> ComponentHealth {
+    HEALTH.lock().unwrap_or_else(|e| e.into_inner()).clone().unwrap_or_default()
+}
+
+/// Remember the state of the services for the next report.
+pub fn record_health(health: ComponentHealth) {
+    *HEALTH.lock().unwrap_or_else(|e| e.into_inner()) = Some(health);
+}
+
+fn build(info: &PanicHookInfo<'_>) -> CrashReport {
+    let message = info
+        .payload()
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| info.payload().downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string());
+
+    CrashReport {
+        occurred_at: chrono::Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        platform: format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH),
+        thread: std::thread::current().name().map(str::to_string),
+        location: info.location().map(|location| format!("{}:{}", location.file(), location.line())),
+        // Payloads may quote data being processed, clipboard text included
+        message: log_buffer::sanitize_line(&message),
+        recent_errors: log_buffer::recent_errors(SCANNED_LINES, RECENT_ERRORS),
+        health: health(),
+        backtrace: std::backtrace::Backtrace::force_capture().to_string(),
+    }
+}
+
+fn write(dir: &Path, report: &CrashReport) -> Result<()> {
+    fs::create_dir_all(dir)?;
+    fs::write(dir.join(CRASH_REPORT_FILE), serde_json::to_vec_pretty(report)?)?;
+    Ok(())
+}
+
+/// The report left by the last crash, if any.
+pub fn load(dir: &Path) -> Result<Option<CrashReport>> {
+    let path = dir.join(CRASH_REPORT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+}
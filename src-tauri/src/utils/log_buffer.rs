@@ -0,0 +1,90 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Log lines kept in memory.
+pub const CAPACITY: usize = 1000;
+const MAX_LINE_CHARS: usize = 500;
+/// Tokens at least this long made only of base64/hex characters are treated
+/// as key material, signatures, or ciphertext and redacted.
+const SECRET_TOKEN_MIN_LEN: usize = 24;
+
+static BUFFER: OnceLock<Mutex<VecDeque<String>>> = OnceLock::new();
+
+fn buffer() -> &'static Mutex<VecDeque<String>> {
+    BUFFER.get_or_init(|| Mutex::new(VecDeque::with_capacity(CAPACITY)))
+}
+
+/// `MakeWriter` that keeps the most recent formatted log lines in memory
+/// so they can be inspected without access to stdout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogBufferWriter;
+
+impl io::Write for LogBufferWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let text = String::from_utf8_lossy(buf);
+        let mut lines = buffer().lock().unwrap_or_else(|e| e.into_inner());
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            if lines.len() == CAPACITY {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogBufferWriter {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
+
+/// The last `count` captured log lines, oldest first.
+pub fn recent_lines(count: usize) -> Vec<String> {
+    let lines = buffer().lock().unwrap_or_else(|e| e.into_inner());
+    let skip = lines.len().saturating_sub(count);
+    lines.iter().skip(skip).cloned().collect()
+}
+
+/// The last `keep` warning and error lines among the last `scanned` lines,
+/// sanitized.
+pub fn recent_errors(scanned: usize, keep: usize) -> Vec<String> {
+    let errors: Vec<String> = recent_lines(scanned)
+        .iter()
+        .filter(|line| line.contains(" ERROR ") || line.contains(" WARN "))
+        .map(|line| sanitize_line(line))
+        .collect();
+    let skip = errors.len().saturating_sub(keep);
+    errors.into_iter().skip(skip).collect()
+}
+
+/// Redact secret-looking tokens and cap the line length so a log line is
+/// safe to hand to another device.
+pub fn sanitize_line(line: &str) -> String {
+    let sanitized = line
+        .split(' ')
+        .map(|token| {
+            let looks_secret = token.len() >= SECRET_TOKEN_MIN_LEN
+                && token
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='));
+            if looks_secret { "[redacted]" } else { token }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    if sanitized.chars().count() > MAX_LINE_CHARS {
+        let truncated: String = sanitized.chars().take(MAX_LINE_CHARS).collect();
+        format!("{}…", truncated)
+    } else {
+        sanitized
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use anyhow::Result;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Directory under the app data dir holding the log files.
+pub const LOG_DIR: &str = "logs";
+const FILE_PREFIX: &str = "unimesh-clip";
+const FILE_SUFFIX: &str = "log";
+/// Daily files kept; older ones are deleted as the log rolls over.
+pub const MAX_LOG_FILES: usize = 7;
+/// Most lines `recent_lines` reads back at once.
+pub const MAX_RECENT_LINES: usize = 5000;
+
+static LOG_FILES: OnceLock<(PathBuf, RollingFileAppender)> = OnceLock::new();
+
+/// Start writing the log to daily files under `dir`. Lines logged before
+/// this only reach stdout and the in-memory buffer.
+pub fn set_dir(dir: &Path) -> Result<()> {
+    let log_dir = dir.join(LOG_DIR);
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(FILE_PREFIX)
+        .filename_suffix(FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&log_dir)?;
+    let _ = LOG_FILES.set((log_dir, appender));
+    Ok(())
+}
+
+/// `MakeWriter` that appends formatted log lines to the current file, once
+/// `set_dir` has chosen where files go.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LogFileWriter;
+
+impl io::Write for LogFileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match LOG_FILES.get() {
+            Some((_, appender)) => appender.make_writer().write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match LOG_FILES.get() {
+            Some((_, appender)) => appender.make_writer().flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogFileWriter {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
+
+/// The log files, newest first.
+fn files(log_dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(log_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.file_name().and_then(|name| name.to_str()).is_some_and(|name| name.starts_with(FILE_PREFIX)))
+        .collect();
+    // Dated names sort by day
+    files.sort();
+    files.reverse();
+    Ok(files)
+}
+
+/// The last `count` lines written to the log files, oldest first, reading
+/// back into earlier days as needed. Covers earlier runs too, unlike the
+/// in-memory buffer.
+///
+/// # Returns
+/// `None` when file logging is not set up
+pub fn recent_lines(count: usize) -> Result<Option<Vec<String>>> {
+    let Some((log_dir, _)) = LOG_FILES.get() else {
+        return Ok(None);
+    };
+    let count = count.min(MAX_RECENT_LINES);
+    let mut lines = Vec::new();
+    for path in files(log_dir)? {
+        let file_lines: Vec<String> = BufReader::new(fs::File::open(&path)?).lines().map_while(Result::ok).collect();
+        let skip = file_lines.len().saturating_sub(count - lines.len());
+        // Earlier files go in front of what was already read
+        lines.splice(0..0, file_lines.into_iter().skip(skip));
+        if lines.len() >= count {
+            break;
+        }
+    }
+    Ok(Some(lines))
+}
@@ -0,0 +1,42 @@
+use std::sync::OnceLock;
+use anyhow::Result;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::{reload, Registry};
+
+/// Level the app logs at unless something raises it.
+pub const DEFAULT_LEVEL: LevelFilter = LevelFilter::INFO;
+
+type Handle = reload::Handle<LevelFilter, Registry>;
+
+static HANDLE: OnceLock<Handle> = OnceLock::new();
+
+/// The global level filter, adjustable at runtime through `set`.
+pub fn layer() -> reload::Layer<LevelFilter, Registry> {
+    let (layer, handle) = reload::Layer::new(DEFAULT_LEVEL);
+    let _ = HANDLE.set(handle);
+    layer
+}
+
+/// The level currently in effect.
+pub fn current() -> LevelFilter {
+    HANDLE.get()
+        .and_then(|handle| handle.clone_current())
+        .unwrap_or(DEFAULT_LEVEL)
+}
+
+/// Change the level for every log output.
+pub fn set(level: LevelFilter) -> Result<()> {
+    let handle = HANDLE.get().ok_or_else(|| anyhow::anyhow!("Logging is not initialised"))?;
+    handle.reload(level)?;
+    Ok(())
+}
+
+/// Read a level name such as `debug` or `INFO`, or `off`.
+///
+/// # Errors
+/// Names the accepted levels when `name` is none of them
+pub fn parse(name: &str) -> Result<LevelFilter> {
+    name.trim().parse().map_err(|_| {
+        anyhow::anyhow!("Unknown log level {:?}; expected trace, debug, info, warn, error, or off", name)
+    })
+}
@@ -1 +1,12 @@
-pub mod crypto;
\ No newline at end of file
+pub use unimesh_proto::{crypto, hashing};
+pub mod log_buffer;
+pub mod port_probe;
+pub mod crash_report;
+pub mod net;
+pub mod paths;
+pub mod log_level;
+pub mod zip;
+pub mod concealed;
+pub mod safe_mode;
+pub mod sync_marker;
+pub mod log_file;
\ No newline at end of file
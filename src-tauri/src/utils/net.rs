@@ -0,0 +1,138 @@
+use std::net::{IpAddr, SocketAddr, SocketAddrV6};
+use if_addrs::{get_if_addrs, IfAddr};
+use serde::Serialize;
+
+/// One address on one of this machine's network interfaces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LocalInterface {
+    pub name: String,
+    pub ip: IpAddr,
+    pub prefix_len: u8,
+    #[serde(skip)]
+    pub index: Option<u32>,
+}
+
+impl LocalInterface {
+    /// Whether `ip` is on this interface's subnet, so reachable without a router.
+    pub fn is_on_link(&self, ip: IpAddr) -> bool {
+        match (self.ip, ip) {
+            (IpAddr::V4(local), IpAddr::V4(other)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix_len)).unwrap_or(0);
+                u32::from(local) & mask == u32::from(other) & mask
+            }
+            (IpAddr::V6(local), IpAddr::V6(other)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix_len)).unwrap_or(0);
+                u128::from(local) & mask == u128::from(other) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Every non-loopback interface address of this machine, best first (see
+/// `rank`). Ethernet, Wi-Fi, and VPN adapters are all included.
+pub fn interfaces() -> Vec<LocalInterface> {
+    let mut interfaces: Vec<LocalInterface> = match get_if_addrs() {
+        Ok(interfaces) => interfaces
+            .into_iter()
+            .filter(|interface| !interface.is_loopback())
+            .map(|interface| LocalInterface {
+                ip: interface.ip(),
+                prefix_len: match interface.addr {
+                    IfAddr::V4(ref v4) => v4.prefixlen,
+                    IfAddr::V6(ref v6) => v6.prefixlen,
+                },
+                index: interface.index,
+                name: interface.name,
+            })
+            .collect(),
+        Err(e) => {
+            tracing::warn!("Failed to list network interfaces: {}", e);
+            Vec::new()
+        }
+    };
+    interfaces.sort_by_key(|interface| rank(interface.ip));
+    interfaces
+}
+
+/// Name of the interface that owns the local address `ip`.
+pub fn interface_name(ip: IpAddr) -> Option<String> {
+    interfaces().into_iter().find(|interface| interface.ip == ip).map(|interface| interface.name)
+}
+
+/// Non-loopback addresses of this machine, best first (see `rank`).
+/// IPv6 link-local addresses carry the index of their interface.
+pub fn local_addresses() -> Vec<(IpAddr, Option<u32>)> {
+    let mut addresses: Vec<(IpAddr, Option<u32>)> = interfaces()
+        .into_iter()
+        .map(|interface| (interface.ip, interface.index))
+        .collect();
+    addresses.dedup_by_key(|(ip, _)| *ip);
+    addresses
+}
+
+fn is_link_local(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => ip.is_link_local(),
+        IpAddr::V6(ip) => ip.segments()[0] & 0xffc0 == 0xfe80,
+    }
+}
+
+/// Preference when a device has several addresses: private IPv4, other
+/// IPv4, routable IPv6, then link-local addresses.
+pub fn rank(ip: IpAddr) -> u8 {
+    match ip {
+        _ if is_link_local(ip) => 4,
+        IpAddr::V4(v4) if v4.is_private() => 0,
+        IpAddr::V4(_) => 1,
+        IpAddr::V6(_) => 2,
+    }
+}
+
+/// Text form of a discovered address. IPv6 link-local addresses are only
+/// reachable through an interface, so they get the scope of the first local
+/// interface with a link-local address, as `fe80::1%3`.
+pub fn format_address(ip: IpAddr) -> String {
+    if ip.is_ipv6() && is_link_local(ip) {
+        let scope = local_addresses()
+            .into_iter()
+            .find(|(local, _)| local.is_ipv6() && is_link_local(*local))
+            .and_then(|(_, index)| index);
+        if let Some(scope) = scope {
+            return format!("{}%{}", ip, scope);
+        }
+    }
+    ip.to_string()
+}
+
+/// IP part of an address that may carry a `%scope` suffix.
+pub fn parse_ip(address: &str) -> Option<IpAddr> {
+    address.split('%').next()?.trim().parse().ok()
+}
+
+/// Socket address from `ip` or `ip%scope` text and a port.
+pub fn parse_socket_addr(address: &str, port: u16) -> Option<SocketAddr> {
+    let ip = parse_ip(address)?;
+    match (ip, address.split_once('%')) {
+        (IpAddr::V6(v6), Some((_, scope))) => {
+            Some(SocketAddr::V6(SocketAddrV6::new(v6, port, 0, scope.parse().ok()?)))
+        }
+        _ => Some(SocketAddr::new(ip, port)),
+    }
+}
+
+/// WebSocket URL for `addr`. The scope of an IPv6 address is left out; it
+/// only matters to the socket, which is already connected.
+pub fn ws_url(secure: bool, addr: SocketAddr) -> String {
+    let scheme = if secure { "wss" } else { "ws" };
+    match addr {
+        SocketAddr::V4(v4) => format!("{}://{}/", scheme, v4),
+        SocketAddr::V6(v6) => format!("{}://[{}]:{}/", scheme, v6.ip(), v6.port()),
+    }
+}
+
+/// Peer address with IPv4-mapped IPv6 (as reported by dual-stack sockets)
+/// turned back into plain IPv4, so comparisons with discovered addresses hold.
+pub fn canonical(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
@@ -0,0 +1,62 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use anyhow::{Context, Result};
+use tauri::{AppHandle, Manager};
+
+/// Settings store holding config, trust decisions, tokens, and tombstones.
+pub const SETTINGS_FILE: &str = "settings.json";
+/// File next to the executable that switches on portable mode.
+pub const PORTABLE_FLAG_FILE: &str = "portable";
+/// Command line switch with the same effect as the flag file.
+pub const PORTABLE_ARG: &str = "--portable";
+/// Directory beside the executable that holds all state in portable mode.
+const PORTABLE_DATA_DIR: &str = "data";
+
+static PORTABLE_ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Decide once whether this run is portable. Called at startup, before
+/// anything resolves a path.
+pub fn init() {
+    let root = PORTABLE_ROOT.get_or_init(detect_portable_root);
+    match root {
+        Some(dir) => tracing::info!("Portable mode: keeping state in {}", dir.display()),
+        None => tracing::debug!("Keeping state in the app data directory"),
+    }
+}
+
+/// Whether state lives beside the executable rather than in app data.
+pub fn is_portable() -> bool {
+    portable_root().is_some()
+}
+
+/// Directory holding the history database, certificates, and crash reports.
+pub fn data_dir(app: &AppHandle) -> Result<PathBuf> {
+    match portable_root() {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Portable data directory {} is not writable", dir.display()))?;
+            Ok(dir.to_path_buf())
+        }
+        None => Ok(app.path().app_data_dir()?),
+    }
+}
+
+/// Path to open the settings store with. The store plugin resolves relative
+/// paths against app data and takes absolute ones as they are.
+pub fn settings_store() -> PathBuf {
+    match portable_root() {
+        Some(dir) => dir.join(SETTINGS_FILE),
+        None => PathBuf::from(SETTINGS_FILE),
+    }
+}
+
+fn portable_root() -> Option<&'static Path> {
+    PORTABLE_ROOT.get_or_init(detect_portable_root).as_deref()
+}
+
+fn detect_portable_root() -> Option<PathBuf> {
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    let requested = std::env::args().skip(1).any(|arg| arg == PORTABLE_ARG)
+        || exe_dir.join(PORTABLE_FLAG_FILE).exists();
+    requested.then(|| exe_dir.join(PORTABLE_DATA_DIR))
+}
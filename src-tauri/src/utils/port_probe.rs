@@ -0,0 +1,173 @@
+use std::io;
+use std::net::TcpListener;
+use serde::Serialize;
+
+/// How many ports above the configured one to try when suggesting an alternative.
+const SUGGESTION_RANGE: u16 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BindFailure {
+    AddrInUse,
+    PermissionDenied,
+    Other,
+}
+
+/// Process currently listening on a port, as far as the platform lets us see.
+#[derive(Debug, Clone, Serialize)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub process_name: Option<String>,
+}
+
+/// Actionable description of why the sync server could not bind its port.
+#[derive(Debug, Clone, Serialize, thiserror::Error)]
+#[error("{}", self.describe())]
+pub struct BindError {
+    pub port: u16,
+    pub reason: BindFailure,
+    pub message: String,
+    pub owner: Option<PortOwner>,
+    /// The owner looks like another (possibly stale) UniMesh Clip process
+    pub own_instance: bool,
+    pub suggested_port: Option<u16>,
+}
+
+impl BindError {
+    /// Diagnose a failed bind of `port`.
+    pub fn probe(port: u16, error: &io::Error) -> Self {
+        let reason = match error.kind() {
+            io::ErrorKind::AddrInUse => BindFailure::AddrInUse,
+            io::ErrorKind::PermissionDenied => BindFailure::PermissionDenied,
+            _ => BindFailure::Other,
+        };
+        let owner = if reason == BindFailure::AddrInUse { find_listener(port) } else { None };
+        let own_instance = owner.as_ref().is_some_and(is_own_instance);
+
+        Self {
+            port,
+            reason,
+            message: error.to_string(),
+            owner,
+            own_instance,
+            suggested_port: next_free_port(port),
+        }
+    }
+
+    fn describe(&self) -> String {
+        let mut text = match self.reason {
+            BindFailure::AddrInUse => format!("Port {} is already in use", self.port),
+            BindFailure::PermissionDenied => format!("Not allowed to listen on port {}", self.port),
+            BindFailure::Other => format!("Cannot listen on port {}: {}", self.port, self.message),
+        };
+        if let Some(ref owner) = self.owner {
+            let name = owner.process_name.as_deref().unwrap_or("unknown process");
+            text.push_str(&format!(" by {} (pid {})", name, owner.pid));
+        }
+        if self.own_instance {
+            text.push_str(", which looks like another UniMesh Clip instance");
+        }
+        if let Some(port) = self.suggested_port {
+            text.push_str(&format!("; port {} is free", port));
+        }
+        text
+    }
+}
+
+fn is_own_instance(owner: &PortOwner) -> bool {
+    let Some(ref name) = owner.process_name else {
+        return false;
+    };
+    let own_name = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem().map(|stem| stem.to_string_lossy().to_lowercase()));
+    let name = name.to_lowercase();
+    let name = name.trim_end_matches(".exe");
+    own_name.is_some_and(|own| own == name) || name.contains("uni-mesh-clip") || name.contains("unimesh")
+}
+
+/// First port above `port` that can currently be bound on all interfaces.
+pub fn next_free_port(port: u16) -> Option<u16> {
+    (1..=SUGGESTION_RANGE)
+        .filter_map(|offset| port.checked_add(offset))
+        .find(|candidate| TcpListener::bind(("0.0.0.0", *candidate)).is_ok())
+}
+
+/// Best-effort lookup of the process listening on a TCP port.
+#[cfg(target_os = "linux")]
+pub fn find_listener(port: u16) -> Option<PortOwner> {
+    use std::fs;
+
+    // Find the socket inode of the listener in /proc/net/tcp{,6}
+    const TCP_LISTEN: &str = "0A";
+    let inode = ["/proc/net/tcp", "/proc/net/tcp6"].iter().find_map(|table| {
+        fs::read_to_string(table).ok()?.lines().skip(1).find_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let local_port = fields.get(1)?.rsplit(':').next()?;
+            let matches = u16::from_str_radix(local_port, 16).ok()? == port
+                && *fields.get(3)? == TCP_LISTEN;
+            matches.then(|| fields.get(9).map(|inode| inode.to_string())).flatten()
+        })
+    })?;
+
+    // Find the process holding that inode
+    let target = format!("socket:[{}]", inode);
+    fs::read_dir("/proc").ok()?.flatten().find_map(|entry| {
+        let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+        let holds_socket = fs::read_dir(entry.path().join("fd")).ok()?.flatten().any(|fd| {
+            fs::read_link(fd.path()).is_ok_and(|link| link.to_string_lossy() == target)
+        });
+        holds_socket.then(|| PortOwner {
+            pid,
+            process_name: fs::read_to_string(entry.path().join("comm"))
+                .ok()
+                .map(|name| name.trim().to_string()),
+        })
+    })
+}
+
+/// Best-effort lookup of the process listening on a TCP port.
+#[cfg(target_os = "macos")]
+pub fn find_listener(port: u16) -> Option<PortOwner> {
+    let output = std::process::Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-Fpc"])
+        .output()
+        .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let pid = stdout.lines().find_map(|line| line.strip_prefix('p')?.parse().ok())?;
+    let process_name = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix('c').map(str::to_string));
+    Some(PortOwner { pid, process_name })
+}
+
+/// Best-effort lookup of the process listening on a TCP port.
+#[cfg(target_os = "windows")]
+pub fn find_listener(port: u16) -> Option<PortOwner> {
+    let output = std::process::Command::new("netstat")
+        .args(["-ano", "-p", "TCP"])
+        .output()
+        .ok()?;
+    let suffix = format!(":{}", port);
+    let pid: u32 = String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let listening = fields.get(1)?.ends_with(&suffix) && *fields.get(3)? == "LISTENING";
+        listening.then(|| fields.get(4)?.parse().ok()).flatten()
+    })?;
+
+    let tasklist = std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+        .output()
+        .ok();
+    let process_name = tasklist.and_then(|out| {
+        let text = String::from_utf8_lossy(&out.stdout).to_string();
+        text.split(',').next().map(|name| name.trim_matches('"').to_string())
+    });
+    Some(PortOwner { pid, process_name })
+}
+
+/// Best-effort lookup of the process listening on a TCP port.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn find_listener(_port: u16) -> Option<PortOwner> {
+    None
+}
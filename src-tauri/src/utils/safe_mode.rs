@@ -0,0 +1,79 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+use crate::models::SafeModeStatus;
+use super::crash_report;
+
+/// File under the app data dir counting startups that have not yet
+/// lasted `STABLE_AFTER`.
+pub const STARTUP_MARKER_FILE: &str = "unfinished_startups";
+/// Unfinished startups in a row that put the next one in safe mode.
+pub const CRASH_THRESHOLD: u32 = 3;
+/// How long a run must last to count as a successful startup.
+pub const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+static MARKER_DIR: OnceLock<PathBuf> = OnceLock::new();
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static UNFINISHED: AtomicU32 = AtomicU32::new(0);
+
+fn read_count(dir: &Path) -> u32 {
+    fs::read_to_string(dir.join(STARTUP_MARKER_FILE))
+        .ok()
+        .and_then(|text| text.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+fn write_count(dir: &Path, count: u32) {
+    let result = fs::create_dir_all(dir).and_then(|_| fs::write(dir.join(STARTUP_MARKER_FILE), count.to_string()));
+    if let Err(e) = result {
+        tracing::warn!("Failed to update startup marker: {}", e);
+    }
+}
+
+/// Record that a startup began, entering safe mode when the last
+/// `CRASH_THRESHOLD` startups all ended early.
+///
+/// # Returns
+/// Whether this run is in safe mode
+pub fn begin(dir: PathBuf) -> bool {
+    let unfinished = read_count(&dir);
+    write_count(&dir, unfinished + 1);
+    UNFINISHED.store(unfinished, Ordering::Relaxed);
+    if unfinished >= CRASH_THRESHOLD {
+        tracing::warn!("The last {} startups ended early; starting in safe mode", unfinished);
+        ACTIVE.store(true, Ordering::Relaxed);
+    }
+    let _ = MARKER_DIR.set(dir);
+    is_active()
+}
+
+/// Count this run as a successful startup.
+pub fn mark_stable() {
+    if let Some(dir) = MARKER_DIR.get() {
+        write_count(dir, 0);
+    }
+}
+
+/// Whether services must stay stopped until the user leaves safe mode.
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Whether safe mode is on, why, and the last crash report to show
+/// alongside the recovery options.
+pub fn status() -> SafeModeStatus {
+    SafeModeStatus {
+        active: is_active(),
+        unfinished_startups: UNFINISHED.load(Ordering::Relaxed),
+        last_crash: MARKER_DIR.get().and_then(|dir| crash_report::load(dir).ok().flatten()),
+    }
+}
+
+/// Leave safe mode and clear the startup markers, so services may start.
+pub fn exit() {
+    ACTIVE.store(false, Ordering::Relaxed);
+    mark_stable();
+    tracing::info!("Left safe mode");
+}
@@ -0,0 +1,78 @@
+//! The "do not sync this copy" marker: a clipboard format other tools set
+//! to keep a copy on this device, and that we set on clips we apply so
+//! other sync tools do not send them on again.
+//!
+//! Only macOS and Windows let us list and add formats alongside the
+//! content; elsewhere nothing is ever reported as marked and applied
+//! clips go unmarked.
+
+/// Format whose presence asks sync tools to leave the copy alone.
+#[cfg(any(target_os = "macos", windows))]
+const DO_NOT_SYNC_TYPE: &str = "x-special/do-not-sync";
+
+/// Pasteboard type under the nspasteboard.org convention for copies that
+/// should not be recorded or passed on.
+#[cfg(target_os = "macos")]
+const TRANSIENT_TYPE: &str = "org.nspasteboard.TransientType";
+
+/// Value stored under the marker, naming who set it.
+#[cfg(any(target_os = "macos", windows))]
+const MARKER_VALUE: &str = "uni-mesh-clip";
+
+/// Whether what is on the clipboard right now asks not to be synced.
+#[cfg(target_os = "macos")]
+pub fn is_marked() -> bool {
+    use objc2_app_kit::NSPasteboard;
+
+    let Some(types) = NSPasteboard::generalPasteboard().types() else {
+        return false;
+    };
+    types.iter().any(|kind| {
+        let kind = kind.to_string();
+        kind == DO_NOT_SYNC_TYPE || kind == TRANSIENT_TYPE
+    })
+}
+
+/// Add the marker to what is on the clipboard, which we just wrote.
+#[cfg(target_os = "macos")]
+pub fn mark_applied() {
+    use objc2_app_kit::NSPasteboard;
+    use objc2_foundation::{NSArray, NSString};
+
+    let pasteboard = NSPasteboard::generalPasteboard();
+    let kind = NSString::from_str(DO_NOT_SYNC_TYPE);
+    // SAFETY: no owner is passed, so there is nothing to type-check
+    unsafe { pasteboard.addTypes_owner(&NSArray::from_slice(&[&*kind]), None) };
+    if !pasteboard.setString_forType(&NSString::from_str(MARKER_VALUE), &kind) {
+        tracing::debug!("Failed to mark applied clip as not to be synced");
+    }
+}
+
+/// Whether what is on the clipboard right now asks not to be synced.
+#[cfg(windows)]
+pub fn is_marked() -> bool {
+    clipboard_win::register_format(DO_NOT_SYNC_TYPE).is_some_and(|format| clipboard_win::is_format_avail(format.get()))
+}
+
+/// Add the marker to what is on the clipboard, which we just wrote.
+#[cfg(windows)]
+pub fn mark_applied() {
+    let Some(format) = clipboard_win::register_format(DO_NOT_SYNC_TYPE) else {
+        return;
+    };
+    let marked = clipboard_win::Clipboard::new_attempts(10)
+        .and_then(|_open| clipboard_win::raw::set_without_clear(format.get(), MARKER_VALUE.as_bytes()));
+    if let Err(e) = marked {
+        tracing::debug!("Failed to mark applied clip as not to be synced: {}", e);
+    }
+}
+
+/// Whether what is on the clipboard right now asks not to be synced.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn is_marked() -> bool {
+    false
+}
+
+/// Add the marker to what is on the clipboard, which we just wrote.
+#[cfg(not(any(target_os = "macos", windows)))]
+pub fn mark_applied() {}
@@ -0,0 +1,77 @@
+use chrono::{Datelike, Local, Timelike};
+
+/// Writes an uncompressed zip archive in memory. Enough for diagnostic
+/// bundles, which are small and mostly read by hand.
+#[derive(Debug, Default)]
+pub struct ZipBuilder {
+    data: Vec<u8>,
+    central: Vec<u8>,
+    entries: u16,
+}
+
+impl ZipBuilder {
+    /// Add a file stored as is under `name`.
+    pub fn add(&mut self, name: &str, contents: &[u8]) {
+        let (time, date) = dos_timestamp();
+        let crc = crc32fast::hash(contents);
+        let size = contents.len() as u32;
+        let offset = self.data.len() as u32;
+
+        // Local file header
+        self.data.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        self.data.extend_from_slice(&0x0800u16.to_le_bytes()); // UTF-8 names
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // stored
+        self.data.extend_from_slice(&time.to_le_bytes());
+        self.data.extend_from_slice(&date.to_le_bytes());
+        self.data.extend_from_slice(&crc.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data.extend_from_slice(&size.to_le_bytes());
+        self.data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // extra length
+        self.data.extend_from_slice(name.as_bytes());
+        self.data.extend_from_slice(contents);
+
+        // Central directory entry
+        self.central.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+        self.central.extend_from_slice(&20u16.to_le_bytes()); // version made by
+        self.central.extend_from_slice(&20u16.to_le_bytes()); // version needed
+        self.central.extend_from_slice(&0x0800u16.to_le_bytes());
+        self.central.extend_from_slice(&0u16.to_le_bytes());
+        self.central.extend_from_slice(&time.to_le_bytes());
+        self.central.extend_from_slice(&date.to_le_bytes());
+        self.central.extend_from_slice(&crc.to_le_bytes());
+        self.central.extend_from_slice(&size.to_le_bytes());
+        self.central.extend_from_slice(&size.to_le_bytes());
+        self.central.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        self.central.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+        self.central.extend_from_slice(&offset.to_le_bytes());
+        self.central.extend_from_slice(name.as_bytes());
+        self.entries += 1;
+    }
+
+    /// The finished archive.
+    pub fn finish(mut self) -> Vec<u8> {
+        let central_offset = self.data.len() as u32;
+        let central_size = self.central.len() as u32;
+        self.data.append(&mut self.central);
+
+        // End of central directory
+        self.data.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        self.data.extend_from_slice(&[0; 4]); // disk numbers
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&self.entries.to_le_bytes());
+        self.data.extend_from_slice(&central_size.to_le_bytes());
+        self.data.extend_from_slice(&central_offset.to_le_bytes());
+        self.data.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.data
+    }
+}
+
+/// Current local time in the MS-DOS format zip headers use.
+fn dos_timestamp() -> (u16, u16) {
+    let now = Local::now();
+    let time = ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16;
+    let date = (((now.year().max(1980) - 1980) as u32) << 9) | (now.month() << 5) | now.day();
+    (time, date as u16)
+}
@@ -1,21 +1,72 @@
 import { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
 import { Settings } from './components/Settings'
 import { DeviceDiscovery } from './components/DeviceDiscovery'
 import { StatusIndicator } from './components/StatusIndicator'
-import { Config } from './types'
+import { SyncStats } from './components/SyncStats'
+import { Announcement, Config, KeyRotatedEvent, SafeModeStatus, StartSyncError, SyncStatusEvent } from './types'
 
 function App() {
   const [config, setConfig] = useState<Config>({
     websocket_port: 8765,
     mdns_service_name: 'unimesh-clip',
+    device_name: '',
     security_key: undefined,
+    key_rotation_grace_hours: 24,
+    legacy_key_peers: false,
     auto_start: true,
-    sync_enabled: false
+    launch_at_login: false,
+    sync_enabled: false,
+    allow_remote_log_requests: false,
+    apply_cue: 'none',
+    apply_cue_rules: [],
+    dangerously_log_clipboard_content: false,
+    trusted_only: false,
+    history_retention_days: 30,
+    history_max_entries: 10000,
+    history_tag_retention: {},
+    blob_store_max_mb: 512,
+    bandwidth_caps: {},
+    skip_concealed_content: true,
+    filter_same_host: true,
+    api_allowed_sources: [],
+    api_allowed_origins: [],
+    metrics_port: undefined,
+    binary_framing: true,
+    max_content_bytes: 10485760,
+    oversize_policy: 'skip',
+    conflict_policies: { text: 'last_writer_wins', image: 'never_overwrite', file: 'history_only' },
+    format_security: {
+      sanitize_html: true,
+      drop_rtf_objects: true,
+      blocked_file_extensions: [
+        'exe', 'com', 'scr', 'pif', 'bat', 'cmd', 'msi', 'msp', 'dll', 'cpl', 'hta', 'jar',
+        'js', 'jse', 'vbs', 'vbe', 'wsf', 'ps1', 'reg', 'lnk', 'sh', 'command', 'app', 'scpt'
+      ]
+    },
+    content_filters: [],
+    language_routes: [],
+    work_desktops: [],
+    watched_folders: [],
+    resume_policy: 'auto',
+    sync_mode: 'bidirectional',
+    tls_enabled: false,
+    monitor_clipboard: true,
+    push_shortcut: undefined,
+    send_receipts: true,
+    notifications_enabled: false,
+    announcement_verbosity: 'off',
+    heartbeat_min_secs: 5,
+    heartbeat_max_secs: 30,
+    manual_peers: [],
+    static_peers: []
   })
   const [syncEnabled, setSyncEnabled] = useState(false)
   const [isToggling, setIsToggling] = useState(false)
-  const [isInitialized, setIsInitialized] = useState(false)
+  const [safeMode, setSafeMode] = useState<SafeModeStatus | null>(null)
+  const [announcement, setAnnouncement] = useState<Announcement | null>(null)
+  const [keyRotation, setKeyRotation] = useState<KeyRotatedEvent | null>(null)
 
   useEffect(() => {
     const initializeApp = async () => {
@@ -27,26 +78,38 @@ function App() {
         // Load config and sync status from backend
         await loadConfig()
         await checkSyncStatus()
+        setSafeMode(await invoke<SafeModeStatus>('get_safe_mode_status'))
         
-        setIsInitialized(true)
         console.log('App initialization complete')
       } catch (error) {
         console.error('Failed to initialize app:', error)
-        setIsInitialized(true) // Still mark as initialized to show UI
       }
     }
 
     initializeApp()
     
-    // Check sync status periodically, but only after initialization
-    const interval = setInterval(() => {
-      if (isInitialized) {
-        checkSyncStatus()
-      }
-    }, 2000)
+    // The backend reports start/stop from anywhere, including the tray
+    const unlisten = listen<SyncStatusEvent>('sync-status-changed', (event) => {
+      setSyncEnabled(event.payload.running)
+    })
+    const unlistenSafeMode = listen<SafeModeStatus>('safe-mode', (event) => {
+      setSafeMode(event.payload)
+    })
+    // Spoken by screen readers through the live region below
+    const unlistenAnnouncement = listen<Announcement>('announcement', (event) => {
+      setAnnouncement(event.payload)
+    })
+    const unlistenKeyRotation = listen<KeyRotatedEvent>('key-rotated', (event) => {
+      setKeyRotation(event.payload)
+    })
     
-    return () => clearInterval(interval)
-  }, [isInitialized])
+    return () => {
+      unlisten.then((fn) => fn())
+      unlistenSafeMode.then((fn) => fn())
+      unlistenAnnouncement.then((fn) => fn())
+      unlistenKeyRotation.then((fn) => fn())
+    }
+  }, [])
 
   const loadConfig = async () => {
     try {
@@ -109,11 +172,18 @@ function App() {
       console.log('Sync toggled successfully')
     } catch (error) {
       console.error('Failed to toggle sync:', error)
-      const errorMessage = error instanceof Error ? error.message : String(error)
+      const startError = typeof error === 'object' && error !== null && 'kind' in error
+        ? error as StartSyncError
+        : undefined
+      const errorMessage = error instanceof Error
+        ? error.message
+        : startError ? startError.message : String(error)
       
       // Provide more helpful error messages
       let userMessage = errorMessage
-      if (errorMessage.includes('timed out')) {
+      if (startError?.kind === 'port_unavailable') {
+        userMessage = describePortError(startError)
+      } else if (errorMessage.includes('timed out')) {
         userMessage = `Operation timed out. This often happens when:\n• Clipboard permissions are required (check System Preferences > Security & Privacy)\n• Port ${config.websocket_port} is already in use\n• Another instance is running`
       } else if (errorMessage.includes('permission')) {
         userMessage = `Permission denied. Please grant clipboard access in System Preferences > Security & Privacy > Privacy > Accessibility`
@@ -129,16 +199,73 @@ function App() {
     }
   }
 
+  const describePortError = (error: StartSyncError & { kind: 'port_unavailable' }) => {
+    const lines = [error.reason === 'permission_denied'
+      ? `Not allowed to listen on port ${error.port}.`
+      : `Port ${error.port} is already in use.`]
+    if (error.owner) {
+      lines.push(`• Held by ${error.owner.process_name ?? 'unknown process'} (pid ${error.owner.pid})`)
+    }
+    if (error.own_instance) {
+      lines.push('• This looks like another UniMesh Clip instance; quit it and try again')
+    }
+    if (error.suggested_port) {
+      lines.push(`• Port ${error.suggested_port} is free; change the port in settings to use it`)
+    }
+    return lines.join('\n')
+  }
+
   const handleConfigSave = async () => {
     await loadConfig()
     // Check if we need to update sync status
     await checkSyncStatus()
   }
 
+  const leaveSafeMode = async (startSync: boolean) => {
+    // Safe mode is left even when sync then fails to start
+    try {
+      await invoke('exit_safe_mode', { startSync })
+    } catch (error) {
+      console.error('Sync did not start after leaving safe mode:', error)
+      alert('Left safe mode, but sync failed to start. Check the settings and try Start Sync again.')
+    }
+    setSafeMode(null)
+    await checkSyncStatus()
+  }
+
   return (
     <div className="container">
       <h1>UniMesh Clip</h1>
+      <div className="sr-only" role="status" aria-live={announcement?.politeness ?? 'polite'}>
+        {announcement?.message}
+      </div>
+      {safeMode?.active && (
+        <div className="safe-mode-banner">
+          <strong>Safe mode</strong>
+          <p>
+            The app closed unexpectedly during its last {safeMode.unfinished_startups} starts, so sync
+            and clipboard monitoring are off. Review the settings below, then leave safe mode.
+          </p>
+          {safeMode.last_crash && (
+            <p>Last crash: {safeMode.last_crash.message} ({new Date(safeMode.last_crash.occurred_at).toLocaleString()})</p>
+          )}
+          <button onClick={() => leaveSafeMode(true)}>Leave Safe Mode and Start Sync</button>
+          <button onClick={() => leaveSafeMode(false)}>Leave Safe Mode</button>
+        </div>
+      )}
+      {keyRotation && (
+        <div className="safe-mode-banner">
+          <strong>Security key changed</strong>
+          <p>
+            {keyRotation.device} ({keyRotation.address}) now uses a new security key and accepts the
+            old one until {new Date(keyRotation.grace_until).toLocaleString()}. Enter the new key in the
+            settings below to keep syncing with it.
+          </p>
+          <button onClick={() => setKeyRotation(null)}>Dismiss</button>
+        </div>
+      )}
       <StatusIndicator isActive={syncEnabled} />
+      {syncEnabled && <SyncStats />}
       
       <div className="sync-control">
         <button 
@@ -1,6 +1,7 @@
 import React, { useState, useEffect } from 'react'
 import { invoke } from '@tauri-apps/api/core'
-import { DiscoveredDevice } from '../types'
+import { listen } from '@tauri-apps/api/event'
+import { DiscoveredDevice, DiscoveryStatus } from '../types'
 
 export const DeviceDiscovery: React.FC = () => {
   const [devices, setDevices] = useState<DiscoveredDevice[]>([])
@@ -8,12 +9,21 @@ export const DeviceDiscovery: React.FC = () => {
   const [showAddDevice, setShowAddDevice] = useState(false)
   const [newDevice, setNewDevice] = useState({ name: '', address: '', port: '8765' })
   const [isDevMode, setIsDevMode] = useState(false)
+  const [showManualPeer, setShowManualPeer] = useState(false)
+  const [manualPeer, setManualPeer] = useState({ address: '', port: '8765' })
+  const [discovery, setDiscovery] = useState<DiscoveryStatus | null>(null)
 
   useEffect(() => {
     loadDevices()
     checkDevMode()
-    const interval = setInterval(loadDevices, 2000) // Update every 2 seconds
-    return () => clearInterval(interval)
+    invoke<DiscoveryStatus>('get_discovery_status').then(setDiscovery).catch((error) => {
+      console.error('Failed to load discovery status:', error)
+    })
+    // Reload when the backend reports a change instead of polling
+    const unlisten = ['device-discovered', 'device-lost', 'peer-connected', 'peer-disconnected', 'peer-version-mismatch', 'guest-expired']
+      .map((event) => listen(event, loadDevices))
+    unlisten.push(listen<DiscoveryStatus>('discovery-status-changed', (event) => setDiscovery(event.payload)))
+    return () => unlisten.forEach((stop) => stop.then((fn) => fn()))
   }, [])
 
   const checkDevMode = async () => {
@@ -56,6 +66,37 @@ export const DeviceDiscovery: React.FC = () => {
     return diffMs < 10000 // Active if seen within last 10 seconds
   }
 
+  const handleTrustChange = async (device: DiscoveredDevice, command: 'trust_device' | 'untrust_device' | 'forget_device') => {
+    try {
+      await invoke(command, { address: device.address, port: device.port })
+      await loadDevices()
+    } catch (error) {
+      console.error(`Failed to ${command.replace('_device', '')} device:`, error)
+    }
+  }
+
+  const handlePairGuest = async (device: DiscoveredDevice) => {
+    try {
+      await invoke('pair_guest_device', { address: device.address, port: device.port, hours: null })
+      await loadDevices()
+    } catch (error) {
+      console.error('Failed to pair guest device:', error)
+    }
+  }
+
+  const handleMute = async (device: DiscoveredDevice, mute: boolean) => {
+    try {
+      if (mute) {
+        await invoke('mute_device', { address: device.address, minutes: 60 })
+      } else {
+        await invoke('unmute_device', { address: device.address })
+      }
+      await loadDevices()
+    } catch (error) {
+      console.error(`Failed to ${mute ? 'mute' : 'unmute'} device:`, error)
+    }
+  }
+
   const handleAddDevice = async () => {
     if (!newDevice.name || !newDevice.address) return
     
@@ -66,7 +107,8 @@ export const DeviceDiscovery: React.FC = () => {
           address: newDevice.address,
           port: parseInt(newDevice.port) || 8765,
           last_seen: new Date().toISOString(),
-          trusted: false
+          trusted: false,
+          paused: false
         }
       })
       setNewDevice({ name: '', address: '', port: '8765' })
@@ -77,6 +119,55 @@ export const DeviceDiscovery: React.FC = () => {
     }
   }
 
+  const handleAddManualPeer = async () => {
+    if (!manualPeer.address) return
+
+    try {
+      await invoke('add_manual_peer', {
+        address: manualPeer.address,
+        port: parseInt(manualPeer.port) || 8765
+      })
+      setManualPeer({ address: '', port: '8765' })
+      setShowManualPeer(false)
+    } catch (error) {
+      alert(`Failed to add device: ${error}`)
+    }
+  }
+
+  // mDNS is blocked on many corporate and guest networks
+  const manualPeerControls = (
+    <>
+      <div className="device-actions">
+        <button onClick={() => setShowManualPeer(!showManualPeer)}>
+          {showManualPeer ? 'Cancel' : 'Connect by IP'}
+        </button>
+      </div>
+      {showManualPeer && (
+        <div className="add-device-form">
+          <div className="form-group">
+            <label>IP Address</label>
+            <input
+              type="text"
+              value={manualPeer.address}
+              onChange={(e) => setManualPeer({ ...manualPeer, address: e.target.value })}
+              placeholder="e.g., 192.168.1.100"
+            />
+          </div>
+          <div className="form-group">
+            <label>Port</label>
+            <input
+              type="number"
+              value={manualPeer.port}
+              onChange={(e) => setManualPeer({ ...manualPeer, port: e.target.value })}
+              placeholder="8765"
+            />
+          </div>
+          <button onClick={handleAddManualPeer}>Connect</button>
+        </div>
+      )}
+    </>
+  )
+
   if (loading) {
     return <div>Discovering devices...</div>
   }
@@ -86,8 +177,13 @@ export const DeviceDiscovery: React.FC = () => {
       <div>
         <div className="empty-state">
           <p>No devices found on the network</p>
-          <p className="hint">Make sure other devices are running UniMesh Clip and are on the same network</p>
+          {discovery?.state === 'multicast_unavailable' ? (
+            <p className="hint" title={discovery.reason}>Multicast is unavailable on this network, so devices cannot be discovered. Add them by address instead.</p>
+          ) : (
+            <p className="hint">Make sure other devices are running UniMesh Clip and are on the same network</p>
+          )}
         </div>
+        {manualPeerControls}
         {isDevMode && (
           <div className="device-actions">
             <button onClick={() => setShowAddDevice(true)}>Add Test Device</button>
@@ -105,14 +201,47 @@ export const DeviceDiscovery: React.FC = () => {
           return (
             <div key={`${device.address}:${device.port}`} className="device-item">
               <div className="device-info">
-                <div className="device-name">{device.name}</div>
+                <div className="device-name">{device.display_name || device.name}</div>
                 <div className="device-details">
                   <span className="device-address">{device.address}:{device.port}</span>
+                  {device.app_version && <span className="device-version">• v{device.app_version}</span>}
                   <span className="device-last-seen">• Last seen {formatLastSeen(device.last_seen)}</span>
                 </div>
               </div>
               <div className="device-status">
-                {device.trusted && <span className="trust-badge">Trusted</span>}
+                {device.liveness === 'connected' && <span className="trust-badge">Connected</span>}
+                {device.liveness === 'stale' && <span className="pause-badge">Not responding</span>}
+                {device.trusted && !device.guest_until && <span className="trust-badge">Trusted</span>}
+                {device.guest_until && (
+                  <span className="trust-badge">Guest until {new Date(device.guest_until).toLocaleTimeString()}</span>
+                )}
+                {device.paused && <span className="pause-badge">Paused</span>}
+                {device.muted_until && (
+                  <span className="pause-badge" title="This device asked not to receive clips">
+                    Not sending until {new Date(device.muted_until).toLocaleTimeString()}
+                  </span>
+                )}
+                {device.mute_requested_until && (
+                  <span className="pause-badge">Muted until {new Date(device.mute_requested_until).toLocaleTimeString()}</span>
+                )}
+                {device.version_mismatch && (
+                  <span
+                    className="pause-badge"
+                    title={`Version ${device.version_mismatch.peer_app_version ?? 'unknown'}; ${device.version_mismatch.upgrade === 'local' ? 'upgrade this device' : 'upgrade that device'}`}
+                  >
+                    {device.version_mismatch.severity === 'incompatible' ? 'Incompatible' : 'Outdated'}
+                  </span>
+                )}
+                <button onClick={() => handleTrustChange(device, device.trusted ? 'untrust_device' : 'trust_device')}>
+                  {device.trusted ? 'Untrust' : 'Trust'}
+                </button>
+                {!device.trusted && <button onClick={() => handlePairGuest(device)}>Pair as guest (8h)</button>}
+                {device.liveness === 'connected' && (
+                  <button onClick={() => handleMute(device, !device.mute_requested_until)}>
+                    {device.mute_requested_until ? 'Unmute' : 'Mute 1h'}
+                  </button>
+                )}
+                <button onClick={() => handleTrustChange(device, 'forget_device')}>Forget</button>
                 <div 
                   className={`connection-indicator ${isActive ? 'active' : ''}`} 
                   title={isActive ? 'Active' : 'Inactive'} 
@@ -122,6 +251,8 @@ export const DeviceDiscovery: React.FC = () => {
           )
         })}
       </div>
+
+      {manualPeerControls}
       
       {isDevMode && (
         <div className="device-actions">
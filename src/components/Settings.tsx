@@ -1,6 +1,6 @@
 import React, { useState } from 'react'
 import { invoke } from '@tauri-apps/api/core'
-import { Config } from '../types'
+import { AnnouncementVerbosity, Config, ConflictPolicy, FilterAction, FilterRule, LanguageRoute, OversizePolicy, PeerEndpoint, ResumePolicy, SyncMode, WatchedFolder } from '../types'
 
 interface SettingsProps {
   config: Config
@@ -10,16 +10,97 @@ interface SettingsProps {
 export const Settings: React.FC<SettingsProps> = ({ config, onSave }) => {
   const [formData, setFormData] = useState<Config>(config)
   const [saving, setSaving] = useState(false)
+  const [currentDesktop, setCurrentDesktop] = useState<string | null>(null)
 
   // Update form data when config prop changes
   React.useEffect(() => {
     setFormData(config)
   }, [config])
 
+  React.useEffect(() => {
+    invoke<string | null>('get_current_desktop').then(setCurrentDesktop).catch(() => setCurrentDesktop(null))
+  }, [])
+
   const handleChange = (field: keyof Config, value: any) => {
     setFormData(prev => ({ ...prev, [field]: value }))
   }
 
+  const formatStaticPeers = (peers: PeerEndpoint[]) =>
+    peers.map((peer) => `${peer.host}:${peer.port}`).join('\n')
+
+  // One `host:port` per line; pinned fingerprints of unchanged entries are kept
+  const parseStaticPeers = (text: string): PeerEndpoint[] =>
+    text.split('\n').map((line) => line.trim()).filter(Boolean).flatMap((line) => {
+      const split = line.lastIndexOf(':')
+      const host = split > 0 ? line.slice(0, split) : line
+      const port = split > 0 ? parseInt(line.slice(split + 1)) : 8765
+      if (!host || !port) return []
+      const known = formData.static_peers.find((peer) => peer.host === host && peer.port === port)
+      return [{ host, port, fingerprint: known?.fingerprint }]
+    })
+
+  const formatTagRetention = (table: Record<string, number>) =>
+    Object.entries(table).map(([tag, days]) => `${tag}=${days}`).join('\n')
+
+  // One `tag=days` per line; 0 days keeps tagged entries forever
+  const parseTagRetention = (text: string): Record<string, number> =>
+    Object.fromEntries(text.split('\n').map((line) => line.trim()).filter(Boolean).flatMap((line) => {
+      const [tag, days] = line.split('=').map((part) => part.trim())
+      return tag ? [[tag.toLowerCase(), parseInt(days) || 0]] : []
+    }))
+
+  // One `address=KiB/s` per line; 0 lifts the cap
+  const parseBandwidthCaps = (text: string): Record<string, number> =>
+    Object.fromEntries(text.split('\n').map((line) => line.trim()).filter(Boolean).flatMap((line) => {
+      const [address, rate] = line.split('=').map((part) => part.trim())
+      return address ? [[address, parseInt(rate) || 0]] : []
+    }))
+
+  const formatLanguageRoutes = (routes: LanguageRoute[]) =>
+    routes.map((route) => `${route.language}=${route.devices.join(', ')}`).join('\n')
+
+  // One `language=device, device` per line; no devices keeps those clips here
+  const parseLanguageRoutes = (text: string): LanguageRoute[] =>
+    text.split('\n').map((line) => line.trim()).filter(Boolean).flatMap((line) => {
+      const [language, devices = ''] = line.split('=').map((part) => part.trim())
+      return language ? [{ language, devices: devices.split(',').map((device) => device.trim()).filter(Boolean) }] : []
+    })
+
+  const formatWatchedFolders = (folders: WatchedFolder[]) =>
+    folders.map((folder) => [
+      folder.path,
+      ...(folder.extensions.length ? [`ext=${folder.extensions.join(',')}`] : []),
+      `max=${folder.max_bytes}`,
+      ...(folder.delete_after_send ? ['delete'] : []),
+    ].join('; ')).join('\n')
+
+  // One `path; ext=txt,md; max=65536; delete` per line; only the path is required
+  const parseWatchedFolders = (text: string): WatchedFolder[] =>
+    text.split('\n').map((line) => line.trim()).filter(Boolean).map((line) => {
+      const [path, ...options] = line.split(';').map((part) => part.trim())
+      const folder: WatchedFolder = { path, extensions: [], max_bytes: 65536, delete_after_send: false }
+      for (const option of options) {
+        const [key, value = ''] = option.split('=').map((part) => part.trim())
+        if (key === 'ext') folder.extensions = value.split(',').map((ext) => ext.trim()).filter(Boolean)
+        if (key === 'max' && parseInt(value) > 0) folder.max_bytes = parseInt(value)
+        if (key === 'delete') folder.delete_after_send = true
+      }
+      return folder
+    })
+
+  const formatContentFilters = (rules: FilterRule[]) =>
+    rules.map((rule) => `${rule.action} ${rule.pattern}`).join('\n')
+
+  // One `allow|block|redact <regex>` per line, checked top to bottom
+  const parseContentFilters = (text: string): FilterRule[] =>
+    text.split('\n').map((line) => line.trim()).filter(Boolean).flatMap((line) => {
+      const split = line.indexOf(' ')
+      const action = line.slice(0, split) as FilterAction
+      const pattern = line.slice(split + 1).trim()
+      if (split < 0 || !['allow', 'block', 'redact'].includes(action) || !pattern) return []
+      return [{ action, pattern }]
+    })
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault()
     setSaving(true)
@@ -48,6 +129,29 @@ export const Settings: React.FC<SettingsProps> = ({ config, onSave }) => {
         />
       </div>
 
+      <div className="form-group">
+        <label htmlFor="metrics_port">Prometheus Metrics Port (empty = off)</label>
+        <input
+          id="metrics_port"
+          type="number"
+          value={formData.metrics_port ?? ''}
+          onChange={(e) => handleChange('metrics_port', e.target.value ? parseInt(e.target.value) : undefined)}
+          min="1024"
+          max="65535"
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="device_name">Device Name</label>
+        <input
+          id="device_name"
+          type="text"
+          value={formData.device_name}
+          onChange={(e) => handleChange('device_name', e.target.value)}
+          placeholder="Defaults to this computer's hostname"
+        />
+      </div>
+
       <div className="form-group">
         <label htmlFor="mdns_service_name">mDNS Service Name</label>
         <input
@@ -69,6 +173,27 @@ export const Settings: React.FC<SettingsProps> = ({ config, onSave }) => {
         />
       </div>
 
+      <div className="form-group">
+        <label htmlFor="key_rotation_grace_hours">Accept the Previous Key For (hours after a change)</label>
+        <input
+          id="key_rotation_grace_hours"
+          type="number"
+          value={formData.key_rotation_grace_hours}
+          onChange={(e) => handleChange('key_rotation_grace_hours', parseInt(e.target.value) || 0)}
+          min="0"
+        />
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="legacy_key_peers"
+          type="checkbox"
+          checked={formData.legacy_key_peers}
+          onChange={(e) => handleChange('legacy_key_peers', e.target.checked)}
+        />
+        <label htmlFor="legacy_key_peers">Sync with older versions (weakens the security key)</label>
+      </div>
+
       <div className="checkbox-group">
         <input
           id="auto_start"
@@ -79,6 +204,374 @@ export const Settings: React.FC<SettingsProps> = ({ config, onSave }) => {
         <label htmlFor="auto_start">Start sync automatically</label>
       </div>
 
+      <div className="checkbox-group">
+        <input
+          id="launch_at_login"
+          type="checkbox"
+          checked={formData.launch_at_login}
+          onChange={(e) => handleChange('launch_at_login', e.target.checked)}
+        />
+        <label htmlFor="launch_at_login">Launch at login</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="trusted_only"
+          type="checkbox"
+          checked={formData.trusted_only}
+          onChange={(e) => handleChange('trusted_only', e.target.checked)}
+        />
+        <label htmlFor="trusted_only">Only sync with trusted devices</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="allow_remote_log_requests"
+          type="checkbox"
+          checked={formData.allow_remote_log_requests}
+          onChange={(e) => handleChange('allow_remote_log_requests', e.target.checked)}
+        />
+        <label htmlFor="allow_remote_log_requests">Share recent logs with paired devices</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="monitor_clipboard"
+          type="checkbox"
+          checked={formData.monitor_clipboard}
+          onChange={(e) => handleChange('monitor_clipboard', e.target.checked)}
+        />
+        <label htmlFor="monitor_clipboard">Send clipboard changes automatically</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="send_receipts"
+          type="checkbox"
+          checked={formData.send_receipts}
+          onChange={(e) => handleChange('send_receipts', e.target.checked)}
+        />
+        <label htmlFor="send_receipts">Tell the sending device when its clip is applied here</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="notifications_enabled"
+          type="checkbox"
+          checked={formData.notifications_enabled}
+          onChange={(e) => handleChange('notifications_enabled', e.target.checked)}
+        />
+        <label htmlFor="notifications_enabled">Notify when a clip arrives from another device</label>
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="announcement_verbosity">Screen Reader Announcements</label>
+        <select
+          id="announcement_verbosity"
+          value={formData.announcement_verbosity}
+          onChange={(e) => handleChange('announcement_verbosity', e.target.value as AnnouncementVerbosity)}
+        >
+          <option value="off">Off</option>
+          <option value="essential">Clips received and problems</option>
+          <option value="verbose">All sync activity</option>
+        </select>
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="static_peers">Static Peers (host:port, one per line)</label>
+        <textarea
+          id="static_peers"
+          defaultValue={formatStaticPeers(formData.static_peers)}
+          key={formatStaticPeers(config.static_peers)}
+          onBlur={(e) => handleChange('static_peers', parseStaticPeers(e.target.value))}
+          placeholder="e.g., build-server.lan:8765"
+          rows={3}
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="heartbeat_min_secs">Fastest Heartbeat (seconds)</label>
+        <input
+          id="heartbeat_min_secs"
+          type="number"
+          value={formData.heartbeat_min_secs}
+          onChange={(e) => handleChange('heartbeat_min_secs', parseInt(e.target.value) || 1)}
+          min="1"
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="heartbeat_max_secs">Slowest Heartbeat on Stable Links (seconds)</label>
+        <input
+          id="heartbeat_max_secs"
+          type="number"
+          value={formData.heartbeat_max_secs}
+          onChange={(e) => handleChange('heartbeat_max_secs', parseInt(e.target.value) || 1)}
+          min="1"
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="push_shortcut">Push Clipboard Shortcut</label>
+        <input
+          id="push_shortcut"
+          type="text"
+          value={formData.push_shortcut || ''}
+          onChange={(e) => handleChange('push_shortcut', e.target.value || undefined)}
+          placeholder="e.g. CommandOrControl+Shift+C"
+        />
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="tls_enabled"
+          type="checkbox"
+          checked={formData.tls_enabled}
+          onChange={(e) => handleChange('tls_enabled', e.target.checked)}
+        />
+        <label htmlFor="tls_enabled">Encrypt connections with TLS (wss://)</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="binary_framing"
+          type="checkbox"
+          checked={formData.binary_framing}
+          onChange={(e) => handleChange('binary_framing', e.target.checked)}
+        />
+        <label htmlFor="binary_framing">Use compact binary messages with peers that support them</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="filter_same_host"
+          type="checkbox"
+          checked={formData.filter_same_host}
+          onChange={(e) => handleChange('filter_same_host', e.target.checked)}
+        />
+        <label htmlFor="filter_same_host">Ignore other instances on this machine's addresses</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="skip_concealed_content"
+          type="checkbox"
+          checked={formData.skip_concealed_content}
+          onChange={(e) => handleChange('skip_concealed_content', e.target.checked)}
+        />
+        <label htmlFor="skip_concealed_content">Never send passwords copied from password managers</label>
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="history_retention_days">Keep History (days, 0 = forever)</label>
+        <input
+          id="history_retention_days"
+          type="number"
+          value={formData.history_retention_days}
+          onChange={(e) => handleChange('history_retention_days', parseInt(e.target.value) || 0)}
+          min="0"
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="history_max_entries">Max History Entries (0 = unlimited)</label>
+        <input
+          id="history_max_entries"
+          type="number"
+          value={formData.history_max_entries}
+          onChange={(e) => handleChange('history_max_entries', parseInt(e.target.value) || 0)}
+          min="0"
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="blob_store_max_mb">Max Blob Storage in MiB (0 = unlimited)</label>
+        <input
+          id="blob_store_max_mb"
+          type="number"
+          value={formData.blob_store_max_mb}
+          onChange={(e) => handleChange('blob_store_max_mb', parseInt(e.target.value) || 0)}
+          min="0"
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="history_tag_retention">Keep Tagged History (tag=days per line, 0 = forever)</label>
+        <textarea
+          id="history_tag_retention"
+          defaultValue={formatTagRetention(formData.history_tag_retention)}
+          key={formatTagRetention(config.history_tag_retention)}
+          onBlur={(e) => handleChange('history_tag_retention', parseTagRetention(e.target.value))}
+          placeholder="e.g., license=0"
+          rows={3}
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="max_content_bytes">Max Clip Size (bytes, 0 = unlimited)</label>
+        <input
+          id="max_content_bytes"
+          type="number"
+          value={formData.max_content_bytes}
+          onChange={(e) => handleChange('max_content_bytes', parseInt(e.target.value) || 0)}
+          min="0"
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="oversize_policy">Larger Clips</label>
+        <select
+          id="oversize_policy"
+          value={formData.oversize_policy}
+          onChange={(e) => handleChange('oversize_policy', e.target.value as OversizePolicy)}
+        >
+          <option value="skip">Skip</option>
+          <option value="truncate">Truncate</option>
+          <option value="prompt">Ask</option>
+        </select>
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="bandwidth_caps">Bandwidth Caps (address=KiB/s per line)</label>
+        <textarea
+          id="bandwidth_caps"
+          defaultValue={formatTagRetention(formData.bandwidth_caps)}
+          key={formatTagRetention(config.bandwidth_caps)}
+          onBlur={(e) => handleChange('bandwidth_caps', parseBandwidthCaps(e.target.value))}
+          placeholder="e.g., 192.168.1.20=2048"
+          rows={3}
+        />
+      </div>
+
+      {(['text', 'image', 'file'] as const).map((kind) => (
+        <div className="form-group" key={kind}>
+          <label htmlFor={`conflict_policy_${kind}`}>
+            When a {kind === 'text' ? 'Text' : kind === 'image' ? 'Image' : 'File'} Clip Arrives During a Local Copy
+          </label>
+          <select
+            id={`conflict_policy_${kind}`}
+            value={formData.conflict_policies[kind]}
+            onChange={(e) => handleChange('conflict_policies', {
+              ...formData.conflict_policies,
+              [kind]: e.target.value as ConflictPolicy,
+            })}
+          >
+            <option value="last_writer_wins">Keep the newest</option>
+            <option value="never_overwrite">Ask before replacing</option>
+            <option value="history_only">Add to history only</option>
+          </select>
+        </div>
+      ))}
+
+      <div className="checkbox-group">
+        <input
+          id="sanitize_html"
+          type="checkbox"
+          checked={formData.format_security.sanitize_html}
+          onChange={(e) => handleChange('format_security', { ...formData.format_security, sanitize_html: e.target.checked })}
+        />
+        <label htmlFor="sanitize_html">Strip scripts and external content from received HTML</label>
+      </div>
+
+      <div className="checkbox-group">
+        <input
+          id="drop_rtf_objects"
+          type="checkbox"
+          checked={formData.format_security.drop_rtf_objects}
+          onChange={(e) => handleChange('format_security', { ...formData.format_security, drop_rtf_objects: e.target.checked })}
+        />
+        <label htmlFor="drop_rtf_objects">Drop received RTF that embeds objects</label>
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="blocked_file_extensions">Blocked File Types</label>
+        <input
+          id="blocked_file_extensions"
+          type="text"
+          value={formData.format_security.blocked_file_extensions.join(', ')}
+          onChange={(e) => handleChange('format_security', {
+            ...formData.format_security,
+            blocked_file_extensions: e.target.value.split(',').map((ext) => ext.trim())
+          })}
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="content_filters">Outgoing Content Filters (action and regex per line)</label>
+        <textarea
+          id="content_filters"
+          defaultValue={formatContentFilters(formData.content_filters)}
+          key={formatContentFilters(config.content_filters)}
+          onBlur={(e) => handleChange('content_filters', parseContentFilters(e.target.value))}
+          placeholder="e.g., block -----BEGIN [A-Z ]*PRIVATE KEY-----"
+          rows={3}
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="watched_folders">Watched Folders (text files dropped here are sent; one per line)</label>
+        <textarea
+          id="watched_folders"
+          defaultValue={formatWatchedFolders(formData.watched_folders)}
+          key={formatWatchedFolders(config.watched_folders)}
+          onBlur={(e) => handleChange('watched_folders', parseWatchedFolders(e.target.value))}
+          placeholder="e.g., /home/me/to-clipboard; ext=txt,md; delete"
+          rows={3}
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="language_routes">Language Routing (language=devices per line)</label>
+        <textarea
+          id="language_routes"
+          defaultValue={formatLanguageRoutes(formData.language_routes)}
+          key={formatLanguageRoutes(config.language_routes)}
+          onBlur={(e) => handleChange('language_routes', parseLanguageRoutes(e.target.value))}
+          placeholder="e.g., cjk=studio-mac"
+          rows={3}
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="work_desktops">
+          Work Desktops (one per line{currentDesktop ? `; this one is ${currentDesktop}` : ''})
+        </label>
+        <textarea
+          id="work_desktops"
+          defaultValue={formData.work_desktops.join('\n')}
+          key={config.work_desktops.join('\n')}
+          onBlur={(e) => handleChange('work_desktops', e.target.value.split('\n').map((line) => line.trim()).filter(Boolean))}
+          placeholder="Empty applies clips on every desktop"
+          rows={2}
+        />
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="sync_mode">Sync Direction</label>
+        <select
+          id="sync_mode"
+          value={formData.sync_mode}
+          onChange={(e) => handleChange('sync_mode', e.target.value as SyncMode)}
+        >
+          <option value="bidirectional">Send and receive</option>
+          <option value="send_only">Send only (never overwrite this clipboard)</option>
+          <option value="receive_only">Receive only (never share this clipboard)</option>
+        </select>
+      </div>
+
+      <div className="form-group">
+        <label htmlFor="resume_policy">After Waking From Sleep</label>
+        <select
+          id="resume_policy"
+          value={formData.resume_policy}
+          onChange={(e) => handleChange('resume_policy', e.target.value as ResumePolicy)}
+        >
+          <option value="auto">Resume sync</option>
+          <option value="ask">Ask me</option>
+          <option value="stay_paused">Stay paused</option>
+        </select>
+      </div>
+
       <div className="form-actions">
         <button type="submit" disabled={saving}>
           {saving ? 'Saving...' : 'Save Settings'}
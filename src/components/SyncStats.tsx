@@ -0,0 +1,74 @@
+import React, { useState, useEffect } from 'react'
+import { invoke } from '@tauri-apps/api/core'
+import { listen } from '@tauri-apps/api/event'
+import { PeerMetrics, SyncMetrics } from '../types'
+
+const formatBytes = (bytes: number) => {
+  if (bytes < 1024) return `${bytes} B`
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`
+  return `${(bytes / (1024 * 1024)).toFixed(1)} MB`
+}
+
+const formatUptime = (secs: number) => {
+  const hours = Math.floor(secs / 3600)
+  const mins = Math.floor((secs % 3600) / 60)
+  return hours > 0 ? `${hours}h ${mins}m` : `${mins}m`
+}
+
+export const SyncStats: React.FC = () => {
+  const [metrics, setMetrics] = useState<SyncMetrics | null>(null)
+  const [peers, setPeers] = useState<PeerMetrics[]>([])
+
+  useEffect(() => {
+    const loadMetrics = async () => {
+      try {
+        setMetrics(await invoke<SyncMetrics | null>('get_sync_metrics'))
+        setPeers(await invoke<PeerMetrics[]>('get_peer_metrics'))
+      } catch (error) {
+        console.error('Failed to load sync metrics:', error)
+      }
+    }
+
+    loadMetrics()
+    // Pushed events carry the counters; re-read for a fresh uptime
+    const unlisten = listen('metrics-updated', loadMetrics)
+    return () => {
+      unlisten.then((fn) => fn())
+    }
+  }, [])
+
+  const resetMetrics = async () => {
+    try {
+      await invoke('reset_sync_metrics')
+      setMetrics(await invoke<SyncMetrics | null>('get_sync_metrics'))
+      setPeers(await invoke<PeerMetrics[]>('get_peer_metrics'))
+    } catch (error) {
+      console.error('Failed to reset sync metrics:', error)
+    }
+  }
+
+  if (!metrics) return null
+
+  return (
+    <>
+      <div className="sync-stats">
+        <span>{metrics.connected_peers} peers</span>
+        <span>↑ {metrics.messages_sent} ({formatBytes(metrics.bytes_sent)})</span>
+        <span>↓ {metrics.messages_received} ({formatBytes(metrics.bytes_received)})</span>
+        <span>{metrics.messages_rejected} rejected</span>
+        <span>up {formatUptime(metrics.uptime_secs)}</span>
+        {metrics.reset_at && <span>since {new Date(metrics.reset_at).toLocaleTimeString()}</span>}
+        <button onClick={resetMetrics}>Reset</button>
+      </div>
+      {peers.map((peer) => (
+        <div key={peer.peer_id} className="sync-stats peer-stats">
+          <span>{peer.device || peer.address}</span>
+          <span>↑ {peer.messages_sent} ({formatBytes(peer.bytes_sent)})</span>
+          <span>↓ {peer.messages_received} ({formatBytes(peer.bytes_received)})</span>
+          <span>{peer.failures} failed</span>
+          <span>♥ every {peer.heartbeat_interval_secs}s</span>
+        </div>
+      ))}
+    </>
+  )
+}
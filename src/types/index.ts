@@ -1,30 +1,494 @@
 export interface Config {
   websocket_port: number
   mdns_service_name: string
+  device_name: string
   security_key?: string
+  key_rotation_grace_hours: number
+  legacy_key_peers: boolean
   auto_start: boolean
+  launch_at_login: boolean
   sync_enabled: boolean
+  allow_remote_log_requests: boolean
+  apply_cue: ApplyCue
+  apply_cue_rules: ApplyCueRule[]
+  dangerously_log_clipboard_content: boolean
+  trusted_only: boolean
+  history_retention_days: number
+  history_max_entries: number
+  history_tag_retention: Record<string, number>
+  blob_store_max_mb: number
+  bandwidth_caps: Record<string, number>
+  skip_concealed_content: boolean
+  filter_same_host: boolean
+  api_allowed_sources: string[]
+  api_allowed_origins: string[]
+  metrics_port?: number
+  binary_framing: boolean
+  max_content_bytes: number
+  oversize_policy: OversizePolicy
+  conflict_policies: ConflictPolicies
+  format_security: FormatSecurityPolicy
+  content_filters: FilterRule[]
+  language_routes: LanguageRoute[]
+  work_desktops: string[]
+  watched_folders: WatchedFolder[]
+  resume_policy: ResumePolicy
+  sync_mode: SyncMode
+  tls_enabled: boolean
+  monitor_clipboard: boolean
+  push_shortcut?: string
+  send_receipts: boolean
+  notifications_enabled: boolean
+  announcement_verbosity: AnnouncementVerbosity
+  heartbeat_min_secs: number
+  heartbeat_max_secs: number
+  manual_peers: ManualPeer[]
+  static_peers: PeerEndpoint[]
+}
+
+export type ResumePolicy = 'auto' | 'ask' | 'stay_paused'
+
+export interface FormatSecurityPolicy {
+  sanitize_html: boolean
+  drop_rtf_objects: boolean
+  blocked_file_extensions: string[]
+}
+
+export interface AuditEntry {
+  timestamp: string
+  action: string
+  device?: string
+  details: string[]
+}
+
+export type MessageType =
+  | 'clipboard_update'
+  | 'heartbeat'
+  | 'device_info'
+  | 'log_request'
+  | 'log_response'
+  | 'sync_state'
+  | 'device_removed'
+  | 'receipt'
+  | 'clipboard_delta'
+  | 'delta_ack'
+  | 'mute_request'
+  | 'hello'
+  | 'unknown'
+
+export type TraceKind = 'handshake' | 'received' | 'sent' | 'closed'
+
+export interface TraceEvent {
+  timestamp: string
+  kind: TraceKind
+  direction?: ConnectionDirection
+  peer?: string
+  message_id?: string
+  message_type?: MessageType
+  bytes?: number
+  encrypted: boolean
+  verdict: string
+}
+
+export interface TraceFilter {
+  peer?: string
+  kind?: TraceKind
+  message_type?: MessageType
+  problems_only?: boolean
+}
+
+export type SyncMode = 'bidirectional' | 'send_only' | 'receive_only'
+
+export interface ResumeEvent {
+  policy: ResumePolicy
+  slept_secs: number
+  paused: boolean
+  awaiting_decision: boolean
+  timestamp: string
+}
+
+export type OversizePolicy = 'skip' | 'truncate' | 'prompt'
+
+export type AnnouncementVerbosity = 'off' | 'essential' | 'verbose'
+
+export interface Announcement {
+  message: string
+  politeness: 'polite' | 'assertive'
+  timestamp: string
+}
+
+export type ConflictPolicy = 'last_writer_wins' | 'never_overwrite' | 'history_only'
+
+export interface ConflictPolicies {
+  text: ConflictPolicy
+  image: ConflictPolicy
+  file: ConflictPolicy
+}
+
+export interface ClipboardConflictEvent {
+  id: string
+  device: string
+  kind: 'text' | 'image' | 'file'
+  bytes: number
+  timestamp: string
+}
+
+export interface LocalInterface {
+  name: string
+  ip: string
+  prefix_len: number
+}
+
+export interface InterfacesChangedEvent {
+  interfaces: LocalInterface[]
+  lost: string[]
+  gained: string[]
+}
+
+export interface OversizeEvent {
+  id: string
+  direction: 'outgoing' | 'incoming'
+  bytes: number
+  limit: number
+  device?: string
+  timestamp: string
+}
+
+export type ContentKind = 'text' | 'image' | 'file'
+
+export type ApplyCue = 'none' | 'sound' | 'notification'
+
+export interface ApplyCueRule {
+  device?: string
+  content_kind?: ContentKind
+  cue: ApplyCue
+}
+
+export interface ApplyCueEvent {
+  cue: ApplyCue
+  device: string
+  content_kind: ContentKind
+  timestamp: string
 }
 
 export interface DiscoveredDevice {
   name: string
   address: string
+  addresses: string[]
   port: number
   last_seen: string
   trusted: boolean
+  paused: boolean
+  tls_fingerprint?: string
+  device_id?: string
+  display_name: string
+  app_version?: string
+  protocol_version?: number
+  version_mismatch?: PeerVersionMismatch
+  liveness: DeviceLiveness
+  muted_until?: string
+  mute_requested_until?: string
+  guest_until?: string
+}
+
+export type DeviceLiveness = 'connected' | 'online' | 'stale' | 'offline'
+
+export interface PeerVersionMismatch {
+  address: string
+  peer_app_version?: string
+  peer_protocol: number
+  local_app_version: string
+  local_protocol: number
+  severity: 'deprecated' | 'incompatible'
+  upgrade: 'local' | 'peer'
+  detected_at: string
 }
 
 export interface ClipboardMessage {
   id: string
-  type: 'clipboard_update' | 'heartbeat' | 'device_info'
+  type: 'clipboard_update' | 'heartbeat' | 'device_info' | 'log_request' | 'log_response' | 'sync_state' | 'device_removed' | 'receipt'
   content?: string
   timestamp: string
   signature?: string
   device?: DeviceInfo
+  encrypted?: boolean
+  formats?: string
+  content_hash?: string
+}
+
+export interface ClipboardContent {
+  text: string
+  html?: string
+  rtf?: string
+}
+
+export interface ManualPeer {
+  address: string
+  port: number
+  fingerprint?: string
+}
+
+export interface PeerEndpoint {
+  host: string
+  port: number
+  fingerprint?: string
 }
 
 export interface DeviceInfo {
   name: string
   platform: string
   version: string
-}
\ No newline at end of file
+}
+export type HistoryOrigin = 'local' | 'remote'
+
+export interface HistoryEntry {
+  id: number
+  content: string
+  origin: HistoryOrigin
+  device?: string
+  created_at: string
+  sent_to?: number
+  applied_on: number
+  pinned: boolean
+  tags: string[]
+}
+
+export interface HistoryPage {
+  entries: HistoryEntry[]
+  total: number
+}
+
+export interface PortOwner {
+  pid: number
+  process_name?: string
+}
+
+export interface BindError {
+  port: number
+  reason: 'addr_in_use' | 'permission_denied' | 'other'
+  message: string
+  owner?: PortOwner
+  own_instance: boolean
+  suggested_port?: number
+}
+
+export type StartSyncError =
+  | ({ kind: 'port_unavailable' } & BindError)
+  | { kind: 'other'; message: string }
+
+export interface MaintenanceReport {
+  entries_removed: number
+  blobs_evicted: number
+  bytes_reclaimed: number
+  finished_at: string
+}
+
+export type ApiScope = 'read' | 'read_write'
+
+export interface ApiTokenInfo {
+  id: string
+  scope: ApiScope
+  created_at: string
+}
+
+export interface CreatedApiToken extends ApiTokenInfo {
+  token: string
+}
+
+export interface ApiClipboardEvent {
+  content: ClipboardContent
+  origin: HistoryOrigin
+  device?: string
+  timestamp: string
+}
+
+export interface DeviceLostEvent {
+  name: string
+  address: string
+  port: number
+}
+
+export interface PeerEvent {
+  peer_id: string
+  address: string
+  connected_peers: number
+}
+
+export interface ClipboardSyncedEvent {
+  direction: 'sent' | 'received'
+  message_id?: string
+  device?: string
+  sender?: DeviceInfo
+  bytes: number
+  timestamp: string
+}
+
+export interface MessagePreview {
+  message_id: string
+  direction: 'sent' | 'received'
+  kind: 'text' | 'image' | 'file'
+  rich_formats: string[]
+  bytes: number
+  first_line: string
+  truncated: boolean
+  content_hash: string
+  created_at: string
+}
+
+export interface ApplyVerificationFailedEvent {
+  device: string
+  timestamp: string
+}
+
+export interface SyncStatusEvent {
+  running: boolean
+  paused: boolean
+}
+
+export interface SyncMetrics {
+  messages_sent: number
+  messages_received: number
+  messages_failed: number
+  messages_rejected: number
+  clipboard_updates_applied: number
+  clipboard_updates_failed: number
+  apply_verification_failures: number
+  last_sync_time?: string
+  connected_peers: number
+  bytes_sent: number
+  bytes_received: number
+  started_at?: string
+  uptime_secs: number
+  reset_at?: string
+}
+
+export type ConnectionDirection = 'inbound' | 'outbound'
+
+export interface ConnectedPeer {
+  peer_id: string
+  address: string
+  device?: string
+  direction: ConnectionDirection
+  local_address?: string
+  interface?: string
+  connected_since: string
+  last_activity: string
+  paused: boolean
+}
+
+export interface PeerMetrics {
+  peer_id: string
+  address: string
+  device?: string
+  direction: ConnectionDirection
+  local_address?: string
+  interface?: string
+  connected_at: string
+  last_activity: string
+  last_heartbeat?: string
+  heartbeat_interval_secs: number
+  messages_sent: number
+  messages_received: number
+  bytes_sent: number
+  bytes_received: number
+  failures: number
+  capabilities?: PeerCapabilities
+}
+
+export interface PeerCapabilities {
+  device_id?: string
+  name?: string
+  formats: string[]
+  max_message_bytes?: number
+  compression: string[]
+}
+
+export interface EffectiveConfig {
+  config: Config;
+  security_key_set: boolean;
+  running: boolean;
+  paused: boolean;
+  bound_port?: number;
+  advertised_address?: string;
+  tls_active: boolean;
+  clipboard_monitoring: boolean;
+  history_available: boolean;
+  portable: boolean;
+  data_dir?: string;
+  clipboard_backend: string;
+  discovery_backend: string;
+  app_version: string;
+  platform: string;
+}
+
+export interface ComponentHealth {
+  running: boolean;
+  paused: boolean;
+  websocket: boolean;
+  mdns: boolean;
+  clipboard_monitoring: boolean;
+  history: boolean;
+}
+
+export interface CrashReport {
+  occurred_at: string;
+  app_version: string;
+  platform: string;
+  thread?: string;
+  location?: string;
+  message: string;
+  recent_errors: string[];
+  health: ComponentHealth;
+  backtrace: string;
+}
+
+export interface KeyRotatedEvent {
+  device: string
+  address: string
+  rotated_at: string
+  grace_until: string
+}
+
+export interface SafeModeStatus {
+  active: boolean
+  unfinished_startups: number
+  last_crash?: CrashReport
+}
+
+export type PresentationRole = 'idle' | 'presenting' | 'viewing'
+
+export interface PresentationStatus {
+  role: PresentationRole
+  code?: string
+  presenter?: string
+  viewers: string[]
+  started_at?: string
+}
+
+export type DiscoveryState = 'stopped' | 'active' | 'retrying' | 'multicast_unavailable'
+
+export type FilterAction = 'allow' | 'block' | 'redact'
+
+export interface WatchedFolder {
+  path: string
+  extensions: string[]
+  max_bytes: number
+  delete_after_send: boolean
+}
+
+export interface LanguageRoute {
+  language: string
+  devices: string[]
+}
+
+export interface FilterRule {
+  pattern: string
+  action: FilterAction
+}
+
+export interface DiscoveryStatus {
+  state: DiscoveryState
+  reason?: string
+  attempts: number
+  next_attempt_at?: string
+}
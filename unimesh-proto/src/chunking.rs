@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Messages larger than this are split into chunk frames.
+pub const CHUNK_THRESHOLD: usize = 256 * 1024;
+/// Target size of one chunk frame's payload.
+pub const CHUNK_SIZE: usize = 64 * 1024;
+/// Largest message a peer may stream to us.
+pub const MAX_REASSEMBLY_BYTES: usize = 64 * 1024 * 1024;
+/// Partial messages not completed within this window are dropped.
+const PARTIAL_TIMEOUT: Duration = Duration::from_secs(60);
+/// Concurrent partial messages accepted per connection.
+const MAX_PARTIALS: usize = 4;
+/// Bytes all partial messages on one connection may announce together.
+pub const MAX_IN_FLIGHT_BYTES: usize = MAX_REASSEMBLY_BYTES;
+
+/// Transport frames for streaming a serialized message in pieces. Frames of
+/// one message share its `message_id`; `hash` covers the reassembled text.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChunkFrame {
+    ChunkBegin {
+        message_id: Uuid,
+        total_bytes: usize,
+        chunk_count: usize,
+        hash: String,
+    },
+    Chunk {
+        message_id: Uuid,
+        index: usize,
+        data: String,
+    },
+    ChunkEnd {
+        message_id: Uuid,
+    },
+}
+
+impl ChunkFrame {
+    /// Cheap check so ordinary messages are not parsed twice. serde writes
+    /// the tag first, so every chunk frame starts this way.
+    pub fn looks_like_chunk(text: &str) -> bool {
+        text.starts_with(r#"{"type":"chunk"#)
+    }
+}
+
+/// Split `json` into begin/chunk/end frames, or `None` if it is small
+/// enough to send as a single frame.
+pub fn split(message_id: Uuid, json: &str) -> Option<Vec<String>> {
+    if json.len() <= CHUNK_THRESHOLD {
+        return None;
+    }
+
+    let mut pieces = Vec::new();
+    let mut rest = json;
+    while !rest.is_empty() {
+        // Keep each piece valid UTF-8 so it can travel as a text frame
+        let mut end = CHUNK_SIZE.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (piece, tail) = rest.split_at(end);
+        pieces.push(piece);
+        rest = tail;
+    }
+
+    let mut frames = Vec::with_capacity(pieces.len() + 2);
+    frames.push(ChunkFrame::ChunkBegin {
+        message_id,
+        total_bytes: json.len(),
+        chunk_count: pieces.len(),
+        hash: blake3::hash(json.as_bytes()).to_hex().to_string(),
+    });
+    frames.extend(pieces.into_iter().enumerate().map(|(index, data)| ChunkFrame::Chunk {
+        message_id,
+        index,
+        data: data.to_string(),
+    }));
+    frames.push(ChunkFrame::ChunkEnd { message_id });
+
+    frames.iter().map(serde_json::to_string).collect::<Result<Vec<_>, _>>().ok()
+}
+
+struct Partial {
+    total_bytes: usize,
+    chunk_count: usize,
+    next_index: usize,
+    expected_hash: String,
+    hasher: blake3::Hasher,
+    data: String,
+    started: Instant,
+}
+
+/// Per-connection reassembly of chunked messages.
+#[derive(Default)]
+pub struct Reassembler {
+    partials: HashMap<Uuid, Partial>,
+}
+
+impl Reassembler {
+    /// Feed one frame. Messages announced as larger than `max_bytes` (or
+    /// `MAX_REASSEMBLY_BYTES`), or that would take the connection past
+    /// `MAX_IN_FLIGHT_BYTES`, are refused before any data is buffered.
+    /// Buffers grow with the data that arrives, not with what was announced.
+    ///
+    /// # Returns
+    /// The complete message text once its last frame arrives
+    ///
+    /// # Errors
+    /// When the frame violates the protocol or the payload is corrupt; the
+    /// partial message is discarded
+    pub fn accept(&mut self, frame: ChunkFrame, max_bytes: usize) -> Result<Option<String>, String> {
+        self.partials.retain(|id, partial| {
+            let fresh = partial.started.elapsed() < PARTIAL_TIMEOUT;
+            if !fresh {
+                tracing::warn!("Dropping incomplete chunked message {}", id);
+            }
+            fresh
+        });
+
+        match frame {
+            ChunkFrame::ChunkBegin { message_id, total_bytes, chunk_count, hash } => {
+                if total_bytes > max_bytes.min(MAX_REASSEMBLY_BYTES) {
+                    return Err(format!("chunked message {} is too large ({} bytes)", message_id, total_bytes));
+                }
+                if self.partials.len() >= MAX_PARTIALS {
+                    return Err(format!("too many concurrent chunked messages, dropping {}", message_id));
+                }
+                let in_flight: usize = self.partials.iter()
+                    .filter(|(id, _)| **id != message_id)
+                    .map(|(_, partial)| partial.total_bytes)
+                    .sum();
+                if in_flight + total_bytes > MAX_IN_FLIGHT_BYTES {
+                    return Err(format!("chunked message {} exceeds the bytes in flight on this connection", message_id));
+                }
+                self.partials.insert(message_id, Partial {
+                    total_bytes,
+                    chunk_count,
+                    next_index: 0,
+                    expected_hash: hash,
+                    hasher: blake3::Hasher::new(),
+                    data: String::new(),
+                    started: Instant::now(),
+                });
+                Ok(None)
+            }
+            ChunkFrame::Chunk { message_id, index, data } => {
+                let partial = self.partials.get_mut(&message_id)
+                    .ok_or_else(|| format!("chunk for unknown message {}", message_id))?;
+                if index != partial.next_index || partial.data.len() + data.len() > partial.total_bytes {
+                    self.partials.remove(&message_id);
+                    return Err(format!("out of order or oversized chunk for message {}", message_id));
+                }
+                partial.hasher.update(data.as_bytes());
+                partial.data.push_str(&data);
+                partial.next_index += 1;
+                Ok(None)
+            }
+            ChunkFrame::ChunkEnd { message_id } => {
+                let partial = self.partials.remove(&message_id)
+                    .ok_or_else(|| format!("end of unknown message {}", message_id))?;
+                if partial.next_index != partial.chunk_count || partial.data.len() != partial.total_bytes {
+                    return Err(format!("chunked message {} is incomplete", message_id));
+                }
+                if partial.hasher.finalize().to_hex().as_str() != partial.expected_hash {
+                    return Err(format!("chunked message {} failed its integrity check", message_id));
+                }
+                Ok(Some(partial.data))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frames(message_id: Uuid, json: &str) -> Vec<ChunkFrame> {
+        split(message_id, json)
+            .expect("large enough to chunk")
+            .iter()
+            .map(|frame| serde_json::from_str(frame).unwrap())
+            .collect()
+    }
+
+    fn large_json() -> String {
+        format!("{{\"content\":\"{}\"}}", "é".repeat(CHUNK_THRESHOLD))
+    }
+
+    #[test]
+    fn small_messages_are_not_split() {
+        assert!(split(Uuid::new_v4(), "{}").is_none());
+    }
+
+    #[test]
+    fn split_frames_reassemble() {
+        let (id, json) = (Uuid::new_v4(), large_json());
+        let mut reassembler = Reassembler::default();
+        let mut result = None;
+        for frame in frames(id, &json) {
+            result = reassembler.accept(frame, MAX_REASSEMBLY_BYTES).unwrap();
+        }
+        assert_eq!(result.as_deref(), Some(json.as_str()));
+    }
+
+    #[test]
+    fn out_of_order_chunks_are_refused() {
+        let id = Uuid::new_v4();
+        let mut frames = frames(id, &large_json());
+        frames.swap(1, 2);
+        let mut reassembler = Reassembler::default();
+        reassembler.accept(frames.remove(0), MAX_REASSEMBLY_BYTES).unwrap();
+        assert!(reassembler.accept(frames.remove(0), MAX_REASSEMBLY_BYTES).is_err());
+        // The partial message is gone, so its remaining frames are unknown
+        assert!(reassembler.accept(frames.remove(0), MAX_REASSEMBLY_BYTES).is_err());
+    }
+
+    #[test]
+    fn chunks_past_the_announced_size_are_refused() {
+        let id = Uuid::new_v4();
+        let mut reassembler = Reassembler::default();
+        let begin = ChunkFrame::ChunkBegin { message_id: id, total_bytes: 4, chunk_count: 1, hash: String::new() };
+        reassembler.accept(begin, MAX_REASSEMBLY_BYTES).unwrap();
+        let chunk = ChunkFrame::Chunk { message_id: id, index: 0, data: "too long".to_string() };
+        assert!(reassembler.accept(chunk, MAX_REASSEMBLY_BYTES).is_err());
+    }
+
+    #[test]
+    fn oversized_announcements_are_refused() {
+        let mut reassembler = Reassembler::default();
+        let begin = |total_bytes| ChunkFrame::ChunkBegin { message_id: Uuid::new_v4(), total_bytes, chunk_count: 1, hash: String::new() };
+        assert!(reassembler.accept(begin(1024), 512).is_err());
+        assert!(reassembler.accept(begin(MAX_REASSEMBLY_BYTES + 1), usize::MAX).is_err());
+        reassembler.accept(begin(MAX_IN_FLIGHT_BYTES / 2), usize::MAX).unwrap();
+        reassembler.accept(begin(MAX_IN_FLIGHT_BYTES / 2), usize::MAX).unwrap();
+        assert!(reassembler.accept(begin(1), usize::MAX).is_err());
+    }
+
+    #[test]
+    fn corrupt_payloads_fail_the_integrity_check() {
+        let id = Uuid::new_v4();
+        let mut frames = frames(id, &large_json());
+        if let ChunkFrame::Chunk { ref mut data, .. } = frames[1] {
+            data.replace_range(0..2, "e!");
+        }
+        let mut reassembler = Reassembler::default();
+        let results: Vec<_> = frames.into_iter().map(|frame| reassembler.accept(frame, MAX_REASSEMBLY_BYTES)).collect();
+        assert!(results.last().unwrap().is_err());
+    }
+}
@@ -0,0 +1,164 @@
+use argon2::Argon2;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use base64::{Engine as _, engine::general_purpose};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use anyhow::Result;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Domain separator so the content key never equals the HMAC key.
+const CONTENT_KEY_CONTEXT: &[u8] = b"unimesh-clip/content-encryption/v1";
+const NONCE_LEN: usize = 12;
+
+pub fn generate_signature(key: &str, message: &str) -> String {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(message.as_bytes());
+    let result = mac.finalize();
+    general_purpose::STANDARD.encode(result.into_bytes())
+}
+
+/// Verify a base64 HMAC-SHA256 signature in constant time.
+pub fn verify_signature(key: &str, message: &str, signature: &str) -> bool {
+    let Ok(signature) = general_purpose::STANDARD.decode(signature) else {
+        return false;
+    };
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key.as_bytes())
+        .expect("HMAC can take key of any size");
+    mac.update(message.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// Derive the wire key from a typed passphrase with Argon2id, so a key
+/// captured in traffic takes real work per guess to test. `salt` is the
+/// sending installation's, so one device's derivation is no help against
+/// another's.
+///
+/// # Returns
+/// Base64 of the 32-byte key, usable wherever a security key is
+///
+/// # Errors
+/// When `salt` is shorter than Argon2 allows
+pub fn derive_key(passphrase: &str, salt: &[u8]) -> Result<String> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| anyhow::anyhow!("Failed to derive key: {}", e))?;
+    Ok(general_purpose::STANDARD.encode(key))
+}
+
+/// Derive the AES-256 content key from the shared security key.
+fn derive_content_key(key: &str) -> Key<Aes256Gcm> {
+    let mut hasher = Sha256::new();
+    hasher.update(CONTENT_KEY_CONTEXT);
+    hasher.update(key.as_bytes());
+    hasher.finalize()
+}
+
+/// Encrypt clipboard content with AES-256-GCM.
+///
+/// # Returns
+/// Base64 of `nonce || ciphertext`, suitable for `ClipboardMessage.content`
+pub fn encrypt_content(key: &str, plaintext: &str) -> Result<String> {
+    let cipher = Aes256Gcm::new(&derive_content_key(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt clipboard content"))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(general_purpose::STANDARD.encode(payload))
+}
+
+/// Decrypt content produced by [`encrypt_content`].
+///
+/// # Errors
+/// When the payload is malformed, was encrypted with a different key, or was tampered with
+pub fn decrypt_content(key: &str, encoded: &str) -> Result<String> {
+    let payload = general_purpose::STANDARD.decode(encoded)?;
+    if payload.len() < NONCE_LEN {
+        return Err(anyhow::anyhow!("Encrypted payload is too short"));
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+
+    let cipher = Aes256Gcm::new(&derive_content_key(key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Failed to decrypt clipboard content"))?;
+    Ok(String::from_utf8(plaintext)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "c2hhcmVkLWtleS1mb3ItdGVzdHMtb25seS0zMmIhIQ==";
+    const SALT: &[u8] = b"0123456789abcdef";
+
+    #[test]
+    fn encrypted_content_round_trips() {
+        let sealed = encrypt_content(KEY, "hello").unwrap();
+        assert_ne!(sealed, "hello");
+        assert_eq!(decrypt_content(KEY, &sealed).unwrap(), "hello");
+    }
+
+    #[test]
+    fn each_encryption_uses_a_fresh_nonce() {
+        assert_ne!(encrypt_content(KEY, "hello").unwrap(), encrypt_content(KEY, "hello").unwrap());
+    }
+
+    #[test]
+    fn decrypting_with_another_key_fails() {
+        let sealed = encrypt_content(KEY, "hello").unwrap();
+        assert!(decrypt_content("another key", &sealed).is_err());
+    }
+
+    #[test]
+    fn tampered_ciphertext_fails() {
+        let mut payload = general_purpose::STANDARD.decode(encrypt_content(KEY, "hello").unwrap()).unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+        assert!(decrypt_content(KEY, &general_purpose::STANDARD.encode(payload)).is_err());
+    }
+
+    #[test]
+    fn truncated_payload_fails() {
+        assert!(decrypt_content(KEY, &general_purpose::STANDARD.encode([0u8; NONCE_LEN - 1])).is_err());
+        assert!(decrypt_content(KEY, "not base64!").is_err());
+    }
+
+    #[test]
+    fn signatures_verify_only_with_the_key_and_message_signed() {
+        let signature = generate_signature(KEY, "payload");
+        assert!(verify_signature(KEY, "payload", &signature));
+        assert!(!verify_signature("another key", "payload", &signature));
+        assert!(!verify_signature(KEY, "payload!", &signature));
+    }
+
+    #[test]
+    fn tampered_signature_fails() {
+        let mut signature = general_purpose::STANDARD.decode(generate_signature(KEY, "payload")).unwrap();
+        signature[0] ^= 1;
+        assert!(!verify_signature(KEY, "payload", &general_purpose::STANDARD.encode(signature)));
+        assert!(!verify_signature(KEY, "payload", "not base64!"));
+    }
+
+    #[test]
+    fn derived_keys_are_deterministic_per_salt() {
+        let key = derive_key("passphrase", SALT).unwrap();
+        assert_eq!(derive_key("passphrase", SALT).unwrap(), key);
+        assert_ne!(derive_key("passphrase", b"fedcba9876543210").unwrap(), key);
+        assert_ne!(derive_key("other passphrase", SALT).unwrap(), key);
+        assert_eq!(general_purpose::STANDARD.decode(key).unwrap().len(), 32);
+    }
+
+    #[test]
+    fn short_salts_are_refused() {
+        assert!(derive_key("passphrase", b"short").is_err());
+    }
+}
@@ -0,0 +1,55 @@
+use crate::message::ClipboardContent;
+
+/// Incremental BLAKE3 hasher for clipboard payloads. Fields are
+/// length-prefixed so different splits of the same bytes never collide.
+pub struct ContentHasher {
+    hasher: blake3::Hasher,
+}
+
+impl ContentHasher {
+    pub fn new() -> Self {
+        Self {
+            hasher: blake3::Hasher::new(),
+        }
+    }
+
+    /// Feed one field (or one chunk of a streamed payload).
+    pub fn update(&mut self, part: &[u8]) -> &mut Self {
+        self.hasher.update(&(part.len() as u64).to_le_bytes());
+        self.hasher.update(part);
+        self
+    }
+
+    pub fn finalize(&self) -> blake3::Hash {
+        self.hasher.finalize()
+    }
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hash of plain text alone, used to detect clipboard changes.
+pub fn hash_text(text: &str) -> blake3::Hash {
+    blake3::hash(text.as_bytes())
+}
+
+/// Hash of every format in a clip, hex encoded for the wire.
+pub fn hash_content(content: &ClipboardContent) -> String {
+    let mut hasher = ContentHasher::new();
+    hasher.update(content.text.as_bytes());
+    for format in [&content.html, &content.rtf] {
+        match format {
+            Some(value) => hasher.update(&[1]).update(value.as_bytes()),
+            None => hasher.update(&[0]),
+        };
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// First 16 hex characters of a BLAKE3 hash, for correlating payloads in logs.
+pub fn short_hash(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex()[..16].to_string()
+}
@@ -0,0 +1,13 @@
+//! Wire protocol of uni-mesh-clip: message definitions, signing and
+//! encryption, content hashing, and chunked framing.
+//!
+//! The desktop app is built on this crate, so other clients that depend on
+//! it speak exactly the same protocol.
+
+pub mod chunking;
+pub mod crypto;
+pub mod hashing;
+pub mod message;
+pub mod version;
+
+pub use message::*;
@@ -0,0 +1,436 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::version::ProtocolVersion;
+
+/// Tag opening the second signing payload format, which covers every field
+/// that changes how a receiver handles the message.
+pub const SIGNING_FORMAT_V2: &str = "unimesh-sig/2";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    ClipboardUpdate,
+    Heartbeat,
+    DeviceInfo,
+    LogRequest,
+    LogResponse,
+    SyncState,
+    DeviceRemoved,
+    Receipt,
+    /// A text clip sent as an edit of one the peer confirmed holding
+    ClipboardDelta,
+    DeltaAck,
+    /// Ask a peer to stop sending us content for a while
+    MuteRequest,
+    /// Versions, sent once when a connection opens
+    Hello,
+    /// The sender changed its security key; signed with the old one so
+    /// peers still holding it can read the notice
+    KeyRotation,
+    /// A type from a newer build, skipped rather than failing the message
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipboardMessage {
+    pub id: Uuid,
+    #[serde(rename = "type")]
+    pub msg_type: MessageType,
+    pub content: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    pub signature: Option<String>,
+    pub device: Option<DeviceInfo>,
+    /// `content` is AES-GCM ciphertext derived from the shared security key
+    #[serde(default)]
+    pub encrypted: bool,
+    /// JSON-encoded `RichFormats`, encrypted like `content`. Omitted for plain
+    /// text so older peers keep working with `content` alone.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formats: Option<String>,
+    /// BLAKE3 hash of the plaintext clip (all formats), encrypted like
+    /// `content`. Receivers use it to verify integrity when present.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
+    /// Stable id of the sending device. Signed in payload format 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_id: Option<Uuid>,
+    /// Session of the connection this copy travels on, agreed in the
+    /// handshake. Signed, so a message only counts on its own connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<Uuid>,
+    /// What a clipboard update holds, never encrypted, so receivers can act
+    /// on it before decrypting the body. Signed in payload format 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub descriptor: Option<ContentDescriptor>,
+    /// Protocol the sender wrote this in; `None` from builds that predate
+    /// it. Signed in payload format 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<ProtocolVersion>,
+    /// Base64 salt the key protecting this message was derived with from
+    /// the passphrase; `None` when the passphrase itself is the key, as with
+    /// older builds. Its presence selects signing payload format 2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_salt: Option<String>,
+    /// How the transport protects this message when a key is set; never sent
+    #[serde(skip)]
+    pub protection: Protection,
+}
+
+/// Protection applied to an outgoing message when a security key is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protection {
+    /// Encrypt the payload and sign the message
+    #[default]
+    Sealed,
+    /// Sign only, for control traffic with nothing confidential in it
+    Signed,
+}
+
+/// Clipboard contents in every format we can carry. `text` is always set so
+/// plain-text-only peers and platforms still get something useful.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClipboardContent {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtf: Option<String>,
+}
+
+impl ClipboardContent {
+    pub fn plain(text: String) -> Self {
+        Self { text, ..Default::default() }
+    }
+
+    pub fn is_rich(&self) -> bool {
+        self.html.is_some() || self.rtf.is_some()
+    }
+}
+
+/// Whether a clip is plain text, carries rich formats too, or is an
+/// image or files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DescriptorKind {
+    Text,
+    Rich,
+    Image,
+    File,
+}
+
+/// Outline of a clip, sent in the clear alongside its body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentDescriptor {
+    #[serde(rename = "type")]
+    pub kind: DescriptorKind,
+    /// Plaintext bytes across all formats
+    pub size: u64,
+    /// Formats carried, e.g. `["text", "html"]`
+    pub formats: Vec<String>,
+    /// BLAKE3 hash of the plaintext clip. Left out of sealed messages,
+    /// which carry it encrypted in `content_hash` instead.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+}
+
+impl ContentDescriptor {
+    /// Outline of `content`, whose hash the caller has already computed.
+    pub fn of(content: &ClipboardContent, hash: &str) -> Self {
+        let mut formats = vec!["text".to_string()];
+        formats.extend(content.html.as_ref().map(|_| "html".to_string()));
+        formats.extend(content.rtf.as_ref().map(|_| "rtf".to_string()));
+        let rich = content.html.as_ref().map_or(0, String::len) + content.rtf.as_ref().map_or(0, String::len);
+        Self {
+            kind: if content.is_rich() { DescriptorKind::Rich } else { DescriptorKind::Text },
+            size: (content.text.len() + rich) as u64,
+            formats,
+            hash: Some(hash.to_string()),
+        }
+    }
+}
+
+/// Secondary formats carried in `ClipboardMessage::formats`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RichFormats {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rtf: Option<String>,
+}
+
+impl ClipboardMessage {
+    pub fn new(msg_type: MessageType, content: Option<String>) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            msg_type,
+            content,
+            timestamp: Utc::now(),
+            signature: None,
+            device: None,
+            encrypted: false,
+            formats: None,
+            content_hash: None,
+            sender_id: None,
+            session: None,
+            descriptor: None,
+            protocol_version: Some(ProtocolVersion::CURRENT),
+            key_salt: None,
+            protection: Protection::Sealed,
+        }
+    }
+
+    /// Override how the transport protects this message.
+    pub fn with_protection(mut self, protection: Protection) -> Self {
+        self.protection = protection;
+        self
+    }
+
+    /// Stamp the stable id of the device sending this message.
+    pub fn with_sender(mut self, sender_id: Uuid) -> Self {
+        self.sender_id = Some(sender_id);
+        self
+    }
+
+    /// Build a clipboard update carrying plain text plus any rich formats.
+    pub fn clipboard_update(content: ClipboardContent) -> anyhow::Result<Self> {
+        let content_hash = crate::hashing::hash_content(&content);
+        let descriptor = ContentDescriptor::of(&content, &content_hash);
+        let formats = if content.is_rich() {
+            Some(serde_json::to_string(&RichFormats {
+                html: content.html,
+                rtf: content.rtf,
+            })?)
+        } else {
+            None
+        };
+        let mut message = Self::new(MessageType::ClipboardUpdate, Some(content.text));
+        message.formats = formats;
+        message.content_hash = Some(content_hash);
+        message.descriptor = Some(descriptor);
+        Ok(message)
+    }
+
+    /// Canonical string covered by the HMAC signature.
+    ///
+    /// Messages under a derived key (with `key_salt`) sign format 2: a JSON
+    /// array, tagged `SIGNING_FORMAT_V2`, of every field but the signature.
+    /// Only builds that derive keys send those, and receivers pin whether a
+    /// peer does in the handshake, so the format cannot be downgraded.
+    /// Messages keyed with the passphrase itself, for builds that predate
+    /// derivation, sign format 1, where optional fields are appended as
+    /// labelled segments only when present.
+    pub fn signing_payload(&self) -> String {
+        if self.key_salt.is_some() {
+            return serde_json::to_string(&(
+                SIGNING_FORMAT_V2,
+                (self.id, &self.msg_type, &self.content, self.timestamp.to_rfc3339()),
+                (self.encrypted, &self.formats, &self.content_hash, &self.descriptor),
+                (self.sender_id, self.session, &self.device, self.protocol_version, &self.key_salt),
+            )).unwrap_or_default();
+        }
+        let mut payload = format!(
+            "{}|{}|{}|{}",
+            self.id,
+            serde_json::to_string(&self.msg_type).unwrap_or_default(),
+            self.content.as_deref().unwrap_or_default(),
+            self.timestamp.to_rfc3339()
+        );
+        if let Some(ref formats) = self.formats {
+            payload.push_str("|formats=");
+            payload.push_str(formats);
+        }
+        if let Some(ref hash) = self.content_hash {
+            payload.push_str("|hash=");
+            payload.push_str(hash);
+        }
+        if let Some(session) = self.session {
+            payload.push_str("|session=");
+            payload.push_str(&session.to_string());
+        }
+        payload
+    }
+
+    /// Encrypt the content, formats, and hash (if any) and sign the message with the shared key.
+    pub fn seal(&mut self, key: &str) -> anyhow::Result<()> {
+        if let Some(ref content) = self.content {
+            self.content = Some(crate::crypto::encrypt_content(key, content)?);
+            self.encrypted = true;
+        }
+        if let Some(ref formats) = self.formats {
+            self.formats = Some(crate::crypto::encrypt_content(key, formats)?);
+            self.encrypted = true;
+        }
+        if let Some(ref hash) = self.content_hash {
+            self.content_hash = Some(crate::crypto::encrypt_content(key, hash)?);
+        }
+        // A plaintext hash in the clear would let anyone test guesses
+        if let Some(ref mut descriptor) = self.descriptor {
+            descriptor.hash = None;
+        }
+        self.sign(key);
+        Ok(())
+    }
+
+    /// Undo `seal`: decrypt the content, formats, and hash with `key` and
+    /// drop the signature, so the message can be protected again.
+    ///
+    /// # Errors
+    /// When a field was encrypted with a different key
+    pub fn open(&mut self, key: &str) -> anyhow::Result<()> {
+        if self.encrypted {
+            for field in [&mut self.content, &mut self.formats, &mut self.content_hash] {
+                if let Some(ref value) = field {
+                    *field = Some(crate::crypto::decrypt_content(key, value)?);
+                }
+            }
+            self.encrypted = false;
+        }
+        self.signature = None;
+        Ok(())
+    }
+
+    /// Sign the message as it stands with the shared key.
+    pub fn sign(&mut self, key: &str) {
+        self.signature = Some(crate::crypto::generate_signature(key, &self.signing_payload()));
+    }
+
+    /// Stamp the message with the session of the connection it is about to
+    /// travel on, re-signing it with `key` if set. Encrypted fields are
+    /// left as they are.
+    pub fn bind_session(&mut self, session: Uuid, key: Option<&str>) {
+        self.session = Some(session);
+        if let Some(key) = key {
+            self.sign(key);
+        }
+    }
+
+    /// Apply this message's `protection` with `key`; without a key the
+    /// message goes out as is.
+    pub fn protect(&mut self, key: Option<&str>) -> anyhow::Result<()> {
+        match (key, self.protection) {
+            (None, _) => Ok(()),
+            (Some(key), Protection::Sealed) => self.seal(key),
+            (Some(key), Protection::Signed) => {
+                self.sign(key);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Payload of a `Hello` message: the versions each side runs, confirmed
+/// in-band once the connection is up, and what the sender can take. The
+/// capabilities are absent from builds that predate them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    pub app_version: String,
+    pub protocol: ProtocolVersion,
+    /// Oldest major version the sender still talks to
+    pub min_protocol: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_id: Option<Uuid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Clip formats the sender reads, named as in `ContentDescriptor`
+    #[serde(default)]
+    pub formats: Vec<String>,
+    /// Largest clip the sender accepts, in plaintext bytes; `None` for no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_message_bytes: Option<u64>,
+    /// Compression schemes the sender reads, in order of preference
+    #[serde(default)]
+    pub compression: Vec<String>,
+}
+
+/// Payload of a `KeyRotation` message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyRotationNotice {
+    pub rotated_at: DateTime<Utc>,
+    /// Until when the sender still accepts the old key
+    pub grace_until: DateTime<Utc>,
+}
+
+/// Payload of a `LogRequest` message.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogRequest {
+    pub lines: usize,
+}
+
+/// Payload of a `LogResponse` message. `granted` is false when the
+/// remote device has not opted in to sharing its logs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogResponse {
+    pub request_id: Uuid,
+    pub granted: bool,
+    pub lines: Vec<String>,
+}
+
+/// Payload of a `SyncState` message, sent when a device pauses or resumes
+/// sync so peers can show it as paused and stop sending it content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncStateNotice {
+    pub paused: bool,
+}
+
+/// Payload of a `MuteRequest` message: don't send the requester content
+/// until `until`, or send it again when `None`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MuteRequest {
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// Payload of a `DeviceRemoved` message: a device was forgotten on one
+/// machine and every trusted peer should drop it too.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceTombstone {
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+    pub removed_at: DateTime<Utc>,
+}
+
+/// Sender identity carried by clipboard updates. Only signed in payload
+/// format 2, so from older builds it only serves for display.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceInfo {
+    pub name: String,
+    pub platform: String,
+    pub version: String,
+}
+
+/// Payload of a `Receipt` message: a device applied a peer's clip.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipReceipt {
+    /// Id of the `ClipboardUpdate` message that was applied
+    pub message_id: Uuid,
+    pub device: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Payload of a `ClipboardDelta` message. The clip is the base's first
+/// `prefix` bytes, then `insert`, then the base's last `suffix` bytes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClipDelta {
+    /// Id of the `ClipboardUpdate` this stands for
+    pub message_id: Uuid,
+    /// Clip the receiver confirmed holding
+    pub base_id: Uuid,
+    pub prefix: usize,
+    pub suffix: usize,
+    pub insert: String,
+    /// Hash of the rebuilt clip, as in a `ClipboardUpdate`
+    pub content_hash: String,
+}
+
+/// Payload of a `DeltaAck` message: whether the receiver now holds a clip,
+/// or could not rebuild it from a delta and wants it whole.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaAck {
+    pub message_id: Uuid,
+    pub accepted: bool,
+}
+
+#[cfg(test)]
+mod tests;
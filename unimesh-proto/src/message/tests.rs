@@ -0,0 +1,142 @@
+use super::*;
+use crate::crypto::verify_signature;
+
+const KEY: &str = "c2lnbmluZy1rZXktZm9yLXRlc3RzLW9ubHktMzJiIQ==";
+
+fn verifies(message: &ClipboardMessage) -> bool {
+    verify_signature(KEY, &message.signing_payload(), message.signature.as_deref().unwrap_or_default())
+}
+
+fn signed_v2() -> ClipboardMessage {
+    let mut message = ClipboardMessage::clipboard_update(ClipboardContent::plain("hello".to_string())).unwrap().with_sender(Uuid::new_v4());
+    message.key_salt = Some("c2FsdA==".to_string());
+    message.sign(KEY);
+    message
+}
+
+#[test]
+fn messages_carry_only_the_sender_they_are_given() {
+    assert_eq!(ClipboardMessage::new(MessageType::Heartbeat, None).sender_id, None);
+    let sender = Uuid::new_v4();
+    assert_eq!(ClipboardMessage::new(MessageType::Heartbeat, None).with_sender(sender).sender_id, Some(sender));
+}
+
+#[test]
+fn format_2_covers_every_field_a_receiver_acts_on() {
+    let tampered: Vec<fn(&mut ClipboardMessage)> = vec![
+        |m| m.encrypted = true,
+        |m| m.key_salt = Some("b3RoZXI=".to_string()),
+        |m| m.sender_id = Some(Uuid::new_v4()),
+        |m| m.device = Some(DeviceInfo { name: "other".into(), platform: "linux".into(), version: "0".into() }),
+        |m| m.protocol_version = None,
+        |m| m.descriptor = None,
+        |m| m.session = Some(Uuid::new_v4()),
+        |m| m.content = Some("changed".to_string()),
+    ];
+    assert!(verifies(&signed_v2()));
+    for tamper in tampered {
+        let mut message = signed_v2();
+        tamper(&mut message);
+        assert!(!verifies(&message), "{:?}", message);
+    }
+}
+
+#[test]
+fn format_2_is_tagged() {
+    assert!(signed_v2().signing_payload().contains(SIGNING_FORMAT_V2));
+}
+
+#[test]
+fn messages_without_a_salt_keep_format_1() {
+    let mut message = ClipboardMessage::new(MessageType::Heartbeat, Some("ping".to_string()));
+    let expected = format!("{}|\"heartbeat\"|ping|{}", message.id, message.timestamp.to_rfc3339());
+    assert_eq!(message.signing_payload(), expected);
+    message.sign(KEY);
+    // Unsigned fields of format 1 stay outside the signature
+    message.sender_id = Some(Uuid::new_v4());
+    assert!(verifies(&message));
+}
+
+fn rich() -> ClipboardContent {
+    ClipboardContent { text: "hello".to_string(), html: Some("<b>hello</b>".to_string()), rtf: None }
+}
+
+fn sealed_v2() -> ClipboardMessage {
+    let mut message = ClipboardMessage::clipboard_update(rich()).unwrap();
+    message.key_salt = Some("c2FsdA==".to_string());
+    message.seal(KEY).unwrap();
+    message
+}
+
+#[test]
+fn sealed_messages_open_to_the_original() {
+    let plain = ClipboardMessage::clipboard_update(rich()).unwrap();
+    let mut message = sealed_v2();
+    assert!(message.encrypted);
+    assert_ne!(message.content, plain.content);
+    assert_ne!(message.formats, plain.formats);
+    assert_ne!(message.content_hash, plain.content_hash);
+    assert!(message.descriptor.as_ref().is_some_and(|descriptor| descriptor.hash.is_none()));
+    assert!(verifies(&message));
+
+    message.open(KEY).unwrap();
+    assert!(!message.encrypted);
+    assert!(message.signature.is_none());
+    assert_eq!(message.content, plain.content);
+    assert_eq!(message.formats, plain.formats);
+    assert_eq!(message.content_hash, plain.content_hash);
+}
+
+#[test]
+fn sealed_messages_need_the_key() {
+    let message = sealed_v2();
+    assert!(!verify_signature("another key", &message.signing_payload(), message.signature.as_deref().unwrap()));
+    assert!(message.clone().open("another key").is_err());
+}
+
+#[test]
+fn tampered_ciphertext_fails_verification_and_opening() {
+    let mut message = sealed_v2();
+    let sealed = message.content.take().unwrap();
+    let flipped = if sealed.starts_with('A') { "B" } else { "A" };
+    message.content = Some(format!("{}{}", flipped, &sealed[1..]));
+    assert!(!verifies(&message));
+    assert!(message.open(KEY).is_err());
+}
+
+#[test]
+fn tampered_signature_fails_verification() {
+    let mut message = sealed_v2();
+    message.signature = Some(crate::crypto::generate_signature("another key", &message.signing_payload()));
+    assert!(!verifies(&message));
+    message.signature = None;
+    assert!(!verifies(&message));
+}
+
+#[test]
+fn stripping_the_encrypted_flag_fails_verification() {
+    let mut message = sealed_v2();
+    message.encrypted = false;
+    assert!(!verifies(&message));
+}
+
+#[test]
+fn signatures_survive_a_json_round_trip() {
+    for message in [sealed_v2(), signed_v2()] {
+        let json = serde_json::to_string(&message).unwrap();
+        let received: ClipboardMessage = serde_json::from_str(&json).unwrap();
+        assert_eq!(received.signing_payload(), message.signing_payload());
+        assert!(verifies(&received));
+    }
+}
+
+#[test]
+fn clipboard_updates_outline_their_content_under_one_hash() {
+    let content = rich();
+    let message = ClipboardMessage::clipboard_update(content.clone()).unwrap();
+    let descriptor = message.descriptor.unwrap();
+    assert_eq!(descriptor.hash, message.content_hash);
+    assert_eq!(descriptor.hash.as_deref(), Some(crate::hashing::hash_content(&content).as_str()));
+    assert_eq!(descriptor.formats, ["text", "html"]);
+    assert_eq!(descriptor.kind, DescriptorKind::Rich);
+}
@@ -0,0 +1,46 @@
+//! Protocol versions and the handshake headers that carry them.
+
+use serde::{Deserialize, Serialize};
+
+/// Wire protocol this build speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest protocol this build still talks to.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+/// Additions to `PROTOCOL_VERSION` that builds on a lower minor do
+/// without: 1 adds the `Hello` exchange.
+pub const PROTOCOL_MINOR: u32 = 1;
+
+/// Handshake header with the sender's app version.
+pub const APP_VERSION_HEADER: &str = "x-unimesh-app-version";
+/// Handshake header with the protocol the sender speaks.
+pub const PROTOCOL_HEADER: &str = "x-unimesh-protocol";
+/// Handshake header with the oldest protocol the sender accepts.
+pub const MIN_PROTOCOL_HEADER: &str = "x-unimesh-min-protocol";
+/// Handshake header with the sender's `PROTOCOL_MINOR`.
+pub const PROTOCOL_MINOR_HEADER: &str = "x-unimesh-protocol-minor";
+/// Handshake header with each side's random half of the session id.
+pub const SESSION_NONCE_HEADER: &str = "x-unimesh-session-nonce";
+
+/// Protocol a message was written in. Builds on a supported major read
+/// each other's messages; a lower minor skips message types it does not
+/// know.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtocolVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ProtocolVersion {
+    pub const CURRENT: Self = Self { major: PROTOCOL_VERSION, minor: PROTOCOL_MINOR };
+
+    /// Whether this build can read messages written in this version.
+    pub fn is_supported(&self) -> bool {
+        (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&self.major)
+    }
+}
+
+impl std::fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}